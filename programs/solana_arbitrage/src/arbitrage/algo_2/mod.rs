@@ -1,20 +1,110 @@
 use crate::arbitrage::base::Edge;
 use crate::programs::SolarBError;
 use anchor_lang::prelude::*;
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
 
 const MIN_PROFIT: i128 = 40_000;
-
-#[derive(Clone, Debug)]
+/// Profit above which a path is treated as coming from mis-read pool data
+pub const MAX_PLAUSIBLE_PROFIT: i128 = 1_000_000_000;
+/// Compute units a path's swaps may use, leaving room for parsing and quoting under the
+/// 1.4M per-transaction limit
+pub const MAX_PATH_COMPUTE_UNITS: u32 = 1_000_000;
+/// Paths `run_arbitrage` ranks when it may fall back past a best path that can't be traded
+pub const MAX_ALTERNATIVE_PATHS: usize = 3;
+/// Edges the search may go through once `max_edges` has cut the set down; the cycle
+/// search grows with their square, so more risks running out of compute
+pub const MAX_SEARCH_EDGES: usize = 64;
+
+#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
 pub struct ArbitragePath {
     pub edges: Vec<Edge>,
+    /// Output the search expects from each edge, in execution order, at the edges' marginal
+    /// prices. These are optimistic, as a real swap pays out less for its price impact, so
+    /// `execute_arbitrage` re-quotes them through the pools before they become minimum
+    /// outputs.
+    pub amounts_out: Vec<u128>,
     pub profit: i128,
     pub final_amount: u128,
     pub start_amount: u128,
 }
 
-fn calculate_swap_amount(edge: &Edge, amount_in: u128) -> u128 {
-    (amount_in as f64 * edge.get_price()) as u128
+impl ArbitragePath {
+    /// Order paths are ranked in, best first: most profit, then fewest hops, then fewest
+    /// estimated compute units
+    pub fn rank_key(&self) -> (Reverse<i128>, usize, u64) {
+        rank_key(self.profit, self.edges.iter())
+    }
+}
+
+fn rank_key<'a>(
+    profit: i128,
+    edges: impl ExactSizeIterator<Item = &'a Edge>,
+) -> (Reverse<i128>, usize, u64) {
+    let hops = edges.len();
+    let compute_units = edges.map(|edge| u64::from(edge.compute_units)).sum();
+    (Reverse(profit), hops, compute_units)
+}
+
+/// Output of `amount_in` at the edge's price, `None` past `u128::MAX`
+fn calculate_swap_amount(edge: &Edge, amount_in: u128) -> Option<u128> {
+    edge.apply(amount_in)
+}
+
+/// Profitable cycles found so far, kept sorted by `ArbitragePath::rank_key` (earlier finds
+/// first among equal ranks) and capped at `max_paths`
+struct RankedPaths {
+    paths: Vec<ArbitragePath>,
+    max_paths: usize,
+    /// Whether a profitable cycle was dropped for feeding an edge less than its
+    /// `min_amount_in`, so an empty result can say why
+    undersized: bool,
+}
+
+impl RankedPaths {
+    fn new(max_paths: usize) -> Self {
+        Self {
+            paths: Vec::new(),
+            max_paths,
+            undersized: false,
+        }
+    }
+
+    fn insert(&mut self, edges: &[&Edge], amounts_out: &[u128], profit: i128, start_amount: u128) {
+        // Each edge is fed the previous one's output, the first the start amount
+        let amounts_in = std::iter::once(start_amount).chain(amounts_out.iter().copied());
+        if edges
+            .iter()
+            .zip(amounts_in)
+            .any(|(edge, amount_in)| amount_in < u128::from(edge.min_amount_in))
+        {
+            self.undersized = true;
+            return;
+        }
+        let key = rank_key(profit, edges.iter().copied());
+        let position = self.paths.partition_point(|path| path.rank_key() <= key);
+        if position >= self.max_paths {
+            return;
+        }
+        self.paths.insert(
+            position,
+            ArbitragePath {
+                edges: edges.iter().map(|&edge| edge.clone()).collect(),
+                amounts_out: amounts_out.to_vec(),
+                profit,
+                final_amount: amounts_out.last().copied().unwrap_or(start_amount),
+                start_amount,
+            },
+        );
+        self.paths.truncate(self.max_paths);
+    }
+}
+
+/// Start tokens in ascending order, since `HashMap` iteration order isn't stable
+fn sorted_roots(adj: &HashMap<Pubkey, Vec<&Edge>>) -> Vec<Pubkey> {
+    let mut roots: Vec<Pubkey> = adj.keys().cloned().collect();
+    roots.sort();
+    roots
 }
 
 /// Highly efficient iterative check for 2-hop (Cross) Arbitrage.
@@ -26,9 +116,30 @@ pub fn find_cross_arbitrage_iterative(
     min_profit: i128,
     start_token: Option<Pubkey>,
 ) -> Option<ArbitragePath> {
-    let mut best_path: Option<ArbitragePath> = None;
-    let mut max_profit = 0i128;
+    find_cross_arbitrage_ranked(edges, start_amount, min_profit, start_token, 1).pop()
+}
+
+/// The `max_paths` most profitable 2-hop cycles, best first. Cycles that feed an edge less
+/// than its `min_amount_in` are left out.
+pub fn find_cross_arbitrage_ranked(
+    edges: &[&Edge],
+    start_amount: u128,
+    min_profit: i128,
+    start_token: Option<Pubkey>,
+    max_paths: usize,
+) -> Vec<ArbitragePath> {
+    let mut paths = RankedPaths::new(max_paths);
+    collect_cross_arbitrage(edges, start_amount, min_profit, start_token, &mut paths);
+    paths.paths
+}
 
+fn collect_cross_arbitrage(
+    edges: &[&Edge],
+    start_amount: u128,
+    min_profit: i128,
+    start_token: Option<Pubkey>,
+    paths: &mut RankedPaths,
+) {
     // Group edges by start token for O(1) lookup
     // Map: StartToken -> List of Edges
     let mut adj: HashMap<Pubkey, Vec<&Edge>> = HashMap::new();
@@ -42,7 +153,7 @@ pub fn find_cross_arbitrage_iterative(
     let root_tokens: Vec<Pubkey> = if let Some(token) = start_token {
         vec![token]
     } else {
-        adj.keys().cloned().collect()
+        sorted_roots(&adj)
     };
 
     for root in root_tokens {
@@ -50,27 +161,30 @@ pub fn find_cross_arbitrage_iterative(
             // Hop 1: Root -> B
             for edge1 in root_edges {
                 let token_b = edge1.right.mint_account;
-                let amount_b = calculate_swap_amount(edge1, start_amount);
+                // An output past u128::MAX can't be traded anyway
+                let Some(amount_b) = calculate_swap_amount(edge1, start_amount) else {
+                    continue;
+                };
 
                 // Hop 2: B -> Root
                 if let Some(b_edges) = adj.get(&token_b) {
                     for edge2 in b_edges {
-                        // Ensure we go back to root AND use a different program/market
-                        if edge2.right.mint_account == root && edge2.program != edge1.program {
+                        // Ensure we go back to root AND use a different pool, which may
+                        // belong to the same program as the first
+                        if edge2.right.mint_account == root && edge2.pool_id != edge1.pool_id {
                             // Found 2-hop cycle
-                            let final_amount = calculate_swap_amount(edge2, amount_b);
+                            let Some(final_amount) = calculate_swap_amount(edge2, amount_b) else {
+                                continue;
+                            };
                             let profit = final_amount as i128 - start_amount as i128;
 
-                            // Only update if this path is MORE profitable than current best
-                            // This ensures we find the BEST path, not just the first valid one
-                            if profit > max_profit && profit >= min_profit {
-                                max_profit = profit;
-                                best_path = Some(ArbitragePath {
-                                    edges: vec![(*edge1).clone(), (*edge2).clone()],
+                            if profit > 0 && profit >= min_profit {
+                                paths.insert(
+                                    &[*edge1, *edge2],
+                                    &[amount_b, final_amount],
                                     profit,
-                                    final_amount,
                                     start_amount,
-                                });
+                                );
                             }
                         }
                     }
@@ -78,8 +192,6 @@ pub fn find_cross_arbitrage_iterative(
             }
         }
     }
-
-    best_path
 }
 
 /// Optimized O(E) check for 3-hop (Triangular) Arbitrage using Map lookup.
@@ -91,9 +203,30 @@ pub fn find_triangular_arbitrage_iterative<'info>(
     min_profit: i128,
     start_token: Option<Pubkey>,
 ) -> Option<ArbitragePath> {
-    let mut best_path: Option<ArbitragePath> = None;
-    let mut max_profit = 0i128;
+    find_triangular_arbitrage_ranked(edges, start_amount, min_profit, start_token, 1).pop()
+}
+
+/// The `max_paths` most profitable 3-hop cycles, best first. Cycles that feed an edge less
+/// than its `min_amount_in` are left out.
+pub fn find_triangular_arbitrage_ranked(
+    edges: &[&Edge],
+    start_amount: u128,
+    min_profit: i128,
+    start_token: Option<Pubkey>,
+    max_paths: usize,
+) -> Vec<ArbitragePath> {
+    let mut paths = RankedPaths::new(max_paths);
+    collect_triangular_arbitrage(edges, start_amount, min_profit, start_token, &mut paths);
+    paths.paths
+}
 
+fn collect_triangular_arbitrage(
+    edges: &[&Edge],
+    start_amount: u128,
+    min_profit: i128,
+    start_token: Option<Pubkey>,
+    paths: &mut RankedPaths,
+) {
     // 1. Build Adjacency List (Start -> [Edges])
     let mut adj: HashMap<Pubkey, Vec<&Edge>> = HashMap::new();
 
@@ -114,7 +247,7 @@ pub fn find_triangular_arbitrage_iterative<'info>(
     let root_tokens: Vec<Pubkey> = if let Some(token) = start_token {
         vec![token]
     } else {
-        adj.keys().cloned().collect()
+        sorted_roots(&adj)
     };
 
     for root in root_tokens {
@@ -122,7 +255,9 @@ pub fn find_triangular_arbitrage_iterative<'info>(
             // Hop 1: Root -> B
             for edge1 in root_edges {
                 let token_b = edge1.right.mint_account;
-                let amount_b = calculate_swap_amount(edge1, start_amount);
+                let Some(amount_b) = calculate_swap_amount(edge1, start_amount) else {
+                    continue;
+                };
 
                 if !adj.contains_key(&token_b) {
                     continue;
@@ -138,7 +273,9 @@ pub fn find_triangular_arbitrage_iterative<'info>(
                             continue;
                         }
 
-                        let amount_c = calculate_swap_amount(edge2, amount_b);
+                        let Some(amount_c) = calculate_swap_amount(edge2, amount_b) else {
+                            continue;
+                        };
 
                         // Hop 3: C -> Root (Optimized Lookup)
                         // Instead of iterating adj[token_c] and filtering for 'root',
@@ -146,24 +283,22 @@ pub fn find_triangular_arbitrage_iterative<'info>(
                         if let Some(third_leg_edges) = pair_map.get(&(token_c, root)) {
                             for edge3 in third_leg_edges {
                                 // Found 3-hop cycle
-                                let final_amount = calculate_swap_amount(edge3, amount_c);
+                                let Some(final_amount) = calculate_swap_amount(edge3, amount_c)
+                                else {
+                                    continue;
+                                };
                                 let profit = final_amount as i128 - start_amount as i128;
 
                                 // Debug logging
                                 // msg!("Triangular: profit={}, min_profit={}", profit, min_profit);
 
-                                if profit > max_profit && profit >= min_profit {
-                                    max_profit = profit;
-                                    best_path = Some(ArbitragePath {
-                                        edges: vec![
-                                            (*edge1).clone(),
-                                            (*edge2).clone(),
-                                            (*edge3).clone(),
-                                        ],
+                                if profit > 0 && profit >= min_profit {
+                                    paths.insert(
+                                        &[*edge1, *edge2, *edge3],
+                                        &[amount_b, amount_c, final_amount],
                                         profit,
-                                        final_amount,
                                         start_amount,
-                                    });
+                                    );
                                 }
                             }
                         }
@@ -172,17 +307,33 @@ pub fn find_triangular_arbitrage_iterative<'info>(
             }
         }
     }
-
-    best_path
 }
 
 /// Main entry point for arbitrage calculation.
+///
+/// Ties in profit must resolve deterministically: the path with fewer hops wins, then the
+/// one with fewer estimated compute units, and among full ties the path found first, so
+/// the lowest start mint and then the first edges in `edges` order (see
+/// `Edge::ordering_key`) are kept.
 pub fn check_arbitrage(
     edges: &[&Edge],
     start_amount: u128,
     start_token: Option<Pubkey>,
     min_profit: Option<i128>,
 ) -> Result<ArbitragePath> {
+    let mut paths = check_arbitrage_ranked(edges, start_amount, start_token, min_profit, 1)?;
+    Ok(paths.remove(0))
+}
+
+/// Like `check_arbitrage`, but returns up to `max_paths` profitable paths, best first, so a
+/// caller can fall back to the runner-up when the best path can't be traded
+pub fn check_arbitrage_ranked(
+    edges: &[&Edge],
+    start_amount: u128,
+    start_token: Option<Pubkey>,
+    min_profit: Option<i128>,
+    max_paths: usize,
+) -> Result<Vec<ArbitragePath>> {
     let min_profit = min_profit.unwrap_or(MIN_PROFIT);
 
     // 1. Determine Unique Tokens to decide strategy
@@ -194,17 +345,23 @@ pub fn check_arbitrage(
 
     let num_tokens = unique_tokens.len();
 
-    // 2. Strategy Selection
-    let arbitrage = if num_tokens <= 2 {
-        find_cross_arbitrage_iterative(edges, start_amount, min_profit, start_token)
-    } else {
-        find_triangular_arbitrage_iterative(edges, start_amount, min_profit, start_token)
-    };
+    // 2. Strategy Selection: 2-hop cycles always, 3-hop ones once there is a third token,
+    // which they are ranked against
+    let mut ranked = RankedPaths::new(max_paths);
+    collect_cross_arbitrage(edges, start_amount, min_profit, start_token, &mut ranked);
+    if num_tokens > 2 {
+        collect_triangular_arbitrage(edges, start_amount, min_profit, start_token, &mut ranked);
+    }
 
-    match arbitrage {
-        Some(arb) if arb.profit >= MIN_PROFIT => Ok(arb),
-        _ => Err(SolarBError::NoProfitFound.into()),
+    let mut paths = ranked.paths;
+    paths.retain(|arb| arb.profit >= min_profit);
+    if paths.is_empty() {
+        if ranked.undersized {
+            return Err(SolarBError::SwapTooSmall.into());
+        }
+        return Err(SolarBError::NoProfitFound.into());
     }
+    Ok(paths)
 }
 
 #[cfg(test)]
@@ -212,7 +369,9 @@ mod tests {
     use super::*;
     use crate::arbitrage::base::{Edge, EdgeSide, Pool};
     use crate::programs::ProgramMeta;
+    use crate::utils::utils::SwapUserAccounts;
     use anchor_lang::prelude::Pubkey;
+    use anchor_lang::solana_program::instruction::Instruction;
 
     // Mock ProgramMeta implementation for testing
     struct MockProgram {
@@ -232,10 +391,34 @@ mod tests {
             &self.id
         }
 
+        fn program_label(&self) -> &'static str {
+            "Mock"
+        }
+
+        fn pool_key(&self) -> &Pubkey {
+            &self.id
+        }
+
         fn get_vaults(&self) -> (&AccountInfo<'_>, &AccountInfo<'_>) {
             panic!("Not implemented for test");
         }
 
+        fn compute_price_swap_base_in(
+            &self,
+            _base_amount: u128,
+            _quote_amount: u128,
+        ) -> Result<f64> {
+            Ok(0.0) // Mock implementation
+        }
+
+        fn compute_price_swap_base_out(
+            &self,
+            _base_amount: u128,
+            _quote_amount: u128,
+        ) -> Result<f64> {
+            Ok(0.0) // Mock implementation
+        }
+
         fn swap_base_in(&self, _input_mint: Pubkey, _amount_in: u64, _clock: Clock) -> Result<u64> {
             Ok(0) // Mock implementation
         }
@@ -243,7 +426,7 @@ mod tests {
         fn swap_base_out(
             &self,
             _input_mint: Pubkey,
-            _amount_in: u64,
+            _amount_out: u64,
             _clock: Clock,
         ) -> Result<u64> {
             Ok(0) // Mock implementation
@@ -254,7 +437,7 @@ mod tests {
             _input_mint: Pubkey,
             _max_amount_in: u64,
             _amount_out: Option<u64>,
-            _payer: AccountInfo<'a>,
+            _authority: AccountInfo<'a>,
             _user_mint_1_token_account: AccountInfo<'a>,
             _user_mint_2_token_account: AccountInfo<'a>,
             _mint_1_account: AccountInfo<'a>,
@@ -270,7 +453,7 @@ mod tests {
             _input_mint: Pubkey,
             _amount_in: u64,
             _min_amount_out: Option<u64>,
-            _payer: AccountInfo<'a>,
+            _authority: AccountInfo<'a>,
             _user_mint_1_token_account: AccountInfo<'a>,
             _user_mint_2_token_account: AccountInfo<'a>,
             _mint_1_account: AccountInfo<'a>,
@@ -281,6 +464,20 @@ mod tests {
             Ok(()) // Mock implementation
         }
 
+        fn build_swap_ix(
+            &self,
+            _direction: EdgeSide,
+            _amount_in: u64,
+            _min_out: u64,
+            _user_accounts: &SwapUserAccounts,
+        ) -> Result<Instruction> {
+            Ok(Instruction::new_with_bytes(self.id, &[], Vec::new())) // Mock implementation
+        }
+
+        fn estimate_compute_units(&self) -> u32 {
+            0 // Mock implementation
+        }
+
         fn log_accounts(&self) -> Result<()> {
             Ok(()) // Mock implementation
         }
@@ -323,8 +520,10 @@ mod tests {
             0.0
         };
         let prog1_id = *program1.get_id();
+        let pool1_id = Pubkey::new_unique();
         let edge1_1_a = Edge::new(
             prog1_id,
+            pool1_id,
             EdgeSide::LeftToRight,
             price_a_lr,
             pool_a_left.clone(),
@@ -332,6 +531,7 @@ mod tests {
         );
         let edge1_1_b = Edge::new(
             prog1_id,
+            pool1_id,
             EdgeSide::RightToLeft,
             price_a_rl,
             pool_a_right.clone(),
@@ -347,8 +547,10 @@ mod tests {
             0.0
         };
         let prog2_id = *program2.get_id();
+        let pool2_id = Pubkey::new_unique();
         let edge_2_sol_to_usdc = Edge::new(
             prog2_id,
+            pool2_id,
             EdgeSide::LeftToRight,
             price_b_sol_usdc,
             pool_b_sol_to_usdc_left.clone(),
@@ -372,6 +574,7 @@ mod tests {
         };
         let edge_2_a = Edge::new(
             prog2_id,
+            pool2_id,
             EdgeSide::LeftToRight,
             price_b_lr,
             pool_b_left.clone(),
@@ -379,6 +582,7 @@ mod tests {
         );
         let edge_2_b = Edge::new(
             prog2_id,
+            pool2_id,
             EdgeSide::RightToLeft,
             price_b_rl,
             pool_b_right.clone(),
@@ -400,8 +604,10 @@ mod tests {
             0.0
         };
         let prog3_id = *program3.get_id();
+        let pool3_id = Pubkey::new_unique();
         let edge_3_a = Edge::new(
             prog3_id,
+            pool3_id,
             EdgeSide::LeftToRight,
             price_c_lr,
             pool_c_left.clone(),
@@ -409,6 +615,7 @@ mod tests {
         );
         let edge_3_b = Edge::new(
             prog3_id,
+            pool3_id,
             EdgeSide::RightToLeft,
             price_c_rl,
             pool_c_right.clone(),
@@ -450,8 +657,8 @@ mod tests {
             && prog3_edge.right.mint_account == sol
             && prog2_sol_to_usdc.program != prog3_edge.program
         {
-            let amount_b = calculate_swap_amount(prog2_sol_to_usdc, start_amount);
-            let final_amount = calculate_swap_amount(prog3_edge, amount_b);
+            let amount_b = calculate_swap_amount(prog2_sol_to_usdc, start_amount).unwrap();
+            let final_amount = calculate_swap_amount(prog3_edge, amount_b).unwrap();
             let profit = final_amount as i128 - start_amount as i128;
             writeln!(handle, "Path: Program2 -> Program3 (EXPECTED BEST)").unwrap();
             writeln!(
@@ -459,7 +666,7 @@ mod tests {
                 "  Step 1: {} SOL -> {} USDC (via Program2, price: {})",
                 start_amount as f64 / 1e9,
                 amount_b as f64 / 1e9,
-                prog2_sol_to_usdc.price
+                prog2_sol_to_usdc.get_price()
             )
             .unwrap();
             writeln!(
@@ -467,7 +674,7 @@ mod tests {
                 "  Step 2: {} USDC -> {} SOL (via Program3, price: {})",
                 amount_b as f64 / 1e9,
                 final_amount as f64 / 1e9,
-                prog3_edge.price
+                prog3_edge.get_price()
             )
             .unwrap();
             writeln!(handle, "  Profit: {} ({})", profit, profit as f64 / 1e9).unwrap();
@@ -481,8 +688,8 @@ mod tests {
             && prog2_edge.right.mint_account == sol
             && prog1_edge.program != prog2_edge.program
         {
-            let amount_b = calculate_swap_amount(prog1_edge, start_amount);
-            let final_amount = calculate_swap_amount(prog2_edge, amount_b);
+            let amount_b = calculate_swap_amount(prog1_edge, start_amount).unwrap();
+            let final_amount = calculate_swap_amount(prog2_edge, amount_b).unwrap();
             let profit = final_amount as i128 - start_amount as i128;
             writeln!(handle, "Path: Program1 -> Program2").unwrap();
             writeln!(
@@ -490,7 +697,7 @@ mod tests {
                 "  Step 1: {} SOL -> {} USDC (via Program1, price: {})",
                 start_amount as f64 / 1e9,
                 amount_b as f64 / 1e9,
-                prog1_edge.price
+                prog1_edge.get_price()
             )
             .unwrap();
             writeln!(
@@ -498,7 +705,7 @@ mod tests {
                 "  Step 2: {} USDC -> {} SOL (via Program2, price: {})",
                 amount_b as f64 / 1e9,
                 final_amount as f64 / 1e9,
-                prog2_edge.price
+                prog2_edge.get_price()
             )
             .unwrap();
             writeln!(handle, "  Profit: {} ({})", profit, profit as f64 / 1e9).unwrap();
@@ -511,8 +718,8 @@ mod tests {
             && prog3_edge.right.mint_account == sol
             && prog1_edge.program != prog3_edge.program
         {
-            let amount_b = calculate_swap_amount(prog1_edge, start_amount);
-            let final_amount = calculate_swap_amount(prog3_edge, amount_b);
+            let amount_b = calculate_swap_amount(prog1_edge, start_amount).unwrap();
+            let final_amount = calculate_swap_amount(prog3_edge, amount_b).unwrap();
             let profit = final_amount as i128 - start_amount as i128;
             writeln!(handle, "Path: Program1 -> Program3").unwrap();
             writeln!(
@@ -520,7 +727,7 @@ mod tests {
                 "  Step 1: {} SOL -> {} USDC (via Program1, price: {})",
                 start_amount as f64 / 1e9,
                 amount_b as f64 / 1e9,
-                prog1_edge.price
+                prog1_edge.get_price()
             )
             .unwrap();
             writeln!(
@@ -528,7 +735,7 @@ mod tests {
                 "  Step 2: {} USDC -> {} SOL (via Program3, price: {})",
                 amount_b as f64 / 1e9,
                 final_amount as f64 / 1e9,
-                prog3_edge.price
+                prog3_edge.get_price()
             )
             .unwrap();
             writeln!(handle, "  Profit: {} ({})", profit, profit as f64 / 1e9).unwrap();
@@ -591,7 +798,7 @@ mod tests {
                 edge.left.mint_account,
                 direction,
                 edge.right.mint_account,
-                edge.price,
+                edge.get_price(),
                 program_name,
                 edge.program
             )
@@ -649,6 +856,7 @@ mod tests {
         let prog_id = *program.get_id();
         let edge1 = Edge::new(
             prog_id,
+            Pubkey::new_unique(),
             EdgeSide::LeftToRight,
             price_ab,
             pool_ab_left.clone(),
@@ -665,6 +873,7 @@ mod tests {
         };
         let edge2 = Edge::new(
             prog_id,
+            Pubkey::new_unique(),
             EdgeSide::LeftToRight,
             price_bc,
             pool_bc_left.clone(),
@@ -682,6 +891,7 @@ mod tests {
         };
         let edge3 = Edge::new(
             prog_id,
+            Pubkey::new_unique(),
             EdgeSide::LeftToRight,
             price_ca,
             pool_ca_left.clone(),
@@ -699,5 +909,274 @@ mod tests {
         assert_eq!(arb.final_amount, 1_200_000_000);
         assert_eq!(arb.profit, 200_000_000);
         assert_eq!(arb.edges.len(), 3);
+        // The amount expected out of each hop, for execution to reuse
+        assert_eq!(
+            arb.amounts_out,
+            vec![2_000_000_000, 6_000_000_000, 1_200_000_000]
+        );
+    }
+
+    #[test]
+    fn test_cross_arbitrage_between_two_pools_of_one_program() {
+        let sol = Pubkey::new_unique();
+        let usdc = Pubkey::new_unique();
+        // Two pools of the same AMM program, e.g. two Pump AMM SOL/USDC pools
+        let program_id = Pubkey::new_unique();
+        let pool_a = Pubkey::new_unique();
+        let pool_b = Pubkey::new_unique();
+
+        // Pool A: 1 SOL = 100 USDC, pool B: 1 SOL = 97 USDC
+        let edges = [
+            Edge::new(
+                program_id,
+                pool_a,
+                EdgeSide::LeftToRight,
+                100.0,
+                Pool::new(&sol, 1_000_000_000),
+                Pool::new(&usdc, 100_000_000_000),
+            ),
+            Edge::new(
+                program_id,
+                pool_a,
+                EdgeSide::RightToLeft,
+                1.0 / 100.0,
+                Pool::new(&usdc, 100_000_000_000),
+                Pool::new(&sol, 1_000_000_000),
+            ),
+            Edge::new(
+                program_id,
+                pool_b,
+                EdgeSide::LeftToRight,
+                97.0,
+                Pool::new(&sol, 1_000_000_000),
+                Pool::new(&usdc, 97_000_000_000),
+            ),
+            Edge::new(
+                program_id,
+                pool_b,
+                EdgeSide::RightToLeft,
+                1.0 / 97.0,
+                Pool::new(&usdc, 97_000_000_000),
+                Pool::new(&sol, 1_000_000_000),
+            ),
+        ];
+        let edge_refs: Vec<&Edge> = edges.iter().collect();
+
+        // Selling SOL into A and buying it back from B is the only profitable cycle
+        let arb = check_arbitrage(&edge_refs, 1_000_000_000, Some(sol), None).unwrap();
+        assert_eq!(arb.edges.len(), 2);
+        assert_eq!(arb.edges[0].pool_id, pool_a);
+        assert_eq!(arb.edges[0].side, EdgeSide::LeftToRight);
+        assert_eq!(arb.edges[1].pool_id, pool_b);
+        assert_eq!(arb.edges[1].side, EdgeSide::RightToLeft);
+        assert!(arb.edges.iter().all(|edge| edge.program == program_id));
+        assert!(arb.profit > 0);
+
+        // A single pool never trades against itself
+        let one_pool: Vec<&Edge> = edges[..2].iter().collect();
+        assert!(check_arbitrage(&one_pool, 1_000_000_000, Some(sol), None).is_err());
+    }
+
+    #[test]
+    fn test_check_arbitrage_ranked_orders_by_profit() {
+        let sol = Pubkey::new_unique();
+        let usdc = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+
+        // Three SOL/USDC pools at 100, 103 and 106 USDC per SOL
+        let mut edges = Vec::new();
+        for price in [100.0, 103.0, 106.0] {
+            let pool_id = Pubkey::new_unique();
+            let sol_pool = Pool::new(&sol, 1_000_000_000);
+            let usdc_pool = Pool::new(&usdc, (price * 1e9) as u128);
+            edges.push(Edge::new(
+                program_id,
+                pool_id,
+                EdgeSide::LeftToRight,
+                price,
+                sol_pool.clone(),
+                usdc_pool.clone(),
+            ));
+            edges.push(Edge::new(
+                program_id,
+                pool_id,
+                EdgeSide::RightToLeft,
+                1.0 / price,
+                usdc_pool,
+                sol_pool,
+            ));
+        }
+        let edge_refs: Vec<&Edge> = edges.iter().collect();
+
+        let paths = check_arbitrage_ranked(&edge_refs, 1_000_000_000, Some(sol), None, 2).unwrap();
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].profit > paths[1].profit);
+        // Best: sell at 106, buy back at 100; runner-up: sell at 103, buy back at 100
+        let legs = |path: &ArbitragePath| (path.edges[0].pool_id, path.edges[1].pool_id);
+        assert_eq!(legs(&paths[0]), (edges[4].pool_id, edges[1].pool_id));
+        assert_eq!(legs(&paths[1]), (edges[2].pool_id, edges[1].pool_id));
+
+        let best = check_arbitrage(&edge_refs, 1_000_000_000, Some(sol), None).unwrap();
+        assert_eq!(best.edges, paths[0].edges);
+    }
+
+    #[test]
+    fn test_check_arbitrage_ranked_honours_min_profit() {
+        let sol = Pubkey::new_unique();
+        let usdc = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+
+        let mut edges = Vec::new();
+        for price in [100.0, 106.0] {
+            let pool_id = Pubkey::new_unique();
+            let sol_pool = Pool::new(&sol, 1_000_000_000);
+            let usdc_pool = Pool::new(&usdc, (price * 1e9) as u128);
+            edges.push(Edge::new(
+                program_id,
+                pool_id,
+                EdgeSide::LeftToRight,
+                price,
+                sol_pool.clone(),
+                usdc_pool.clone(),
+            ));
+            edges.push(Edge::new(
+                program_id,
+                pool_id,
+                EdgeSide::RightToLeft,
+                1.0 / price,
+                usdc_pool,
+                sol_pool,
+            ));
+        }
+        let edge_refs: Vec<&Edge> = edges.iter().collect();
+
+        // About 6_000 profit on 100_000: short of the default minimum, past a lower one
+        assert_eq!(
+            check_arbitrage_ranked(&edge_refs, 100_000, Some(sol), None, 2).unwrap_err(),
+            error!(SolarBError::NoProfitFound)
+        );
+        let paths = check_arbitrage_ranked(&edge_refs, 100_000, Some(sol), Some(1_000), 2).unwrap();
+        assert!(paths[0].profit >= 1_000 && paths[0].profit < MIN_PROFIT);
+    }
+
+    #[test]
+    fn test_check_arbitrage_prefers_fewer_hops_then_compute_units() {
+        let token_a = Pubkey::new_unique();
+        let token_b = Pubkey::new_unique();
+        let token_c = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let edge = |from: Pubkey, to: Pubkey, price: f64, compute_units: u32| {
+            Edge::new(
+                program_id,
+                Pubkey::new_unique(),
+                EdgeSide::LeftToRight,
+                price,
+                Pool::new(&from, 1_000_000_000),
+                Pool::new(&to, 1_000_000_000),
+            )
+            .with_compute_units(compute_units)
+        };
+
+        // Every cycle from A turns 1 into exactly 1.25: A -> B -> A through either of two
+        // pools back, and A -> B -> C -> A through the cheapest pools of all
+        let edges = [
+            edge(token_a, token_b, 2.0, 1_000),
+            edge(token_b, token_a, 0.625, 50_000),
+            edge(token_b, token_a, 0.625, 10_000),
+            edge(token_b, token_c, 4.0, 1_000),
+            edge(token_c, token_a, 0.15625, 1_000),
+        ];
+        let edge_refs: Vec<&Edge> = edges.iter().collect();
+
+        let paths =
+            check_arbitrage_ranked(&edge_refs, 1_000_000_000, Some(token_a), None, 3).unwrap();
+        assert!(paths.iter().all(|path| path.final_amount == 1_250_000_000));
+        let pools = |path: &ArbitragePath| -> Vec<Pubkey> {
+            path.edges.iter().map(|edge| edge.pool_id).collect()
+        };
+        assert_eq!(pools(&paths[0]), [edges[0].pool_id, edges[2].pool_id]);
+        assert_eq!(pools(&paths[1]), [edges[0].pool_id, edges[1].pool_id]);
+        assert_eq!(
+            pools(&paths[2]),
+            [edges[0].pool_id, edges[3].pool_id, edges[4].pool_id]
+        );
+
+        let best = check_arbitrage(&edge_refs, 1_000_000_000, Some(token_a), None).unwrap();
+        assert_eq!(best.edges, paths[0].edges);
+    }
+
+    #[test]
+    fn test_check_arbitrage_skips_hops_past_u128_max() {
+        let sol = Pubkey::new_unique();
+        let usdc = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+
+        // SOL/USDC pools at 106 and 100 USDC per SOL, and a broken one at 1e19 whose
+        // output for 1e20 lamports doesn't fit in a u128
+        let start_amount = 100_000_000_000_000_000_000;
+        let mut edges = Vec::new();
+        for price in [106.0, 100.0, 1e19] {
+            let pool_id = Pubkey::new_unique();
+            let sol_pool = Pool::new(&sol, 1_000_000_000);
+            let usdc_pool = Pool::new(&usdc, 1_000_000_000);
+            edges.push(Edge::new(
+                program_id,
+                pool_id,
+                EdgeSide::LeftToRight,
+                price,
+                sol_pool.clone(),
+                usdc_pool.clone(),
+            ));
+            edges.push(Edge::new(
+                program_id,
+                pool_id,
+                EdgeSide::RightToLeft,
+                1.0 / price,
+                usdc_pool,
+                sol_pool,
+            ));
+        }
+        let edge_refs: Vec<&Edge> = edges.iter().collect();
+        assert_eq!(edges[4].apply(start_amount), None);
+
+        let best = check_arbitrage(&edge_refs, start_amount, Some(sol), None).unwrap();
+        assert_eq!(best.edges[0].pool_id, edges[0].pool_id);
+        assert_eq!(best.edges[1].pool_id, edges[3].pool_id);
+        assert!(best.final_amount.abs_diff(start_amount / 100 * 106) < 1_000_000);
+    }
+
+    #[test]
+    fn test_check_arbitrage_rejects_dust_edge_inputs() {
+        let token_a = Pubkey::new_unique();
+        let token_b = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let edge = |from: Pubkey, to: Pubkey, price: f64| {
+            Edge::new(
+                program_id,
+                Pubkey::new_unique(),
+                EdgeSide::LeftToRight,
+                price,
+                Pool::new(&from, 1_000_000_000),
+                Pool::new(&to, 1_000_000_000),
+            )
+        };
+
+        // B is thin: 2^20 atoms of A buy one atom of B, which the second pool only takes
+        // two at a time
+        let buy_b = edge(token_a, token_b, 1.0 / 1_048_576.0);
+        let sell_b = edge(token_b, token_a, 1.1 * 1_048_576.0).with_min_amount_in(2);
+        let edges = [&buy_b, &sell_b];
+
+        let err = check_arbitrage(&edges, 1_048_576, Some(token_a), None).unwrap_err();
+        assert_eq!(err, error!(SolarBError::SwapTooSmall));
+        // The same cycle without the minimum would have been taken
+        let unbounded = edge(token_b, token_a, 1.1 * 1_048_576.0);
+        assert!(check_arbitrage(&[&buy_b, &unbounded], 1_048_576, Some(token_a), None).is_ok());
+
+        let path = check_arbitrage(&edges, 2 * 1_048_576, Some(token_a), None).unwrap();
+        assert_eq!(path.amounts_out[0], 2);
+        // Nothing to take at all still reads as no profit
+        let err = check_arbitrage(&[&buy_b], 2 * 1_048_576, Some(token_a), None).unwrap_err();
+        assert_eq!(err, error!(SolarBError::NoProfitFound));
     }
 }
@@ -1,37 +1,117 @@
 use std::{fmt::Debug, hash::Hash};
 
-use anchor_lang::prelude::Pubkey;
+use anchor_lang::prelude::*;
 
 use super::pool::Pool;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, AnchorSerialize, AnchorDeserialize,
+)]
 pub enum EdgeSide {
     LeftToRight,
     RightToLeft,
 }
 
-#[derive(Clone)]
+/// 1.0 as a Q64.64 price
+const PRICE_SCALE: u128 = 1 << 64;
+const LOW_BITS: u128 = u64::MAX as u128;
+
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
 pub struct Edge {
     pub program: Pubkey,
+    /// Pool the edge trades through, so two pools of one program stay distinct
+    pub pool_id: Pubkey,
     pub side: EdgeSide,
-    pub price: f64, // Stored as scaled integer: actual_price * 1_000_000_000
+    /// Output per unit of input as Q64.64 fixed point, see `Edge::apply`
+    pub price: u128,
     pub left: Pool,
     pub right: Pool,
+    /// Estimated compute units of the swap, 0 where unknown. Breaks ties between paths of
+    /// equal profit and length.
+    pub compute_units: u32,
+    /// Smallest input the pool pays a non-zero output for, see
+    /// `ProgramMeta::min_swap_amount`; 0 where unknown
+    pub min_amount_in: u64,
 }
 
 impl Edge {
-    pub fn new(program: Pubkey, side: EdgeSide, price: f64, left: Pool, right: Pool) -> Self {
+    pub fn new(
+        program: Pubkey,
+        pool_id: Pubkey,
+        side: EdgeSide,
+        price: f64,
+        left: Pool,
+        right: Pool,
+    ) -> Self {
         Edge {
             program,
+            pool_id,
             side,
-            price,
+            price: Self::price_to_q64(price),
             left,
             right,
+            compute_units: 0,
+            min_amount_in: 0,
         }
     }
 
+    pub fn with_compute_units(mut self, compute_units: u32) -> Self {
+        self.compute_units = compute_units;
+        self
+    }
+
+    pub fn with_min_amount_in(mut self, min_amount_in: u64) -> Self {
+        self.min_amount_in = min_amount_in;
+        self
+    }
+
+    /// Stable sort key (program id, pool, then side) that keeps the search order, and so
+    /// the path picked among equally profitable ones, independent of account order
+    pub fn ordering_key(&self) -> (&Pubkey, &Pubkey, &EdgeSide) {
+        (&self.program, &self.pool_id, &self.side)
+    }
+
+    /// Mint the edge takes in. `generate_edges` lays both sides out from the mint going in,
+    /// so `side` only says which of the pool's mints that is: its base for `LeftToRight`,
+    /// its quote for `RightToLeft`. Either way the input is `left`.
+    pub fn input_mint(&self) -> &Pubkey {
+        &self.left.mint_account
+    }
+
+    /// Mint the edge pays out, the counterpart of `input_mint`
+    pub fn output_mint(&self) -> &Pubkey {
+        &self.right.mint_account
+    }
+
+    /// `price` as Q64.64, saturating past `u128::MAX` and flooring negative or NaN prices
+    /// to 0
+    pub fn price_to_q64(price: f64) -> u128 {
+        (price * PRICE_SCALE as f64) as u128
+    }
+
     pub fn get_price(&self) -> f64 {
-        return self.price;
+        return self.price as f64 / PRICE_SCALE as f64;
+    }
+
+    /// Whole output tokens per whole input token, where `get_price` counts atoms. Atom
+    /// prices are what the search multiplies, and the decimals cancel around a cycle, so
+    /// this only matters when comparing prices across pools or showing them.
+    pub fn normalized_price(&self) -> f64 {
+        let shift = i32::from(self.left.decimals) - i32::from(self.right.decimals);
+        self.get_price() * 10f64.powi(shift)
+    }
+
+    /// Output of `amount` at the edge's price, rounded down, or `None` when it doesn't fit
+    /// in a `u128`
+    pub fn apply(&self, amount: u128) -> Option<u128> {
+        // amount * price >> 64 over the full 256-bit product, one 64-bit half at a time
+        let (amount_hi, amount_lo) = (amount >> 64, amount & LOW_BITS);
+        let (price_hi, price_lo) = (self.price >> 64, self.price & LOW_BITS);
+        (amount_hi * price_hi)
+            .checked_mul(PRICE_SCALE)?
+            .checked_add(amount_hi * price_lo)?
+            .checked_add(amount_lo * price_hi)?
+            .checked_add((amount_lo * price_lo) >> 64)
     }
 
     fn get_pools_amount_difference(&self) -> u128 {
@@ -39,7 +119,7 @@ impl Edge {
     }
 
     pub fn compute_amount(&mut self, amount: u128) -> u128 {
-        (amount as f64 * self.get_price()) as u128
+        self.apply(amount).unwrap_or(u128::MAX)
     }
 }
 
@@ -61,6 +141,7 @@ impl Debug for Edge {
 impl PartialEq for Edge {
     fn eq(&self, other: &Edge) -> bool {
         return self.program.eq(&other.program)
+            && self.pool_id.eq(&other.pool_id)
             && self.left.mint_account.eq(&other.left.mint_account)
             && self.right.mint_account.eq(&other.right.mint_account);
     }
@@ -69,10 +150,92 @@ impl PartialEq for Edge {
 impl Hash for Edge {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         let program_id = self.program;
+        let pool_id = self.pool_id;
         let left_pool = self.left.mint_account;
         let right_pool = self.right.mint_account;
-        format!("{} {} {}", program_id, left_pool, right_pool).hash(state);
+        format!("{} {} {} {}", program_id, pool_id, left_pool, right_pool).hash(state);
     }
 }
 
 impl Eq for Edge {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_and_output_mint_follow_the_swap_direction() {
+        let base = Pool::new(&Pubkey::new_unique(), 1_000);
+        let quote = Pool::new(&Pubkey::new_unique(), 2_000);
+        let program = Pubkey::new_unique();
+        let pool_id = Pubkey::new_unique();
+
+        // Selling the pool's base mint
+        let base_in = Edge::new(
+            program,
+            pool_id,
+            EdgeSide::LeftToRight,
+            2.0,
+            base.clone(),
+            quote.clone(),
+        );
+        assert_eq!(base_in.input_mint(), &base.mint_account);
+        assert_eq!(base_in.output_mint(), &quote.mint_account);
+
+        // Selling its quote mint
+        let quote_in = Edge::new(
+            program,
+            pool_id,
+            EdgeSide::RightToLeft,
+            0.5,
+            quote.clone(),
+            base.clone(),
+        );
+        assert_eq!(quote_in.input_mint(), &quote.mint_account);
+        assert_eq!(quote_in.output_mint(), &base.mint_account);
+    }
+
+    fn edge_at(price: f64) -> Edge {
+        Edge::new(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            EdgeSide::LeftToRight,
+            price,
+            Pool::new(&Pubkey::new_unique(), 1_000),
+            Pool::new(&Pubkey::new_unique(), 2_000),
+        )
+    }
+
+    #[test]
+    fn test_price_round_trips_through_q64() {
+        for price in [0.5, 1.0, 2.0, 0.9975, 123_456.789] {
+            assert_eq!(edge_at(price).get_price(), price);
+        }
+        assert_eq!(edge_at(1.0).price, 1 << 64);
+        assert_eq!(edge_at(-1.0).price, 0);
+        assert_eq!(edge_at(f64::NAN).price, 0);
+        assert_eq!(edge_at(f64::INFINITY).price, u128::MAX);
+        // Q64.64 keeps 64 fractional bits, fewer than a tiny f64 price's precision
+        assert!((edge_at(1e-9).get_price() - 1e-9).abs() < 1e-18);
+    }
+
+    #[test]
+    fn test_apply_rounds_down() {
+        assert_eq!(edge_at(2.0).apply(1_000), Some(2_000));
+        assert_eq!(edge_at(0.5).apply(1_001), Some(500));
+        assert_eq!(edge_at(1.0 / 3.0).apply(10), Some(3));
+        assert_eq!(edge_at(0.0).apply(u128::MAX), Some(0));
+    }
+
+    #[test]
+    fn test_apply_near_u128_max_does_not_wrap() {
+        assert_eq!(edge_at(1.0).apply(u128::MAX), Some(u128::MAX));
+        assert_eq!(edge_at(0.5).apply(u128::MAX), Some(u128::MAX / 2));
+        assert_eq!(edge_at(2.0).apply(u128::MAX / 2), Some(u128::MAX - 1));
+        assert_eq!(edge_at(2.0).apply(u128::MAX / 2 + 1), None);
+        assert_eq!(edge_at(1.0 + 1e-9).apply(u128::MAX), None);
+        assert_eq!(edge_at(1e12).apply(u128::MAX / 1_000), None);
+        assert_eq!(edge_at(f64::INFINITY).apply(1 << 64), Some(u128::MAX));
+        assert_eq!(edge_at(f64::INFINITY).apply((1 << 64) + 1), None);
+    }
+}
@@ -40,9 +40,11 @@ impl<'info, T: ProgramMeta + ?Sized> Market<'info, T> {
             0.0
         };
         let program_id = *self.program.get_id();
+        let pool_id = *self.program.get_vaults().0.key;
         vec![
             Edge::new(
                 program_id,
+                pool_id,
                 EdgeSide::LeftToRight,
                 price_left_to_right,
                 self.left.clone(),
@@ -50,6 +52,7 @@ impl<'info, T: ProgramMeta + ?Sized> Market<'info, T> {
             ),
             Edge::new(
                 program_id,
+                pool_id,
                 EdgeSide::RightToLeft,
                 price_right_to_left,
                 self.right.clone(),
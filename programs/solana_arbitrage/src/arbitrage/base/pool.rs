@@ -1,9 +1,11 @@
-use anchor_lang::prelude::Pubkey;
+use anchor_lang::prelude::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, AnchorSerialize, AnchorDeserialize)]
 pub struct Pool {
     pub mint_account: Pubkey,
     pub amount: u128,
+    /// Decimals of `mint_account`, 0 when unknown
+    pub decimals: u8,
 }
 
 impl Pool {
@@ -11,9 +13,15 @@ impl Pool {
         Pool {
             mint_account: *mint_account,
             amount,
+            decimals: 0,
         }
     }
 
+    pub fn with_decimals(mut self, decimals: u8) -> Self {
+        self.decimals = decimals;
+        self
+    }
+
     pub fn get_amount(&self) -> &u128 {
         &self.amount
     }
@@ -1,3 +1,8 @@
-pub mod algo;
+// Legacy f64 search, superseded by `algo_2` and kept only for its tests
+#[allow(dead_code, unused_imports)]
+pub(crate) mod algo;
 pub mod algo_2;
 pub mod base;
+
+pub use algo_2::{check_arbitrage, check_arbitrage_ranked, ArbitragePath};
+pub use base::{Edge, EdgeSide, Pool};
@@ -0,0 +1,381 @@
+//! Off-chain helpers for assembling `initialize` transactions
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::InstructionData as _;
+use solana_compute_budget_interface::ComputeBudgetInstruction;
+
+use std::ops::RangeInclusive;
+
+#[cfg(feature = "lifinity")]
+use crate::programs::LifinityV2;
+#[cfg(feature = "damm-v1")]
+use crate::programs::MeteoraDammV1;
+#[cfg(feature = "damm-v2")]
+use crate::programs::MeteoraDammV2;
+#[cfg(feature = "dlmm")]
+use crate::programs::MeteoraDlmm;
+#[cfg(feature = "phoenix")]
+use crate::programs::Phoenix;
+#[cfg(feature = "pump-amm")]
+use crate::programs::PumpAmm;
+#[cfg(feature = "saber")]
+use crate::programs::Saber;
+use crate::programs::SolarBError;
+use crate::InstructionData;
+
+#[cfg(feature = "client")]
+pub mod path_finder;
+#[cfg(feature = "client")]
+pub use path_finder::{find_arbitrage, EdgeBuilder};
+#[cfg(feature = "client")]
+pub mod sizing;
+#[cfg(feature = "client")]
+pub use sizing::{find_optimal_amount, quote_path, QuoteCache};
+
+/// Venue an instance span is laid out for. Only venues `initialize` dispatches are listed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgramKind {
+    #[cfg(feature = "pump-amm")]
+    PumpAmm,
+    #[cfg(feature = "damm-v1")]
+    MeteoraDammV1,
+    #[cfg(feature = "damm-v2")]
+    MeteoraDammV2,
+    #[cfg(feature = "dlmm")]
+    MeteoraDlmm,
+    #[cfg(feature = "lifinity")]
+    LifinityV2,
+    #[cfg(feature = "phoenix")]
+    Phoenix,
+    #[cfg(feature = "saber")]
+    Saber,
+}
+
+impl ProgramKind {
+    pub fn program_id(self) -> Pubkey {
+        match self {
+            #[cfg(feature = "pump-amm")]
+            ProgramKind::PumpAmm => PumpAmm::PROGRAM_ID,
+            #[cfg(feature = "damm-v1")]
+            ProgramKind::MeteoraDammV1 => MeteoraDammV1::PROGRAM_ID,
+            #[cfg(feature = "damm-v2")]
+            ProgramKind::MeteoraDammV2 => MeteoraDammV2::PROGRAM_ID,
+            #[cfg(feature = "dlmm")]
+            ProgramKind::MeteoraDlmm => MeteoraDlmm::PROGRAM_ID,
+            #[cfg(feature = "lifinity")]
+            ProgramKind::LifinityV2 => LifinityV2::PROGRAM_ID,
+            #[cfg(feature = "phoenix")]
+            ProgramKind::Phoenix => Phoenix::PROGRAM_ID,
+            #[cfg(feature = "saber")]
+            ProgramKind::Saber => Saber::PROGRAM_ID,
+        }
+    }
+
+    /// Span lengths the venue's adapter accepts, program id included
+    ///
+    /// Pump AMM takes an optional vault ATA and authority after its 16 accounts, DAMM v2
+    /// an optional referral token account, and DLMM any number of bin arrays.
+    pub fn expected_len(self) -> RangeInclusive<usize> {
+        match self {
+            #[cfg(feature = "pump-amm")]
+            ProgramKind::PumpAmm => PumpAmm::EXPECTED_ACCOUNTS..=18,
+            #[cfg(feature = "damm-v1")]
+            ProgramKind::MeteoraDammV1 => MeteoraDammV1::EXPECTED_ACCOUNTS..=15,
+            #[cfg(feature = "damm-v2")]
+            ProgramKind::MeteoraDammV2 => MeteoraDammV2::EXPECTED_ACCOUNTS..=9,
+            #[cfg(feature = "dlmm")]
+            ProgramKind::MeteoraDlmm => MeteoraDlmm::EXPECTED_ACCOUNTS..=usize::MAX,
+            #[cfg(feature = "lifinity")]
+            ProgramKind::LifinityV2 => LifinityV2::EXPECTED_ACCOUNTS..=12,
+            #[cfg(feature = "phoenix")]
+            ProgramKind::Phoenix => Phoenix::EXPECTED_ACCOUNTS..=8,
+            #[cfg(feature = "saber")]
+            ProgramKind::Saber => Saber::EXPECTED_ACCOUNTS..=Saber::EXPECTED_ACCOUNTS,
+        }
+    }
+}
+
+/// Lays out instance spans so the account metas and `accounts_length` always agree
+///
+/// Each span starts with its venue's program id, which goes in readonly; the rest of the
+/// span goes in writable, as the adapters forward them to the venue's swap.
+#[derive(Clone, Debug, Default)]
+pub struct AccountsLayoutBuilder {
+    accounts: Vec<AccountMeta>,
+    accounts_length: Vec<u32>,
+}
+
+impl AccountsLayoutBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append the span of one instance, rejecting a span its adapter would not parse
+    pub fn push(&mut self, kind: ProgramKind, span: Vec<Pubkey>) -> Result<&mut Self> {
+        require!(
+            kind.expected_len().contains(&span.len()),
+            SolarBError::AccountSpanMismatch
+        );
+        require!(span[0] == kind.program_id(), SolarBError::UnknownProgram);
+        let span_len =
+            u32::try_from(span.len()).map_err(|_| error!(SolarBError::InvalidAccountsLength))?;
+
+        self.accounts
+            .push(AccountMeta::new_readonly(span[0], false));
+        self.accounts
+            .extend(span[1..].iter().map(|key| AccountMeta::new(*key, false)));
+        self.accounts_length.push(span_len);
+        Ok(self)
+    }
+
+    /// Account metas of every span in push order, with the matching `accounts_length`
+    pub fn build(self) -> (Vec<AccountMeta>, Vec<u32>) {
+        (self.accounts, self.accounts_length)
+    }
+}
+
+/// Instructions of an arbitrage transaction: the compute unit limit and price, then
+/// `initialize` over the payer, the authority, the user accounts and one span per instance.
+///
+/// `authority` owns the user token accounts and signs the swaps; left `None` or equal to
+/// `payer`, the payer plays both roles. `user_accounts` holds the (mint, token program,
+/// user token account) of every mint the path may trade, and `instances_accounts` the
+/// account metas of each instance in the order its adapter parses them.
+/// `data.accounts_length`, `data.user_mint_count` and `data.separate_authority` are
+/// filled in from those, so callers only set the remaining options.
+pub fn build_arbitrage_tx(
+    payer: &Pubkey,
+    authority: Option<&Pubkey>,
+    user_accounts: &[(Pubkey, Pubkey, Pubkey)],
+    instances_accounts: &[Vec<AccountMeta>],
+    mut data: InstructionData,
+    cu_limit: u32,
+    cu_price: u64,
+) -> Result<Vec<Instruction>> {
+    data.user_mint_count = u8::try_from(user_accounts.len())
+        .map_err(|_| error!(SolarBError::InvalidAccountsLength))?;
+    data.accounts_length = instances_accounts
+        .iter()
+        .map(|span| u32::try_from(span.len()))
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| error!(SolarBError::InvalidAccountsLength))?;
+
+    let authority = authority.filter(|authority| *authority != payer);
+    data.separate_authority = authority.is_some();
+
+    let mut accounts = vec![AccountMeta::new(*payer, true)];
+    accounts.extend(authority.map(|authority| AccountMeta::new(*authority, true)));
+    for (mint, token_program, token_account) in user_accounts {
+        accounts.push(AccountMeta::new_readonly(*mint, false));
+        accounts.push(AccountMeta::new_readonly(*token_program, false));
+        accounts.push(AccountMeta::new(*token_account, false));
+    }
+    accounts.extend(instances_accounts.iter().flatten().cloned());
+
+    let initialize = Instruction {
+        program_id: crate::ID,
+        accounts,
+        data: crate::instruction::Initialize { data }.data(),
+    };
+    Ok(vec![
+        ComputeBudgetInstruction::set_compute_unit_limit(cu_limit),
+        ComputeBudgetInstruction::set_compute_unit_price(cu_price),
+        initialize,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Discriminator;
+
+    fn options() -> InstructionData {
+        InstructionData {
+            accounts_length: Vec::new(),
+            epoch: 0,
+            selected_instances: None,
+            max_plausible_profit: None,
+            max_compute_units: None,
+            wrap_native: false,
+            try_alternatives: false,
+            user_mint_count: 0,
+            recompute_quotes: false,
+            profit_mint: None,
+            min_liquidity: 0,
+            start_amount: 1_000_000,
+            slippage_bps: 0,
+            min_profit: 0,
+            skip_unknown: false,
+            max_price_deviation_bps: 0,
+            start_tokens: Vec::new(),
+            sweep_dust: false,
+            max_edges: 0,
+            bin_array_counts: Vec::new(),
+            max_bins: 0,
+            separate_authority: false,
+        }
+    }
+
+    fn span(len: usize) -> Vec<AccountMeta> {
+        (0..len)
+            .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+            .collect()
+    }
+
+    #[test]
+    fn test_build_arbitrage_tx_lays_out_initialize() {
+        let payer = Pubkey::new_unique();
+        let user_accounts: Vec<(Pubkey, Pubkey, Pubkey)> = (0..2)
+            .map(|_| {
+                (
+                    Pubkey::new_unique(),
+                    anchor_spl::token::ID,
+                    Pubkey::new_unique(),
+                )
+            })
+            .collect();
+        let instances_accounts = vec![span(9), span(13)];
+
+        let instructions = build_arbitrage_tx(
+            &payer,
+            None,
+            &user_accounts,
+            &instances_accounts,
+            options(),
+            400_000,
+            10_000,
+        )
+        .unwrap();
+        assert_eq!(
+            instructions[..2],
+            [
+                ComputeBudgetInstruction::set_compute_unit_limit(400_000),
+                ComputeBudgetInstruction::set_compute_unit_price(10_000),
+            ]
+        );
+
+        let initialize = &instructions[2];
+        assert_eq!(initialize.program_id, crate::ID);
+        // Payer and two user account triples, then the spans
+        assert_eq!(initialize.accounts.len(), 9 + 13 + 7);
+        assert_eq!(initialize.accounts[0], AccountMeta::new(payer, true));
+        assert_eq!(initialize.accounts[7..], instances_accounts.concat()[..]);
+
+        let (discriminator, mut encoded) = initialize.data.split_at(8);
+        assert_eq!(discriminator, crate::instruction::Initialize::DISCRIMINATOR);
+        let data = InstructionData::deserialize(&mut encoded).unwrap();
+        assert_eq!(data.accounts_length, vec![9, 13]);
+        assert_eq!(data.user_mint_count, 2);
+        assert!(!data.separate_authority);
+    }
+
+    #[test]
+    fn test_build_arbitrage_tx_places_separate_authority() {
+        let payer = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let user_accounts = [(
+            Pubkey::new_unique(),
+            anchor_spl::token::ID,
+            Pubkey::new_unique(),
+        )];
+        let instances_accounts = vec![span(9)];
+        let build = |authority| {
+            let instructions = build_arbitrage_tx(
+                &payer,
+                authority,
+                &user_accounts,
+                &instances_accounts,
+                options(),
+                400_000,
+                0,
+            )
+            .unwrap();
+            let initialize = instructions[2].clone();
+            let data = InstructionData::deserialize(&mut &initialize.data[8..]).unwrap();
+            (initialize.accounts, data.separate_authority)
+        };
+
+        let (accounts, separate_authority) = build(Some(&authority));
+        assert!(separate_authority);
+        assert_eq!(accounts.len(), 2 + 3 + 9);
+        assert_eq!(accounts[0], AccountMeta::new(payer, true));
+        assert_eq!(accounts[1], AccountMeta::new(authority, true));
+        assert_eq!(accounts[5..], instances_accounts[0][..]);
+
+        // The payer as its own authority is laid out as if none was given
+        assert_eq!(build(Some(&payer)), build(None));
+        assert!(!build(None).1);
+    }
+
+    #[test]
+    fn test_build_arbitrage_tx_counts_every_span() {
+        let user_accounts = [(
+            Pubkey::new_unique(),
+            anchor_spl::token::ID,
+            Pubkey::new_unique(),
+        ); 3];
+        for spans in [vec![], vec![6], vec![9, 13, 6]] {
+            let instances_accounts: Vec<Vec<AccountMeta>> =
+                spans.iter().map(|&len| span(len)).collect();
+            let instructions = build_arbitrage_tx(
+                &Pubkey::new_unique(),
+                None,
+                &user_accounts,
+                &instances_accounts,
+                options(),
+                1_400_000,
+                0,
+            )
+            .unwrap();
+            assert_eq!(
+                instructions[2].accounts.len(),
+                spans.iter().sum::<usize>() + 1 + 3 * user_accounts.len()
+            );
+        }
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_layout_builder_rejects_malformed_spans() {
+        let kind = ProgramKind::MeteoraDammV2;
+        let span = |len: usize| -> Vec<Pubkey> {
+            std::iter::once(kind.program_id())
+                .chain((1..len).map(|_| Pubkey::new_unique()))
+                .collect()
+        };
+
+        let mut builder = AccountsLayoutBuilder::new();
+        for len in [7, 10] {
+            assert_eq!(
+                builder.push(kind, span(len)).unwrap_err(),
+                error!(SolarBError::AccountSpanMismatch)
+            );
+        }
+        let mut foreign = span(9);
+        foreign[0] = Pubkey::new_unique();
+        assert_eq!(
+            builder.push(kind, foreign).unwrap_err(),
+            error!(SolarBError::UnknownProgram)
+        );
+
+        // Rejected spans leave nothing behind
+        builder
+            .push(kind, span(8))
+            .unwrap()
+            .push(kind, span(9))
+            .unwrap();
+        let (accounts, accounts_length) = builder.build();
+        assert_eq!(accounts_length, vec![8, 9]);
+        assert_eq!(accounts.len(), 17);
+        assert_eq!(
+            accounts[0],
+            AccountMeta::new_readonly(kind.program_id(), false)
+        );
+        assert!(accounts[1..8].iter().all(|meta| meta.is_writable));
+        assert_eq!(
+            accounts[8],
+            AccountMeta::new_readonly(kind.program_id(), false)
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! The on-chain arbitrage search, run over reserves and fees fetched by RPC instead of the
+//! pools' accounts, so a bot can tell whether a transaction is worth sending
+
+use anchor_lang::prelude::*;
+
+pub use crate::arbitrage::algo_2::{check_arbitrage, ArbitragePath};
+pub use crate::arbitrage::base::{Edge, EdgeSide, Pool};
+use crate::programs::programs::marginal_price;
+
+/// Both edges of one constant-product pool, priced from its reserves the way
+/// `generate_edges` prices them from the vaults: at the margin, less the fee on the input
+#[derive(Clone, Debug)]
+pub struct EdgeBuilder {
+    program: Pubkey,
+    pool_id: Pubkey,
+    base: Pool,
+    quote: Pool,
+    fee_rate: f64,
+}
+
+impl EdgeBuilder {
+    /// The pool gets a unique id, as the search only needs to tell pools apart; set the
+    /// on-chain base vault with `pool_id` to match the edges the program would generate
+    pub fn from_reserves(
+        program: Pubkey,
+        base_mint: Pubkey,
+        base_reserve: u128,
+        quote_mint: Pubkey,
+        quote_reserve: u128,
+        fee_bps: u16,
+    ) -> Self {
+        EdgeBuilder {
+            program,
+            pool_id: Pubkey::new_unique(),
+            base: Pool::new(&base_mint, base_reserve),
+            quote: Pool::new(&quote_mint, quote_reserve),
+            fee_rate: f64::from(fee_bps) / 10_000.0,
+        }
+    }
+
+    pub fn pool_id(mut self, pool_id: Pubkey) -> Self {
+        self.pool_id = pool_id;
+        self
+    }
+
+    /// The base-in edge, then the quote-in one
+    pub fn build(self) -> [Edge; 2] {
+        let base_in = marginal_price(self.base.amount, self.quote.amount, self.fee_rate);
+        let quote_in = marginal_price(self.quote.amount, self.base.amount, self.fee_rate);
+        [
+            Edge::new(
+                self.program,
+                self.pool_id,
+                EdgeSide::LeftToRight,
+                base_in,
+                self.base.clone(),
+                self.quote.clone(),
+            ),
+            Edge::new(
+                self.program,
+                self.pool_id,
+                EdgeSide::RightToLeft,
+                quote_in,
+                self.quote,
+                self.base,
+            ),
+        ]
+    }
+}
+
+/// Best path through `edges` for `start_amount`, searched in the order `get_edges` hands
+/// them to `run_arbitrage`, so ties resolve as they would on-chain
+pub fn find_arbitrage(
+    edges: &[Edge],
+    start_amount: u128,
+    start_token: Option<Pubkey>,
+) -> Result<ArbitragePath> {
+    let mut edge_refs: Vec<&Edge> = edges.iter().collect();
+    edge_refs.sort_by(|a, b| a.ordering_key().cmp(&b.ordering_key()));
+    check_arbitrage(&edge_refs, start_amount, start_token, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::programs::SolarBError;
+
+    #[test]
+    fn test_from_reserves_prices_both_sides() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+        let pool_id = Pubkey::new_unique();
+        let [base_in, quote_in] = EdgeBuilder::from_reserves(
+            Pubkey::new_unique(),
+            base_mint,
+            1_000_000_000,
+            quote_mint,
+            2_000_000_000,
+            25,
+        )
+        .pool_id(pool_id)
+        .build();
+
+        assert_eq!(base_in.input_mint(), &base_mint);
+        assert_eq!(quote_in.input_mint(), &quote_mint);
+        assert!(base_in.pool_id == pool_id && quote_in.pool_id == pool_id);
+        assert!((base_in.get_price() - 2.0 * 0.9975).abs() < 1e-12);
+        assert!((quote_in.get_price() - 0.5 * 0.9975).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_find_arbitrage_over_raw_reserves() {
+        let sol = Pubkey::new_unique();
+        let usdc = Pubkey::new_unique();
+        let program = Pubkey::new_unique();
+        let pool = |sol_reserve: u128, usdc_reserve: u128| {
+            EdgeBuilder::from_reserves(program, sol, sol_reserve, usdc, usdc_reserve, 25).build()
+        };
+
+        // 10% apart, so selling SOL into the dearer pool and buying it back pays
+        let edges = [
+            pool(1_000_000_000, 2_000_000_000),
+            pool(1_000_000_000, 2_200_000_000),
+        ]
+        .concat();
+        let path = find_arbitrage(&edges, 1_000_000, Some(sol)).unwrap();
+        assert!(path.profit > 0);
+        assert_eq!(path.edges.len(), 2);
+        assert_eq!(path.edges[0].input_mint(), &sol);
+        assert_eq!(path.edges[1].output_mint(), &sol);
+        assert_ne!(path.edges[0].pool_id, path.edges[1].pool_id);
+
+        // The same price on both pools leaves only fees to pay
+        let edges = [
+            pool(1_000_000_000, 2_000_000_000),
+            pool(3_000_000_000, 6_000_000_000),
+        ]
+        .concat();
+        assert_eq!(
+            find_arbitrage(&edges, 1_000_000, Some(sol)).unwrap_err(),
+            error!(SolarBError::NoProfitFound)
+        );
+    }
+}
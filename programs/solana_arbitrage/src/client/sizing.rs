@@ -0,0 +1,309 @@
+//! Sizing a path's start amount off-chain, by searching the size that maximises its quoted
+//! profit. `initialize` quotes each hop once at the size it's given and doesn't size paths.
+
+use anchor_lang::prelude::*;
+use std::collections::HashMap;
+
+use crate::arbitrage::base::{Edge, EdgeSide};
+use crate::find_edge_instance;
+use crate::programs::ProgramMeta;
+
+/// Ternary search for the start size maximising `profit_at` within `[lo, hi]`, returned with
+/// its profit, or `None` when no size in the bracket quotes.
+/// Assumes profit is unimodal in the start size, which holds for constant-product paths.
+///
+/// A failed quote (e.g. the size exceeds a pool's reserves) means that size and every larger
+/// one are infeasible, so the bracket is clamped below it rather than the search aborting.
+fn search_amount_bracket<F>(
+    mut lo: u128,
+    mut hi: u128,
+    tolerance: u128,
+    profit_at: &mut F,
+) -> Option<(u128, i128)>
+where
+    F: FnMut(u128) -> Option<i128>,
+{
+    while hi - lo > tolerance.max(2) {
+        let third = (hi - lo) / 3;
+        let left = lo + third;
+        let right = hi - third;
+        let Some(left_profit) = profit_at(left) else {
+            hi = left - 1;
+            continue;
+        };
+        let Some(right_profit) = profit_at(right) else {
+            hi = right - 1;
+            continue;
+        };
+        if left_profit < right_profit {
+            lo = left;
+        } else {
+            hi = right;
+        }
+    }
+    // `lo` only ever moves to a size that quoted, so it stands in for an infeasible midpoint
+    let amount = lo + (hi - lo) / 2;
+    profit_at(amount)
+        .map(|profit| (amount, profit))
+        .or_else(|| profit_at(lo).map(|profit| (lo, profit)))
+}
+
+/// Finds the start size in `[min_amount, max_amount]` that maximises `profit_at`, to within
+/// `tolerance`, and returns it with its profit. Sizes whose quote fails count as
+/// infeasible and cap the search below them; `None` means not even the smallest size quotes.
+///
+/// `hint` (e.g. the previous run's optimum) seeds the search with the bracket
+/// `[hint / 2, hint * 2]`, which saves quote evaluations when consecutive opportunities
+/// are similar. If the optimum lands on an inner edge of that bracket it lies outside it,
+/// and the full range is searched instead.
+pub fn find_optimal_amount<F>(
+    min_amount: u128,
+    max_amount: u128,
+    tolerance: u128,
+    hint: Option<u128>,
+    mut profit_at: F,
+) -> Option<(u128, i128)>
+where
+    F: FnMut(u128) -> Option<i128>,
+{
+    if min_amount > max_amount {
+        return None;
+    }
+
+    let seeded = hint.and_then(|hint| {
+        let lo = (hint / 2).clamp(min_amount, max_amount);
+        let hi = hint.saturating_mul(2).clamp(min_amount, max_amount);
+        let (amount, profit) = search_amount_bracket(lo, hi, tolerance, &mut profit_at)?;
+        let on_inner_edge = (lo > min_amount && amount - lo <= tolerance)
+            || (hi < max_amount && hi - amount <= tolerance);
+        (!on_inner_edge).then_some((amount, profit))
+    });
+
+    seeded.or_else(|| search_amount_bracket(min_amount, max_amount, tolerance, &mut profit_at))
+}
+
+/// Exact-in quotes already computed, keyed by (pool, amount in, side)
+///
+/// A sizing search such as `find_optimal_amount` revisits the same amounts, and each quote
+/// re-reads pool state. Identical quotes are computed once; failed quotes aren't stored.
+#[derive(Debug, Default)]
+pub struct QuoteCache {
+    quotes: HashMap<(Pubkey, u64, EdgeSide), u64>,
+    hits: u32,
+    misses: u32,
+}
+
+impl QuoteCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cached output for `amount_in` through `pool` on `side`, running `quote` on a miss
+    pub fn get_or_quote<F>(
+        &mut self,
+        pool: Pubkey,
+        amount_in: u64,
+        side: EdgeSide,
+        quote: F,
+    ) -> Result<u64>
+    where
+        F: FnOnce() -> Result<u64>,
+    {
+        let key = (pool, amount_in, side);
+        if let Some(&amount_out) = self.quotes.get(&key) {
+            self.hits += 1;
+            return Ok(amount_out);
+        }
+        let amount_out = quote()?;
+        self.misses += 1;
+        self.quotes.insert(key, amount_out);
+        Ok(amount_out)
+    }
+
+    /// Quotes served from the cache
+    pub fn hits(&self) -> u32 {
+        self.hits
+    }
+
+    /// Quotes that had to be computed
+    pub fn misses(&self) -> u32 {
+        self.misses
+    }
+}
+
+/// Output of trading `amount_in` along `edges`, quoting each hop exact-in through its pool
+///
+/// Hops already quoted for the same pool, amount and side come from `cache`, so a search
+/// sizing a path over many amounts reads each pool once per distinct quote.
+pub fn quote_path<'info>(
+    edges: &[Edge],
+    instances: &[Box<dyn ProgramMeta + 'info>],
+    amount_in: u64,
+    clock: &Clock,
+    cache: &mut QuoteCache,
+) -> Result<u64> {
+    let mut amount = amount_in;
+    for edge in edges {
+        let instance = &instances[find_edge_instance(instances, edge)?];
+        amount = cache.get_or_quote(edge.pool_id, amount, edge.side.clone(), || {
+            instance.swap_base_in(*edge.input_mint(), amount, clock.clone())
+        })?;
+    }
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::programs::SolarBError;
+
+    // Profit of a round trip through two constant-product pools quoting opposite prices
+    fn round_trip_profit(amount_in: u128) -> Option<i128> {
+        let swap = |amount: u128, reserve_in: u128, reserve_out: u128| {
+            let out = reserve_out.checked_mul(amount)? / reserve_in.checked_add(amount)?;
+            Some(out)
+        };
+        let mid = swap(amount_in, 1_000_000_000_000, 1_100_000_000_000)?;
+        let out = swap(mid, 1_000_000_000_000, 1_000_000_000_000)?;
+        Some(out as i128 - amount_in as i128)
+    }
+
+    #[test]
+    fn test_find_optimal_amount_seed_reduces_quotes() {
+        let evaluations = std::cell::Cell::new(0u32);
+        let counted = |amount| {
+            evaluations.set(evaluations.get() + 1);
+            round_trip_profit(amount)
+        };
+
+        let (cold_amount, cold_profit) =
+            find_optimal_amount(1_000, 1_000_000_000_000, 1_000, None, counted).unwrap();
+        let cold_evaluations = evaluations.replace(0);
+        assert!(cold_profit > 0);
+
+        // Last run's optimum, slightly off as it would be after the pools moved
+        let hint = cold_amount + cold_amount / 100;
+        let (seeded_amount, seeded_profit) =
+            find_optimal_amount(1_000, 1_000_000_000_000, 1_000, Some(hint), counted).unwrap();
+        let seeded_evaluations = evaluations.get();
+
+        assert!(
+            seeded_evaluations < cold_evaluations,
+            "seeded={} cold={}",
+            seeded_evaluations,
+            cold_evaluations
+        );
+        // Profit is flat around the optimum, so compare sizes relatively
+        assert!(seeded_amount.abs_diff(cold_amount) <= cold_amount / 10_000);
+        assert!(seeded_profit.abs_diff(cold_profit) <= 10);
+    }
+
+    #[test]
+    fn test_find_optimal_amount_falls_back_when_hint_is_off() {
+        let (cold_amount, cold_profit) =
+            find_optimal_amount(1_000, 1_000_000_000_000, 1_000, None, round_trip_profit).unwrap();
+
+        for hint in [cold_amount / 100, cold_amount * 100] {
+            let (amount, profit) = find_optimal_amount(
+                1_000,
+                1_000_000_000_000,
+                1_000,
+                Some(hint),
+                round_trip_profit,
+            )
+            .unwrap();
+            assert!(
+                amount.abs_diff(cold_amount) <= cold_amount / 10_000,
+                "hint={}",
+                hint
+            );
+            assert!(profit.abs_diff(cold_profit) <= 10, "hint={}", hint);
+        }
+    }
+
+    #[test]
+    fn test_find_optimal_amount_clamps_below_infeasible_sizes() {
+        // Pools so shallow that sizes past `reserve` fail to quote, well short of the
+        // 23_200_000_000 the round trip would otherwise peak at
+        let reserve = 10_000_000_000;
+        let capped = |amount: u128| {
+            if amount > reserve {
+                return None;
+            }
+            round_trip_profit(amount)
+        };
+
+        // Half the range is infeasible; profit still rises up to the last feasible size
+        let (amount, profit) =
+            find_optimal_amount(1_000, 2 * reserve, 1_000, None, capped).unwrap();
+        assert!(
+            amount <= reserve && reserve - amount <= 1_000,
+            "amount={}",
+            amount
+        );
+        assert_eq!(Some(profit), round_trip_profit(amount));
+
+        // A hint past the feasible sizes lands on them too
+        let (hinted, _) =
+            find_optimal_amount(1_000, 2 * reserve, 1_000, Some(reserve * 3 / 2), capped).unwrap();
+        assert!(
+            hinted <= reserve && reserve - hinted <= 1_000,
+            "hinted={}",
+            hinted
+        );
+
+        // Nothing quotes at all
+        assert!(find_optimal_amount(1_000, 2 * reserve, 1_000, None, |_| None).is_none());
+    }
+
+    #[test]
+    fn test_quote_cache_computes_identical_quotes_once() {
+        let pool = Pubkey::new_unique();
+        let quotes = std::cell::Cell::new(0u32);
+        let quote = |amount_in: u64| {
+            quotes.set(quotes.get() + 1);
+            Ok(round_trip_profit(amount_in as u128).unwrap() as u64)
+        };
+
+        let mut cache = QuoteCache::new();
+        let first = cache
+            .get_or_quote(pool, 5_000_000_000, EdgeSide::LeftToRight, || {
+                quote(5_000_000_000)
+            })
+            .unwrap();
+        let second = cache
+            .get_or_quote(pool, 5_000_000_000, EdgeSide::LeftToRight, || {
+                quote(5_000_000_000)
+            })
+            .unwrap();
+        assert_eq!(first.to_le_bytes(), second.to_le_bytes());
+        assert_eq!(quotes.get(), 1);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // The other side of the pool and another amount are separate quotes
+        cache
+            .get_or_quote(pool, 5_000_000_000, EdgeSide::RightToLeft, || {
+                quote(5_000_000_000)
+            })
+            .unwrap();
+        cache
+            .get_or_quote(pool, 6_000_000_000, EdgeSide::LeftToRight, || {
+                quote(6_000_000_000)
+            })
+            .unwrap();
+        assert_eq!(quotes.get(), 3);
+
+        // A failed quote is retried rather than remembered
+        let failing = || Err(error!(SolarBError::MathOverflow));
+        assert!(cache
+            .get_or_quote(pool, 7, EdgeSide::LeftToRight, failing)
+            .is_err());
+        assert_eq!(
+            cache
+                .get_or_quote(pool, 7, EdgeSide::LeftToRight, || Ok(7))
+                .unwrap(),
+            7
+        );
+        assert_eq!((cache.hits(), cache.misses()), (1, 4));
+    }
+}
@@ -1,21 +1,391 @@
+//! On-chain arbitrage across the supported DEX adapters
+//!
+//! The search, its edges and the adapters are re-exported here, so callers don't depend on
+//! the module layout:
+//!
+//! ```
+//! use anchor_lang::prelude::Pubkey;
+//! use solana_arbitrage::{check_arbitrage, ArbitragePath, Edge, EdgeSide, Pool};
+//! use solana_arbitrage::{find_program_instance, ProgramMeta, SolarBError};
+//!
+//! // SOL buys 110 USDC on one pool and costs 100 on the other
+//! let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+//! let sell_sol = Edge::new(
+//!     Pubkey::new_unique(),
+//!     Pubkey::new_unique(),
+//!     EdgeSide::LeftToRight,
+//!     110.0,
+//!     Pool::new(&sol, 1_000_000_000_000),
+//!     Pool::new(&usdc, 110_000_000_000_000),
+//! );
+//! let buy_sol = Edge::new(
+//!     Pubkey::new_unique(),
+//!     Pubkey::new_unique(),
+//!     EdgeSide::RightToLeft,
+//!     0.01,
+//!     Pool::new(&usdc, 100_000_000_000_000),
+//!     Pool::new(&sol, 1_000_000_000_000),
+//! );
+//! let path: ArbitragePath =
+//!     check_arbitrage(&[&sell_sol, &buy_sol], 1_000_000_000, Some(sol), None)?;
+//! assert!(path.profit > 0);
+//!
+//! let unknown = find_program_instance(&Pubkey::new_unique(), &[], None, 0);
+//! assert_eq!(unknown.err(), Some(SolarBError::UnknownProgram.into()));
+//! # let _: Option<&dyn ProgramMeta> = None;
+//! # Ok::<(), anchor_lang::error::Error>(())
+//! ```
+
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::Instruction, program::invoke, program_pack::Pack};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token::spl_token::{self, native_mint};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+};
+use std::collections::HashSet;
 
 pub mod arbitrage;
+#[cfg(not(target_os = "solana"))]
+pub mod client;
 pub mod math;
 pub mod programs;
 pub mod utils;
 
-use arbitrage::algo_2::{check_arbitrage, ArbitragePath};
-use arbitrage::base::{Edge, EdgeSide, Pool};
-use programs::{MeteoraDammV1, MeteoraDammV2, MeteoraDlmm, ProgramMeta, PumpAmm, SolarBError};
+use arbitrage::algo_2::{
+    MAX_ALTERNATIVE_PATHS, MAX_PATH_COMPUTE_UNITS, MAX_PLAUSIBLE_PROFIT, MAX_SEARCH_EDGES,
+};
+pub use arbitrage::{check_arbitrage, check_arbitrage_ranked, ArbitragePath, Edge, EdgeSide, Pool};
+#[cfg(feature = "lifinity")]
+pub use programs::LifinityV2;
+#[cfg(feature = "damm-v1")]
+pub use programs::MeteoraDammV1;
+#[cfg(feature = "damm-v2")]
+pub use programs::MeteoraDammV2;
+#[cfg(feature = "dlmm")]
+pub use programs::MeteoraDlmm;
+#[cfg(feature = "phoenix")]
+pub use programs::Phoenix;
+#[cfg(feature = "pump-amm")]
+pub use programs::PumpAmm;
+#[cfg(feature = "raydium-cpmm")]
+pub use programs::RaydiumCPMM;
+#[cfg(feature = "saber")]
+pub use programs::Saber;
+pub use programs::{PriceSource, ProgramMeta, SolarBError};
 use utils::utils::parse_token_account;
 
 declare_id!("Ckgi61iKuKeVLfCgAuqaURw18e52D7SvqVj9TUw6NftF");
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct InstructionData {
-    pub accounts_length: [u32; 5],
+    pub accounts_length: Vec<u32>,
+    /// Epoch whose Token-2022 transfer fee rates the quotes apply, so a rate change
+    /// scheduled for the next epoch can be priced ahead of it; 0 uses the cluster's epoch
     pub epoch: u16,
+    /// Indices of the parsed instances taking part in the run; `None` uses all of them.
+    /// Unselected spans are still parsed so the account layout stays the same.
+    pub selected_instances: Option<Vec<u32>>,
+    /// Profit, in `profit_mint` when that is set, above which the run aborts instead of
+    /// trading; `None` uses `MAX_PLAUSIBLE_PROFIT` of the start token
+    pub max_plausible_profit: Option<u64>,
+    /// Estimated compute units the path's swaps may use; `None` uses `MAX_PATH_COMPUTE_UNITS`
+    pub max_compute_units: Option<u32>,
+    /// Sync the wSOL account before and close it after the run when the path starts from
+    /// native SOL; the lamports to wrap must already be in the account
+    pub wrap_native: bool,
+    /// Consider the runner-up paths too and take the best one within the compute budget and
+    /// `max_plausible_profit`
+    pub try_alternatives: bool,
+    /// Number of (mint, token program, user token account) triples following the payer
+    /// (and authority, see `separate_authority`), one per mint the path may trade; at
+    /// least 2, and 3 for a triangular path
+    pub user_mint_count: u8,
+    /// Quote each edge right before its swap, on what the previous swap actually paid out.
+    /// Otherwise every edge is quoted once, from the start amount, before the first swap.
+    pub recompute_quotes: bool,
+    /// Mint the profit is reported and checked against `max_plausible_profit` in, when it
+    /// isn't the start token; converted at the best price an edge quotes from the start token
+    pub profit_mint: Option<Pubkey>,
+    /// Pools holding less than this on either side are left out of the search, as their
+    /// price impact makes for misleading opportunities. 0 keeps every pool.
+    pub min_liquidity: u128,
+    /// Amount of the start token the search tries the paths with
+    pub start_amount: u64,
+    /// How far, in basis points, each swap may pay out below its quote before it reverts
+    pub slippage_bps: u16,
+    /// Least profit, in start token units, worth trading; 0 keeps the search's threshold
+    pub min_profit: u64,
+    /// Leave out spans whose program isn't a supported venue instead of failing the whole
+    /// parse. `selected_instances` then counts only the instances that were parsed.
+    pub skip_unknown: bool,
+    /// How far, in basis points, each swap's quoted rate may stray from its edge's marginal
+    /// price either way before execution aborts, against a pool quoting absurd amounts;
+    /// 0 skips the check
+    pub max_price_deviation_bps: u32,
+    /// Start tokens to search from, each with its own start amount, in place of
+    /// `start_amount` from any token; the single most profitable path across them is
+    /// traded, its profit compared in `profit_mint`, which start tokens of more than one
+    /// mint require
+    pub start_tokens: Vec<(Pubkey, u128)>,
+    /// Close the wSOL account after a path from and back to native SOL, returning its
+    /// lamports, dust profit included, to the authority. Ignored when `wrap_native` already
+    /// closes it.
+    pub sweep_dust: bool,
+    /// Edges the search goes through at most, keeping those of the deepest pools by the
+    /// product of their reserves; 0 keeps every edge. Past `MAX_SEARCH_EDGES` the
+    /// instruction fails either way.
+    pub max_edges: u32,
+    /// Buy and sell bin array counts of each span, in `accounts_length` order, splitting a
+    /// DLMM span's bin arrays without the SOL mint separator. Spans past its end or left
+    /// `None` use the separator; other venues ignore their entry.
+    pub bin_array_counts: Vec<Option<(u8, u8)>>,
+    /// Bins a DLMM exact-in quote may cross before failing with `QuoteTooDeep`, so a swap
+    /// deep into the book can't use up the compute budget; 0 sets no limit
+    pub max_bins: u32,
+    /// The header carries a trade authority right after the fee payer. The authority owns
+    /// the user token accounts and signs the swaps; the fee payer only funds the token
+    /// accounts execution creates. Without it the payer plays both roles.
+    pub separate_authority: bool,
+}
+
+/// `InstructionData::start_amount` the builder defaults to, 1 SOL in lamports
+pub const DEFAULT_START_AMOUNT: u64 = 1_000_000_000;
+/// `InstructionData::slippage_bps` the builder defaults to
+pub const DEFAULT_SLIPPAGE_BPS: u16 = 50;
+
+impl InstructionData {
+    /// Builder starting from the defaults: a 1 SOL start amount, 50 bps slippage, the
+    /// search's own profit threshold, the cluster's epoch, two user mints, every instance
+    /// and pool, and no optional checks
+    pub fn builder() -> InstructionDataBuilder {
+        InstructionDataBuilder::default()
+    }
+}
+
+/// Chained setters over `InstructionData`, see `InstructionData::builder`
+#[derive(Clone)]
+pub struct InstructionDataBuilder {
+    data: InstructionData,
+}
+
+impl Default for InstructionDataBuilder {
+    fn default() -> Self {
+        InstructionDataBuilder {
+            data: InstructionData {
+                accounts_length: Vec::new(),
+                epoch: 0,
+                selected_instances: None,
+                max_plausible_profit: None,
+                max_compute_units: None,
+                wrap_native: false,
+                try_alternatives: false,
+                user_mint_count: 2,
+                recompute_quotes: false,
+                profit_mint: None,
+                min_liquidity: 0,
+                start_amount: DEFAULT_START_AMOUNT,
+                slippage_bps: DEFAULT_SLIPPAGE_BPS,
+                min_profit: 0,
+                skip_unknown: false,
+                max_price_deviation_bps: 0,
+                start_tokens: Vec::new(),
+                sweep_dust: false,
+                max_edges: 0,
+                bin_array_counts: Vec::new(),
+                max_bins: 0,
+                separate_authority: false,
+            },
+        }
+    }
+}
+
+impl InstructionDataBuilder {
+    pub fn accounts_length(mut self, accounts_length: Vec<u32>) -> Self {
+        self.data.accounts_length = accounts_length;
+        self
+    }
+
+    pub fn epoch(mut self, epoch: u16) -> Self {
+        self.data.epoch = epoch;
+        self
+    }
+
+    pub fn selected_instances(mut self, selected_instances: Vec<u32>) -> Self {
+        self.data.selected_instances = Some(selected_instances);
+        self
+    }
+
+    pub fn max_plausible_profit(mut self, max_plausible_profit: u64) -> Self {
+        self.data.max_plausible_profit = Some(max_plausible_profit);
+        self
+    }
+
+    pub fn max_compute_units(mut self, max_compute_units: u32) -> Self {
+        self.data.max_compute_units = Some(max_compute_units);
+        self
+    }
+
+    pub fn wrap_native(mut self, wrap_native: bool) -> Self {
+        self.data.wrap_native = wrap_native;
+        self
+    }
+
+    pub fn try_alternatives(mut self, try_alternatives: bool) -> Self {
+        self.data.try_alternatives = try_alternatives;
+        self
+    }
+
+    pub fn user_mint_count(mut self, user_mint_count: u8) -> Self {
+        self.data.user_mint_count = user_mint_count;
+        self
+    }
+
+    pub fn recompute_quotes(mut self, recompute_quotes: bool) -> Self {
+        self.data.recompute_quotes = recompute_quotes;
+        self
+    }
+
+    pub fn profit_mint(mut self, profit_mint: Pubkey) -> Self {
+        self.data.profit_mint = Some(profit_mint);
+        self
+    }
+
+    pub fn min_liquidity(mut self, min_liquidity: u128) -> Self {
+        self.data.min_liquidity = min_liquidity;
+        self
+    }
+
+    pub fn start_amount(mut self, start_amount: u64) -> Self {
+        self.data.start_amount = start_amount;
+        self
+    }
+
+    pub fn slippage_bps(mut self, slippage_bps: u16) -> Self {
+        self.data.slippage_bps = slippage_bps;
+        self
+    }
+
+    pub fn min_profit(mut self, min_profit: u64) -> Self {
+        self.data.min_profit = min_profit;
+        self
+    }
+
+    pub fn skip_unknown(mut self, skip_unknown: bool) -> Self {
+        self.data.skip_unknown = skip_unknown;
+        self
+    }
+
+    pub fn max_price_deviation_bps(mut self, max_price_deviation_bps: u32) -> Self {
+        self.data.max_price_deviation_bps = max_price_deviation_bps;
+        self
+    }
+
+    pub fn start_tokens(mut self, start_tokens: Vec<(Pubkey, u128)>) -> Self {
+        self.data.start_tokens = start_tokens;
+        self
+    }
+
+    pub fn sweep_dust(mut self, sweep_dust: bool) -> Self {
+        self.data.sweep_dust = sweep_dust;
+        self
+    }
+
+    pub fn max_edges(mut self, max_edges: u32) -> Self {
+        self.data.max_edges = max_edges;
+        self
+    }
+
+    pub fn bin_array_counts(mut self, bin_array_counts: Vec<Option<(u8, u8)>>) -> Self {
+        self.data.bin_array_counts = bin_array_counts;
+        self
+    }
+
+    pub fn max_bins(mut self, max_bins: u32) -> Self {
+        self.data.max_bins = max_bins;
+        self
+    }
+
+    pub fn separate_authority(mut self, separate_authority: bool) -> Self {
+        self.data.separate_authority = separate_authority;
+        self
+    }
+
+    pub fn build(self) -> InstructionData {
+        self.data
+    }
+}
+
+/// Best path found by `simulate`, written to the instruction's return data
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct SimulatedPath {
+    pub profit: i128,
+    pub edge_count: u8,
+    /// Program of each edge, in execution order
+    pub programs: Vec<Pubkey>,
+}
+
+impl From<&ArbitragePath> for SimulatedPath {
+    fn from(arbitrage_path: &ArbitragePath) -> Self {
+        SimulatedPath {
+            profit: arbitrage_path.profit,
+            edge_count: arbitrage_path.edges.len() as u8,
+            programs: arbitrage_path
+                .edges
+                .iter()
+                .map(|edge| edge.program)
+                .collect(),
+        }
+    }
+}
+
+/// One edge of an `ArbitrageFound` event
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct EdgeSummary {
+    pub program: Pubkey,
+    pub side: EdgeSide,
+    pub input_mint: Pubkey,
+}
+
+impl From<&Edge> for EdgeSummary {
+    fn from(edge: &Edge) -> Self {
+        EdgeSummary {
+            program: edge.program,
+            side: edge.side.clone(),
+            input_mint: *edge.input_mint(),
+        }
+    }
+}
+
+/// Emitted by `run_arbitrage` once it has settled on a profitable path
+#[event]
+#[derive(Debug, PartialEq)]
+pub struct ArbitrageFound {
+    pub profit: i128,
+    /// Edges in execution order
+    pub edges: Vec<EdgeSummary>,
+}
+
+impl From<&ArbitragePath> for ArbitrageFound {
+    fn from(arbitrage_path: &ArbitragePath) -> Self {
+        ArbitrageFound {
+            profit: arbitrage_path.profit,
+            edges: arbitrage_path.edges.iter().map(EdgeSummary::from).collect(),
+        }
+    }
+}
+
+/// Argument of `damm_v2_liquidity`, passed on to the cp_amm instruction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LiquidityData {
+    /// Remove `liquidity_delta` from the position instead of adding it
+    pub remove: bool,
+    pub liquidity_delta: u128,
+    /// Most token A a deposit may take, or least a withdrawal must return
+    pub token_a_amount_threshold: u64,
+    /// Most token B a deposit may take, or least a withdrawal must return
+    pub token_b_amount_threshold: u64,
 }
 
 #[derive(Accounts)]
@@ -54,40 +424,400 @@ pub mod solar_b {
         // msg!("Remaining accounts {:?}", ctx.remaining_accounts);
 
         // Work directly with remaining_accounts slice - don't clone AccountInfo
-        require!(
-            ctx.remaining_accounts.len() >= 7,
-            SolarBError::InsufficientAccounts
-        );
-        let first_accounts = &ctx.remaining_accounts[..7];
-
-        let payer = &first_accounts[0];
-        if payer.lamports() == 0 {
-            return Err(error!(SolarBError::InsufficientFunds));
-        }
-        let rest = &ctx.remaining_accounts[7..];
+        let (first_accounts, rest) = split_user_accounts(ctx.remaining_accounts, &data)?;
+        require_payer_signer(first_accounts, data.separate_authority)?;
 
-        let mut instances = parse_accounts(rest, &data)?;
+        let instances = parse_accounts(rest, &data)?;
+        let mut instances = select_instances(instances, data.selected_instances.as_deref())?;
         // for instance in instances {
         //     instance.as_ref().log_accounts()?;
         // }
-        // Run arbitrage with default start amount (1 SOL = 1e9 lamports)
-        // TODO: Get start token from context or parameters
-        let arbitrage_path = run_arbitrage(&mut instances, 1_000_000, None).unwrap();
-        execute_arbitrage_path(
-            &arbitrage_path,
+        execute_arbitrage(
             &mut instances,
-            payer,
-            &first_accounts[1], // mint_1
-            &first_accounts[2], // mint_1_token_program
-            &first_accounts[3], // user_mint_1_token_account
-            &first_accounts[4], // mint_2
-            &first_accounts[5], // mint_2_token_program
-            &first_accounts[6], // user_mint_2_token_account
+            first_accounts,
+            &data,
+            &quote_clock(Clock::get()?, data.epoch),
+        )
+    }
+
+    /// Add liquidity to or remove it from a DAMM v2 position, for strategies that provide
+    /// liquidity around a large swap. The remaining accounts are the position's, as
+    /// `PositionAccounts` reads them, followed by the pool's span as `initialize` takes it.
+    pub fn damm_v2_liquidity(ctx: Context<Initialize>, data: LiquidityData) -> Result<()> {
+        run_damm_v2_liquidity(ctx.remaining_accounts, &data)
+    }
+
+    /// Same account layout as `initialize`, but only looks for the best path and returns it
+    /// as a borsh-encoded `SimulatedPath` in the return data instead of swapping
+    pub fn simulate(ctx: Context<Initialize>, data: InstructionData) -> Result<()> {
+        let simulated_path = simulate_arbitrage(
+            ctx.remaining_accounts,
+            &data,
+            &quote_clock(Clock::get()?, data.epoch),
         )?;
+        anchor_lang::solana_program::program::set_return_data(&borsh::to_vec(&simulated_path)?);
         Ok(())
     }
 }
 
+/// Start tokens and amounts `run_arbitrage` searches from: `InstructionData::start_tokens`,
+/// or `start_amount` from any token when none are given
+fn search_starts(data: &InstructionData) -> Vec<(Option<Pubkey>, u128)> {
+    if data.start_tokens.is_empty() {
+        return vec![(None, u128::from(data.start_amount))];
+    }
+    data.start_tokens
+        .iter()
+        .map(|&(start_token, start_amount)| (Some(start_token), start_amount))
+        .collect()
+}
+
+/// Minimum profit to hand the search for `InstructionData::min_profit`, where 0 keeps the
+/// search's own threshold
+fn min_profit(min_profit: u64) -> Option<i128> {
+    (min_profit > 0).then_some(i128::from(min_profit))
+}
+
+/// `damm_v2_liquidity` over the position's accounts followed by the pool's span
+#[cfg(feature = "damm-v2")]
+fn run_damm_v2_liquidity(remaining_accounts: &[AccountInfo], data: &LiquidityData) -> Result<()> {
+    require!(
+        remaining_accounts.len() > programs::meteora_damm_v2::PositionAccounts::LEN,
+        SolarBError::InsufficientAccounts
+    );
+    let (position, span) =
+        remaining_accounts.split_at(programs::meteora_damm_v2::PositionAccounts::LEN);
+    require!(
+        span[0].key == &MeteoraDammV2::PROGRAM_ID,
+        SolarBError::UnknownProgram
+    );
+    let pool = MeteoraDammV2::new(span)?;
+    pool.validate_accounts()?;
+    pool.invoke_liquidity(
+        data,
+        &programs::meteora_damm_v2::PositionAccounts::new(position)?,
+    )
+}
+
+#[cfg(not(feature = "damm-v2"))]
+fn run_damm_v2_liquidity(_remaining_accounts: &[AccountInfo], _data: &LiquidityData) -> Result<()> {
+    Err(error!(SolarBError::UnknownProgram))
+}
+
+/// Cluster clock with its epoch replaced by `InstructionData::epoch` when that is set, so
+/// every quote picks its transfer fee rates for the same epoch
+fn quote_clock(clock: Clock, epoch: u16) -> Clock {
+    if epoch == 0 {
+        return clock;
+    }
+    Clock {
+        epoch: u64::from(epoch),
+        ..clock
+    }
+}
+
+/// Find the best path and execute it, planning and executing against the same `clock` so
+/// a pool that activates or changes its fee over time quotes the same in both
+fn execute_arbitrage<'info>(
+    instances: &mut Vec<Box<dyn ProgramMeta + 'info>>,
+    first_accounts: &[AccountInfo<'info>],
+    data: &InstructionData,
+    clock: &Clock,
+) -> Result<()> {
+    let accounts = FirstAccounts::new(first_accounts, data.separate_authority)?;
+    if accounts.fee_payer.lamports() == 0 {
+        return Err(error!(SolarBError::InsufficientFunds));
+    }
+
+    let max_plausible_profit = data.max_plausible_profit.map(i128::from);
+    let uncreated_accounts = token_account_rents(&accounts)?;
+    let mut arbitrage_path = run_arbitrage(
+        instances,
+        &search_starts(data),
+        data.profit_mint,
+        data.min_liquidity,
+        data.max_edges as usize,
+        min_profit(data.min_profit),
+        max_plausible_profit,
+        data.max_compute_units,
+        data.try_alternatives,
+        &uncreated_accounts,
+        clock,
+    )?;
+    let user_accounts = accounts
+        .user_accounts
+        .iter()
+        .map(|account| {
+            (
+                account.mint.key,
+                account.token_program.key,
+                account.token_account.key,
+            )
+        })
+        .collect::<Vec<_>>();
+    let native_wrap = native_wrap_instructions(
+        data.wrap_native,
+        path_start_token(&arbitrage_path),
+        accounts.authority.key,
+        &user_accounts,
+    )?;
+    let sweep = match native_wrap {
+        Some(_) => None,
+        None => sweep_dust_instruction(
+            data.sweep_dust,
+            &arbitrage_path,
+            accounts.authority.key,
+            &user_accounts,
+        )?,
+    };
+    if !data.recompute_quotes {
+        quote_path_amounts(&mut arbitrage_path, instances, clock)?;
+    }
+    if let Some((sync_native, _)) = &native_wrap {
+        invoke(sync_native, first_accounts)?;
+    }
+    execute_arbitrage_path(
+        &arbitrage_path,
+        instances,
+        &accounts,
+        data.recompute_quotes,
+        data.slippage_bps,
+        data.max_price_deviation_bps,
+        clock,
+    )?;
+    if let Some((_, close_account)) = &native_wrap {
+        invoke(close_account, first_accounts)?;
+    }
+    if let Some(close_account) = &sweep {
+        invoke(close_account, first_accounts)?;
+    }
+    Ok(())
+}
+
+/// Parse and select instances like `initialize` and find the best path, without executing it
+pub fn simulate_arbitrage<'info>(
+    remaining_accounts: &[AccountInfo<'info>],
+    data: &InstructionData,
+    clock: &Clock,
+) -> Result<SimulatedPath> {
+    let (first_accounts, rest) = split_user_accounts(remaining_accounts, data)?;
+    let uncreated_accounts = token_account_rents(&FirstAccounts::new(
+        first_accounts,
+        data.separate_authority,
+    )?)?;
+
+    let instances = parse_accounts(rest, data)?;
+    let mut instances = select_instances(instances, data.selected_instances.as_deref())?;
+    let max_plausible_profit = data.max_plausible_profit.map(i128::from);
+    let arbitrage_path = run_arbitrage(
+        &mut instances,
+        &search_starts(data),
+        data.profit_mint,
+        data.min_liquidity,
+        data.max_edges as usize,
+        min_profit(data.min_profit),
+        max_plausible_profit,
+        data.max_compute_units,
+        data.try_alternatives,
+        &uncreated_accounts,
+        clock,
+    )?;
+
+    Ok(SimulatedPath::from(&arbitrage_path))
+}
+
+/// Mint, token program and user token account the swaps move one mint's funds through
+#[derive(Clone, Copy)]
+pub struct UserTokenAccount<'a, 'info> {
+    pub mint: &'a AccountInfo<'info>,
+    pub token_program: &'a AccountInfo<'info>,
+    pub token_account: &'a AccountInfo<'info>,
+}
+
+/// Fail before any pool is parsed when the fee payer or the authority at the head of
+/// `first_accounts` did not sign, rather than deep inside the first swap CPI
+fn require_payer_signer(first_accounts: &[AccountInfo], separate_authority: bool) -> Result<()> {
+    let accounts = FirstAccounts::new(first_accounts, separate_authority)?;
+    require!(
+        accounts.fee_payer.is_signer && accounts.authority.is_signer,
+        SolarBError::PayerNotSigner
+    );
+    Ok(())
+}
+
+/// Header split off by `split_user_accounts`, by role: the fee payer, the authority (the
+/// fee payer again unless `separate_authority` is set), then one `UserTokenAccount` per
+/// mint, in the order their triples were passed
+pub struct FirstAccounts<'a, 'info> {
+    /// Funds the rent of the token accounts execution creates
+    pub fee_payer: &'a AccountInfo<'info>,
+    /// Owns the user token accounts and signs the swaps
+    pub authority: &'a AccountInfo<'info>,
+    pub user_accounts: Vec<UserTokenAccount<'a, 'info>>,
+}
+
+impl<'a, 'info> FirstAccounts<'a, 'info> {
+    pub fn new(first_accounts: &'a [AccountInfo<'info>], separate_authority: bool) -> Result<Self> {
+        let (fee_payer, rest) = first_accounts
+            .split_first()
+            .ok_or(error!(SolarBError::InsufficientAccounts))?;
+        let (authority, triples) = match separate_authority {
+            true => rest
+                .split_first()
+                .ok_or(error!(SolarBError::InsufficientAccounts))?,
+            false => (fee_payer, rest),
+        };
+        let user_accounts = triples
+            .chunks_exact(3)
+            .map(|triple| UserTokenAccount {
+                mint: &triple[0],
+                token_program: &triple[1],
+                token_account: &triple[2],
+            })
+            .collect();
+        Ok(FirstAccounts {
+            fee_payer,
+            authority,
+            user_accounts,
+        })
+    }
+}
+
+/// Splits the remaining accounts into the payer, the authority when `separate_authority` is
+/// set, and `user_mint_count` user account triples, and the pool spans after them
+fn split_user_accounts<'a, 'info>(
+    remaining_accounts: &'a [AccountInfo<'info>],
+    data: &InstructionData,
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    require!(data.user_mint_count >= 2, SolarBError::InsufficientAccounts);
+    let header_len =
+        1 + usize::from(data.separate_authority) + 3 * usize::from(data.user_mint_count);
+    require!(
+        remaining_accounts.len() >= header_len,
+        SolarBError::InsufficientAccounts
+    );
+    Ok(remaining_accounts.split_at(header_len))
+}
+
+/// Mints whose user token account doesn't exist yet and is the authority's associated
+/// token account, which execution creates before the first swap paying into it
+fn uncreated_token_accounts(first_accounts: &FirstAccounts) -> Vec<Pubkey> {
+    first_accounts
+        .user_accounts
+        .iter()
+        .filter(|account| account.token_account.data_is_empty())
+        .filter(|account| {
+            *account.token_account.key
+                == get_associated_token_address_with_program_id(
+                    first_accounts.authority.key,
+                    account.mint.key,
+                    account.token_program.key,
+                )
+        })
+        .map(|account| *account.mint.key)
+        .collect()
+}
+
+/// Mints of `uncreated_token_accounts`, each with the rent-exempt minimum of the account
+/// created for it. The cluster's rent is read only when there is one.
+fn token_account_rents(first_accounts: &FirstAccounts) -> Result<Vec<(Pubkey, u64)>> {
+    let uncreated_mints = uncreated_token_accounts(first_accounts);
+    if uncreated_mints.is_empty() {
+        return Ok(Vec::new());
+    }
+    let rent = Rent::get()?;
+    first_accounts
+        .user_accounts
+        .iter()
+        .filter(|account| uncreated_mints.contains(account.mint.key))
+        .map(|account| {
+            let len = token_account_len(account.mint, account.token_program.key)?;
+            Ok((*account.mint.key, rent.minimum_balance(len)))
+        })
+        .collect()
+}
+
+/// Size of the associated token account created for `mint`: the plain account under SPL
+/// Token, and under Token-2022 the account extensions the mint requires plus
+/// `ImmutableOwner`, which every associated token account carries
+fn token_account_len(mint: &AccountInfo, token_program: &Pubkey) -> Result<usize> {
+    if *token_program != spl_token_2022::ID {
+        return Ok(spl_token::state::Account::LEN);
+    }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)
+        .map_err(|_| error!(SolarBError::MintLoadFailed))?;
+    let mut extensions =
+        ExtensionType::get_required_init_account_extensions(&state.get_extension_types()?);
+    if !extensions.contains(&ExtensionType::ImmutableOwner) {
+        extensions.push(ExtensionType::ImmutableOwner);
+    }
+    Ok(ExtensionType::try_calculate_account_len::<
+        spl_token_2022::state::Account,
+    >(&extensions)?)
+}
+
+/// User accounts for the edge's input and output mints, in that order
+fn edge_user_accounts<'a, 'info>(
+    user_accounts: &[UserTokenAccount<'a, 'info>],
+    edge: &Edge,
+) -> Result<(UserTokenAccount<'a, 'info>, UserTokenAccount<'a, 'info>)> {
+    let find = |mint: &Pubkey| {
+        user_accounts
+            .iter()
+            .find(|account| account.mint.key == mint)
+            .copied()
+            .ok_or(error!(SolarBError::AccountMismatch))
+    };
+    Ok((find(edge.input_mint())?, find(edge.output_mint())?))
+}
+
+/// Create the authority's token account `output`, its rent paid by `fee_payer`, through the
+/// first instance whose span carries the associated token program
+fn create_token_account<'info>(
+    instances: &[Box<dyn ProgramMeta + 'info>],
+    fee_payer: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    output: &UserTokenAccount<'_, 'info>,
+) -> Result<()> {
+    let instance = instances
+        .iter()
+        .find(|instance| instance.associated_token_program().is_some())
+        .ok_or(error!(SolarBError::MissingAssociatedTokenProgram))?;
+    msg!(
+        "Creating token account {} for mint {}",
+        output.token_account.key,
+        output.mint.key
+    );
+    instance.invoke_create_token_account(
+        fee_payer.clone(),
+        authority.clone(),
+        output.token_account.clone(),
+        output.mint.clone(),
+        output.token_program.clone(),
+    )
+}
+
+/// Side a swap of `input_mint` takes through `instance`: selling its base mint is
+/// `LeftToRight` and selling its quote mint `RightToLeft`. Execution routes by the pool's own
+/// mints rather than the edge's label, so a pool holding SOL on either side swaps the way
+/// the path's mints say.
+fn swap_direction(instance: &(dyn ProgramMeta + '_), input_mint: &Pubkey) -> Result<EdgeSide> {
+    let (base_mint, quote_mint) = instance.get_mints();
+    if input_mint == base_mint {
+        Ok(EdgeSide::LeftToRight)
+    } else if input_mint == quote_mint {
+        Ok(EdgeSide::RightToLeft)
+    } else {
+        msg!(
+            "Mint {} is neither side of pool {}",
+            input_mint,
+            instance.pool_key()
+        );
+        Err(error!(SolarBError::AccountMismatch))
+    }
+}
+
 fn parse_accounts<'info>(
     accounts: &[AccountInfo<'info>],
     data: &InstructionData,
@@ -98,7 +828,7 @@ fn parse_accounts<'info>(
     let estimated_capacity = data.accounts_length.iter().filter(|&&len| len > 0).count();
     let mut instances = Vec::with_capacity(estimated_capacity);
 
-    for &raw_span in data.accounts_length.iter() {
+    for (span_index, &raw_span) in data.accounts_length.iter().enumerate() {
         let span = usize::try_from(raw_span).map_err(|_| SolarBError::InvalidAccountsLength)?;
         if span == 0 {
             continue;
@@ -111,7 +841,18 @@ fn parse_accounts<'info>(
         let segment = &accounts[index..index + span];
         // Avoid cloning AccountInfo - just pass the reference's key
         let program_key = segment[0].key;
-        let instance: Box<dyn ProgramMeta> = find_program_instance(program_key, segment)?;
+        let bin_array_counts = data.bin_array_counts.get(span_index).copied().flatten();
+        let instance: Box<dyn ProgramMeta> =
+            match find_program_instance(program_key, segment, bin_array_counts, data.max_bins) {
+                Ok(instance) => instance,
+                Err(err) if data.skip_unknown && err == error!(SolarBError::UnknownProgram) => {
+                    msg!("Skipping span of unknown program {}", program_key);
+                    index += span;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+        instance.validate_accounts()?;
         // TODO: Implement find_program_instance to create ProgramMeta instances
         instances.push(instance);
         // instance.log_accounts()?;
@@ -123,122 +864,325 @@ fn parse_accounts<'info>(
     Ok(instances)
 }
 
+/// Keep only the instances at the given indices, in the order they were parsed
+fn select_instances<'info>(
+    instances: Vec<Box<dyn ProgramMeta + 'info>>,
+    selected: Option<&[u32]>,
+) -> Result<Vec<Box<dyn ProgramMeta + 'info>>> {
+    let Some(selected) = selected else {
+        return Ok(instances);
+    };
+    for &index in selected {
+        require!(
+            (index as usize) < instances.len(),
+            SolarBError::InvalidInstanceSelection
+        );
+    }
+    Ok(instances
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| selected.contains(&(*index as u32)))
+        .map(|(_, instance)| instance)
+        .collect())
+}
+
+/// Adapter for `program_id`'s span, which must hold at least the venue's `EXPECTED_ACCOUNTS`
+#[cfg_attr(
+    not(any(
+        feature = "pump-amm",
+        feature = "raydium-cpmm",
+        feature = "damm-v1",
+        feature = "damm-v2",
+        feature = "dlmm",
+        feature = "lifinity",
+        feature = "phoenix",
+        feature = "saber"
+    )),
+    allow(unused_variables)
+)]
+#[cfg_attr(not(feature = "dlmm"), allow(unused_variables))]
 pub fn find_program_instance<'info>(
     program_id: &Pubkey,
     payload_accounts: &[AccountInfo<'info>],
+    bin_array_counts: Option<(u8, u8)>,
+    max_bins: u32,
 ) -> Result<Box<dyn ProgramMeta + 'info>> {
-    // msg!(
-    //     "Creating program for program_id: {}, accounts.len(): {}",
-    //     program_id,
-    //     payload_accounts.len()
-    // );
-    // if program_id == &RaydiumCPMM::PROGRAM_ID {
-    //     msg!(
-    //         "Initializing RaydiumCPMM with {} accounts",
-    //         payload_accounts.len()
-    //     );
-    //     let pr = RaydiumCPMM::new(payload_accounts)?;
-    //     return Ok(Box::new(pr));
-    // }
-    // if program_id == &RaydiumAmm::PROGRAM_ID {
-    //     msg!(
-    //         "Initializing RaydiumAmm with {} accounts",
-    //         payload_accounts.len()
-    //     );
-    //     let pr = RaydiumAmm::new(payload_accounts)?;
-    //     return Ok(Box::new(pr));
-    // }
-    // if program_id == &RaydiumClmm::PROGRAM_ID {
-    //     msg!(
-    //         "Initializing RaydiumClmm with {} accounts",
-    //         payload_accounts.len()
-    //     );
-    //     let pr = RaydiumClmm::new(payload_accounts)?;
-    //     return Ok(Box::new(pr));
-    // }
+    #[cfg(feature = "pump-amm")]
     if program_id == &PumpAmm::PROGRAM_ID {
+        require!(
+            payload_accounts.len() >= PumpAmm::EXPECTED_ACCOUNTS,
+            SolarBError::InsufficientAccounts
+        );
         let pr = PumpAmm::new(payload_accounts)?;
         return Ok(Box::new(pr));
     }
-    // if program_id == &Whirlpools::PROGRAM_ID {
-    //     msg!(
-    //         "Initializing Whirlpools with {} accounts",
-    //         payload_accounts.len()
-    //     );
-    //     let pr = Whirlpools::new(payload_accounts)?;
-    //     return Ok(Box::new(pr));
-    // }
+    #[cfg(feature = "raydium-cpmm")]
+    if program_id == &RaydiumCPMM::PROGRAM_ID {
+        require!(
+            payload_accounts.len() >= RaydiumCPMM::EXPECTED_ACCOUNTS,
+            SolarBError::InsufficientAccounts
+        );
+        let pr = RaydiumCPMM::new(payload_accounts)?;
+        return Ok(Box::new(pr));
+    }
+    #[cfg(feature = "damm-v2")]
     if program_id == &MeteoraDammV2::PROGRAM_ID {
+        require!(
+            payload_accounts.len() >= MeteoraDammV2::EXPECTED_ACCOUNTS,
+            SolarBError::InsufficientAccounts
+        );
         let pr = MeteoraDammV2::new(payload_accounts)?;
         return Ok(Box::new(pr));
     }
+    #[cfg(feature = "damm-v1")]
     if program_id == &MeteoraDammV1::PROGRAM_ID {
+        require!(
+            payload_accounts.len() >= MeteoraDammV1::EXPECTED_ACCOUNTS,
+            SolarBError::InsufficientAccounts
+        );
         let pr = MeteoraDammV1::new(payload_accounts)?;
         return Ok(Box::new(pr));
     }
+    #[cfg(feature = "dlmm")]
     if program_id == &MeteoraDlmm::PROGRAM_ID {
         require!(
-            payload_accounts.len() >= 13,
+            payload_accounts.len() >= MeteoraDlmm::EXPECTED_ACCOUNTS,
+            SolarBError::InsufficientAccounts
+        );
+        let pr = MeteoraDlmm::new_with_bin_array_counts(payload_accounts, bin_array_counts)?
+            .with_max_bins(max_bins as usize);
+        return Ok(Box::new(pr));
+    }
+    #[cfg(feature = "lifinity")]
+    if program_id == &LifinityV2::PROGRAM_ID {
+        require!(
+            payload_accounts.len() >= LifinityV2::EXPECTED_ACCOUNTS,
+            SolarBError::InsufficientAccounts
+        );
+        let pr = LifinityV2::new(payload_accounts)?;
+        return Ok(Box::new(pr));
+    }
+    #[cfg(feature = "phoenix")]
+    if program_id == &Phoenix::PROGRAM_ID {
+        require!(
+            payload_accounts.len() >= Phoenix::EXPECTED_ACCOUNTS,
+            SolarBError::InsufficientAccounts
+        );
+        let pr = Phoenix::new(payload_accounts)?;
+        return Ok(Box::new(pr));
+    }
+    #[cfg(feature = "saber")]
+    if program_id == &Saber::PROGRAM_ID {
+        require!(
+            payload_accounts.len() >= Saber::EXPECTED_ACCOUNTS,
             SolarBError::InsufficientAccounts
         );
-        let pr = MeteoraDlmm::new(payload_accounts)?;
+        let pr = Saber::new(payload_accounts)?;
         return Ok(Box::new(pr));
     }
     Err(error!(SolarBError::UnknownProgram))
 }
 
-pub fn generate_edges<'info>(program: &'info (dyn ProgramMeta + 'info)) -> Result<Vec<Edge>> {
-    let (base_vault_info, quote_vault_info) = program.get_vaults();
-    let base_vault = parse_token_account(base_vault_info)?;
-    let quote_vault = parse_token_account(quote_vault_info)?;
-    let base_amount = base_vault.amount as u128;
-    let quote_amount = quote_vault.amount as u128;
+/// Label and program id of every venue `find_program_instance` dispatches in this build
+pub fn supported_programs() -> Vec<(&'static str, Pubkey)> {
+    [
+        #[cfg(feature = "pump-amm")]
+        ("PumpAmm", PumpAmm::PROGRAM_ID),
+        #[cfg(feature = "raydium-cpmm")]
+        ("RaydiumCPMM", RaydiumCPMM::PROGRAM_ID),
+        #[cfg(feature = "damm-v2")]
+        ("MeteoraDammV2", MeteoraDammV2::PROGRAM_ID),
+        #[cfg(feature = "damm-v1")]
+        ("MeteoraDammV1", MeteoraDammV1::PROGRAM_ID),
+        #[cfg(feature = "dlmm")]
+        ("MeteoraDlmm", MeteoraDlmm::PROGRAM_ID),
+        #[cfg(feature = "lifinity")]
+        ("LifinityV2", LifinityV2::PROGRAM_ID),
+        #[cfg(feature = "phoenix")]
+        ("Phoenix", Phoenix::PROGRAM_ID),
+        #[cfg(feature = "saber")]
+        ("Saber", Saber::PROGRAM_ID),
+    ]
+    .to_vec()
+}
+
+/// Both edges of `program`'s pool, or none when it doesn't accept swaps, has an empty
+/// reserve or holds less than `min_liquidity` on either side
+pub fn generate_edges<'info>(
+    program: &'info (dyn ProgramMeta + 'info),
+    clock: &Clock,
+    min_liquidity: u128,
+) -> Result<Vec<Edge>> {
+    let (base_vault_info, _) = program.get_vaults();
+    if !program.is_tradable(clock)? {
+        msg!(
+            "Skipping {} pool {} that doesn't accept swaps",
+            program.program_label(),
+            base_vault_info.key
+        );
+        return Ok(Vec::new());
+    }
+    let (base_amount, quote_amount) = program.reserves(clock)?;
+    // A drained side has no price, and the other pools can still be searched
+    if base_amount == 0 || quote_amount == 0 {
+        msg!(
+            "Skipping {} pool {} with an empty reserve ({} base, {} quote)",
+            program.program_label(),
+            base_vault_info.key,
+            base_amount,
+            quote_amount
+        );
+        return Ok(Vec::new());
+    }
+    if base_amount < min_liquidity || quote_amount < min_liquidity {
+        msg!(
+            "Skipping thin {} pool {} ({} base, {} quote, minimum {})",
+            program.program_label(),
+            base_vault_info.key,
+            base_amount,
+            quote_amount,
+            min_liquidity
+        );
+        return Ok(Vec::new());
+    }
     let price_base_in = program.compute_price_swap_base_in(base_amount, quote_amount)?;
     let price_base_out = program.compute_price_swap_base_out(base_amount, quote_amount)?;
 
-    // Extract mints directly from the deserialized token accounts
-    // Pool struct is small (40 bytes: Pubkey 32 + u128 16), but avoid unnecessary clones
-    let base_pool = Pool::new(&base_vault.mint, base_amount);
-    let quote_pool = Pool::new(&quote_vault.mint, quote_amount);
+    // `validate_accounts` checked the vaults hold these mints
+    // Pool struct is small (Pubkey 32 + u128 16 + u8), but avoid unnecessary clones
+    let (base_mint, quote_mint) = program.get_mints();
+    let (base_decimals, quote_decimals) = program.mint_decimals();
+    let base_pool = Pool::new(base_mint, base_amount).with_decimals(base_decimals);
+    let quote_pool = Pool::new(quote_mint, quote_amount).with_decimals(quote_decimals);
     let program_id = *program.get_id();
+    // The base vault belongs to exactly one pool, so it tells apart pools of the same program
+    let pool_id = *base_vault_info.key;
+    let compute_units = program.estimate_compute_units();
+    let min_base_in = program.min_swap_amount(base_mint, clock)?;
+    let min_quote_in = program.min_swap_amount(quote_mint, clock)?;
     Ok(vec![
         Edge::new(
             program_id,
+            pool_id,
             EdgeSide::LeftToRight,
             price_base_in,
             base_pool.clone(),
             quote_pool.clone(),
-        ),
+        )
+        .with_compute_units(compute_units)
+        .with_min_amount_in(min_base_in),
         Edge::new(
             program_id,
+            pool_id,
             EdgeSide::RightToLeft,
             price_base_out,
             quote_pool, // Move instead of clone
             base_pool,  // Move instead of clone
-        ),
+        )
+        .with_compute_units(compute_units)
+        .with_min_amount_in(min_quote_in),
     ])
 }
 
-pub fn get_edges<'info>(instances: &'info [Box<dyn ProgramMeta + 'info>]) -> Result<Vec<Edge>> {
+pub fn get_edges<'info>(
+    instances: &'info [Box<dyn ProgramMeta + 'info>],
+    clock: &Clock,
+    min_liquidity: u128,
+) -> Result<Vec<Edge>> {
     // Pre-allocate capacity: each instance generates 2 edges
     let mut edges = Vec::with_capacity(instances.len() * 2);
+    let mut seen_pools = HashSet::with_capacity(instances.len());
     for instance in instances {
-        let instance_edges = generate_edges(instance.as_ref())?;
+        // A pool passed twice would only add duplicate edges
+        if !seen_pools.insert((instance.get_id(), instance.pool_key())) {
+            msg!("Skipping duplicate pool {}", instance.pool_key());
+            continue;
+        }
+        let instance_edges = generate_edges(instance.as_ref(), clock, min_liquidity)?;
         edges.extend(instance_edges);
     }
+    // Search in a fixed order so profit ties resolve the same way however the accounts came in
+    edges.sort_by(|a, b| a.ordering_key().cmp(&b.ordering_key()));
+    Ok(edges)
+}
+
+/// Rebuild only the edges of `instance` in `edges` from its current reserves, leaving the
+/// other pools' edges as they are, for off-chain loops where one pool moved between runs.
+/// Edges are matched by program id and pool, so other pools of the same program are kept;
+/// a pool `generate_edges` now skips loses its edges, and a new one gains them in order.
+pub fn refresh_edge<'info>(
+    edges: &mut Vec<Edge>,
+    instance: &'info (dyn ProgramMeta + 'info),
+    clock: &Clock,
+    min_liquidity: u128,
+) -> Result<()> {
+    let program_id = *instance.get_id();
+    let pool_id = *instance.get_vaults().0.key;
+    let is_pool_edge = |edge: &Edge| edge.program == program_id && edge.pool_id == pool_id;
+    let refreshed = generate_edges(instance, clock, min_liquidity)?;
+
+    if edges.iter().filter(|edge| is_pool_edge(edge)).count() == refreshed.len() {
+        for edge in edges.iter_mut().filter(|edge| is_pool_edge(edge)) {
+            if let Some(new_edge) = refreshed.iter().find(|new_edge| new_edge.side == edge.side) {
+                *edge = new_edge.clone();
+            }
+        }
+    } else {
+        edges.retain(|edge| !is_pool_edge(edge));
+        edges.extend(refreshed);
+        edges.sort_by(|a, b| a.ordering_key().cmp(&b.ordering_key()));
+    }
+    Ok(())
+}
+
+/// `edges` cut down to the `max_edges` edges of the deepest pools, depth being the product
+/// of a pool's reserves, keeping search order; 0 keeps every edge. A pool's two edges are
+/// kept or dropped together. Fails with `TooManyEdges` when more than `MAX_SEARCH_EDGES`
+/// are left.
+pub fn limit_edges(mut edges: Vec<Edge>, max_edges: usize) -> Result<Vec<Edge>> {
+    if max_edges > 0 && edges.len() > max_edges {
+        let depth = |edge: &Edge| edge.left.amount.saturating_mul(edge.right.amount);
+        // Both sides of a pool share its depth and sort next to each other
+        edges.sort_by(|a, b| {
+            depth(b)
+                .cmp(&depth(a))
+                .then_with(|| a.ordering_key().cmp(&b.ordering_key()))
+        });
+        edges.truncate(max_edges - max_edges % 2);
+        edges.sort_by(|a, b| a.ordering_key().cmp(&b.ordering_key()));
+    }
+    if edges.len() > MAX_SEARCH_EDGES {
+        msg!(
+            "{} edges left, searching at most {}",
+            edges.len(),
+            MAX_SEARCH_EDGES
+        );
+        return Err(error!(SolarBError::TooManyEdges));
+    }
     Ok(edges)
 }
 
 pub fn run_arbitrage<'info>(
     instances: &mut Vec<Box<dyn ProgramMeta + 'info>>,
-    start_amount: u128,
-    start_token: Option<Pubkey>,
+    starts: &[(Option<Pubkey>, u128)],
+    profit_mint: Option<Pubkey>,
+    min_liquidity: u128,
+    max_edges: usize,
+    min_profit: Option<i128>,
+    max_plausible_profit: Option<i128>,
+    max_compute_units: Option<u32>,
+    try_alternatives: bool,
+    uncreated_accounts: &[(Pubkey, u64)],
+    clock: &Clock,
 ) -> Result<ArbitragePath> {
-    // Note: We don't actually use epoch, so avoid creating full Clock struct
-    // If epoch is needed later, get it separately: Clock::get()?.epoch
-
+    // Profits of different start tokens only rank against each other in one mint
+    require!(
+        profit_mint.is_some() || starts.iter().all(|(token, _)| *token == starts[0].0),
+        SolarBError::ProfitMintRequired
+    );
     // Extract edges - Vec<Edge> is on heap, only Vec metadata (24 bytes) on stack
-    let edges = get_edges(instances.as_slice())?;
+    let edges = get_edges(instances.as_slice(), clock, min_liquidity)?;
+    let edges = limit_edges(edges, max_edges)?;
 
     // Check for arbitrage opportunities
     // Pre-allocate Vec<&Edge> with known capacity to avoid reallocations
@@ -246,41 +1190,197 @@ pub fn run_arbitrage<'info>(
     for edge in &edges {
         edge_refs.push(edge);
     }
-    let arbitrage_path = check_arbitrage(&edge_refs, start_amount, start_token, None)?;
+    let max_paths = if try_alternatives {
+        MAX_ALTERNATIVE_PATHS
+    } else {
+        1
+    };
+    let mut paths = Vec::new();
+    // Whether every start that found nothing only had paths too small to trade
+    let mut all_undersized = !starts.is_empty();
+    for &(start_token, start_amount) in starts {
+        match check_arbitrage_ranked(&edge_refs, start_amount, start_token, min_profit, max_paths) {
+            Ok(start_paths) => paths.extend(start_paths),
+            // Another start token may still pay
+            Err(err) if err == error!(SolarBError::NoProfitFound) => {
+                msg!("No profitable path from {:?}", start_token);
+                all_undersized = false;
+            }
+            Err(err) if err == error!(SolarBError::SwapTooSmall) => {
+                msg!("Only undersized paths from {:?}", start_token);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    if paths.is_empty() && all_undersized {
+        return Err(error!(SolarBError::SwapTooSmall));
+    }
+    require!(!paths.is_empty(), SolarBError::NoProfitFound);
 
     // Explicitly drop to free Vec metadata (24 bytes) from stack immediately
     // edges Vec is on heap, but Vec struct metadata (ptr+len+cap) is on stack
     drop(edge_refs);
-    drop(edges);
 
-    if arbitrage_path.profit < 0 {
-        return Err(error!(SolarBError::NoProfitFound));
+    // Creating a token account the path pays into costs its rent in lamports, which the
+    // profit pays for at the best price any edge quotes from the native mint. A path whose
+    // start token no edge prices in the native mint can't be told to cover it, so it's left
+    // out rather than failing every other path.
+    if !uncreated_accounts.is_empty() {
+        let mut netted = Vec::with_capacity(paths.len());
+        for mut path in paths {
+            let rent = path_token_account_rent(&path, uncreated_accounts);
+            if let (true, Some(start_token)) = (rent > 0, path_start_token(&path)) {
+                match profit_in_mint(i128::from(rent), native_mint::id(), start_token, &edges) {
+                    Ok(rent) => path.profit -= rent,
+                    Err(err) if err == error!(SolarBError::NoPriceForProfitMint) => {
+                        msg!(
+                            "Skipping a path from {}: its rent can't be priced",
+                            start_token
+                        );
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            netted.push(path);
+        }
+        paths = netted;
+        if let Some(min_profit) = min_profit {
+            paths.retain(|path| path.profit >= min_profit);
+        }
+        require!(!paths.is_empty(), SolarBError::NoProfitFound);
+    }
+
+    if let Some(profit_mint) = profit_mint {
+        for path in paths.iter_mut() {
+            if let Some(start_token) = path_start_token(path) {
+                path.profit = profit_in_mint(path.profit, start_token, profit_mint, &edges)?;
+            }
+        }
     }
+    // Each start's paths come best first; a stable sort keeps that order among equal
+    // ranks, and the earlier start ahead of a later one. Netting rent may reorder them.
+    if starts.len() > 1 || !uncreated_accounts.is_empty() {
+        paths.sort_by_key(ArbitragePath::rank_key);
+    }
+
+    let check_path = |path: &ArbitragePath| -> Result<()> {
+        if path.profit < 0 {
+            return Err(error!(SolarBError::NoProfitFound));
+        }
+        // The default maximum counts start token atoms, so it's converted along with the
+        // profit
+        let max_plausible_profit = match (max_plausible_profit, profit_mint) {
+            (Some(max_plausible_profit), _) => max_plausible_profit,
+            (None, Some(profit_mint)) => match path_start_token(path) {
+                Some(start_token) => {
+                    profit_in_mint(MAX_PLAUSIBLE_PROFIT, start_token, profit_mint, &edges)?
+                }
+                None => MAX_PLAUSIBLE_PROFIT,
+            },
+            (None, None) => MAX_PLAUSIBLE_PROFIT,
+        };
+        check_plausible_profit(path, max_plausible_profit)?;
+        check_compute_budget(
+            path,
+            instances,
+            max_compute_units.unwrap_or(MAX_PATH_COMPUTE_UNITS),
+        )
+    };
+    let arbitrage_path = if try_alternatives {
+        select_checked_path(paths, check_path)?
+    } else {
+        let arbitrage_path = paths.remove(0);
+        check_path(&arbitrage_path)?;
+        arbitrage_path
+    };
+    drop(edges);
 
     msg!("= {:?}", arbitrage_path.profit);
+    emit!(ArbitrageFound::from(&arbitrage_path));
 
     Ok(arbitrage_path)
 }
 
-pub fn execute_arbitrage_path<'info>(
-    arbitrage_path: &ArbitragePath,
-    instances: &mut Vec<Box<dyn ProgramMeta + 'info>>,
-    payer: &AccountInfo<'info>,
-    mint_1: &AccountInfo<'info>,
-    mint_1_token_program: &AccountInfo<'info>,
-    user_mint_1_token_account: &AccountInfo<'info>,
-    mint_2: &AccountInfo<'info>,
-    mint_2_token_program: &AccountInfo<'info>,
-    user_mint_2_token_account: &AccountInfo<'info>,
-) -> Result<()> {
-    let mut current_amount = arbitrage_path.start_amount;
+/// `profit` in `start_token` units converted into `profit_mint`, at the best price any edge
+/// quotes for selling `start_token` directly into `profit_mint`
+fn profit_in_mint(
+    profit: i128,
+    start_token: Pubkey,
+    profit_mint: Pubkey,
+    edges: &[Edge],
+) -> Result<i128> {
+    if start_token == profit_mint {
+        return Ok(profit);
+    }
+    let best_price = edges
+        .iter()
+        .filter(|edge| *edge.input_mint() == start_token && *edge.output_mint() == profit_mint)
+        .map(|edge| edge.get_price())
+        .reduce(f64::max);
+    let Some(price) = best_price else {
+        msg!("No edge prices {} in {}", start_token, profit_mint);
+        return Err(error!(SolarBError::NoPriceForProfitMint));
+    };
+    Ok((profit as f64 * price) as i128)
+}
 
-    // Clock is now fetched inside the loop block scope for each iteration
-    // This ensures it's dropped immediately after each swap operation
+/// Lamports `path` locks up creating the payer's token accounts for the mints among
+/// `uncreated_accounts` its swaps pay into, at the rent listed for each. The start token's
+/// account already holds the amount the path spends.
+fn path_token_account_rent(path: &ArbitragePath, uncreated_accounts: &[(Pubkey, u64)]) -> u64 {
+    let start_token = path_start_token(path);
+    let created: HashSet<&Pubkey> = path
+        .edges
+        .iter()
+        .map(|edge| edge.output_mint())
+        .filter(|mint| Some(**mint) != start_token)
+        .collect();
+    uncreated_accounts
+        .iter()
+        .filter(|(mint, _)| created.contains(mint))
+        .map(|(_, rent)| rent)
+        .sum()
+}
 
-    for (i, edge) in arbitrage_path.edges.iter().enumerate() {
+/// First of `paths` (best first) that passes `check`, so a best path over the compute
+/// budget or with an implausible profit falls back to the runner-up instead of failing the
+/// run. Paused pools never get this far: `generate_edges` leaves them out. Fails with the
+/// best path's error when no path passes.
+fn select_checked_path(
+    paths: Vec<ArbitragePath>,
+    check: impl Fn(&ArbitragePath) -> Result<()>,
+) -> Result<ArbitragePath> {
+    let mut first_err = None;
+    for (i, path) in paths.into_iter().enumerate() {
+        match check(&path) {
+            Ok(()) => return Ok(path),
+            Err(err) => {
+                msg!("Skipping path {}: {}", i, err);
+                first_err.get_or_insert(err);
+            }
+        }
+    }
+    Err(first_err.unwrap_or_else(|| error!(SolarBError::NoProfitFound)))
+}
+
+/// Abort on a profit too large to be real, logging the reserves that produced it
+fn check_plausible_profit(
+    arbitrage_path: &ArbitragePath,
+    max_plausible_profit: i128,
+) -> Result<()> {
+    if arbitrage_path.profit <= max_plausible_profit {
+        return Ok(());
+    }
+
+    msg!(
+        "Implausible profit {} (max {})",
+        arbitrage_path.profit,
+        max_plausible_profit
+    );
+    for edge in arbitrage_path.edges.iter() {
         msg!(
-            "Edge {:?} -> {:?} / base_mint {}, base_amount={}, quote_mint {}, quote_amount={}",
+            "Suspicious reserves {:?} -> {:?} / base_mint {}, base_amount={}, quote_mint {}, quote_amount={}",
             edge.program,
             edge.side,
             edge.left.mint_account,
@@ -288,73 +1388,352 @@ pub fn execute_arbitrage_path<'info>(
             edge.right.mint_account,
             edge.right.get_amount()
         );
+    }
+    Err(error!(SolarBError::ImplausibleProfit))
+}
+
+/// Reject a path whose swaps are estimated to need more than `max_compute_units`
+fn check_compute_budget<'info>(
+    arbitrage_path: &ArbitragePath,
+    instances: &[Box<dyn ProgramMeta + 'info>],
+    max_compute_units: u32,
+) -> Result<()> {
+    let mut compute_units: u32 = 0;
+    for edge in arbitrage_path.edges.iter() {
+        let instance = &instances[find_edge_instance(instances, edge)?];
+        compute_units = compute_units.saturating_add(instance.estimate_compute_units());
+    }
+
+    if compute_units > max_compute_units {
+        msg!(
+            "Path needs an estimated {} compute units (max {})",
+            compute_units,
+            max_compute_units
+        );
+        return Err(error!(SolarBError::ComputeBudgetExceeded));
+    }
+    Ok(())
+}
+
+/// Reject a path with two consecutive edges through the same pool. The second swap would
+/// only undo the first minus fees, so any profit the search credited it with is bogus.
+fn check_degenerate_path(arbitrage_path: &ArbitragePath) -> Result<()> {
+    for pair in arbitrage_path.edges.windows(2) {
+        if pair[0].program == pair[1].program && pair[0].pool_id == pair[1].pool_id {
+            msg!(
+                "Path trades twice in a row through pool {} of {}",
+                pair[0].pool_id,
+                pair[0].program
+            );
+            return Err(error!(SolarBError::DegeneratePath));
+        }
+    }
+    Ok(())
+}
+
+/// Reject a path whose last edge doesn't pay out the mint the first one takes in: its
+/// final amount is in another token, so comparing it with the start amount says nothing
+/// about profit, and the transaction must not succeed on it.
+fn check_closed_path(arbitrage_path: &ArbitragePath) -> Result<()> {
+    let (Some(first), Some(last)) = (arbitrage_path.edges.first(), arbitrage_path.edges.last())
+    else {
+        return Ok(());
+    };
+    if last.output_mint() != first.input_mint() {
+        msg!(
+            "Path starts in {} but ends in {}",
+            first.input_mint(),
+            last.output_mint()
+        );
+        return Err(error!(SolarBError::PathNotClosed));
+    }
+    Ok(())
+}
+
+/// Index of the instance an edge trades through, matched on program and pool
+pub(crate) fn find_edge_instance<'info>(
+    instances: &[Box<dyn ProgramMeta + 'info>],
+    edge: &Edge,
+) -> Result<usize> {
+    instances
+        .iter()
+        .position(|instance| {
+            instance.get_id() == &edge.program && instance.get_vaults().0.key == &edge.pool_id
+        })
+        .ok_or(error!(SolarBError::UnknownProgram))
+}
+
+/// Replaces the search's marginal-price `amounts_out` of `arbitrage_path` with each edge's
+/// exact-in quote through its pool, chained from the start amount, so swaps sent without
+/// re-quoting carry minimum outputs they can actually meet. One quote per edge, taken before
+/// any swap moves a pool.
+fn quote_path_amounts<'info>(
+    arbitrage_path: &mut ArbitragePath,
+    instances: &[Box<dyn ProgramMeta + 'info>],
+    clock: &Clock,
+) -> Result<()> {
+    let mut amount = swap_amount(arbitrage_path.start_amount)?;
+    let mut amounts_out = Vec::with_capacity(arbitrage_path.edges.len());
+    for (i, edge) in arbitrage_path.edges.iter().enumerate() {
+        let instance = &instances[find_edge_instance(instances, edge)?];
+        amount = instance
+            .swap_base_in(*edge.input_mint(), amount, clock.clone())
+            .map_err(|err| edge_execution_failed(i, edge, err))?;
+        amounts_out.push(u128::from(amount));
+    }
+    arbitrage_path.amounts_out = amounts_out;
+    Ok(())
+}
+
+/// Input mint of the path's first edge
+fn path_start_token(arbitrage_path: &ArbitragePath) -> Option<Pubkey> {
+    arbitrage_path.edges.first().map(|edge| *edge.input_mint())
+}
+
+/// `SyncNative` to run before and `CloseAccount` (unwrapping to the owner) to run after the
+/// swaps, when `wrap_native` is set and the path starts from the native mint.
+/// `user_accounts` holds the (mint, token program, token account) of every user account;
+/// the one on the native mint is the wSOL account.
+pub fn native_wrap_instructions(
+    wrap_native: bool,
+    start_token: Option<Pubkey>,
+    owner: &Pubkey,
+    user_accounts: &[(&Pubkey, &Pubkey, &Pubkey)],
+) -> Result<Option<(Instruction, Instruction)>> {
+    if !wrap_native || start_token != Some(native_mint::id()) {
+        return Ok(None);
+    }
+    let (_, token_program, wsol_account) = user_accounts
+        .iter()
+        .find(|(mint, _, _)| **mint == native_mint::id())
+        .ok_or(SolarBError::AccountMismatch)?;
+    let sync_native = spl_token::instruction::sync_native(token_program, wsol_account)?;
+    let close_account =
+        spl_token::instruction::close_account(token_program, wsol_account, owner, owner, &[])?;
+    Ok(Some((sync_native, close_account)))
+}
+
+/// `CloseAccount` on the wSOL account, sending its lamports to the owner, when
+/// `sweep_dust` is set and the path both starts and ends on the native mint
+pub fn sweep_dust_instruction(
+    sweep_dust: bool,
+    arbitrage_path: &ArbitragePath,
+    owner: &Pubkey,
+    user_accounts: &[(&Pubkey, &Pubkey, &Pubkey)],
+) -> Result<Option<Instruction>> {
+    let end_token = arbitrage_path.edges.last().map(|edge| *edge.output_mint());
+    if !sweep_dust
+        || path_start_token(arbitrage_path) != Some(native_mint::id())
+        || end_token != Some(native_mint::id())
+    {
+        return Ok(None);
+    }
+    let (_, token_program, wsol_account) = user_accounts
+        .iter()
+        .find(|(mint, _, _)| **mint == native_mint::id())
+        .ok_or(SolarBError::AccountMismatch)?;
+    let close_account =
+        spl_token::instruction::close_account(token_program, wsol_account, owner, owner, &[])?;
+    Ok(Some(close_account))
+}
+
+/// Amount to hand a swap, which takes a `u64`; larger running amounts are an error rather
+/// than being truncated
+fn swap_amount(amount: u128) -> Result<u64> {
+    u64::try_from(amount).map_err(|_| error!(SolarBError::MathOverflow))
+}
+
+/// Least a swap quoted at `amount` may pay out, `slippage_bps` basis points below the quote
+fn min_amount_out(amount: u64, slippage_bps: u16) -> u64 {
+    let slippage = u128::from(amount) * u128::from(slippage_bps.min(10_000)) / 10_000;
+    amount - slippage as u64
+}
+
+/// Reject a quote of `amount_out` for `amount_in` whose rate is more than
+/// `max_price_deviation_bps` basis points above or below the edge's marginal price, atoms
+/// out per atom in after fees. The reserve ratio would be off wherever the venue's curve
+/// or decimals don't price the pool at it. 0 or an edge without a price skips the check.
+fn check_quote_plausible(
+    edge: &Edge,
+    amount_in: u64,
+    amount_out: u64,
+    max_price_deviation_bps: u32,
+) -> Result<()> {
+    let price = edge.get_price();
+    if max_price_deviation_bps == 0 || price <= 0.0 || amount_in == 0 {
+        return Ok(());
+    }
+    let quoted_rate = amount_out as f64 / amount_in as f64;
+    let multiple = 1.0 + f64::from(max_price_deviation_bps) / 10_000.0;
+    if quoted_rate > price * multiple || quoted_rate * multiple < price {
+        msg!(
+            "Pool {} quotes {} for {}, a rate of {} against a marginal price of {}",
+            edge.pool_id,
+            amount_out,
+            amount_in,
+            quoted_rate,
+            price
+        );
+        return Err(error!(SolarBError::ImplausibleQuote));
+    }
+    Ok(())
+}
+
+/// Signed difference between the amount a path ends and starts with
+fn path_profit(final_amount: u128, start_amount: u128) -> Result<i128> {
+    let final_amount = i128::try_from(final_amount).map_err(|_| SolarBError::MathOverflow)?;
+    let start_amount = i128::try_from(start_amount).map_err(|_| SolarBError::MathOverflow)?;
+    final_amount
+        .checked_sub(start_amount)
+        .ok_or(error!(SolarBError::MathOverflow))
+}
+
+/// Log which edge of the path failed and why, then replace the error with
+/// `EdgeExecutionFailed` carrying the edge's index and program as its compared values
+fn edge_execution_failed(index: usize, edge: &Edge, err: Error) -> Error {
+    msg!(
+        "edge_failed index={} program={} pool={} side={:?} error={}",
+        index,
+        edge.program,
+        edge.pool_id,
+        edge.side,
+        err
+    );
+    error!(SolarBError::EdgeExecutionFailed).with_values((index, edge.program))
+}
+
+pub fn execute_arbitrage_path<'info>(
+    arbitrage_path: &ArbitragePath,
+    instances: &mut Vec<Box<dyn ProgramMeta + 'info>>,
+    first_accounts: &FirstAccounts<'_, 'info>,
+    recompute: bool,
+    slippage_bps: u16,
+    max_price_deviation_bps: u32,
+    clock: &Clock,
+) -> Result<()> {
+    check_degenerate_path(arbitrage_path)?;
+    // The profit is only one token's amount on a closed path, so an open one never swaps
+    check_closed_path(arbitrage_path)?;
+    require!(
+        recompute || arbitrage_path.amounts_out.len() == arbitrage_path.edges.len(),
+        SolarBError::MissingExpectedAmount
+    );
+    // Up front, while every instance is still there to create them through. An edge without
+    // user accounts is left to fail in the swap loop below.
+    for edge in arbitrage_path.edges.iter() {
+        let Ok((_, output)) = edge_user_accounts(&first_accounts.user_accounts, edge) else {
+            continue;
+        };
+        if output.token_account.data_is_empty() {
+            create_token_account(
+                instances,
+                first_accounts.fee_payer,
+                first_accounts.authority,
+                &output,
+            )?;
+        }
+    }
+    let mut current_amount = arbitrage_path.start_amount;
 
+    for (i, edge) in arbitrage_path.edges.iter().enumerate() {
         // Find the index of the program instance first, so we can remove it after execution
-        let instance_index = instances
-            .iter()
-            .position(|instance| instance.get_id() == &edge.program)
-            .ok_or(SolarBError::UnknownProgram)?;
+        let instance_index = find_edge_instance(instances, edge)?;
+        msg!(
+            "Edge {} -> {:?} / base_mint {}, base_amount={}, quote_mint {}, quote_amount={}",
+            instances[instance_index].program_label(),
+            edge.side,
+            edge.left.mint_account,
+            edge.left.get_amount(),
+            edge.right.mint_account,
+            edge.right.get_amount()
+        );
+
+        let amount_in = swap_amount(current_amount)?;
+        // Adapters tell base from quote by the mint, so the order of the two doesn't matter
+        let (input, output) = edge_user_accounts(&first_accounts.user_accounts, edge)?;
+        let input_mint = *edge.input_mint();
+        // The user account the swap pays out to, on the output mint
+        let destination = output.token_account;
+        let balance_before = parse_token_account(destination)?.amount;
 
-        // Wrap swap operations in a block scope so program_instance and clock are dropped immediately
-        // This frees stack space (8 bytes for program_instance reference + ~40 bytes for clock) after execution
+        // Wrap swap operations in a block scope so program_instance is dropped immediately
+        // This frees stack space (8 bytes for program_instance reference) after execution
         let amount_out = {
             // Get program instance by index - scoped to this block
             let program_instance = instances[instance_index].as_ref();
 
-            // Get Clock for this swap (may change between swaps) - scoped to this block
-            let clock = Clock::get()?;
+            // Checked against the live pool, which may have moved since the search
+            let min_amount_in = program_instance.min_swap_amount(&input_mint, clock)?;
+            if amount_in < min_amount_in {
+                msg!(
+                    "Edge {} input {} is below the {} pool's minimum swap of {}",
+                    i,
+                    amount_in,
+                    program_instance.program_label(),
+                    min_amount_in
+                );
+                return Err(error!(SolarBError::SwapTooSmall));
+            }
+
+            // Every venue swaps `amount_in` exactly and takes the slippage off this quote as its
+            // minimum out; Pump's buy, which names the base it buys, re-quotes the whole of it
+            let amount = if recompute {
+                program_instance
+                    .swap_base_in(input_mint, amount_in, clock.clone())
+                    .map_err(|err| edge_execution_failed(i, edge, err))?
+            } else {
+                swap_amount(arbitrage_path.amounts_out[i])?
+            };
+            check_quote_plausible(edge, amount_in, amount, max_price_deviation_bps)?;
 
-            match edge.side {
+            match swap_direction(program_instance, &input_mint)? {
                 EdgeSide::LeftToRight => {
-                    let input_mint = edge.left.mint_account;
-                    let amount =
-                        program_instance.swap_base_out(input_mint, current_amount as u64, clock)?;
                     msg!(
-                        "Invoking swap base out for program {:?} with amount_in={}, amount_out={}",
-                        program_instance.get_id(),
-                        current_amount,
+                        "Invoking swap base out for program {} with amount_in={}, amount_out={}",
+                        program_instance.program_label(),
+                        amount_in,
                         amount
                     );
-                    program_instance.invoke_swap_base_out(
-                        input_mint,
-                        current_amount as u64,
-                        Some(amount),
-                        payer.clone(),
-                        user_mint_1_token_account.clone(),
-                        user_mint_2_token_account.clone(),
-                        mint_1.clone(),
-                        mint_2.clone(),
-                        mint_1_token_program.clone(),
-                        mint_2_token_program.clone(),
-                    )?;
-                    amount
+                    program_instance
+                        .invoke_swap_base_out(
+                            input_mint,
+                            amount_in,
+                            Some(min_amount_out(amount, slippage_bps)),
+                            first_accounts.authority.clone(),
+                            input.token_account.clone(),
+                            output.token_account.clone(),
+                            input.mint.clone(),
+                            output.mint.clone(),
+                            input.token_program.clone(),
+                            output.token_program.clone(),
+                        )
+                        .map_err(|err| edge_execution_failed(i, edge, err))?;
                 }
                 EdgeSide::RightToLeft => {
-                    let input_mint = edge.right.mint_account;
-                    let amount =
-                        program_instance.swap_base_in(input_mint, current_amount as u64, clock)?;
                     msg!(
-                        "Invoking swap base in for program {:?} with amount_in={}, amount_out={}",
-                        program_instance.get_id(),
-                        current_amount,
+                        "Invoking swap base in for program {} with amount_in={}, amount_out={}",
+                        program_instance.program_label(),
+                        amount_in,
                         amount
                     );
-                    program_instance.invoke_swap_base_in(
-                        input_mint,
-                        current_amount as u64,
-                        Some(amount),
-                        payer.clone(),
-                        user_mint_1_token_account.clone(),
-                        user_mint_2_token_account.clone(),
-                        mint_1.clone(),
-                        mint_2.clone(),
-                        mint_1_token_program.clone(),
-                        mint_2_token_program.clone(),
-                    )?;
-                    amount
+                    program_instance
+                        .invoke_swap_base_in(
+                            input_mint,
+                            amount_in,
+                            Some(min_amount_out(amount, slippage_bps)),
+                            first_accounts.authority.clone(),
+                            input.token_account.clone(),
+                            output.token_account.clone(),
+                            input.mint.clone(),
+                            output.mint.clone(),
+                            input.token_program.clone(),
+                            output.token_program.clone(),
+                        )
+                        .map_err(|err| edge_execution_failed(i, edge, err))?;
                 }
             }
-            // program_instance and clock are dropped here when this block ends
+            amount
+            // program_instance is dropped here when this block ends
         };
 
         // Remove the program instance from the vector after it's been used
@@ -362,7 +1741,16 @@ pub fn execute_arbitrage_path<'info>(
         // Order doesn't matter since we're removing after use and finding by program_id
         instances.swap_remove(instance_index);
 
-        current_amount = amount_out as u128;
+        // Fees, rounding and partial fills can leave the received amount short of the quote,
+        // so the next edge swaps what actually arrived
+        let received = parse_token_account(destination)?
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(error!(SolarBError::MathOverflow))?;
+        if received != amount_out {
+            msg!("Edge {} received {}, quoted {}", i, received, amount_out);
+        }
+        current_amount = u128::from(received);
         msg!(
             "Edge {} completed, new current_amount={}",
             i,
@@ -370,7 +1758,7 @@ pub fn execute_arbitrage_path<'info>(
         );
     }
 
-    let final_profit = current_amount as i128 - arbitrage_path.start_amount as i128;
+    let final_profit = path_profit(current_amount, arbitrage_path.start_amount)?;
     msg!(
         "Completed. Final amount: {}, Profit: {}",
         current_amount,
@@ -383,7 +1771,10 @@ pub fn execute_arbitrage_path<'info>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::utils::SwapUserAccounts;
     use anchor_lang::solana_program::{account_info::AccountInfo, pubkey::Pubkey, system_program};
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
 
     // Helper function to create a mock AccountInfo
     fn create_mock_account_info(
@@ -423,28 +1814,20 @@ mod tests {
             .collect()
     }
 
+    #[cfg(feature = "damm-v2")]
     #[test]
     fn test_parse_accounts_success_single_program() {
-        let owner = system_program::id();
         let mut accounts = Vec::new();
 
         // Create MeteoraDammV2 program accounts (9 accounts: program_id + 8 payload)
         let program_id = MeteoraDammV2::PROGRAM_ID;
-        accounts.push(create_mock_account_info(program_id, owner, 0, None));
-        // Add 8 more accounts for MeteoraDammV2
-        for _ in 0..8 {
-            accounts.push(create_mock_account_info(
-                Pubkey::new_unique(),
-                owner,
-                0,
-                None,
-            ));
-        }
+        accounts.extend(create_program_span(program_id, 9));
 
-        let data = InstructionData {
-            accounts_length: [9, 0, 0, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![9])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         assert!(result.is_ok());
@@ -453,39 +1836,24 @@ mod tests {
         assert!(*instances[0].get_id() == program_id);
     }
 
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
     #[test]
     fn test_parse_accounts_success_multiple_programs() {
-        let owner = system_program::id();
         let mut accounts = Vec::new();
 
         // First program: MeteoraDammV2 (9 accounts)
         let program_id_1 = MeteoraDammV2::PROGRAM_ID;
-        accounts.push(create_mock_account_info(program_id_1, owner, 0, None));
-        for _ in 0..8 {
-            accounts.push(create_mock_account_info(
-                Pubkey::new_unique(),
-                owner,
-                0,
-                None,
-            ));
-        }
+        accounts.extend(create_program_span(program_id_1, 9));
 
         // Second program: MeteoraDlmm (13 accounts)
         let program_id_2 = MeteoraDlmm::PROGRAM_ID;
-        accounts.push(create_mock_account_info(program_id_2, owner, 0, None));
-        for _ in 0..12 {
-            accounts.push(create_mock_account_info(
-                Pubkey::new_unique(),
-                owner,
-                0,
-                None,
-            ));
-        }
+        accounts.extend(create_program_span(program_id_2, 13));
 
-        let data = InstructionData {
-            accounts_length: [9, 13, 0, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 13])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         assert!(result.is_ok());
@@ -495,28 +1863,21 @@ mod tests {
         assert!(*instances[1].get_id() == program_id_2);
     }
 
+    #[cfg(feature = "damm-v2")]
     #[test]
     fn test_parse_accounts_skips_zero_span() {
-        let owner = system_program::id();
         let mut accounts = Vec::new();
 
         // Create one program
         let program_id = MeteoraDammV2::PROGRAM_ID;
-        accounts.push(create_mock_account_info(program_id, owner, 0, None));
-        for _ in 0..8 {
-            accounts.push(create_mock_account_info(
-                Pubkey::new_unique(),
-                owner,
-                0,
-                None,
-            ));
-        }
+        accounts.extend(create_program_span(program_id, 9));
 
         // Zero spans should be skipped
-        let data = InstructionData {
-            accounts_length: [9, 0, 0, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 0, 0, 0, 0])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         assert!(result.is_ok());
@@ -524,6 +1885,7 @@ mod tests {
         assert!(instances.len() == 1);
     }
 
+    #[cfg(feature = "damm-v2")]
     #[test]
     fn test_parse_accounts_insufficient_accounts() {
         let owner = system_program::id();
@@ -541,16 +1903,18 @@ mod tests {
             ));
         }
 
-        let data = InstructionData {
-            accounts_length: [9, 0, 0, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![9])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         assert!(result.is_err());
         // Just verify it's an error - Anchor error types are complex to match
     }
 
+    #[cfg(feature = "damm-v2")]
     #[test]
     fn test_parse_accounts_trailing_accounts() {
         let owner = system_program::id();
@@ -558,15 +1922,7 @@ mod tests {
 
         // Create program with 9 accounts
         let program_id = MeteoraDammV2::PROGRAM_ID;
-        accounts.push(create_mock_account_info(program_id, owner, 0, None));
-        for _ in 0..8 {
-            accounts.push(create_mock_account_info(
-                Pubkey::new_unique(),
-                owner,
-                0,
-                None,
-            ));
-        }
+        accounts.extend(create_program_span(program_id, 9));
 
         // Add an extra account that shouldn't be there
         accounts.push(create_mock_account_info(
@@ -576,10 +1932,11 @@ mod tests {
             None,
         ));
 
-        let data = InstructionData {
-            accounts_length: [9, 0, 0, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![9])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         assert!(result.is_err());
@@ -603,26 +1960,118 @@ mod tests {
             ));
         }
 
-        let data = InstructionData {
-            accounts_length: [9, 0, 0, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![9])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         assert!(result.is_err());
         // Just verify it's an error - Anchor error types are complex to match
     }
 
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_parse_accounts_skips_unknown_program_when_asked() {
+        let owner = system_program::id();
+        let mut accounts = create_damm_v2_span(Pubkey::new_unique(), Pubkey::new_unique());
+        accounts
+            .extend((0..5).map(|_| create_mock_account_info(Pubkey::new_unique(), owner, 0, None)));
+        accounts.extend(create_damm_v2_span(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ));
+        let builder = InstructionData::builder().accounts_length(vec![9, 5, 9]);
+
+        assert_eq!(
+            parse_accounts(&accounts, &builder.clone().build())
+                .err()
+                .unwrap(),
+            error!(SolarBError::UnknownProgram)
+        );
+
+        let data = builder.skip_unknown(true).build();
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0].get_vaults().0.key, accounts[2].key);
+        assert_eq!(instances[1].get_vaults().0.key, accounts[16].key);
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_parse_accounts_span_arithmetic_randomized() {
+        // xorshift64 with a fixed seed, so failures reproduce
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+
+        for _ in 0..200 {
+            let mut accounts = Vec::new();
+            let mut accounts_length = Vec::new();
+            let mut all_known = true;
+            for _ in 0..next(6) {
+                match next(4) {
+                    0 => accounts_length.push(0),
+                    1 => {
+                        // Not a program `find_program_instance` knows
+                        let span = 1 + next(12) as usize;
+                        accounts.extend(create_mock_accounts(span, system_program::id()));
+                        accounts_length.push(span as u32);
+                        all_known = false;
+                    }
+                    _ => {
+                        // With or without the referral token account
+                        let span = 8 + next(2) as usize;
+                        accounts.extend(create_program_span(MeteoraDammV2::PROGRAM_ID, span));
+                        accounts_length.push(span as u32);
+                    }
+                }
+            }
+            // Drop or add a few accounts at the end, so the spans may not cover them exactly
+            match next(3) {
+                0 => accounts.truncate(accounts.len().saturating_sub(1 + next(3) as usize)),
+                1 => accounts.extend(create_mock_accounts(
+                    1 + next(3) as usize,
+                    Pubkey::new_unique(),
+                )),
+                _ => {}
+            }
+
+            let spans_sum: usize = accounts_length.iter().map(|&span| span as usize).sum();
+            let data = InstructionData::builder()
+                .accounts_length(accounts_length.clone())
+                .build();
+            let result = parse_accounts(&accounts, &data);
+            assert_eq!(
+                result.is_ok(),
+                spans_sum == accounts.len() && all_known,
+                "spans {:?} over {} accounts",
+                accounts_length,
+                accounts.len()
+            );
+            if let Ok(instances) = result {
+                let non_zero = accounts_length.iter().filter(|&&span| span > 0).count();
+                assert_eq!(instances.len(), non_zero);
+            }
+        }
+    }
+
     #[test]
     fn test_parse_accounts_invalid_accounts_length() {
         let accounts = create_mock_accounts(5, system_program::id());
 
         // Use a span that's too large to convert from u32 to usize
         // On most platforms this won't happen, but we test the error path
-        let data = InstructionData {
-            accounts_length: [u32::MAX, 0, 0, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![u32::MAX])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         // This should either error on conversion or on insufficient accounts
@@ -633,10 +2082,11 @@ mod tests {
     fn test_parse_accounts_empty_segment() {
         let accounts = Vec::new();
 
-        let data = InstructionData {
-            accounts_length: [0, 0, 0, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![0, 0, 0, 0, 0])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         assert!(result.is_ok());
@@ -644,27 +2094,18 @@ mod tests {
         assert!(instances.len() == 0);
     }
 
+    #[cfg(feature = "damm-v1")]
     #[test]
     fn test_parse_accounts_meteora_damm_v1() {
-        let owner = system_program::id();
-        let mut accounts = Vec::new();
-
-        // MeteoraDammV1 needs 10 accounts (no program_id in payload, starts with pool_id)
+        // MeteoraDammV1 needs 15 accounts (program_id, pool, vaults, vault LP accounts, ...)
         let program_id = MeteoraDammV1::PROGRAM_ID;
-        accounts.push(create_mock_account_info(program_id, owner, 0, None));
-        for _ in 0..9 {
-            accounts.push(create_mock_account_info(
-                Pubkey::new_unique(),
-                owner,
-                0,
-                None,
-            ));
-        }
+        let accounts = programs::meteora_damm_v1::tests::create_test_accounts();
 
-        let data = InstructionData {
-            accounts_length: [10, 0, 0, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![15])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         assert!(result.is_ok());
@@ -673,27 +2114,20 @@ mod tests {
         assert!(*instances[0].get_id() == program_id);
     }
 
+    #[cfg(feature = "dlmm")]
     #[test]
     fn test_parse_accounts_meteora_dlmm() {
-        let owner = system_program::id();
         let mut accounts = Vec::new();
 
         // MeteoraDlmm needs 13 accounts
         let program_id = MeteoraDlmm::PROGRAM_ID;
-        accounts.push(create_mock_account_info(program_id, owner, 0, None));
-        for _ in 0..12 {
-            accounts.push(create_mock_account_info(
-                Pubkey::new_unique(),
-                owner,
-                0,
-                None,
-            ));
-        }
+        accounts.extend(create_program_span(program_id, 13));
 
-        let data = InstructionData {
-            accounts_length: [13, 0, 0, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![13])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         assert!(result.is_ok());
@@ -702,6 +2136,7 @@ mod tests {
         assert!(*instances[0].get_id() == program_id);
     }
 
+    #[cfg(feature = "dlmm")]
     #[test]
     fn test_parse_accounts_insufficient_accounts_for_program() {
         let owner = system_program::id();
@@ -719,50 +2154,36 @@ mod tests {
             ));
         }
 
-        let data = InstructionData {
-            accounts_length: [10, 0, 0, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![10])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         assert!(result.is_err());
         // Just verify it's an error - Anchor error types are complex to match
     }
 
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
     #[test]
     fn test_parse_accounts_multiple_programs_with_zero_spans() {
-        let owner = system_program::id();
         let mut accounts = Vec::new();
 
         // First program
         let program_id_1 = MeteoraDammV2::PROGRAM_ID;
-        accounts.push(create_mock_account_info(program_id_1, owner, 0, None));
-        for _ in 0..8 {
-            accounts.push(create_mock_account_info(
-                Pubkey::new_unique(),
-                owner,
-                0,
-                None,
-            ));
-        }
+        accounts.extend(create_program_span(program_id_1, 9));
 
         // Second program
         let program_id_2 = MeteoraDlmm::PROGRAM_ID;
-        accounts.push(create_mock_account_info(program_id_2, owner, 0, None));
-        for _ in 0..12 {
-            accounts.push(create_mock_account_info(
-                Pubkey::new_unique(),
-                owner,
-                0,
-                None,
-            ));
-        }
+        accounts.extend(create_program_span(program_id_2, 13));
 
         // Mix of zero and non-zero spans
-        let data = InstructionData {
-            accounts_length: [9, 0, 13, 0, 0],
-            epoch: 0,
-        };
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 0, 13, 0, 0])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
 
         let result = parse_accounts(&accounts, &data);
         assert!(result.is_ok());
@@ -771,4 +2192,3617 @@ mod tests {
         assert!(*instances[0].get_id() == program_id_1);
         assert!(*instances[1].get_id() == program_id_2);
     }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_parse_accounts_damm_v2_with_and_without_referral() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+
+        // Without the referral token account the span is 8 accounts, with it 9
+        let mut accounts = create_span_with_reserves(
+            MeteoraDammV2::PROGRAM_ID,
+            8,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            2_000_000_000,
+        );
+        accounts.extend(create_damm_v2_span(base_mint, quote_mint));
+        let data = InstructionData::builder()
+            .accounts_length(vec![8, 9])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        assert_eq!(instances.len(), 2);
+        let edges = get_edges(&instances, &Clock::default(), 0).unwrap();
+        assert_eq!(edges.len(), 4);
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_parse_accounts_rejects_inconsistent_accounts() {
+        let data = InstructionData::builder()
+            .accounts_length(vec![9])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        // The base vault holds a different mint than the one supplied as base token
+        let mut accounts = create_program_span(MeteoraDammV2::PROGRAM_ID, 9);
+        accounts[4] = create_mock_account_info(Pubkey::new_unique(), system_program::id(), 0, None);
+        let result = parse_accounts(&accounts, &data);
+        assert_eq!(result.err().unwrap(), error!(SolarBError::AccountMismatch));
+
+        // Swapping base and quote together keeps each vault on its mint, but not on the pool's
+        let mut accounts = create_program_span(MeteoraDammV2::PROGRAM_ID, 9);
+        accounts.swap(2, 3);
+        accounts.swap(4, 5);
+        let result = parse_accounts(&accounts, &data);
+        assert_eq!(
+            result.err().unwrap(),
+            error!(SolarBError::PoolStateMismatch)
+        );
+    }
+
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
+    #[test]
+    fn test_parse_accounts_more_than_five_spans() {
+        let mut accounts = Vec::new();
+
+        // Seven programs alternating MeteoraDammV2 (9 accounts) and MeteoraDlmm (13 accounts)
+        let program_ids = [
+            MeteoraDammV2::PROGRAM_ID,
+            MeteoraDlmm::PROGRAM_ID,
+            MeteoraDammV2::PROGRAM_ID,
+            MeteoraDlmm::PROGRAM_ID,
+            MeteoraDammV2::PROGRAM_ID,
+            MeteoraDlmm::PROGRAM_ID,
+            MeteoraDammV2::PROGRAM_ID,
+        ];
+        let mut accounts_length = Vec::new();
+        for program_id in program_ids.iter() {
+            let span = if *program_id == MeteoraDlmm::PROGRAM_ID {
+                13
+            } else {
+                9
+            };
+            accounts.extend(create_program_span(*program_id, span as usize));
+            accounts_length.push(span);
+        }
+
+        let data = InstructionData::builder()
+            .accounts_length(accounts_length)
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        let result = parse_accounts(&accounts, &data);
+        assert!(result.is_ok());
+        let instances = result.unwrap();
+        assert!(instances.len() == 7);
+        for (instance, program_id) in instances.iter().zip(program_ids.iter()) {
+            assert!(instance.get_id() == program_id);
+        }
+    }
+
+    // Lays the spans out through the client builder and hands back the accounts in the
+    // order of its metas, with the options it emitted
+    #[cfg(any(
+        feature = "damm-v1",
+        feature = "damm-v2",
+        feature = "dlmm",
+        feature = "lifinity",
+        feature = "phoenix"
+    ))]
+    fn layout_spans(
+        spans: Vec<(client::ProgramKind, Vec<AccountInfo<'static>>)>,
+    ) -> (Vec<AccountInfo<'static>>, InstructionData) {
+        let mut builder = client::AccountsLayoutBuilder::new();
+        for (kind, span) in &spans {
+            builder
+                .push(*kind, span.iter().map(|account| *account.key).collect())
+                .unwrap();
+        }
+        let (metas, accounts_length) = builder.build();
+        let accounts: Vec<AccountInfo<'static>> =
+            spans.into_iter().flat_map(|(_, span)| span).collect();
+        let ordered = metas
+            .iter()
+            .map(|meta| {
+                accounts
+                    .iter()
+                    .find(|account| *account.key == meta.pubkey)
+                    .unwrap()
+                    .clone()
+            })
+            .collect();
+        let data = InstructionData::builder()
+            .accounts_length(accounts_length)
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+        (ordered, data)
+    }
+
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
+    #[test]
+    fn test_layout_builder_round_trips_through_parse_accounts() {
+        use client::ProgramKind;
+
+        // Both DAMM v2 layouts and DLMM with extra bin arrays
+        let spans = vec![
+            (
+                ProgramKind::MeteoraDammV2,
+                create_program_span(MeteoraDammV2::PROGRAM_ID, 8),
+            ),
+            (
+                ProgramKind::MeteoraDlmm,
+                create_program_span(MeteoraDlmm::PROGRAM_ID, 15),
+            ),
+            (
+                ProgramKind::MeteoraDammV2,
+                create_program_span(MeteoraDammV2::PROGRAM_ID, 9),
+            ),
+            (
+                ProgramKind::MeteoraDlmm,
+                create_program_span(MeteoraDlmm::PROGRAM_ID, 13),
+            ),
+        ];
+        let program_ids: Vec<Pubkey> = spans.iter().map(|(kind, _)| kind.program_id()).collect();
+        let (accounts, data) = layout_spans(spans);
+        assert_eq!(data.accounts_length, vec![8, 15, 9, 13]);
+
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        assert_eq!(instances.len(), program_ids.len());
+        for (instance, program_id) in instances.iter().zip(&program_ids) {
+            assert_eq!(instance.get_id(), program_id);
+        }
+    }
+
+    #[cfg(feature = "damm-v1")]
+    #[test]
+    fn test_layout_builder_round_trips_damm_v1() {
+        let (accounts, data) = layout_spans(vec![(
+            client::ProgramKind::MeteoraDammV1,
+            programs::meteora_damm_v1::tests::create_test_accounts(),
+        )]);
+        assert_eq!(data.accounts_length, vec![15]);
+
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(*instances[0].get_id(), MeteoraDammV1::PROGRAM_ID);
+    }
+
+    #[cfg(feature = "lifinity")]
+    #[test]
+    fn test_layout_builder_round_trips_lifinity() {
+        let (accounts, data) = layout_spans(vec![(
+            client::ProgramKind::LifinityV2,
+            programs::lifinity::tests::create_test_accounts(),
+        )]);
+        assert_eq!(data.accounts_length, vec![12]);
+
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(*instances[0].get_id(), LifinityV2::PROGRAM_ID);
+        // The oracle sits at 1.0, which edges price from whatever the reserves say
+        let edges = get_edges(&instances, &Clock::default(), 0).unwrap();
+        assert_eq!(edges.len(), 2);
+        assert!(edges
+            .iter()
+            .all(|edge| (edge.get_price() - 0.9975).abs() < 1e-9));
+    }
+
+    #[cfg(feature = "phoenix")]
+    #[test]
+    fn test_layout_builder_round_trips_phoenix() {
+        let (accounts, data) = layout_spans(vec![(
+            client::ProgramKind::Phoenix,
+            programs::phoenix::tests::create_test_accounts(),
+        )]);
+        assert_eq!(data.accounts_length, vec![8]);
+
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(*instances[0].get_id(), Phoenix::PROGRAM_ID);
+        // Edges price off the top of the book: a 0.9999 bid and a 1.0001 ask, less 2 bps
+        let edges = get_edges(&instances, &Clock::default(), 0).unwrap();
+        assert_eq!(edges.len(), 2);
+        assert!((edges[0].get_price() - 0.9999 * 0.9998).abs() < 1e-9);
+        assert!((edges[1].get_price() - 0.9998 / 1.0001).abs() < 1e-9);
+    }
+
+    // Helper to create SPL token account data (mint, owner, amount, initialized state)
+    fn create_token_account_data(mint: Pubkey, amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; 165];
+        data[0..32].copy_from_slice(&mint.to_bytes());
+        data[32..64].copy_from_slice(&Pubkey::new_unique().to_bytes());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data[108] = 1; // AccountState::Initialized
+        data
+    }
+
+    // Helper to create a MeteoraDammV2 span whose vaults hold the given mints
+    #[cfg(feature = "damm-v2")]
+    fn create_damm_v2_span(base_mint: Pubkey, quote_mint: Pubkey) -> Vec<AccountInfo<'static>> {
+        create_span_with_reserves(
+            MeteoraDammV2::PROGRAM_ID,
+            9,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            2_000_000_000,
+        )
+    }
+
+    // Helper to create a span for any program laid out as program, pool, base/quote vault,
+    // base/quote mint, with explicit vault amounts. The pool account holds a fee-free pool
+    // state large enough for the DAMM v2 and DLMM layouts, zeroed except for the vaults and
+    // mints it records.
+    #[cfg(any(feature = "damm-v2", feature = "dlmm"))]
+    fn create_span_with_reserves(
+        program_id: Pubkey,
+        span: usize,
+        base_mint: Pubkey,
+        quote_mint: Pubkey,
+        base_amount: u64,
+        quote_amount: u64,
+    ) -> Vec<AccountInfo<'static>> {
+        let owner = system_program::id();
+        // Large enough for either the DAMM v2 or the DLMM pool state
+        let pool_state_len = 2048;
+        let mut accounts = vec![
+            create_mock_account_info(program_id, owner, 0, None),
+            create_mock_account_info(
+                Pubkey::new_unique(),
+                owner,
+                0,
+                Some(vec![0u8; pool_state_len]),
+            ),
+            create_mock_account_info(
+                Pubkey::new_unique(),
+                owner,
+                0,
+                Some(create_token_account_data(base_mint, base_amount)),
+            ),
+            create_mock_account_info(
+                Pubkey::new_unique(),
+                owner,
+                0,
+                Some(create_token_account_data(quote_mint, quote_amount)),
+            ),
+            create_mock_account_info(base_mint, owner, 0, None),
+            create_mock_account_info(quote_mint, owner, 0, None),
+        ];
+        record_pool_keys(&accounts);
+        // The slot where each adapter checks for its program's event authority
+        let event_authority_index = match program_id {
+            #[cfg(feature = "damm-v2")]
+            id if id == MeteoraDammV2::PROGRAM_ID => Some(7),
+            #[cfg(feature = "dlmm")]
+            id if id == MeteoraDlmm::PROGRAM_ID => Some(9),
+            _ => None,
+        };
+        while accounts.len() < span {
+            let key = if event_authority_index == Some(accounts.len()) {
+                utils::utils::derive_event_authority(&program_id)
+            } else {
+                Pubkey::new_unique()
+            };
+            accounts.push(create_mock_account_info(key, owner, 0, None));
+        }
+        accounts
+    }
+
+    // Helper to create a `span`-account span for `program_id` over a fresh pair of mints,
+    // consistent enough to pass account validation
+    #[cfg(any(feature = "damm-v2", feature = "dlmm"))]
+    fn create_program_span(program_id: Pubkey, span: usize) -> Vec<AccountInfo<'static>> {
+        create_span_with_reserves(
+            program_id,
+            span,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            2_000_000_000,
+        )
+    }
+
+    // Writes the span's vaults and mints where its program's pool state records them
+    #[cfg(any(feature = "damm-v2", feature = "dlmm"))]
+    fn record_pool_keys(accounts: &[AccountInfo]) {
+        #[allow(unused_mut)]
+        let mut offsets: Option<[usize; 4]> = None;
+        #[cfg(feature = "damm-v2")]
+        if *accounts[0].key == MeteoraDammV2::PROGRAM_ID {
+            use programs::meteora_damm_v2::Pool;
+            offsets = Some([
+                std::mem::offset_of!(Pool, token_a_vault),
+                std::mem::offset_of!(Pool, token_b_vault),
+                std::mem::offset_of!(Pool, token_a_mint),
+                std::mem::offset_of!(Pool, token_b_mint),
+            ]);
+        }
+        #[cfg(feature = "dlmm")]
+        if *accounts[0].key == MeteoraDlmm::PROGRAM_ID {
+            use dlmm::dlmm::accounts::LbPair;
+            offsets = Some([
+                std::mem::offset_of!(LbPair, reserve_x),
+                std::mem::offset_of!(LbPair, reserve_y),
+                std::mem::offset_of!(LbPair, token_x_mint),
+                std::mem::offset_of!(LbPair, token_y_mint),
+            ]);
+        }
+        let Some(offsets) = offsets else {
+            return;
+        };
+        let mut pool_data = accounts[1].try_borrow_mut_data().unwrap();
+        for (offset, account) in offsets.into_iter().zip(&accounts[2..6]) {
+            // Past the 8-byte discriminator
+            pool_data[8 + offset..8 + offset + 32].copy_from_slice(account.key.as_ref());
+        }
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_select_instances_subset_edges() {
+        let mints: Vec<(Pubkey, Pubkey)> = (0..3)
+            .map(|_| (Pubkey::new_unique(), Pubkey::new_unique()))
+            .collect();
+        let mut accounts = Vec::new();
+        for (base_mint, quote_mint) in mints.iter() {
+            accounts.extend(create_damm_v2_span(*base_mint, *quote_mint));
+        }
+
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 9, 9])
+            .selected_instances(vec![0, 2])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        // All spans are still consumed by the layout
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        assert!(instances.len() == 3);
+
+        let instances = select_instances(instances, data.selected_instances.as_deref()).unwrap();
+        assert!(instances.len() == 2);
+
+        let edges = get_edges(&instances, &Clock::default(), 0).unwrap();
+        assert!(edges.len() == 4);
+        let (skipped_base, skipped_quote) = mints[1];
+        for edge in edges.iter() {
+            assert!(edge.left.mint_account != skipped_base);
+            assert!(edge.left.mint_account != skipped_quote);
+            assert!(edge.right.mint_account != skipped_base);
+            assert!(edge.right.mint_account != skipped_quote);
+        }
+        assert!(edges[0].left.mint_account == mints[0].0);
+        assert!(edges[2].left.mint_account == mints[2].0);
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_select_instances_none_keeps_all() {
+        let mut accounts = create_damm_v2_span(Pubkey::new_unique(), Pubkey::new_unique());
+        accounts.extend(create_damm_v2_span(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ));
+
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 9])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        let instances = select_instances(instances, data.selected_instances.as_deref()).unwrap();
+        assert!(instances.len() == 2);
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_select_instances_out_of_range() {
+        let accounts = create_damm_v2_span(Pubkey::new_unique(), Pubkey::new_unique());
+
+        let data = InstructionData::builder()
+            .accounts_length(vec![9])
+            .selected_instances(vec![1])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        let result = select_instances(instances, data.selected_instances.as_deref());
+        assert!(result.is_err());
+        assert!(result.err().unwrap() == SolarBError::InvalidInstanceSelection.into());
+    }
+
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
+    #[test]
+    fn test_run_arbitrage_rejects_implausible_profit() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+
+        // The DLMM pool's quote vault reads as 1e18, as if deserialized from the wrong offset
+        let mut accounts = create_damm_v2_span(base_mint, quote_mint);
+        accounts.extend(create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            1_000_000_000_000_000_000,
+        ));
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 13])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        let mut instances = parse_accounts(&accounts, &data).unwrap();
+        let result = run_arbitrage(
+            &mut instances,
+            &[(Some(base_mint), 1_000_000)],
+            None,
+            0,
+            0,
+            None,
+            None,
+            None,
+            false,
+            &[],
+            &Clock::default(),
+        );
+        assert_eq!(result.unwrap_err(), error!(SolarBError::ImplausibleProfit));
+    }
+
+    /// Both edges of `pools` pools, pool `i` holding `i + 1` times the base reserve
+    fn edges_by_depth(pools: usize) -> Vec<Edge> {
+        let program = Pubkey::new_unique();
+        let (base_mint, quote_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        (0..pools as u128)
+            .flat_map(|i| {
+                let pool_id = Pubkey::new_unique();
+                let base = Pool::new(&base_mint, (i + 1) * 1_000_000_000);
+                let quote = Pool::new(&quote_mint, (i + 1) * 2_000_000_000);
+                [
+                    Edge::new(
+                        program,
+                        pool_id,
+                        EdgeSide::LeftToRight,
+                        2.0,
+                        base.clone(),
+                        quote.clone(),
+                    ),
+                    Edge::new(program, pool_id, EdgeSide::RightToLeft, 0.5, quote, base),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_limit_edges_keeps_the_deepest_pools() {
+        let mut edges = edges_by_depth(20);
+        edges.sort_by(|a, b| a.ordering_key().cmp(&b.ordering_key()));
+        let keys = |edges: &[Edge]| -> Vec<(Pubkey, Pubkey)> {
+            edges
+                .iter()
+                .map(|edge| (edge.pool_id, *edge.input_mint()))
+                .collect()
+        };
+
+        assert_eq!(keys(&limit_edges(edges.clone(), 0).unwrap()), keys(&edges));
+        assert_eq!(keys(&limit_edges(edges.clone(), 40).unwrap()), keys(&edges));
+
+        let depth = |edge: &Edge| edge.left.amount * edge.right.amount;
+        // The tenth deepest pool's edges are the last kept, an odd limit leaving out the
+        // pool it would split
+        let mut depths: Vec<u128> = edges.iter().map(depth).collect();
+        depths.sort_unstable();
+        for max_edges in [20, 21] {
+            let limited = limit_edges(edges.clone(), max_edges).unwrap();
+            assert_eq!(limited.len(), 20);
+            assert!(limited.iter().all(|edge| depth(edge) >= depths[20]));
+            let mut expected = edges.clone();
+            expected.retain(|edge| depth(edge) >= depths[20]);
+            assert_eq!(keys(&limited), keys(&expected));
+        }
+        assert!(limit_edges(edges, 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_limit_edges_caps_the_search() {
+        let edges = edges_by_depth(MAX_SEARCH_EDGES / 2 + 1);
+        assert_eq!(
+            limit_edges(edges.clone(), 0).unwrap_err(),
+            error!(SolarBError::TooManyEdges)
+        );
+        assert_eq!(
+            limit_edges(edges.clone(), MAX_SEARCH_EDGES + 2).unwrap_err(),
+            error!(SolarBError::TooManyEdges)
+        );
+        assert_eq!(
+            limit_edges(edges, MAX_SEARCH_EDGES).unwrap().len(),
+            MAX_SEARCH_EDGES
+        );
+    }
+
+    #[cfg(feature = "dlmm")]
+    #[test]
+    fn test_run_arbitrage_searches_only_the_deepest_pools() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+
+        // 19 deep pools at one price, and a thin one 10% above them
+        let mut accounts = Vec::new();
+        for depth in 1..20 {
+            accounts.extend(create_span_with_reserves(
+                MeteoraDlmm::PROGRAM_ID,
+                13,
+                base_mint,
+                quote_mint,
+                depth * 1_000_000_000_000,
+                depth * 2_000_000_000_000,
+            ));
+        }
+        accounts.extend(create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            2_200_000_000,
+        ));
+        let data = InstructionData::builder()
+            .accounts_length(vec![13; 20])
+            .build();
+
+        let search = |max_edges: usize| {
+            let mut instances = parse_accounts(&accounts, &data).unwrap();
+            run_arbitrage(
+                &mut instances,
+                &[(Some(base_mint), 1_000_000)],
+                None,
+                0,
+                max_edges,
+                None,
+                None,
+                None,
+                false,
+                &[],
+                &Clock::default(),
+            )
+        };
+        // Edges name their pool by its base vault
+        let thin_pool = *accounts[19 * 13 + 2].key;
+        let path = search(0).unwrap();
+        assert!(path.edges.iter().any(|edge| edge.pool_id == thin_pool));
+        assert_eq!(search(38).unwrap_err(), error!(SolarBError::NoProfitFound));
+    }
+
+    #[test]
+    fn test_require_payer_signer() {
+        let mut accounts = create_execution_accounts(&[Pubkey::new_unique(), Pubkey::new_unique()]);
+        assert_eq!(
+            require_payer_signer(&accounts, false).unwrap_err(),
+            error!(SolarBError::PayerNotSigner)
+        );
+        accounts[0].is_signer = true;
+        require_payer_signer(&accounts, false).unwrap();
+        assert_eq!(
+            require_payer_signer(&[], false).unwrap_err(),
+            error!(SolarBError::InsufficientAccounts)
+        );
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_damm_v2_liquidity_checks_the_accounts() {
+        use programs::meteora_damm_v2::{tests as damm_v2_tests, PositionAccounts};
+
+        let data = LiquidityData {
+            remove: false,
+            liquidity_delta: 1,
+            token_a_amount_threshold: 0,
+            token_b_amount_threshold: 0,
+        };
+        let position: Vec<AccountInfo> = (0..PositionAccounts::LEN)
+            .map(|_| create_mock_account_info(Pubkey::new_unique(), system_program::id(), 0, None))
+            .collect();
+        let span = damm_v2_tests::create_swap_test_accounts(&damm_v2_tests::create_test_pool());
+
+        assert_eq!(
+            run_damm_v2_liquidity(&position, &data).unwrap_err(),
+            error!(SolarBError::InsufficientAccounts)
+        );
+        // The pool span has to come after the position's accounts
+        let swapped = [span.clone(), position.clone()].concat();
+        assert_eq!(
+            run_damm_v2_liquidity(&swapped, &data).unwrap_err(),
+            error!(SolarBError::UnknownProgram)
+        );
+        let mut foreign_vault = [position, span].concat();
+        foreign_vault[PositionAccounts::LEN + 2] =
+            create_mock_account_info(Pubkey::new_unique(), system_program::id(), 0, None);
+        assert!(run_damm_v2_liquidity(&foreign_vault, &data).is_err());
+    }
+
+    #[cfg(feature = "dlmm")]
+    #[test]
+    fn test_parse_accounts_passes_bin_array_counts_to_dlmm() {
+        // Two accounts follow the fixed ones of the span
+        let accounts = create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            1_000_000_000,
+        );
+        let data = |bin_array_counts| {
+            InstructionData::builder()
+                .accounts_length(vec![13])
+                .bin_array_counts(bin_array_counts)
+                .build()
+        };
+
+        for bin_array_counts in [vec![], vec![None], vec![Some((1, 1))], vec![Some((2, 0))]] {
+            assert!(parse_accounts(&accounts, &data(bin_array_counts)).is_ok());
+        }
+        assert_eq!(
+            parse_accounts(&accounts, &data(vec![Some((1, 2))])).err(),
+            Some(error!(SolarBError::AccountSpanMismatch))
+        );
+    }
+
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
+    #[test]
+    fn test_run_arbitrage_max_plausible_profit_is_configurable() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+
+        // 10% price gap between the two pools
+        let mut accounts = create_damm_v2_span(base_mint, quote_mint);
+        accounts.extend(create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            2_200_000_000,
+        ));
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 13])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        let mut instances = parse_accounts(&accounts, &data).unwrap();
+        let path = run_arbitrage(
+            &mut instances,
+            &[(Some(base_mint), 1_000_000)],
+            None,
+            0,
+            0,
+            None,
+            None,
+            None,
+            false,
+            &[],
+            &Clock::default(),
+        )
+        .unwrap();
+        assert!(path.profit > 0);
+
+        let mut instances = parse_accounts(&accounts, &data).unwrap();
+        let result = run_arbitrage(
+            &mut instances,
+            &[(Some(base_mint), 1_000_000)],
+            None,
+            0,
+            0,
+            None,
+            Some(path.profit - 1),
+            None,
+            false,
+            &[],
+            &Clock::default(),
+        );
+        assert_eq!(result.unwrap_err(), error!(SolarBError::ImplausibleProfit));
+    }
+
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
+    #[test]
+    fn test_run_arbitrage_picks_the_most_profitable_start_token() {
+        let sol = Pubkey::new_unique();
+        let usdc = Pubkey::new_unique();
+        let mut accounts = create_damm_v2_span(sol, usdc);
+        accounts.extend(create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            sol,
+            usdc,
+            1_000_000_000,
+            2_200_000_000,
+        ));
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 13])
+            .build();
+        let run = |starts: &[(Option<Pubkey>, u128)], profit_mint: Option<Pubkey>| {
+            let mut instances = parse_accounts(&accounts, &data).unwrap();
+            run_arbitrage(
+                &mut instances,
+                starts,
+                profit_mint,
+                0,
+                0,
+                None,
+                None,
+                None,
+                false,
+                &[],
+                &Clock::default(),
+            )
+        };
+
+        // The same 10% gap either way round, but too little SOL to clear the minimum profit
+        let sol_start = (Some(sol), 100_000);
+        let usdc_start = (Some(usdc), 1_000_000);
+        assert_eq!(
+            run(&[sol_start], None).unwrap_err(),
+            error!(SolarBError::NoProfitFound)
+        );
+        let from_usdc = run(&[usdc_start], None).unwrap();
+        assert!(from_usdc.profit > 0);
+
+        // SOL and USDC atoms don't compare, so the profits have to be in one mint
+        for starts in [[sol_start, usdc_start], [usdc_start, sol_start]] {
+            assert_eq!(
+                run(&starts, None).unwrap_err(),
+                error!(SolarBError::ProfitMintRequired)
+            );
+            let path = run(&starts, Some(usdc)).unwrap();
+            assert_eq!(path_start_token(&path), Some(usdc));
+            assert_eq!(path.profit, from_usdc.profit);
+        }
+    }
+
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
+    #[test]
+    fn test_run_arbitrage_nets_out_token_account_rent() {
+        let sol = native_mint::id();
+        let usdc = Pubkey::new_unique();
+        let mut accounts = create_damm_v2_span(sol, usdc);
+        accounts.extend(create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            sol,
+            usdc,
+            1_000_000_000,
+            2_200_000_000,
+        ));
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 13])
+            .build();
+        let run = |uncreated_accounts: &[(Pubkey, u64)]| {
+            let mut instances = parse_accounts(&accounts, &data).unwrap();
+            run_arbitrage(
+                &mut instances,
+                &[(Some(sol), 10_000_000)],
+                None,
+                0,
+                0,
+                None,
+                None,
+                None,
+                false,
+                uncreated_accounts,
+                &Clock::default(),
+            )
+        };
+
+        let funded = run(&[]).unwrap();
+        // Neither a mint off the path nor the start token, funded already, costs rent
+        assert_eq!(
+            run(&[(Pubkey::new_unique(), 10_000), (sol, 10_000)])
+                .unwrap()
+                .profit,
+            funded.profit
+        );
+        let unfunded = run(&[(usdc, 10_000)]).unwrap();
+        assert_eq!(unfunded.profit, funded.profit - 10_000);
+        assert_eq!(unfunded.final_amount, funded.final_amount);
+        assert_eq!(
+            run(&[(usdc, funded.profit as u64 + 1)]).unwrap_err(),
+            error!(SolarBError::NoProfitFound)
+        );
+
+        // Only an empty account at the payer's associated token address gets created
+        let payer = Pubkey::new_unique();
+        let ata = get_associated_token_address_with_program_id(&payer, &usdc, &spl_token::ID);
+        let header = [
+            create_mock_account_info(payer, system_program::id(), 1, None),
+            create_mock_account_info(usdc, spl_token::ID, 0, None),
+            create_mock_account_info(spl_token::ID, Pubkey::default(), 0, None),
+            create_mock_account_info(ata, system_program::id(), 0, None),
+            create_mock_account_info(sol, spl_token::ID, 0, None),
+            create_mock_account_info(spl_token::ID, Pubkey::default(), 0, None),
+            create_mock_account_info(Pubkey::new_unique(), system_program::id(), 0, None),
+        ];
+        assert_eq!(
+            uncreated_token_accounts(&FirstAccounts::new(&header, false).unwrap()),
+            vec![usdc]
+        );
+    }
+
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
+    #[test]
+    fn test_run_arbitrage_skips_paths_whose_rent_cant_be_priced() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+        let mut accounts = create_damm_v2_span(base_mint, quote_mint);
+        accounts.extend(create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            2_200_000_000,
+        ));
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 13])
+            .build();
+        let run = |uncreated_accounts: &[(Pubkey, u64)]| {
+            let mut instances = parse_accounts(&accounts, &data).unwrap();
+            run_arbitrage(
+                &mut instances,
+                &[(Some(base_mint), 1_000_000)],
+                None,
+                0,
+                0,
+                None,
+                None,
+                None,
+                false,
+                uncreated_accounts,
+                &Clock::default(),
+            )
+        };
+
+        assert!(run(&[]).is_ok());
+        // No edge prices the base mint in SOL, so the only path is dropped rather than the
+        // run failing on the missing price
+        assert_eq!(
+            run(&[(quote_mint, 10_000)]).unwrap_err(),
+            error!(SolarBError::NoProfitFound)
+        );
+    }
+
+    #[test]
+    fn test_token_account_len_follows_token_program() {
+        use anchor_spl::token_2022::spl_token_2022::extension::{
+            transfer_hook::TransferHook, BaseStateWithExtensionsMut, StateWithExtensionsMut,
+        };
+
+        let mint = |extensions: &[ExtensionType]| {
+            let space =
+                ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions)
+                    .unwrap();
+            let mut data = vec![0u8; space];
+            let mut state =
+                StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(
+                    &mut data,
+                )
+                .unwrap();
+            if extensions.contains(&ExtensionType::TransferHook) {
+                state.init_extension::<TransferHook>(true).unwrap();
+            }
+            state.base.decimals = 6;
+            state.base.is_initialized = true;
+            state.pack_base();
+            state.init_account_type().unwrap();
+            create_mock_account_info(Pubkey::new_unique(), spl_token_2022::ID, 0, Some(data))
+        };
+
+        let spl_mint = create_mock_account_info(Pubkey::new_unique(), spl_token::ID, 0, None);
+        assert_eq!(token_account_len(&spl_mint, &spl_token::ID).unwrap(), 165);
+        // The account type byte and an empty ImmutableOwner entry
+        assert_eq!(
+            token_account_len(&mint(&[]), &spl_token_2022::ID).unwrap(),
+            170
+        );
+        // A hooked mint's accounts also carry the hook's transferring flag
+        assert_eq!(
+            token_account_len(&mint(&[ExtensionType::TransferHook]), &spl_token_2022::ID).unwrap(),
+            175
+        );
+    }
+
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
+    #[test]
+    fn test_run_arbitrage_reports_profit_in_profit_mint() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+
+        let mut accounts = create_damm_v2_span(base_mint, quote_mint);
+        accounts.extend(create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            2_200_000_000,
+        ));
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 13])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+        let run = |profit_mint: Option<Pubkey>| {
+            let mut instances = parse_accounts(&accounts, &data).unwrap();
+            run_arbitrage(
+                &mut instances,
+                &[(Some(base_mint), 1_000_000)],
+                profit_mint,
+                0,
+                0,
+                None,
+                None,
+                None,
+                false,
+                &[],
+                &Clock::default(),
+            )
+        };
+
+        // Profit in SOL-like base units, unchanged when asked for in the start token
+        let in_base = run(None).unwrap();
+        assert!(in_base.profit > 0);
+        assert_eq!(run(Some(base_mint)).unwrap().profit, in_base.profit);
+
+        // The same path reported in USDC-like quote units, at the best base -> quote price
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        let best_price = get_edges(&instances, &Clock::default(), 0)
+            .unwrap()
+            .iter()
+            .filter(|edge| {
+                edge.left.mint_account == base_mint && edge.right.mint_account == quote_mint
+            })
+            .map(|edge| edge.get_price())
+            .reduce(f64::max)
+            .unwrap();
+        let in_quote = run(Some(quote_mint)).unwrap();
+        assert_eq!(in_quote.edges, in_base.edges);
+        assert_eq!(
+            in_quote.profit,
+            (in_base.profit as f64 * best_price) as i128
+        );
+        assert!(in_quote.profit > in_base.profit);
+
+        assert_eq!(
+            run(Some(Pubkey::new_unique())).unwrap_err(),
+            error!(SolarBError::NoPriceForProfitMint)
+        );
+    }
+
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
+    #[test]
+    fn test_run_arbitrage_converts_default_max_profit_to_profit_mint() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+
+        // Quote atoms are worth 20_000 times less than base atoms
+        let mut accounts = create_span_with_reserves(
+            MeteoraDammV2::PROGRAM_ID,
+            9,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            20_000_000_000_000,
+        );
+        accounts.extend(create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            22_000_000_000_000,
+        ));
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 13])
+            .build();
+        let mut instances = parse_accounts(&accounts, &data).unwrap();
+        let path = run_arbitrage(
+            &mut instances,
+            &[(Some(base_mint), 10_000_000)],
+            Some(quote_mint),
+            0,
+            0,
+            None,
+            None,
+            None,
+            false,
+            &[],
+            &Clock::default(),
+        )
+        .unwrap();
+
+        // Past the default maximum in quote atoms, but well within it in base atoms
+        assert!(path.profit > MAX_PLAUSIBLE_PROFIT);
+    }
+
+    #[cfg(feature = "dlmm")]
+    #[test]
+    fn test_run_arbitrage_routes_between_two_pools_of_one_program() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+
+        // Two DLMM pools on the same pair, 10% apart
+        let mut accounts = create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            2_000_000_000,
+        );
+        accounts.extend(create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            2_200_000_000,
+        ));
+        let data = InstructionData::builder()
+            .accounts_length(vec![13, 13])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        let mut instances = parse_accounts(&accounts, &data).unwrap();
+        let path = run_arbitrage(
+            &mut instances,
+            &[(Some(base_mint), 1_000_000)],
+            None,
+            0,
+            0,
+            None,
+            None,
+            None,
+            false,
+            &[],
+            &Clock::default(),
+        )
+        .unwrap();
+        assert!(path.profit > 0);
+        assert_eq!(path.edges.len(), 2);
+        assert!(path
+            .edges
+            .iter()
+            .all(|edge| edge.program == MeteoraDlmm::PROGRAM_ID));
+
+        // Each edge executes on its own pool: sell base where it is dearer, buy it back
+        let first = find_edge_instance(&instances, &path.edges[0]).unwrap();
+        let second = find_edge_instance(&instances, &path.edges[1]).unwrap();
+        assert_eq!((first, second), (1, 0));
+    }
+
+    #[cfg(feature = "dlmm")]
+    #[test]
+    fn test_run_arbitrage_falls_back_past_rejected_path() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+
+        // Three DLMM pools on one pair; the dearest (index 1) makes the best path
+        let mut accounts = Vec::new();
+        for quote_amount in [2_000_000_000, 2_400_000_000, 2_200_000_000] {
+            accounts.extend(create_span_with_reserves(
+                MeteoraDlmm::PROGRAM_ID,
+                13,
+                base_mint,
+                quote_mint,
+                1_000_000_000,
+                quote_amount,
+            ));
+        }
+        // Edges identify their pool by its base vault
+        let dear_pool = *accounts[13 + 2].key;
+        let data = InstructionData::builder()
+            .accounts_length(vec![13, 13, 13])
+            .try_alternatives(true)
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+        let clock = Clock::default();
+
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        let edges = get_edges(&instances, &clock, 0).unwrap();
+        let edge_refs: Vec<&Edge> = edges.iter().collect();
+        let paths = check_arbitrage_ranked(
+            &edge_refs,
+            1_000_000,
+            Some(base_mint),
+            None,
+            MAX_ALTERNATIVE_PATHS,
+        )
+        .unwrap();
+        assert!(paths[0].edges.iter().any(|edge| edge.pool_id == dear_pool));
+
+        // Only the best path's profit is past the maximum
+        let run = |try_alternatives: bool| {
+            let mut instances = parse_accounts(&accounts, &data).unwrap();
+            run_arbitrage(
+                &mut instances,
+                &[(Some(base_mint), 1_000_000)],
+                None,
+                0,
+                0,
+                None,
+                Some(paths[0].profit - 1),
+                None,
+                try_alternatives,
+                &[],
+                &clock,
+            )
+        };
+        assert_eq!(
+            run(false).unwrap_err(),
+            error!(SolarBError::ImplausibleProfit)
+        );
+        let fallback = run(true).unwrap();
+        assert_eq!(fallback.edges, paths[1].edges);
+        assert!(fallback.profit > 0 && fallback.profit < paths[0].profit);
+
+        // A pool paused at search time yields no edges, so no path crosses it
+        let status_offset = 8 + std::mem::offset_of!(dlmm::dlmm::accounts::LbPair, status);
+        accounts[13 + 1].try_borrow_mut_data().unwrap()[status_offset] = 1;
+        let mut instances = parse_accounts(&accounts, &data).unwrap();
+        let path = run_arbitrage(
+            &mut instances,
+            &[(Some(base_mint), 1_000_000)],
+            None,
+            0,
+            0,
+            None,
+            None,
+            None,
+            false,
+            &[],
+            &clock,
+        )
+        .unwrap();
+        assert!(path.edges.iter().all(|edge| edge.pool_id != dear_pool));
+    }
+
+    #[cfg(feature = "dlmm")]
+    #[test]
+    fn test_run_arbitrage_ignores_instance_order() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+
+        // One cheap DLMM pool and two identical dear ones, so two paths tie on profit
+        let spans: Vec<Vec<AccountInfo<'static>>> = [2_000_000_000, 2_200_000_000, 2_200_000_000]
+            .into_iter()
+            .map(|quote_amount| {
+                create_span_with_reserves(
+                    MeteoraDlmm::PROGRAM_ID,
+                    13,
+                    base_mint,
+                    quote_mint,
+                    1_000_000_000,
+                    quote_amount,
+                )
+            })
+            .collect();
+        let data = InstructionData::builder()
+            .accounts_length(vec![13, 13, 13])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        let paths: Vec<ArbitragePath> = [[0, 1, 2], [2, 1, 0], [1, 2, 0], [2, 0, 1]]
+            .iter()
+            .map(|order| {
+                let accounts: Vec<AccountInfo> =
+                    order.iter().flat_map(|&i| spans[i].clone()).collect();
+                let mut instances = parse_accounts(&accounts, &data).unwrap();
+                run_arbitrage(
+                    &mut instances,
+                    &[(None, 1_000_000)],
+                    None,
+                    0,
+                    0,
+                    None,
+                    None,
+                    None,
+                    false,
+                    &[],
+                    &Clock::default(),
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let pools = |path: &ArbitragePath| -> Vec<(Pubkey, Pubkey)> {
+            path.edges
+                .iter()
+                .map(|edge| (edge.program, edge.pool_id))
+                .collect()
+        };
+        for path in &paths[1..] {
+            assert_eq!(pools(path), pools(&paths[0]));
+            assert_eq!(path.profit, paths[0].profit);
+            assert_eq!(path.final_amount, paths[0].final_amount);
+        }
+    }
+
+    #[cfg(feature = "dlmm")]
+    #[test]
+    fn test_triangular_path_resolves_user_accounts_per_mint() {
+        let mints = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let token_program = spl_token::id();
+        let owner = system_program::id();
+
+        // Payer, then a (mint, token program, user token account) triple per mint
+        let mut accounts = vec![create_mock_account_info(
+            Pubkey::new_unique(),
+            owner,
+            1,
+            None,
+        )];
+        for mint in mints {
+            accounts.push(create_mock_account_info(mint, owner, 0, None));
+            accounts.push(create_mock_account_info(token_program, owner, 0, None));
+            accounts.push(create_mock_account_info(
+                Pubkey::new_unique(),
+                owner,
+                0,
+                Some(create_token_account_data(mint, 0)),
+            ));
+        }
+        // A -> B -> C -> A, where C buys back more A than the cycle started with
+        for (base, quote, base_amount, quote_amount) in [
+            (0, 1, 1_000_000_000, 2_000_000_000),
+            (1, 2, 2_000_000_000, 2_000_000_000),
+            (2, 0, 1_000_000_000, 600_000_000),
+        ] {
+            accounts.extend(create_span_with_reserves(
+                MeteoraDlmm::PROGRAM_ID,
+                13,
+                mints[base],
+                mints[quote],
+                base_amount,
+                quote_amount,
+            ));
+        }
+        let data = InstructionData::builder()
+            .accounts_length(vec![13, 13, 13])
+            .user_mint_count(3)
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        let (first_accounts, rest) = split_user_accounts(&accounts, &data).unwrap();
+        let user_accounts = FirstAccounts::new(first_accounts, false)
+            .unwrap()
+            .user_accounts;
+        assert_eq!(user_accounts.len(), 3);
+
+        let mut instances = parse_accounts(rest, &data).unwrap();
+        let path = run_arbitrage(
+            &mut instances,
+            &[(Some(mints[0]), 1_000_000)],
+            None,
+            0,
+            0,
+            None,
+            None,
+            None,
+            false,
+            &[],
+            &Clock::default(),
+        )
+        .unwrap();
+        assert_eq!(path.edges.len(), 3);
+
+        for edge in &path.edges {
+            let (input, output) = edge_user_accounts(&user_accounts, edge).unwrap();
+            assert_eq!(*input.mint.key, edge.left.mint_account);
+            assert_eq!(*output.mint.key, edge.right.mint_account);
+            assert_eq!(
+                parse_token_account(input.token_account).unwrap().mint,
+                edge.left.mint_account
+            );
+            assert_eq!(
+                parse_token_account(output.token_account).unwrap().mint,
+                edge.right.mint_account
+            );
+        }
+
+        // Without the third mint's accounts, the edges touching it can't be executed
+        let missing: Vec<_> = path
+            .edges
+            .iter()
+            .filter_map(|edge| edge_user_accounts(&user_accounts[..2], edge).err())
+            .collect();
+        assert_eq!(missing.len(), 2);
+        assert!(missing
+            .into_iter()
+            .all(|error| error == error!(SolarBError::AccountMismatch)));
+    }
+
+    #[test]
+    fn test_split_user_accounts_checks_mint_count() {
+        let accounts: Vec<AccountInfo> = (0..10)
+            .map(|_| create_mock_account_info(Pubkey::new_unique(), system_program::id(), 0, None))
+            .collect();
+        let data = |user_mint_count| {
+            InstructionData::builder()
+                .user_mint_count(user_mint_count)
+                .start_amount(1_000_000)
+                .slippage_bps(0)
+                .build()
+        };
+
+        let (first_accounts, rest) = split_user_accounts(&accounts, &data(3)).unwrap();
+        assert_eq!((first_accounts.len(), rest.len()), (10, 0));
+        let (first_accounts, rest) = split_user_accounts(&accounts, &data(2)).unwrap();
+        assert_eq!((first_accounts.len(), rest.len()), (7, 3));
+
+        for user_mint_count in [0, 1, 4] {
+            assert_eq!(
+                split_user_accounts(&accounts, &data(user_mint_count)).unwrap_err(),
+                error!(SolarBError::InsufficientAccounts)
+            );
+        }
+    }
+
+    #[test]
+    fn test_first_accounts_maps_roles() {
+        let accounts: Vec<AccountInfo> = (0..7)
+            .map(|_| create_mock_account_info(Pubkey::new_unique(), system_program::id(), 0, None))
+            .collect();
+
+        let first_accounts = FirstAccounts::new(&accounts, false).unwrap();
+        assert_eq!(first_accounts.fee_payer.key, accounts[0].key);
+        assert_eq!(first_accounts.authority.key, accounts[0].key);
+        assert_eq!(first_accounts.user_accounts.len(), 2);
+        for (user_account, triple) in first_accounts
+            .user_accounts
+            .iter()
+            .zip(accounts[1..].chunks_exact(3))
+        {
+            assert_eq!(user_account.mint.key, triple[0].key);
+            assert_eq!(user_account.token_program.key, triple[1].key);
+            assert_eq!(user_account.token_account.key, triple[2].key);
+        }
+
+        assert_eq!(
+            FirstAccounts::new(&[], false).err().unwrap(),
+            error!(SolarBError::InsufficientAccounts)
+        );
+    }
+
+    #[test]
+    fn test_separate_authority_splits_roles() {
+        let (usdc, sol) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let fee_payer = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let ata = get_associated_token_address_with_program_id(&authority, &usdc, &spl_token::ID);
+        let mut header = vec![
+            create_mock_account_info(fee_payer, system_program::id(), 1, None),
+            create_mock_account_info(authority, system_program::id(), 0, None),
+            create_mock_account_info(usdc, spl_token::ID, 0, None),
+            create_mock_account_info(spl_token::ID, Pubkey::default(), 0, None),
+            create_mock_account_info(ata, system_program::id(), 0, None),
+            create_mock_account_info(sol, spl_token::ID, 0, None),
+            create_mock_account_info(spl_token::ID, Pubkey::default(), 0, None),
+            create_mock_account_info(Pubkey::new_unique(), spl_token::ID, 1, None),
+        ];
+        header.extend((0..9).map(|_| {
+            create_mock_account_info(Pubkey::new_unique(), system_program::id(), 0, None)
+        }));
+
+        let data = InstructionData::builder()
+            .accounts_length(vec![9])
+            .user_mint_count(2)
+            .separate_authority(true)
+            .build();
+        let (first_accounts, rest) = split_user_accounts(&header, &data).unwrap();
+        assert_eq!((first_accounts.len(), rest.len()), (8, 9));
+
+        let roles = FirstAccounts::new(first_accounts, true).unwrap();
+        assert_eq!(roles.fee_payer.key, &fee_payer);
+        assert_eq!(roles.authority.key, &authority);
+        assert_eq!(roles.user_accounts.len(), 2);
+        assert_eq!(roles.user_accounts[0].mint.key, &usdc);
+        // The account to create is the authority's, not the fee payer's
+        assert_eq!(uncreated_token_accounts(&roles), vec![usdc]);
+
+        // Both roles have to sign
+        for signer in [0, 1] {
+            header[signer].is_signer = true;
+            assert_eq!(
+                require_payer_signer(&header[..8], true).unwrap_err(),
+                error!(SolarBError::PayerNotSigner)
+            );
+            header[signer].is_signer = false;
+        }
+        header[0].is_signer = true;
+        header[1].is_signer = true;
+        require_payer_signer(&header[..8], true).unwrap();
+        assert_eq!(
+            require_payer_signer(&header[..1], true).unwrap_err(),
+            error!(SolarBError::InsufficientAccounts)
+        );
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_generate_edges_skips_damm_v2_pool_before_activation() {
+        let accounts = create_damm_v2_span(Pubkey::new_unique(), Pubkey::new_unique());
+        // Slot-activated pool that opens at slot 1_000
+        let activation_offset =
+            8 + std::mem::offset_of!(programs::meteora_damm_v2::Pool, activation_point);
+        accounts[1].try_borrow_mut_data().unwrap()[activation_offset..activation_offset + 8]
+            .copy_from_slice(&1_000u64.to_le_bytes());
+        let data = InstructionData::builder()
+            .accounts_length(vec![9])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+        let instances = parse_accounts(&accounts, &data).unwrap();
+
+        let before = Clock {
+            slot: 999,
+            ..Clock::default()
+        };
+        assert!(!instances[0].is_tradable(&before).unwrap());
+        assert!(generate_edges(instances[0].as_ref(), &before, 0)
+            .unwrap()
+            .is_empty());
+
+        let after = Clock {
+            slot: 1_000,
+            ..Clock::default()
+        };
+        assert!(instances[0].is_tradable(&after).unwrap());
+        assert_eq!(
+            generate_edges(instances[0].as_ref(), &after, 0)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_get_edges_skips_pool_with_empty_reserve() {
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut accounts = Vec::new();
+        for (base_amount, quote_amount) in [
+            (1_000_000_000, 2_000_000_000),
+            (0, 2_000_000_000),
+            (1_000_000_000, 0),
+            (3_000_000_000, 1_000_000_000),
+        ] {
+            accounts.extend(create_span_with_reserves(
+                MeteoraDammV2::PROGRAM_ID,
+                9,
+                mint_a,
+                mint_b,
+                base_amount,
+                quote_amount,
+            ));
+        }
+        let data = InstructionData::builder()
+            .accounts_length(vec![9; 4])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+        let instances = parse_accounts(&accounts, &data).unwrap();
+
+        let edges = get_edges(&instances, &Clock::default(), 0).unwrap();
+        assert_eq!(edges.len(), 4);
+        let mut pool_ids: Vec<Pubkey> = edges.iter().map(|edge| edge.pool_id).collect();
+        pool_ids.dedup();
+        assert_eq!(pool_ids.len(), 2);
+        for pool_id in [accounts[2].key, accounts[29].key] {
+            assert!(pool_ids.contains(pool_id));
+        }
+        assert!(edges
+            .iter()
+            .all(|edge| (1..u128::MAX).contains(&edge.price)));
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_get_edges_skips_duplicate_pool() {
+        let span = create_span_with_reserves(
+            MeteoraDammV2::PROGRAM_ID,
+            9,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            2_000_000_000,
+        );
+        let accounts = [span.clone(), span].concat();
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 9])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0].pool_key(), instances[1].pool_key());
+
+        let edges = get_edges(&instances, &Clock::default(), 0).unwrap();
+        assert_eq!(edges.len(), 2);
+        assert_ne!(edges[0].side, edges[1].side);
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_refresh_edge_rebuilds_only_its_pool() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+        let accounts: Vec<AccountInfo<'static>> = (0..3)
+            .flat_map(|_| create_damm_v2_span(base_mint, quote_mint))
+            .collect();
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 9, 9])
+            .build();
+        let instances = parse_accounts(&accounts, &data).unwrap();
+        let clock = Clock::default();
+        let mut edges = get_edges(&instances, &clock, 0).unwrap();
+        let before: Vec<Vec<u8>> = edges
+            .iter()
+            .map(|edge| borsh::to_vec(edge).unwrap())
+            .collect();
+
+        // The second pool's base vault doubles
+        let moved_vault = &accounts[9 + 2];
+        moved_vault.try_borrow_mut_data().unwrap()[64..72]
+            .copy_from_slice(&2_000_000_000u64.to_le_bytes());
+        refresh_edge(&mut edges, instances[1].as_ref(), &clock, 0).unwrap();
+
+        assert_eq!(edges.len(), 6);
+        for (edge, before) in edges.iter().zip(&before) {
+            let changed = borsh::to_vec(edge).unwrap() != *before;
+            assert_eq!(changed, edge.pool_id == *moved_vault.key);
+        }
+        let refreshed: Vec<&Edge> = edges
+            .iter()
+            .filter(|edge| edge.pool_id == *moved_vault.key)
+            .collect();
+        assert_eq!(refreshed.len(), 2);
+        for edge in refreshed {
+            let base = if edge.side == EdgeSide::LeftToRight {
+                &edge.left
+            } else {
+                &edge.right
+            };
+            assert_eq!(base.amount, 2_000_000_000);
+        }
+
+        // A drained pool loses its edges, and gets them back once refilled
+        moved_vault.try_borrow_mut_data().unwrap()[64..72].copy_from_slice(&0u64.to_le_bytes());
+        refresh_edge(&mut edges, instances[1].as_ref(), &clock, 0).unwrap();
+        assert_eq!(edges.len(), 4);
+        assert!(edges.iter().all(|edge| edge.pool_id != *moved_vault.key));
+        moved_vault.try_borrow_mut_data().unwrap()[64..72]
+            .copy_from_slice(&1_000_000_000u64.to_le_bytes());
+        refresh_edge(&mut edges, instances[1].as_ref(), &clock, 0).unwrap();
+        let rebuilt: Vec<Vec<u8>> = edges
+            .iter()
+            .map(|edge| borsh::to_vec(edge).unwrap())
+            .collect();
+        assert_eq!(rebuilt, before);
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_get_edges_skips_thin_pools() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+        // Thin on the base side, thin on the quote side, then deep on both
+        let reserves = [
+            (1_000, 2_000_000_000),
+            (1_000_000_000, 2_000),
+            (1_000_000_000, 2_000_000_000),
+        ];
+        let accounts: Vec<AccountInfo<'static>> = reserves
+            .iter()
+            .flat_map(|&(base_amount, quote_amount)| {
+                create_span_with_reserves(
+                    MeteoraDammV2::PROGRAM_ID,
+                    9,
+                    base_mint,
+                    quote_mint,
+                    base_amount,
+                    quote_amount,
+                )
+            })
+            .collect();
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 9, 9])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+        let instances = parse_accounts(&accounts, &data).unwrap();
+
+        // No threshold keeps every pool
+        let edges = get_edges(&instances, &Clock::default(), 0).unwrap();
+        assert_eq!(edges.len(), 6);
+
+        let edges = get_edges(&instances, &Clock::default(), 1_000_000).unwrap();
+        assert_eq!(edges.len(), 2);
+        let deep_vault = instances[2].get_vaults().0.key;
+        assert!(edges.iter().all(|edge| edge.pool_id == *deep_vault));
+    }
+
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
+    #[test]
+    fn test_run_arbitrage_rejects_path_over_compute_budget() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+
+        let mut accounts = create_damm_v2_span(base_mint, quote_mint);
+        accounts.extend(create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            2_200_000_000,
+        ));
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 13])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        // The path crosses both pools once
+        let mut instances = parse_accounts(&accounts, &data).unwrap();
+        let path_compute_units: u32 = instances
+            .iter()
+            .map(|instance| instance.estimate_compute_units())
+            .sum();
+        assert!(run_arbitrage(
+            &mut instances,
+            &[(Some(base_mint), 1_000_000)],
+            None,
+            0,
+            0,
+            None,
+            None,
+            Some(path_compute_units),
+            false,
+            &[],
+            &Clock::default(),
+        )
+        .is_ok());
+
+        let mut instances = parse_accounts(&accounts, &data).unwrap();
+        let result = run_arbitrage(
+            &mut instances,
+            &[(Some(base_mint), 1_000_000)],
+            None,
+            0,
+            0,
+            None,
+            None,
+            Some(path_compute_units - 1),
+            false,
+            &[],
+            &Clock::default(),
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            error!(SolarBError::ComputeBudgetExceeded)
+        );
+    }
+
+    #[cfg(all(feature = "damm-v2", feature = "dlmm"))]
+    #[test]
+    fn test_simulate_returns_best_path() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+
+        // Payer, mints, token programs and user token accounts, unused by simulate
+        let mut remaining_accounts = create_mock_accounts(7, system_program::id());
+        remaining_accounts.extend(create_damm_v2_span(base_mint, quote_mint));
+        remaining_accounts.extend(create_span_with_reserves(
+            MeteoraDlmm::PROGRAM_ID,
+            13,
+            base_mint,
+            quote_mint,
+            1_000_000_000,
+            2_200_000_000,
+        ));
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 13])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+
+        // The handler reads the Clock sysvar and set_return_data is a no-op off-chain, so
+        // decode the same encoding simulate writes for the clock it would pass
+        let return_data = borsh::to_vec(
+            &simulate_arbitrage(&remaining_accounts, &data, &Clock::default()).unwrap(),
+        )
+        .unwrap();
+        let simulated_path = SimulatedPath::try_from_slice(&return_data).unwrap();
+        assert!(simulated_path.profit > 0);
+        assert_eq!(simulated_path.edge_count, 2);
+        // Either mint can root the cycle, so only the set of programs is fixed
+        assert_eq!(simulated_path.programs.len(), 2);
+        assert!(simulated_path.programs.contains(&MeteoraDammV2::PROGRAM_ID));
+        assert!(simulated_path.programs.contains(&MeteoraDlmm::PROGRAM_ID));
+    }
+
+    #[cfg(feature = "damm-v2")]
+    #[test]
+    fn test_execute_rejects_start_amount_past_u64() {
+        let base_mint = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+        let mut accounts = create_damm_v2_span(base_mint, quote_mint);
+        accounts.extend(create_damm_v2_span(base_mint, quote_mint));
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 9])
+            .start_amount(1_000_000)
+            .slippage_bps(0)
+            .build();
+        let mut instances = parse_accounts(&accounts, &data).unwrap();
+        let edges = get_edges(&instances, &Clock::default(), 0).unwrap();
+        // Out through the first pool and back through the second
+        let arbitrage_path = ArbitragePath {
+            edges: vec![edges[0].clone(), edges[3].clone()],
+            amounts_out: vec![u64::MAX as u128 + 1, u64::MAX as u128 + 1],
+            profit: 0,
+            final_amount: u64::MAX as u128 + 1,
+            start_amount: u64::MAX as u128 + 1,
+        };
+
+        // Fails before any swap instead of truncating the amount to a u64
+        let user_accounts = create_mock_accounts(7, system_program::id());
+        let result = execute_arbitrage_path(
+            &arbitrage_path,
+            &mut instances,
+            &FirstAccounts::new(&user_accounts, false).unwrap(),
+            false,
+            0,
+            0,
+            &Clock::default(),
+        );
+        assert_eq!(result.unwrap_err(), error!(SolarBError::MathOverflow));
+    }
+
+    // Pool that quotes 1:1 and records how often it was quoted and the minimum outputs its
+    // swaps were invoked with
+    struct QuoteCountingProgram {
+        id: Pubkey,
+        vaults: (AccountInfo<'static>, AccountInfo<'static>),
+        quotes: Rc<Cell<u32>>,
+        min_amounts_out: Rc<RefCell<Vec<Option<u64>>>>,
+        // Invoking a swap fails, as a rejected CPI would
+        fails: bool,
+        // How much less than the quoted amount a swap pays out
+        shortfall: u64,
+        // Every clock the search and the execution hand the program
+        clocks: Rc<RefCell<Vec<Clock>>>,
+        mints: (Pubkey, Pubkey),
+        // Pool and side of every swap invoked
+        swaps: Rc<RefCell<Vec<(Pubkey, EdgeSide)>>>,
+    }
+
+    impl QuoteCountingProgram {
+        // Credit the user account on the mint not going in, as the venue's transfer would
+        fn pay_out<'a>(
+            &self,
+            input_mint: Pubkey,
+            amount_out: Option<u64>,
+            mint_1: &AccountInfo<'a>,
+            user_mint_1_token_account: &AccountInfo<'a>,
+            user_mint_2_token_account: &AccountInfo<'a>,
+        ) -> Result<()> {
+            let destination = if *mint_1.key == input_mint {
+                user_mint_2_token_account
+            } else {
+                user_mint_1_token_account
+            };
+            let mut data = destination.try_borrow_mut_data()?;
+            let balance = u64::from_le_bytes(data[64..72].try_into().unwrap());
+            let paid = amount_out.unwrap_or(0) - self.shortfall;
+            data[64..72].copy_from_slice(&(balance + paid).to_le_bytes());
+            Ok(())
+        }
+    }
+
+    impl ProgramMeta for QuoteCountingProgram {
+        fn get_id(&self) -> &Pubkey {
+            &self.id
+        }
+
+        fn program_label(&self) -> &'static str {
+            "QuoteCounting"
+        }
+
+        // The mock vaults hold no token data to derive a minimum from
+        fn min_swap_amount(&self, _input_mint: &Pubkey, _clock: &Clock) -> Result<u64> {
+            Ok(1)
+        }
+
+        fn pool_key(&self) -> &Pubkey {
+            self.vaults.0.key
+        }
+
+        fn get_vaults(&self) -> (&AccountInfo<'_>, &AccountInfo<'_>) {
+            // Same lifetime narrowing as the adapters' `get_vaults`
+            unsafe {
+                (
+                    &*std::ptr::from_ref(&self.vaults.0).cast(),
+                    &*std::ptr::from_ref(&self.vaults.1).cast(),
+                )
+            }
+        }
+
+        fn get_mints(&self) -> (&Pubkey, &Pubkey) {
+            (&self.mints.0, &self.mints.1)
+        }
+
+        fn compute_price_swap_base_in(&self, base: u128, quote: u128) -> Result<f64> {
+            Ok(quote as f64 / base as f64)
+        }
+
+        fn compute_price_swap_base_out(&self, base: u128, quote: u128) -> Result<f64> {
+            Ok(base as f64 / quote as f64)
+        }
+
+        fn is_tradable(&self, clock: &Clock) -> Result<bool> {
+            self.clocks.borrow_mut().push(clock.clone());
+            Ok(true)
+        }
+
+        fn swap_base_in(&self, _input_mint: Pubkey, amount_in: u64, clock: Clock) -> Result<u64> {
+            self.quotes.set(self.quotes.get() + 1);
+            self.clocks.borrow_mut().push(clock);
+            Ok(amount_in)
+        }
+
+        fn swap_base_out(&self, _input_mint: Pubkey, amount_out: u64, clock: Clock) -> Result<u64> {
+            self.quotes.set(self.quotes.get() + 1);
+            self.clocks.borrow_mut().push(clock);
+            Ok(amount_out)
+        }
+
+        fn invoke_swap_base_in<'a>(
+            &self,
+            input_mint: Pubkey,
+            _max_amount_in: u64,
+            amount_out: Option<u64>,
+            _authority: AccountInfo<'a>,
+            user_mint_1_token_account: AccountInfo<'a>,
+            user_mint_2_token_account: AccountInfo<'a>,
+            mint_1_account: AccountInfo<'a>,
+            _mint_2_account: AccountInfo<'a>,
+            _mint_1_token_program: AccountInfo<'a>,
+            _mint_2_token_program: AccountInfo<'a>,
+        ) -> Result<()> {
+            require!(!self.fails, SolarBError::InsufficientFunds);
+            self.min_amounts_out.borrow_mut().push(amount_out);
+            self.swaps
+                .borrow_mut()
+                .push((*self.vaults.0.key, EdgeSide::RightToLeft));
+            self.pay_out(
+                input_mint,
+                amount_out,
+                &mint_1_account,
+                &user_mint_1_token_account,
+                &user_mint_2_token_account,
+            )
+        }
+
+        fn invoke_swap_base_out<'a>(
+            &self,
+            input_mint: Pubkey,
+            _amount_in: u64,
+            min_amount_out: Option<u64>,
+            _authority: AccountInfo<'a>,
+            user_mint_1_token_account: AccountInfo<'a>,
+            user_mint_2_token_account: AccountInfo<'a>,
+            mint_1_account: AccountInfo<'a>,
+            _mint_2_account: AccountInfo<'a>,
+            _mint_1_token_program: AccountInfo<'a>,
+            _mint_2_token_program: AccountInfo<'a>,
+        ) -> Result<()> {
+            require!(!self.fails, SolarBError::InsufficientFunds);
+            self.min_amounts_out.borrow_mut().push(min_amount_out);
+            self.swaps
+                .borrow_mut()
+                .push((*self.vaults.0.key, EdgeSide::LeftToRight));
+            self.pay_out(
+                input_mint,
+                min_amount_out,
+                &mint_1_account,
+                &user_mint_1_token_account,
+                &user_mint_2_token_account,
+            )
+        }
+
+        fn build_swap_ix(
+            &self,
+            _direction: EdgeSide,
+            _amount_in: u64,
+            _min_out: u64,
+            _user_accounts: &SwapUserAccounts,
+        ) -> Result<Instruction> {
+            Ok(Instruction::new_with_bytes(self.id, &[], Vec::new()))
+        }
+
+        fn estimate_compute_units(&self) -> u32 {
+            0
+        }
+
+        fn log_accounts(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    // Real adapter whose swap invokes are stubbed: instead of the CPI, they move the user's
+    // token balances by the amounts the venue would have
+    #[cfg(feature = "pump-amm")]
+    struct SettlingProgram<'info> {
+        inner: Box<dyn ProgramMeta + 'info>,
+        // User token accounts each swap paid from and to
+        transfers: Rc<RefCell<Vec<(Pubkey, Pubkey)>>>,
+        // Smallest input the pool takes in place of the adapter's own
+        min_amount_in: Option<u64>,
+    }
+
+    #[cfg(feature = "pump-amm")]
+    impl SettlingProgram<'_> {
+        fn settle<'a>(
+            &self,
+            from: &AccountInfo<'a>,
+            to: &AccountInfo<'a>,
+            amount_in: u64,
+            amount_out: u64,
+        ) {
+            for (account, debit, credit) in [(from, amount_in, 0), (to, 0, amount_out)] {
+                let mut data = account.try_borrow_mut_data().unwrap();
+                let balance = u64::from_le_bytes(data[64..72].try_into().unwrap());
+                data[64..72].copy_from_slice(&(balance - debit + credit).to_le_bytes());
+            }
+            self.transfers.borrow_mut().push((*from.key, *to.key));
+        }
+    }
+
+    #[cfg(feature = "pump-amm")]
+    impl ProgramMeta for SettlingProgram<'_> {
+        fn get_id(&self) -> &Pubkey {
+            self.inner.get_id()
+        }
+
+        fn program_label(&self) -> &'static str {
+            self.inner.program_label()
+        }
+
+        fn pool_key(&self) -> &Pubkey {
+            self.inner.pool_key()
+        }
+
+        fn get_vaults(&self) -> (&AccountInfo<'_>, &AccountInfo<'_>) {
+            self.inner.get_vaults()
+        }
+
+        fn get_mints(&self) -> (&Pubkey, &Pubkey) {
+            self.inner.get_mints()
+        }
+
+        fn mint_decimals(&self) -> (u8, u8) {
+            self.inner.mint_decimals()
+        }
+
+        fn compute_price_swap_base_in(&self, base: u128, quote: u128) -> Result<f64> {
+            self.inner.compute_price_swap_base_in(base, quote)
+        }
+
+        fn compute_price_swap_base_out(&self, base: u128, quote: u128) -> Result<f64> {
+            self.inner.compute_price_swap_base_out(base, quote)
+        }
+
+        fn is_tradable(&self, clock: &Clock) -> Result<bool> {
+            self.inner.is_tradable(clock)
+        }
+
+        fn validate_accounts(&self) -> Result<()> {
+            self.inner.validate_accounts()
+        }
+
+        fn swap_base_in(&self, input_mint: Pubkey, amount_in: u64, clock: Clock) -> Result<u64> {
+            self.inner.swap_base_in(input_mint, amount_in, clock)
+        }
+
+        fn swap_base_out(&self, input_mint: Pubkey, amount_out: u64, clock: Clock) -> Result<u64> {
+            self.inner.swap_base_out(input_mint, amount_out, clock)
+        }
+
+        fn invoke_swap_base_in<'a>(
+            &self,
+            _input_mint: Pubkey,
+            max_amount_in: u64,
+            amount_out: Option<u64>,
+            _authority: AccountInfo<'a>,
+            user_mint_1_token_account: AccountInfo<'a>,
+            user_mint_2_token_account: AccountInfo<'a>,
+            _mint_1_account: AccountInfo<'a>,
+            _mint_2_account: AccountInfo<'a>,
+            _mint_1_token_program: AccountInfo<'a>,
+            _mint_2_token_program: AccountInfo<'a>,
+        ) -> Result<()> {
+            self.settle(
+                &user_mint_1_token_account,
+                &user_mint_2_token_account,
+                max_amount_in,
+                amount_out.unwrap_or(0),
+            );
+            Ok(())
+        }
+
+        fn invoke_swap_base_out<'a>(
+            &self,
+            _input_mint: Pubkey,
+            amount_in: u64,
+            min_amount_out: Option<u64>,
+            _authority: AccountInfo<'a>,
+            user_mint_1_token_account: AccountInfo<'a>,
+            user_mint_2_token_account: AccountInfo<'a>,
+            _mint_1_account: AccountInfo<'a>,
+            _mint_2_account: AccountInfo<'a>,
+            _mint_1_token_program: AccountInfo<'a>,
+            _mint_2_token_program: AccountInfo<'a>,
+        ) -> Result<()> {
+            self.settle(
+                &user_mint_1_token_account,
+                &user_mint_2_token_account,
+                amount_in,
+                min_amount_out.unwrap_or(0),
+            );
+            Ok(())
+        }
+
+        fn build_swap_ix(
+            &self,
+            direction: EdgeSide,
+            amount_in: u64,
+            min_out: u64,
+            user_accounts: &SwapUserAccounts,
+        ) -> Result<Instruction> {
+            self.inner
+                .build_swap_ix(direction, amount_in, min_out, user_accounts)
+        }
+
+        fn estimate_compute_units(&self) -> u32 {
+            self.inner.estimate_compute_units()
+        }
+
+        fn min_swap_amount(&self, input_mint: &Pubkey, clock: &Clock) -> Result<u64> {
+            self.min_amount_in
+                .map_or_else(|| self.inner.min_swap_amount(input_mint, clock), Ok)
+        }
+
+        fn log_accounts(&self) -> Result<()> {
+            self.inner.log_accounts()
+        }
+    }
+
+    // Payer, then the (mint, token program, empty user token account) of each mint
+    fn create_execution_accounts(mints: &[Pubkey]) -> Vec<AccountInfo<'static>> {
+        let owner = system_program::id();
+        let mut accounts = vec![create_mock_account_info(
+            Pubkey::new_unique(),
+            owner,
+            1,
+            None,
+        )];
+        for mint in mints {
+            accounts.push(create_mock_account_info(*mint, owner, 0, None));
+            accounts.push(create_mock_account_info(spl_token::id(), owner, 0, None));
+            accounts.push(create_mock_account_info(
+                Pubkey::new_unique(),
+                spl_token::id(),
+                0,
+                Some(create_token_account_data(*mint, 0)),
+            ));
+        }
+        accounts
+    }
+
+    #[test]
+    fn test_execute_reuses_search_amounts_unless_recomputing() {
+        let owner = system_program::id();
+        let base = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let quote = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let mints = (base.mint_account, quote.mint_account);
+        let accounts = create_execution_accounts(&[base.mint_account, quote.mint_account]);
+        let first_accounts = FirstAccounts::new(&accounts, false).unwrap();
+
+        let program_id = Pubkey::new_unique();
+        let pools = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let arbitrage_path = ArbitragePath {
+            edges: vec![
+                Edge::new(
+                    program_id,
+                    pools[0],
+                    EdgeSide::LeftToRight,
+                    1.0,
+                    base.clone(),
+                    quote.clone(),
+                ),
+                Edge::new(
+                    program_id,
+                    pools[1],
+                    EdgeSide::RightToLeft,
+                    1.0,
+                    quote.clone(),
+                    base.clone(),
+                ),
+            ],
+            amounts_out: vec![1_050_000, 1_100_000],
+            profit: 100_000,
+            final_amount: 1_100_000,
+            start_amount: 1_000_000,
+        };
+
+        // Quotes made and minimum outputs swapped with, executing in the given mode
+        let execute = |recompute: bool| {
+            let quotes = Rc::new(Cell::new(0));
+            let min_amounts_out = Rc::new(RefCell::new(Vec::new()));
+            let mut instances: Vec<Box<dyn ProgramMeta>> = pools
+                .iter()
+                .map(|pool| {
+                    Box::new(QuoteCountingProgram {
+                        id: program_id,
+                        vaults: (
+                            create_mock_account_info(*pool, owner, 0, None),
+                            create_mock_account_info(Pubkey::new_unique(), owner, 0, None),
+                        ),
+                        quotes: quotes.clone(),
+                        min_amounts_out: min_amounts_out.clone(),
+                        fails: false,
+                        shortfall: 0,
+                        clocks: Default::default(),
+                        mints,
+                        swaps: Default::default(),
+                    }) as Box<dyn ProgramMeta>
+                })
+                .collect();
+            execute_arbitrage_path(
+                &arbitrage_path,
+                &mut instances,
+                &first_accounts,
+                recompute,
+                0,
+                0,
+                &Clock::default(),
+            )
+            .unwrap();
+            let min_amounts_out = min_amounts_out.borrow().clone();
+            (quotes.get(), min_amounts_out)
+        };
+
+        // Reusing the search's amounts quotes nothing and guards each swap with them
+        assert_eq!(execute(false), (0, vec![Some(1_050_000), Some(1_100_000)]));
+        // Fresh quotes cost one per edge; the 1:1 pools quote the start amount throughout
+        assert_eq!(execute(true), (2, vec![Some(1_000_000), Some(1_000_000)]));
+
+        // Without recorded amounts only the recomputing mode can execute
+        let arbitrage_path = ArbitragePath {
+            amounts_out: Vec::new(),
+            ..arbitrage_path
+        };
+        let mut instances = Vec::new();
+        let result = execute_arbitrage_path(
+            &arbitrage_path,
+            &mut instances,
+            &first_accounts,
+            false,
+            0,
+            0,
+            &Clock::default(),
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            error!(SolarBError::MissingExpectedAmount)
+        );
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_quote_path_reuses_cached_quotes() {
+        use crate::client::{quote_path, QuoteCache};
+
+        let owner = system_program::id();
+        let base = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let quote = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let program_id = Pubkey::new_unique();
+        let pools = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let edges = vec![
+            Edge::new(
+                program_id,
+                pools[0],
+                EdgeSide::LeftToRight,
+                1.0,
+                base.clone(),
+                quote.clone(),
+            ),
+            Edge::new(
+                program_id,
+                pools[1],
+                EdgeSide::RightToLeft,
+                1.0,
+                quote,
+                base,
+            ),
+        ];
+        let quotes = Rc::new(Cell::new(0));
+        let instances: Vec<Box<dyn ProgramMeta>> = pools
+            .iter()
+            .map(|pool| {
+                Box::new(QuoteCountingProgram {
+                    id: program_id,
+                    vaults: (
+                        create_mock_account_info(*pool, owner, 0, None),
+                        create_mock_account_info(Pubkey::new_unique(), owner, 0, None),
+                    ),
+                    quotes: quotes.clone(),
+                    min_amounts_out: Rc::new(RefCell::new(Vec::new())),
+                    fails: false,
+                    shortfall: 0,
+                    clocks: Default::default(),
+                    mints: (Pubkey::default(), Pubkey::default()),
+                    swaps: Default::default(),
+                }) as Box<dyn ProgramMeta>
+            })
+            .collect();
+        let clock = Clock::default();
+
+        // Quoting hop by hop without a cache
+        let mut uncached = 1_000_000;
+        for (edge, instance) in edges.iter().zip(&instances) {
+            uncached = instance
+                .swap_base_in(edge.left.mint_account, uncached, clock.clone())
+                .unwrap();
+        }
+        quotes.set(0);
+
+        let mut cache = QuoteCache::new();
+        let first = quote_path(&edges, &instances, 1_000_000, &clock, &mut cache).unwrap();
+        let second = quote_path(&edges, &instances, 1_000_000, &clock, &mut cache).unwrap();
+        assert_eq!(first.to_le_bytes(), uncached.to_le_bytes());
+        assert_eq!(second.to_le_bytes(), uncached.to_le_bytes());
+        // The repeat is served entirely from the cache
+        assert_eq!(quotes.get(), 2);
+        assert_eq!((cache.hits(), cache.misses()), (2, 2));
+
+        quote_path(&edges, &instances, 2_000_000, &clock, &mut cache).unwrap();
+        assert_eq!(quotes.get(), 4);
+    }
+
+    #[test]
+    fn test_execute_reports_failing_edge() {
+        let base = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let quote = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let mints = (base.mint_account, quote.mint_account);
+        let accounts = create_execution_accounts(&[base.mint_account, quote.mint_account]);
+        let first_accounts = FirstAccounts::new(&accounts, false).unwrap();
+
+        let program_id = Pubkey::new_unique();
+        let pools = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let arbitrage_path = ArbitragePath {
+            edges: vec![
+                Edge::new(
+                    program_id,
+                    pools[0],
+                    EdgeSide::LeftToRight,
+                    1.0,
+                    base.clone(),
+                    quote.clone(),
+                ),
+                Edge::new(
+                    program_id,
+                    pools[1],
+                    EdgeSide::RightToLeft,
+                    1.0,
+                    quote,
+                    base,
+                ),
+            ],
+            amounts_out: vec![1_050_000, 1_100_000],
+            profit: 100_000,
+            final_amount: 1_100_000,
+            start_amount: 1_000_000,
+        };
+
+        let min_amounts_out = Rc::new(RefCell::new(Vec::new()));
+        // Only the pool of the second edge rejects its swap
+        let mut instances: Vec<Box<dyn ProgramMeta>> = pools
+            .iter()
+            .enumerate()
+            .map(|(i, pool)| {
+                Box::new(QuoteCountingProgram {
+                    id: program_id,
+                    vaults: (
+                        create_mock_account_info(*pool, system_program::id(), 0, None),
+                        create_mock_account_info(
+                            Pubkey::new_unique(),
+                            system_program::id(),
+                            0,
+                            None,
+                        ),
+                    ),
+                    quotes: Rc::new(Cell::new(0)),
+                    min_amounts_out: min_amounts_out.clone(),
+                    fails: i == 1,
+                    shortfall: 0,
+                    clocks: Default::default(),
+                    mints,
+                    swaps: Default::default(),
+                }) as Box<dyn ProgramMeta>
+            })
+            .collect();
+        let err = execute_arbitrage_path(
+            &arbitrage_path,
+            &mut instances,
+            &first_accounts,
+            false,
+            0,
+            0,
+            &Clock::default(),
+        )
+        .unwrap_err();
+
+        assert_eq!(err, error!(SolarBError::EdgeExecutionFailed));
+        let Error::AnchorError(err) = err else {
+            panic!("expected an anchor error");
+        };
+        let Some(anchor_lang::error::ComparedValues::Values((index, program))) =
+            err.compared_values
+        else {
+            panic!("expected the failing edge in the compared values");
+        };
+        assert_eq!((index, program), ("1".to_string(), program_id.to_string()));
+        // The first edge went through before the second failed
+        assert_eq!(*min_amounts_out.borrow(), vec![Some(1_050_000)]);
+    }
+
+    #[test]
+    fn test_execute_swaps_received_amount_after_short_fill() {
+        let base = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let quote = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let mints = (base.mint_account, quote.mint_account);
+        let accounts = create_execution_accounts(&[base.mint_account, quote.mint_account]);
+        let first_accounts = FirstAccounts::new(&accounts, false).unwrap();
+
+        let program_id = Pubkey::new_unique();
+        let pools = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let arbitrage_path = ArbitragePath {
+            edges: vec![
+                Edge::new(
+                    program_id,
+                    pools[0],
+                    EdgeSide::LeftToRight,
+                    1.0,
+                    base.clone(),
+                    quote.clone(),
+                ),
+                Edge::new(
+                    program_id,
+                    pools[1],
+                    EdgeSide::RightToLeft,
+                    1.0,
+                    quote,
+                    base,
+                ),
+            ],
+            amounts_out: vec![1_000_000, 1_000_000],
+            profit: 0,
+            final_amount: 1_000_000,
+            start_amount: 1_000_000,
+        };
+
+        let min_amounts_out = Rc::new(RefCell::new(Vec::new()));
+        // The first pool pays out 1_000 less than it quotes
+        let mut instances: Vec<Box<dyn ProgramMeta>> = pools
+            .iter()
+            .enumerate()
+            .map(|(i, pool)| {
+                Box::new(QuoteCountingProgram {
+                    id: program_id,
+                    vaults: (
+                        create_mock_account_info(*pool, system_program::id(), 0, None),
+                        create_mock_account_info(
+                            Pubkey::new_unique(),
+                            system_program::id(),
+                            0,
+                            None,
+                        ),
+                    ),
+                    quotes: Rc::new(Cell::new(0)),
+                    min_amounts_out: min_amounts_out.clone(),
+                    fails: false,
+                    shortfall: if i == 0 { 1_000 } else { 0 },
+                    clocks: Default::default(),
+                    mints,
+                    swaps: Default::default(),
+                }) as Box<dyn ProgramMeta>
+            })
+            .collect();
+        execute_arbitrage_path(
+            &arbitrage_path,
+            &mut instances,
+            &first_accounts,
+            true,
+            0,
+            0,
+            &Clock::default(),
+        )
+        .unwrap();
+
+        // The second edge quotes, and so swaps, the 999_000 that arrived rather than the
+        // 1_000_000 the first one quoted
+        assert_eq!(
+            *min_amounts_out.borrow(),
+            vec![Some(1_000_000), Some(999_000)]
+        );
+    }
+
+    #[test]
+    fn test_execute_rejects_quote_far_from_marginal_price() {
+        // One base trades for two quote at the edge prices, though the reserves are even,
+        // as on a curve not centred on the reserve ratio
+        let base = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let quote = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let mints = (base.mint_account, quote.mint_account);
+        let accounts = create_execution_accounts(&[base.mint_account, quote.mint_account]);
+        let first_accounts = FirstAccounts::new(&accounts, false).unwrap();
+
+        let program_id = Pubkey::new_unique();
+        let pools = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let execute = |amounts_out: Vec<u128>| {
+            let arbitrage_path = ArbitragePath {
+                edges: vec![
+                    Edge::new(
+                        program_id,
+                        pools[0],
+                        EdgeSide::LeftToRight,
+                        2.0,
+                        base.clone(),
+                        quote.clone(),
+                    ),
+                    Edge::new(
+                        program_id,
+                        pools[1],
+                        EdgeSide::RightToLeft,
+                        0.5,
+                        quote.clone(),
+                        base.clone(),
+                    ),
+                ],
+                final_amount: amounts_out[1],
+                amounts_out,
+                profit: 0,
+                start_amount: 1_000_000,
+            };
+            let min_amounts_out = Rc::new(RefCell::new(Vec::new()));
+            let mut instances: Vec<Box<dyn ProgramMeta>> = pools
+                .iter()
+                .map(|pool| {
+                    Box::new(QuoteCountingProgram {
+                        id: program_id,
+                        vaults: (
+                            create_mock_account_info(*pool, system_program::id(), 0, None),
+                            create_mock_account_info(
+                                Pubkey::new_unique(),
+                                system_program::id(),
+                                0,
+                                None,
+                            ),
+                        ),
+                        quotes: Rc::new(Cell::new(0)),
+                        min_amounts_out: min_amounts_out.clone(),
+                        fails: false,
+                        shortfall: 0,
+                        clocks: Default::default(),
+                        mints,
+                        swaps: Default::default(),
+                    }) as Box<dyn ProgramMeta>
+                })
+                .collect();
+            let result = execute_arbitrage_path(
+                &arbitrage_path,
+                &mut instances,
+                &first_accounts,
+                false,
+                0,
+                100,
+                &Clock::default(),
+            );
+            (result, min_amounts_out.take().len())
+        };
+
+        // Within 1% of the price, both swaps go through
+        let (result, swaps) = execute(vec![1_995_000, 997_000]);
+        result.unwrap();
+        assert_eq!(swaps, 2);
+
+        // 100 times the price aborts before the first swap
+        let (result, swaps) = execute(vec![200_000_000, 100_000_000]);
+        assert_eq!(result.unwrap_err(), error!(SolarBError::ImplausibleQuote));
+        assert_eq!(swaps, 0);
+    }
+
+    #[test]
+    fn test_execute_arbitrage_plans_and_executes_on_one_clock() {
+        let base = Pubkey::new_unique();
+        let quote = Pubkey::new_unique();
+        let accounts = create_execution_accounts(&[base, quote]);
+        let clocks = Rc::new(RefCell::new(Vec::new()));
+        let program_id = Pubkey::new_unique();
+        // 10% apart, so buying base in the first pool and selling it in the second pays
+        let mut instances: Vec<Box<dyn ProgramMeta>> = [2_000_000_000, 2_200_000_000]
+            .into_iter()
+            .map(|quote_reserve| {
+                Box::new(QuoteCountingProgram {
+                    id: program_id,
+                    vaults: (
+                        create_mock_account_info(
+                            Pubkey::new_unique(),
+                            spl_token::id(),
+                            0,
+                            Some(create_token_account_data(base, 1_000_000_000)),
+                        ),
+                        create_mock_account_info(
+                            Pubkey::new_unique(),
+                            spl_token::id(),
+                            0,
+                            Some(create_token_account_data(quote, quote_reserve)),
+                        ),
+                    ),
+                    quotes: Rc::new(Cell::new(0)),
+                    min_amounts_out: Rc::new(RefCell::new(Vec::new())),
+                    fails: false,
+                    shortfall: 0,
+                    clocks: clocks.clone(),
+                    mints: (base, quote),
+                    swaps: Default::default(),
+                }) as Box<dyn ProgramMeta>
+            })
+            .collect();
+        let data = InstructionData::builder()
+            .user_mint_count(2)
+            .recompute_quotes(true)
+            .start_tokens(vec![(quote, 1_000_000)])
+            .build();
+        let clock = Clock {
+            slot: 300_000_000,
+            epoch: 700,
+            unix_timestamp: 1_700_000_000,
+            ..Clock::default()
+        };
+
+        execute_arbitrage(&mut instances, &accounts, &data, &clock).unwrap();
+        // Both pools checked while building edges, then both quoted again on execution
+        let clocks = clocks.borrow();
+        assert_eq!(clocks.len(), 4);
+        assert!(clocks.iter().all(|seen| *seen == clock));
+    }
+
+    #[test]
+    fn test_execute_arbitrage_routes_pool_with_sol_as_quote() {
+        let sol = native_mint::id();
+        let usdc = Pubkey::new_unique();
+        let accounts = create_execution_accounts(&[sol, usdc]);
+        let swaps = Rc::new(RefCell::new(Vec::new()));
+        let pools = [Pubkey::new_unique(), Pubkey::new_unique()];
+        // SOL is the first pool's base but the second's quote, the order its vaults come in.
+        // SOL sells for 2.2 USDC in the first and 2 USDC buy one back in the second.
+        let pool = |pool_id: Pubkey, base: (Pubkey, u64), quote: (Pubkey, u64)| {
+            let vault = |key: Pubkey, (mint, amount): (Pubkey, u64)| {
+                create_mock_account_info(
+                    key,
+                    spl_token::id(),
+                    0,
+                    Some(create_token_account_data(mint, amount)),
+                )
+            };
+            Box::new(QuoteCountingProgram {
+                id: Pubkey::new_unique(),
+                vaults: (vault(pool_id, base), vault(Pubkey::new_unique(), quote)),
+                quotes: Rc::new(Cell::new(0)),
+                min_amounts_out: Rc::new(RefCell::new(Vec::new())),
+                fails: false,
+                shortfall: 0,
+                clocks: Default::default(),
+                mints: (base.0, quote.0),
+                swaps: swaps.clone(),
+            }) as Box<dyn ProgramMeta>
+        };
+        let mut instances = vec![
+            pool(pools[0], (sol, 1_000_000_000), (usdc, 2_200_000_000)),
+            pool(pools[1], (usdc, 2_000_000_000), (sol, 1_000_000_000)),
+        ];
+        let data = InstructionData::builder()
+            .user_mint_count(2)
+            .recompute_quotes(true)
+            .start_tokens(vec![(sol, 1_000_000)])
+            .build();
+
+        execute_arbitrage(&mut instances, &accounts, &data, &Clock::default()).unwrap();
+        // Both legs sell their pool's base mint: SOL into the first, USDC into the second
+        assert_eq!(
+            *swaps.borrow(),
+            vec![
+                (pools[0], EdgeSide::LeftToRight),
+                (pools[1], EdgeSide::LeftToRight)
+            ]
+        );
+    }
+
+    #[cfg(feature = "pump-amm")]
+    #[test]
+    fn test_initialize_flow_trades_divergent_pump_pools() {
+        let base = Pubkey::new_unique();
+        let quote = Pubkey::new_unique();
+        let start_amount: u64 = 1_000_000_000;
+        let mut remaining = create_execution_accounts(&[base, quote]);
+        remaining[6].try_borrow_mut_data().unwrap()[64..72]
+            .copy_from_slice(&start_amount.to_le_bytes());
+        // Base costs 1 quote in the first pool and sells for 1.1 in the second
+        for quote_reserve in [1_000_000_000_000, 1_100_000_000_000] {
+            remaining.extend(programs::pump_amm::tests::create_pool_accounts_for_mints(
+                base,
+                quote,
+                1_000_000_000_000,
+                quote_reserve,
+            ));
+        }
+        let data = InstructionData::builder()
+            .accounts_length(vec![18, 18])
+            .start_tokens(vec![(quote, u128::from(start_amount))])
+            .slippage_bps(0)
+            .build();
+        let clock = Clock::default();
+
+        let simulated = simulate_arbitrage(&remaining, &data, &clock).unwrap();
+        // Buying base at 1 / (1 + fee) and selling it at 1.1 * (1 - fee), fee being the
+        // 25 bps lp and protocol fees of a pool without a coin creator
+        let fee = 0.0025;
+        let expected = start_amount as f64 * 1.1 * (1.0 - fee) / (1.0 + fee);
+        assert!((simulated.profit as f64 - (expected - start_amount as f64)).abs() <= 2.0);
+        assert_eq!(simulated.programs, vec![PumpAmm::PROGRAM_ID; 2]);
+
+        // What `initialize` runs once it has the clock, the adapters wrapped so their
+        // swaps settle
+        remaining[0].is_signer = true;
+        let (first_accounts, rest) = split_user_accounts(&remaining, &data).unwrap();
+        assert_eq!(first_accounts.len(), 7);
+        require_payer_signer(first_accounts, false).unwrap();
+        let transfers = Rc::new(RefCell::new(Vec::new()));
+        let instances = parse_accounts(rest, &data).unwrap();
+        // What the two swaps really pay, price impact included, which the search's marginal
+        // prices overstate
+        let bought = instances[0]
+            .swap_base_in(quote, start_amount, clock.clone())
+            .unwrap();
+        let sold = instances[1]
+            .swap_base_in(base, bought, clock.clone())
+            .unwrap();
+        let quoted_profit = i128::from(sold) - i128::from(start_amount);
+        assert!(quoted_profit > 0 && quoted_profit < simulated.profit);
+        let mut instances: Vec<Box<dyn ProgramMeta>> = select_instances(instances, None)
+            .unwrap()
+            .into_iter()
+            .map(|inner| {
+                Box::new(SettlingProgram {
+                    inner,
+                    transfers: transfers.clone(),
+                    min_amount_in: None,
+                }) as Box<dyn ProgramMeta>
+            })
+            .collect();
+        execute_arbitrage(&mut instances, first_accounts, &data, &clock).unwrap();
+
+        // Each swap went out with its real quote as the minimum output, and the settling
+        // mock pays out exactly that
+        let balance = |account: &AccountInfo| parse_token_account(account).unwrap().amount;
+        assert_eq!(balance(&remaining[3]), 0);
+        assert_eq!(
+            i128::from(balance(&remaining[6])) - i128::from(start_amount),
+            quoted_profit
+        );
+        // Quote paid for base in the first pool, then base for quote in the second
+        let (base_account, quote_account) = (*remaining[3].key, *remaining[6].key);
+        assert_eq!(
+            *transfers.borrow(),
+            vec![(quote_account, base_account), (base_account, quote_account)]
+        );
+    }
+
+    #[cfg(feature = "pump-amm")]
+    #[test]
+    fn test_execute_recomputes_every_edge_exact_in() {
+        let base = Pubkey::new_unique();
+        let quote = Pubkey::new_unique();
+        let start_amount: u64 = 1_000_000_000;
+        let mut remaining = create_execution_accounts(&[base, quote]);
+        remaining[0].is_signer = true;
+        remaining[3].try_borrow_mut_data().unwrap()[64..72]
+            .copy_from_slice(&start_amount.to_le_bytes());
+        // Base sells for 1.1 quote in the second pool and buys back at 1 in the first
+        for quote_reserve in [1_000_000_000_000, 1_100_000_000_000] {
+            remaining.extend(programs::pump_amm::tests::create_pool_accounts_for_mints(
+                base,
+                quote,
+                1_000_000_000_000,
+                quote_reserve,
+            ));
+        }
+        let data = InstructionData::builder()
+            .accounts_length(vec![18, 18])
+            .build();
+        let clock = Clock::default();
+        let (first_accounts, rest) = split_user_accounts(&remaining, &data).unwrap();
+        let first_accounts = FirstAccounts::new(first_accounts, false).unwrap();
+        let instances = parse_accounts(rest, &data).unwrap();
+        let edges = get_edges(&instances, &clock, 0).unwrap();
+        let edge_refs: Vec<&Edge> = edges.iter().collect();
+        let path = check_arbitrage(&edge_refs, u128::from(start_amount), Some(base), None).unwrap();
+        // Base in first, so through the swap base out side, then quote back in
+        assert_eq!(path.edges[0].side, EdgeSide::LeftToRight);
+        let expected = path.edges.iter().fold(start_amount, |amount, edge| {
+            let instance = &instances[find_edge_instance(&instances, edge).unwrap()];
+            instance
+                .swap_base_in(*edge.input_mint(), amount, clock.clone())
+                .unwrap()
+        });
+
+        let transfers = Rc::new(RefCell::new(Vec::new()));
+        let mut instances: Vec<Box<dyn ProgramMeta>> = instances
+            .into_iter()
+            .map(|inner| {
+                Box::new(SettlingProgram {
+                    inner,
+                    transfers: transfers.clone(),
+                    min_amount_in: None,
+                }) as Box<dyn ProgramMeta>
+            })
+            .collect();
+        execute_arbitrage_path(&path, &mut instances, &first_accounts, true, 0, 0, &clock).unwrap();
+
+        // Each swap settled exactly its exact-in quote
+        assert_eq!(parse_token_account(&remaining[3]).unwrap().amount, expected);
+    }
+
+    #[cfg(feature = "pump-amm")]
+    #[test]
+    fn test_run_arbitrage_reports_swap_too_small_only_when_every_start_is() {
+        let base = Pubkey::new_unique();
+        let quote = Pubkey::new_unique();
+        // Base sells for 1.1 quote in the second pool and buys back at 1 in the first
+        let mut accounts = Vec::new();
+        for quote_reserve in [1_000_000_000_000, 1_100_000_000_000] {
+            accounts.extend(programs::pump_amm::tests::create_pool_accounts_for_mints(
+                base,
+                quote,
+                1_000_000_000_000,
+                quote_reserve,
+            ));
+        }
+        let data = InstructionData::builder()
+            .accounts_length(vec![18, 18])
+            .build();
+        let run = |starts: &[(Option<Pubkey>, u128)]| {
+            // Neither pool takes less than 2_000_000_000 atoms of either token
+            let mut instances: Vec<Box<dyn ProgramMeta>> = parse_accounts(&accounts, &data)
+                .unwrap()
+                .into_iter()
+                .map(|inner| {
+                    Box::new(SettlingProgram {
+                        inner,
+                        transfers: Default::default(),
+                        min_amount_in: Some(2_000_000_000),
+                    }) as Box<dyn ProgramMeta>
+                })
+                .collect();
+            run_arbitrage(
+                &mut instances,
+                starts,
+                Some(base),
+                0,
+                0,
+                None,
+                None,
+                None,
+                false,
+                &[],
+                &Clock::default(),
+            )
+        };
+
+        let undersized = (Some(base), 1_000_000_000);
+        assert_eq!(
+            run(&[undersized]).unwrap_err(),
+            error!(SolarBError::SwapTooSmall)
+        );
+        // An undersized start doesn't stop a later one that pays
+        let path = run(&[undersized, (Some(base), 5_000_000_000)]).unwrap();
+        assert_eq!(path.start_amount, 5_000_000_000);
+        // A start with nothing to trade at all makes it no profit
+        assert_eq!(
+            run(&[undersized, (Some(Pubkey::new_unique()), 5_000_000_000)]).unwrap_err(),
+            error!(SolarBError::NoProfitFound)
+        );
+    }
+
+    #[cfg(feature = "pump-amm")]
+    #[test]
+    fn test_execute_rejects_dust_swap_on_thin_pool() {
+        let base = Pubkey::new_unique();
+        let quote = Pubkey::new_unique();
+        let mut remaining = create_execution_accounts(&[base, quote]);
+        remaining[0].is_signer = true;
+        // A million base atoms to one quote atom, so selling a single base atom pays nothing
+        for quote_reserve in [1_000_000, 1_100_000] {
+            remaining.extend(programs::pump_amm::tests::create_pool_accounts_for_mints(
+                base,
+                quote,
+                1_000_000_000_000,
+                quote_reserve,
+            ));
+        }
+        let data = InstructionData::builder()
+            .accounts_length(vec![18, 18])
+            .slippage_bps(0)
+            .build();
+        let clock = Clock::default();
+        let (first_accounts, rest) = split_user_accounts(&remaining, &data).unwrap();
+        let first_accounts = FirstAccounts::new(first_accounts, false).unwrap();
+        let instances = parse_accounts(rest, &data).unwrap();
+
+        let min_amount = instances[1].min_swap_amount(&base, &clock).unwrap();
+        assert!(min_amount > 1);
+        // Not even the fees come out of one atom's worth of quote
+        let dust_out = instances[1].swap_base_in(base, 1, clock.clone());
+        assert!(dust_out.map_or(true, |amount_out| amount_out == 0));
+        assert!(
+            instances[1]
+                .swap_base_in(base, min_amount, clock.clone())
+                .unwrap()
+                > 0
+        );
+
+        let edges = get_edges(&instances, &clock, 0).unwrap();
+        // The second pool's base -> quote edge
+        assert_eq!(*edges[2].input_mint(), base);
+        assert_eq!(edges[2].min_amount_in, min_amount);
+        let edge_refs: Vec<&Edge> = edges.iter().collect();
+        let path = check_arbitrage(&edge_refs, 2_000_000_000, Some(base), None).unwrap();
+
+        let transfers = Rc::new(RefCell::new(Vec::new()));
+        let mut instances: Vec<Box<dyn ProgramMeta>> = instances
+            .into_iter()
+            .map(|inner| {
+                Box::new(SettlingProgram {
+                    inner,
+                    transfers: transfers.clone(),
+                    min_amount_in: None,
+                }) as Box<dyn ProgramMeta>
+            })
+            .collect();
+        let execute = |path: &ArbitragePath, instances: &mut Vec<Box<dyn ProgramMeta>>| {
+            remaining[3].try_borrow_mut_data().unwrap()[64..72]
+                .copy_from_slice(&(path.start_amount as u64).to_le_bytes());
+            execute_arbitrage_path(path, instances, &first_accounts, false, 0, 0, &clock)
+        };
+
+        // A single atom in is refused before anything is swapped
+        let dust = ArbitragePath {
+            start_amount: 1,
+            ..path.clone()
+        };
+        assert_eq!(
+            execute(&dust, &mut instances).unwrap_err(),
+            error!(SolarBError::SwapTooSmall)
+        );
+        assert!(transfers.borrow().is_empty());
+
+        execute(&path, &mut instances).unwrap();
+        assert_eq!(transfers.borrow().len(), 2);
+    }
+
+    #[cfg(feature = "pump-amm")]
+    #[test]
+    fn test_edges_normalize_prices_by_mint_decimals() {
+        use crate::programs::pump_amm::tests::create_pool_accounts_for_mints;
+
+        // A 9-decimal base traded against a 6-decimal quote
+        let (base, quote) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mint = |key: Pubkey, decimals: u8| {
+            let mut data = vec![0u8; 82];
+            data[44] = decimals;
+            data[45] = 1;
+            create_mock_account_info(key, spl_token::id(), 0, Some(data))
+        };
+        let pools = |quote_reserves: [u64; 2]| {
+            let mut accounts = create_execution_accounts(&[base, quote]);
+            for quote_reserve in quote_reserves {
+                let mut span =
+                    create_pool_accounts_for_mints(base, quote, 1_000_000_000_000, quote_reserve);
+                span[4] = mint(base, 9);
+                span[5] = mint(quote, 6);
+                accounts.extend(span);
+            }
+            accounts
+        };
+        let start_amount: u64 = 1_000_000_000;
+        let data = InstructionData::builder()
+            .accounts_length(vec![18, 18])
+            .start_tokens(vec![(quote, u128::from(start_amount))])
+            .slippage_bps(0)
+            .build();
+        let clock = Clock::default();
+        let fee = 0.0025;
+        let edges_of = |accounts: &'static [AccountInfo<'static>]| {
+            let (_, rest) = split_user_accounts(accounts, &data).unwrap();
+            let instances = Box::leak(Box::new(parse_accounts(rest, &data).unwrap()));
+            get_edges(instances, &clock, 0).unwrap()
+        };
+
+        // 1,000 tokens against 150,000 in both pools: 0.15 quote atoms per base atom, but
+        // 150 quote per base token
+        let accounts = Box::leak(Box::new(pools([150_000_000_000; 2])));
+        for edge in edges_of(accounts) {
+            let (decimals, normalized) = match edge.side {
+                EdgeSide::LeftToRight => ((9, 6), 150.0 * (1.0 - fee)),
+                // Buying base adds the fees on top of the quote paid in
+                EdgeSide::RightToLeft => ((6, 9), 1.0 / (150.0 * (1.0 + fee))),
+            };
+            assert_eq!((edge.left.decimals, edge.right.decimals), decimals);
+            assert!((edge.normalized_price() / normalized - 1.0).abs() < 1e-9);
+        }
+        assert_eq!(
+            simulate_arbitrage(accounts, &data, &clock).unwrap_err(),
+            error!(SolarBError::NoProfitFound)
+        );
+
+        // Base sells for 10% more in the second pool. The decimals cancel around the cycle,
+        // so its normalized prices multiply out to the same gain as the atom prices the
+        // search trades on, and the search takes it.
+        let accounts = Box::leak(Box::new(pools([150_000_000_000, 165_000_000_000])));
+        let edges = edges_of(accounts);
+        let edge = |pool_id: &Pubkey, side: EdgeSide| {
+            edges
+                .iter()
+                .find(|edge| edge.pool_id == *pool_id && edge.side == side)
+                .unwrap()
+        };
+        let (buy, sell) = (
+            edge(accounts[9].key, EdgeSide::RightToLeft),
+            edge(accounts[27].key, EdgeSide::LeftToRight),
+        );
+        let normalized = buy.normalized_price() * sell.normalized_price();
+        assert!((normalized / (buy.get_price() * sell.get_price()) - 1.0).abs() < 1e-9);
+        assert!((normalized / (1.1 * (1.0 - fee) / (1.0 + fee)) - 1.0).abs() < 1e-9);
+
+        let simulated = simulate_arbitrage(accounts, &data, &clock).unwrap();
+        let expected = start_amount as f64 * 1.1 * (1.0 - fee) / (1.0 + fee);
+        assert!((simulated.profit as f64 - (expected - start_amount as f64)).abs() <= 2.0);
+    }
+
+    #[test]
+    fn test_execute_rejects_open_path() {
+        let owner = system_program::id();
+        let [a, b, c] = [0; 3].map(|_| Pool::new(&Pubkey::new_unique(), 1_000_000_000));
+        let accounts = create_execution_accounts(&[a.mint_account, b.mint_account, c.mint_account]);
+        let first_accounts = FirstAccounts::new(&accounts, false).unwrap();
+
+        let program_id = Pubkey::new_unique();
+        let pools = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let edge = |pool_id: Pubkey, left: &Pool, right: &Pool| {
+            Edge::new(
+                program_id,
+                pool_id,
+                EdgeSide::LeftToRight,
+                1.0,
+                left.clone(),
+                right.clone(),
+            )
+        };
+        // A to B to C: the final amount is in C, so its "profit" over the A put in is not one
+        let arbitrage_path = ArbitragePath {
+            edges: vec![edge(pools[0], &a, &b), edge(pools[1], &b, &c)],
+            amounts_out: vec![1_000_000, 1_100_000],
+            profit: 100_000,
+            final_amount: 1_100_000,
+            start_amount: 1_000_000,
+        };
+        let swaps = Rc::new(RefCell::new(Vec::new()));
+        let mut instances: Vec<Box<dyn ProgramMeta>> = [(pools[0], &a, &b), (pools[1], &b, &c)]
+            .into_iter()
+            .map(|(pool, base, quote)| {
+                Box::new(QuoteCountingProgram {
+                    id: program_id,
+                    vaults: (
+                        create_mock_account_info(pool, owner, 0, None),
+                        create_mock_account_info(Pubkey::new_unique(), owner, 0, None),
+                    ),
+                    quotes: Default::default(),
+                    min_amounts_out: Default::default(),
+                    fails: false,
+                    shortfall: 0,
+                    clocks: Default::default(),
+                    mints: (base.mint_account, quote.mint_account),
+                    swaps: swaps.clone(),
+                }) as Box<dyn ProgramMeta>
+            })
+            .collect();
+
+        let result = execute_arbitrage_path(
+            &arbitrage_path,
+            &mut instances,
+            &first_accounts,
+            false,
+            0,
+            0,
+            &Clock::default(),
+        );
+        assert_eq!(result.unwrap_err(), error!(SolarBError::PathNotClosed));
+        assert!(swaps.borrow().is_empty());
+
+        // Back to A closes it
+        let arbitrage_path = ArbitragePath {
+            edges: vec![
+                edge(pools[0], &a, &b),
+                edge(pools[1], &b, &c),
+                edge(Pubkey::new_unique(), &c, &a),
+            ],
+            ..arbitrage_path
+        };
+        assert!(check_closed_path(&arbitrage_path).is_ok());
+    }
+
+    #[test]
+    fn test_execute_rejects_degenerate_path() {
+        let program_id = Pubkey::new_unique();
+        let pool_id = Pubkey::new_unique();
+        let base = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let quote = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let forward = Edge::new(
+            program_id,
+            pool_id,
+            EdgeSide::LeftToRight,
+            1.0,
+            base.clone(),
+            quote.clone(),
+        );
+        let back = Edge::new(program_id, pool_id, EdgeSide::RightToLeft, 1.0, quote, base);
+        // The claimed profit doesn't matter: a round trip through one pool can't make any
+        let arbitrage_path = ArbitragePath {
+            edges: vec![forward.clone(), back.clone()],
+            amounts_out: vec![1_000_000, 1_001_000],
+            profit: 1_000,
+            final_amount: 1_001_000,
+            start_amount: 1_000_000,
+        };
+
+        let accounts = create_mock_accounts(7, system_program::id());
+        let mut instances = Vec::new();
+        let result = execute_arbitrage_path(
+            &arbitrage_path,
+            &mut instances,
+            &FirstAccounts::new(&accounts, false).unwrap(),
+            false,
+            0,
+            0,
+            &Clock::default(),
+        );
+        assert_eq!(result.unwrap_err(), error!(SolarBError::DegeneratePath));
+
+        // The same program through another pool is a real two-venue trade
+        let mut other_pool = back;
+        other_pool.pool_id = Pubkey::new_unique();
+        let arbitrage_path = ArbitragePath {
+            edges: vec![forward, other_pool],
+            ..arbitrage_path
+        };
+        assert!(check_degenerate_path(&arbitrage_path).is_ok());
+    }
+
+    #[test]
+    fn test_arbitrage_found_event_decodes() {
+        use anchor_lang::Event;
+
+        let (program_a, program_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let base = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let quote = Pool::new(&Pubkey::new_unique(), 2_000_000_000);
+        let arbitrage_path = ArbitragePath {
+            edges: vec![
+                Edge::new(
+                    program_a,
+                    Pubkey::new_unique(),
+                    EdgeSide::LeftToRight,
+                    2.0,
+                    base.clone(),
+                    quote.clone(),
+                ),
+                Edge::new(
+                    program_b,
+                    Pubkey::new_unique(),
+                    EdgeSide::RightToLeft,
+                    0.55,
+                    quote.clone(),
+                    base.clone(),
+                ),
+            ],
+            amounts_out: vec![2_000_000, 1_100_000],
+            profit: 100_000,
+            final_amount: 1_100_000,
+            start_amount: 1_000_000,
+        };
+
+        // emit! logs the event's discriminator followed by its borsh encoding
+        let event = ArbitrageFound::from(&arbitrage_path);
+        let logged = event.data();
+        assert_eq!(&logged[..8], ArbitrageFound::DISCRIMINATOR);
+        let decoded = ArbitrageFound::try_from_slice(&logged[8..]).unwrap();
+        assert_eq!(decoded, event);
+        assert_eq!(decoded.profit, 100_000);
+        assert_eq!(
+            decoded.edges,
+            vec![
+                EdgeSummary {
+                    program: program_a,
+                    side: EdgeSide::LeftToRight,
+                    input_mint: base.mint_account,
+                },
+                EdgeSummary {
+                    program: program_b,
+                    side: EdgeSide::RightToLeft,
+                    input_mint: quote.mint_account,
+                },
+            ]
+        );
+
+        // The full path round-trips too, for off-chain consumers that want every field
+        let decoded_path =
+            ArbitragePath::try_from_slice(&borsh::to_vec(&arbitrage_path).unwrap()).unwrap();
+        assert_eq!(decoded_path.edges, arbitrage_path.edges);
+        assert_eq!(decoded_path.profit, arbitrage_path.profit);
+        assert_eq!(decoded_path.start_amount, arbitrage_path.start_amount);
+    }
+
+    #[test]
+    fn test_native_wrap_instructions_only_for_native_start() {
+        let owner = Pubkey::new_unique();
+        let wsol_mint = native_mint::id();
+        let other_mint = Pubkey::new_unique();
+        let token_program = spl_token::id();
+        let wsol_account = Pubkey::new_unique();
+        let other_account = Pubkey::new_unique();
+        // The wSOL account may sit on either side
+        let user_accounts = [
+            (&other_mint, &token_program, &other_account),
+            (&wsol_mint, &token_program, &wsol_account),
+        ];
+
+        let (sync_native, close_account) =
+            native_wrap_instructions(true, Some(wsol_mint), &owner, &user_accounts)
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            sync_native,
+            spl_token::instruction::sync_native(&token_program, &wsol_account).unwrap()
+        );
+        assert_eq!(close_account.program_id, token_program);
+        assert_eq!(close_account.accounts[0].pubkey, wsol_account);
+        assert_eq!(close_account.accounts[1].pubkey, owner);
+
+        // Flag off, or a path starting from another token, leaves the accounts alone
+        assert!(
+            native_wrap_instructions(false, Some(wsol_mint), &owner, &user_accounts)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            native_wrap_instructions(true, Some(other_mint), &owner, &user_accounts)
+                .unwrap()
+                .is_none()
+        );
+        assert!(native_wrap_instructions(true, None, &owner, &user_accounts)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_path_start_token_is_first_edge_input() {
+        let base = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let quote = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        // A quote -> base edge is built with the quote pool on the left
+        let arbitrage_path = ArbitragePath {
+            edges: vec![Edge::new(
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                EdgeSide::RightToLeft,
+                1.0,
+                quote.clone(),
+                base,
+            )],
+            amounts_out: vec![0],
+            profit: 0,
+            final_amount: 0,
+            start_amount: 0,
+        };
+        assert_eq!(path_start_token(&arbitrage_path), Some(quote.mint_account));
+    }
+
+    #[test]
+    fn test_quote_clock_takes_instruction_epoch_when_set() {
+        let clock = Clock {
+            slot: 300_000_000,
+            epoch_start_timestamp: 1_699_000_000,
+            epoch: 700,
+            leader_schedule_epoch: 701,
+            unix_timestamp: 1_700_000_000,
+        };
+        assert_eq!(quote_clock(clock.clone(), 0), clock);
+
+        let overridden = quote_clock(clock.clone(), 702);
+        assert_eq!(overridden.epoch, 702);
+        assert_eq!(
+            Clock {
+                epoch: clock.epoch,
+                ..overridden
+            },
+            clock
+        );
+    }
+
+    #[test]
+    fn test_instruction_data_builder_defaults() {
+        let data = InstructionData::builder().build();
+        assert_eq!(data.start_amount, 1_000_000_000);
+        assert_eq!(data.slippage_bps, 50);
+        assert_eq!(data.min_profit, 0);
+        // 0 quotes at the cluster's epoch
+        assert_eq!(data.epoch, 0);
+        assert_eq!(data.user_mint_count, 2);
+        assert!(data.accounts_length.is_empty());
+        assert!(data.selected_instances.is_none() && data.profit_mint.is_none());
+        assert!(data.max_plausible_profit.is_none() && data.max_compute_units.is_none());
+        assert!(!data.wrap_native && !data.try_alternatives && !data.recompute_quotes);
+        assert_eq!(data.min_liquidity, 0);
+        assert_eq!(min_profit(data.min_profit), None);
+    }
+
+    #[test]
+    fn test_instruction_data_builder_borsh_round_trip() {
+        let profit_mint = Pubkey::new_unique();
+        let data = InstructionData::builder()
+            .accounts_length(vec![9, 13])
+            .epoch(702)
+            .selected_instances(vec![1])
+            .max_plausible_profit(5_000_000)
+            .max_compute_units(600_000)
+            .try_alternatives(true)
+            .user_mint_count(3)
+            .profit_mint(profit_mint)
+            .min_liquidity(1_000)
+            .start_amount(2_000_000)
+            .slippage_bps(25)
+            .min_profit(10_000)
+            .build();
+
+        let encoded = borsh::to_vec(&data).unwrap();
+        let decoded = InstructionData::deserialize(&mut encoded.as_slice()).unwrap();
+        assert_eq!(borsh::to_vec(&decoded).unwrap(), encoded);
+        assert_eq!(decoded.accounts_length, vec![9, 13]);
+        assert_eq!(decoded.epoch, 702);
+        assert_eq!(decoded.selected_instances, Some(vec![1]));
+        assert_eq!(decoded.max_plausible_profit, Some(5_000_000));
+        assert_eq!(decoded.max_compute_units, Some(600_000));
+        assert!(decoded.try_alternatives && !decoded.wrap_native);
+        assert_eq!(decoded.user_mint_count, 3);
+        assert_eq!(decoded.profit_mint, Some(profit_mint));
+        assert_eq!(decoded.min_liquidity, 1_000);
+        assert_eq!(decoded.start_amount, 2_000_000);
+        assert_eq!(decoded.slippage_bps, 25);
+        assert_eq!(decoded.min_profit, 10_000);
+    }
+
+    #[test]
+    fn test_min_amount_out_applies_slippage() {
+        assert_eq!(min_amount_out(1_000_000, 0), 1_000_000);
+        assert_eq!(min_amount_out(1_000_000, 50), 995_000);
+        // Rounds the allowance down, so the minimum never drops below the slippage asked for
+        assert_eq!(min_amount_out(999, 50), 995);
+        assert_eq!(min_amount_out(u64::MAX, 10_000), 0);
+    }
+
+    #[test]
+    fn test_swap_amount_at_u64_boundary() {
+        assert_eq!(swap_amount(u64::MAX as u128).unwrap(), u64::MAX);
+        assert_eq!(
+            swap_amount(u64::MAX as u128 + 1).unwrap_err(),
+            error!(SolarBError::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn test_path_profit_at_boundaries() {
+        let max = u64::MAX as u128;
+        assert_eq!(path_profit(max, 0).unwrap(), u64::MAX as i128);
+        assert_eq!(path_profit(0, max).unwrap(), -(u64::MAX as i128));
+        assert_eq!(path_profit(max, max).unwrap(), 0);
+        // Amounts past i128::MAX would wrap negative if cast
+        assert_eq!(
+            path_profit(u128::MAX, 0).unwrap_err(),
+            error!(SolarBError::MathOverflow)
+        );
+        assert_eq!(
+            path_profit(0, u128::MAX).unwrap_err(),
+            error!(SolarBError::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn test_native_wrap_instructions_requires_wsol_account() {
+        let owner = Pubkey::new_unique();
+        let token_program = spl_token::id();
+        let (mint_1, mint_2) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (account_1, account_2) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let result = native_wrap_instructions(
+            true,
+            Some(native_mint::id()),
+            &owner,
+            &[
+                (&mint_1, &token_program, &account_1),
+                (&mint_2, &token_program, &account_2),
+            ],
+        );
+        assert_eq!(result.unwrap_err(), error!(SolarBError::AccountMismatch));
+    }
+
+    #[test]
+    fn test_sweep_dust_closes_wsol_only_when_set() {
+        let owner = Pubkey::new_unique();
+        let wsol = Pool::new(&native_mint::id(), 1_000_000_000);
+        let other = Pool::new(&Pubkey::new_unique(), 1_000_000_000);
+        let token_program = spl_token::id();
+        let (wsol_account, other_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let user_accounts = [
+            (&other.mint_account, &token_program, &other_account),
+            (&wsol.mint_account, &token_program, &wsol_account),
+        ];
+        let round_trip = |from: &Pool, to: &Pool| ArbitragePath {
+            edges: vec![
+                Edge::new(
+                    Pubkey::new_unique(),
+                    Pubkey::new_unique(),
+                    EdgeSide::LeftToRight,
+                    1.0,
+                    from.clone(),
+                    to.clone(),
+                ),
+                Edge::new(
+                    Pubkey::new_unique(),
+                    Pubkey::new_unique(),
+                    EdgeSide::RightToLeft,
+                    1.0,
+                    to.clone(),
+                    from.clone(),
+                ),
+            ],
+            amounts_out: vec![0, 0],
+            profit: 0,
+            final_amount: 0,
+            start_amount: 0,
+        };
+
+        let close_account =
+            sweep_dust_instruction(true, &round_trip(&wsol, &other), &owner, &user_accounts)
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            close_account,
+            spl_token::instruction::close_account(
+                &token_program,
+                &wsol_account,
+                &owner,
+                &owner,
+                &[]
+            )
+            .unwrap()
+        );
+
+        // Flag off, or a round trip through another token, leaves the wSOL account open
+        assert!(
+            sweep_dust_instruction(false, &round_trip(&wsol, &other), &owner, &user_accounts)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            sweep_dust_instruction(true, &round_trip(&other, &wsol), &owner, &user_accounts)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    // Names every gated adapter, so building the tests with `--no-default-features --features
+    // <dex>` checks each feature compiles on its own and is wired into the dispatcher
+    #[test]
+    fn test_enabled_programs_are_dispatched() {
+        let mut program_ids = Vec::new();
+        #[cfg(feature = "pump-amm")]
+        program_ids.push(PumpAmm::PROGRAM_ID);
+        #[cfg(feature = "raydium-cpmm")]
+        program_ids.push(RaydiumCPMM::PROGRAM_ID);
+        #[cfg(feature = "damm-v1")]
+        program_ids.push(MeteoraDammV1::PROGRAM_ID);
+        #[cfg(feature = "damm-v2")]
+        program_ids.push(MeteoraDammV2::PROGRAM_ID);
+        #[cfg(feature = "dlmm")]
+        program_ids.push(MeteoraDlmm::PROGRAM_ID);
+        #[cfg(feature = "lifinity")]
+        program_ids.push(LifinityV2::PROGRAM_ID);
+        #[cfg(feature = "phoenix")]
+        program_ids.push(Phoenix::PROGRAM_ID);
+        #[cfg(feature = "saber")]
+        program_ids.push(Saber::PROGRAM_ID);
+
+        for program_id in program_ids {
+            let mut accounts = vec![create_mock_account_info(
+                program_id,
+                system_program::id(),
+                0,
+                None,
+            )];
+            accounts.extend(create_mock_accounts(20, system_program::id()));
+            // Mock accounts won't parse, but the id must reach its adapter
+            if let Err(err) = find_program_instance(&program_id, &accounts, None, 0) {
+                assert_ne!(err, error!(SolarBError::UnknownProgram), "{}", program_id);
+            }
+        }
+    }
+
+    // Every venue's fixture span parses at its `EXPECTED_ACCOUNTS` and is rejected one
+    // account short, before its adapter reads any account
+    #[test]
+    fn test_spans_are_checked_against_expected_accounts() {
+        let mut spans: Vec<(Pubkey, usize, Vec<AccountInfo<'static>>)> = Vec::new();
+        #[cfg(feature = "pump-amm")]
+        spans.push((
+            PumpAmm::PROGRAM_ID,
+            PumpAmm::EXPECTED_ACCOUNTS,
+            programs::pump_amm::tests::create_pool_accounts(1_000_000, 1_000_000),
+        ));
+        #[cfg(feature = "raydium-cpmm")]
+        spans.push((
+            RaydiumCPMM::PROGRAM_ID,
+            RaydiumCPMM::EXPECTED_ACCOUNTS,
+            programs::raydium_cpmm::tests::create_swap_test_accounts(),
+        ));
+        #[cfg(feature = "damm-v1")]
+        spans.push((
+            MeteoraDammV1::PROGRAM_ID,
+            MeteoraDammV1::EXPECTED_ACCOUNTS,
+            programs::meteora_damm_v1::tests::create_test_accounts(),
+        ));
+        #[cfg(feature = "damm-v2")]
+        spans.push((
+            MeteoraDammV2::PROGRAM_ID,
+            MeteoraDammV2::EXPECTED_ACCOUNTS,
+            programs::meteora_damm_v2::tests::create_swap_test_accounts(
+                &programs::meteora_damm_v2::tests::create_test_pool(),
+            ),
+        ));
+        #[cfg(feature = "dlmm")]
+        spans.push((
+            MeteoraDlmm::PROGRAM_ID,
+            MeteoraDlmm::EXPECTED_ACCOUNTS,
+            programs::meteora_dlmm::tests::create_two_sided_test_accounts(),
+        ));
+        #[cfg(feature = "lifinity")]
+        spans.push((
+            LifinityV2::PROGRAM_ID,
+            LifinityV2::EXPECTED_ACCOUNTS,
+            programs::lifinity::tests::create_test_accounts(),
+        ));
+        #[cfg(feature = "phoenix")]
+        spans.push((
+            Phoenix::PROGRAM_ID,
+            Phoenix::EXPECTED_ACCOUNTS,
+            programs::phoenix::tests::create_test_accounts(),
+        ));
+        #[cfg(feature = "saber")]
+        spans.push((
+            Saber::PROGRAM_ID,
+            Saber::EXPECTED_ACCOUNTS,
+            programs::saber::tests::create_test_accounts(),
+        ));
+
+        for (program_id, expected, accounts) in spans {
+            assert!(accounts.len() >= expected, "{}", program_id);
+            let instance = find_program_instance(&program_id, &accounts[..expected], None, 0);
+            assert!(instance.is_ok(), "{}", program_id);
+            assert_eq!(
+                find_program_instance(&program_id, &accounts[..expected - 1], None, 0).err(),
+                Some(error!(SolarBError::InsufficientAccounts)),
+                "{}",
+                program_id
+            );
+        }
+    }
+
+    #[test]
+    fn test_supported_programs_are_dispatched() {
+        let supported = supported_programs();
+        let ids: HashSet<Pubkey> = supported.iter().map(|(_, id)| *id).collect();
+        assert_eq!(ids.len(), supported.len());
+
+        // A listed id reaches its venue's branch, which turns an empty span down for its
+        // length rather than as an unknown program
+        for (label, program_id) in supported {
+            assert_eq!(
+                find_program_instance(&program_id, &[], None, 0).err(),
+                Some(error!(SolarBError::InsufficientAccounts)),
+                "{}",
+                label
+            );
+        }
+        assert_eq!(
+            find_program_instance(&Pubkey::new_unique(), &[], None, 0).err(),
+            Some(error!(SolarBError::UnknownProgram))
+        );
+    }
+
+    #[test]
+    fn test_supported_programs_match_mainnet_ids() {
+        let mainnet_ids = std::collections::HashMap::from([
+            ("PumpAmm", "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA"),
+            (
+                "RaydiumCPMM",
+                "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C",
+            ),
+            (
+                "MeteoraDammV2",
+                "cpamdpZCGKUy5JxQXB4dcpGPiikHawvSWAd6mEn1sGG",
+            ),
+            (
+                "MeteoraDammV1",
+                "Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB",
+            ),
+            ("MeteoraDlmm", "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo"),
+            ("LifinityV2", "2wT8Yq49kHgDzXuPxZSaeLaH1qbmGXtEyPy64bL7aD3c"),
+            ("Phoenix", "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY"),
+            ("Saber", "SSwpkEEcbUqx4vtoEByFjSkhKdCT862DNVb52nZg1UZ"),
+        ]);
+        // The dispatcher recognises each venue by the id its swaps are sent to on mainnet
+        for (label, program_id) in supported_programs() {
+            assert_eq!(program_id.to_string(), mainnet_ids[label], "{}", label);
+            assert_ne!(program_id, crate::ID, "{}", label);
+        }
+    }
 }
@@ -8,3 +8,245 @@ pub fn safe_div(numerator: &u128, denominator: &u128) -> u128 {
         .and_then(|n| n.checked_div(*denominator))
         .unwrap_or(0)
 }
+
+/// Basis points in one whole, the denominator of every `fee_bps`
+pub const BPS_DENOMINATOR: u128 = 10_000;
+
+/// Division rounded up, `None` on a zero denominator or overflow
+pub fn ceil_div(numerator: u128, denominator: u128) -> Option<u128> {
+    numerator
+        .checked_add(denominator.checked_sub(1)?)?
+        .checked_div(denominator)
+}
+
+/// Which way a quote rounds a division that does not come out even
+///
+/// Exact-in quotes round the output down and exact-out quotes round the input up, so a
+/// quote never promises more than the swap pays out nor asks for less than it takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+/// `a * b / denominator` rounded as `rounding` says, `None` on a zero denominator or
+/// overflow
+pub fn mul_div(a: u128, b: u128, denominator: u128, rounding: Rounding) -> Option<u128> {
+    let product = a.checked_mul(b)?;
+    match rounding {
+        Rounding::Down => product.checked_div(denominator),
+        Rounding::Up => ceil_div(product, denominator),
+    }
+}
+
+/// Output of a constant-product swap of `amount_in`, rounded down
+///
+/// `fee_bps` is taken from the input before it reaches the curve. `None` on overflow or a
+/// fee of 100% or more.
+pub fn constant_product_out(
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    fee_bps: u16,
+) -> Option<u128> {
+    let fee_factor = BPS_DENOMINATOR.checked_sub(fee_bps as u128)?;
+    if fee_factor == 0 {
+        return None;
+    }
+    let amount_in = mul_div(amount_in, fee_factor, BPS_DENOMINATOR, Rounding::Down)?;
+    mul_div(
+        reserve_out,
+        amount_in,
+        reserve_in.checked_add(amount_in)?,
+        Rounding::Down,
+    )
+}
+
+/// Input (fee included) a constant-product swap needs to return exactly `amount_out`,
+/// rounded up
+///
+/// The inverse of `constant_product_out` with the same fee; `None` when `amount_out`
+/// would drain the output reserve, on overflow or a fee of 100% or more.
+pub fn constant_product_in(
+    amount_out: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    fee_bps: u16,
+) -> Option<u128> {
+    let fee_factor = BPS_DENOMINATOR.checked_sub(fee_bps as u128)?;
+    if fee_factor == 0 || amount_out >= reserve_out {
+        return None;
+    }
+    let amount_in = mul_div(
+        reserve_in,
+        amount_out,
+        reserve_out - amount_out,
+        Rounding::Up,
+    )?;
+    mul_div(amount_in, BPS_DENOMINATOR, fee_factor, Rounding::Up)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reserves of the pump pool the PumpAmm tests quote against
+    const PUMP_BASE_RESERVE: u128 = 936_605_012_306_479;
+    const PUMP_QUOTE_RESERVE: u128 = 18_905_080_188;
+
+    #[test]
+    fn test_mul_div_rounds_as_asked() {
+        assert_eq!(mul_div(7, 3, 2, Rounding::Down), Some(10));
+        assert_eq!(mul_div(7, 3, 2, Rounding::Up), Some(11));
+        // Even divisions are exact either way
+        assert_eq!(mul_div(8, 3, 2, Rounding::Down), Some(12));
+        assert_eq!(mul_div(8, 3, 2, Rounding::Up), Some(12));
+        assert_eq!(mul_div(0, 3, 2, Rounding::Up), Some(0));
+        assert_eq!(mul_div(1, 1, 0, Rounding::Down), None);
+        assert_eq!(mul_div(1, 1, 0, Rounding::Up), None);
+        assert_eq!(mul_div(u128::MAX, 2, 2, Rounding::Down), None);
+    }
+
+    #[test]
+    fn test_constant_product_out_without_fee() {
+        assert_eq!(
+            constant_product_out(1_000, 1_000_000, 1_000_000, 0),
+            Some(999)
+        );
+        assert_eq!(constant_product_out(0, 1_000_000, 1_000_000, 0), Some(0));
+        assert_eq!(constant_product_out(1_000, 1_000_000, 0, 0), Some(0));
+        assert_eq!(constant_product_out(0, 0, 1_000_000, 0), None);
+    }
+
+    #[test]
+    fn test_constant_product_out_matches_pump_quotes() {
+        // Buying with 0.01 and 1 SOL after the 0.25% pump fee comes off the input
+        assert_eq!(
+            constant_product_out(9_975_062, PUMP_QUOTE_RESERVE, PUMP_BASE_RESERVE, 0),
+            Some(493_928_933_528)
+        );
+        assert_eq!(
+            constant_product_out(997_506_234, PUMP_QUOTE_RESERVE, PUMP_BASE_RESERVE, 0),
+            Some(46_942_106_857_962)
+        );
+        // Selling 1 SOL of base into the token reserve, before fees
+        assert_eq!(
+            constant_product_out(1_000_000_000, PUMP_QUOTE_RESERVE, PUMP_BASE_RESERVE, 0),
+            Some(47_053_566_399_150)
+        );
+    }
+
+    #[test]
+    fn test_constant_product_fee_comes_off_the_input() {
+        // 0.3% of 10_000 leaves 9_970 to swap
+        assert_eq!(
+            constant_product_out(10_000, 1_000_000, 1_000_000, 30),
+            constant_product_out(9_970, 1_000_000, 1_000_000, 0)
+        );
+        assert_eq!(
+            constant_product_out(10_000, 1_000_000, 1_000_000, 9_999),
+            Some(0)
+        );
+        assert_eq!(
+            constant_product_out(10_000, 1_000_000, 1_000_000, 10_000),
+            None
+        );
+        assert_eq!(
+            constant_product_out(10_000, 1_000_000, 1_000_000, u16::MAX),
+            None
+        );
+        assert_eq!(constant_product_in(100, 1_000_000, 1_000_000, 10_000), None);
+    }
+
+    #[test]
+    fn test_constant_product_at_max_reserves() {
+        let max = u64::MAX as u128;
+        // u64 reserves and amounts never overflow
+        assert_eq!(constant_product_out(max, max, max, 0), Some(max / 2));
+        assert!(constant_product_out(max, max, max, 25).is_some());
+        assert_eq!(constant_product_in(max / 2, max, max, 0), Some(max - 1));
+        assert!(constant_product_in(max / 2, max, max, 25).is_some());
+
+        // Wider ones can
+        assert_eq!(constant_product_out(2, u128::MAX, u128::MAX, 0), None);
+        assert_eq!(constant_product_out(u128::MAX, 1, 1, 0), None);
+        assert_eq!(constant_product_in(1, u128::MAX, u128::MAX, 0), None);
+        // Nearly draining a u64 reserve needs an input past u128 once the fee is added
+        assert_eq!(constant_product_in(max - 1, max, max, 25), None);
+    }
+
+    #[test]
+    fn test_constant_product_in_rejects_draining_output() {
+        assert_eq!(
+            constant_product_in(1_000_000, 1_000_000, 1_000_000, 0),
+            None
+        );
+        assert_eq!(
+            constant_product_in(2_000_000, 1_000_000, 1_000_000, 30),
+            None
+        );
+        assert_eq!(constant_product_in(0, 1_000_000, 1_000_000, 30), Some(0));
+        assert_eq!(
+            constant_product_in(999_999, 1_000_000, 1_000_000, 0),
+            Some(999_999_000_000)
+        );
+    }
+
+    #[test]
+    fn test_constant_product_in_inverts_out() {
+        let reserves = [
+            (1_000_000, 1_000_000),
+            (PUMP_QUOTE_RESERVE, PUMP_BASE_RESERVE),
+            (PUMP_BASE_RESERVE, PUMP_QUOTE_RESERVE),
+        ];
+        for (reserve_in, reserve_out) in reserves {
+            for fee_bps in [0, 1, 25, 30, 100, 5_000] {
+                for amount_in in [1, 997, 10_000_000, 1_000_000_000] {
+                    let amount_out =
+                        constant_product_out(amount_in, reserve_in, reserve_out, fee_bps).unwrap();
+                    if amount_out == 0 {
+                        continue;
+                    }
+                    // The required input buys at least the output, and no more than what
+                    // produced it
+                    let required =
+                        constant_product_in(amount_out, reserve_in, reserve_out, fee_bps).unwrap();
+                    assert!(required <= amount_in);
+                    assert!(
+                        constant_product_out(required, reserve_in, reserve_out, fee_bps).unwrap()
+                            >= amount_out
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_constant_product_in_is_never_one_unit_short() {
+        // Reserves that leave a remainder on nearly every division
+        for (reserve_in, reserve_out) in [(1_000_003, 999_983), (7_919, 104_729)] {
+            for fee_bps in [0, 3, 25, 30, 997] {
+                for amount_out in (1..reserve_out / 2).step_by(997) {
+                    let required =
+                        constant_product_in(amount_out, reserve_in, reserve_out, fee_bps).unwrap();
+                    // Enough to produce the output, and one unit less is not
+                    assert!(
+                        constant_product_out(required, reserve_in, reserve_out, fee_bps).unwrap()
+                            >= amount_out
+                    );
+                    assert!(
+                        constant_product_out(required - 1, reserve_in, reserve_out, fee_bps)
+                            .unwrap()
+                            < amount_out,
+                        "{} out of {}/{} at {} bps needs less than {}",
+                        amount_out,
+                        reserve_in,
+                        reserve_out,
+                        fee_bps,
+                        required
+                    );
+                }
+            }
+        }
+    }
+}
@@ -22,4 +22,60 @@ pub enum SolarBError {
     InsufficientFunds,
     #[msg("TransferFee calculation error")]
     TransferFeeCalculationError,
+    #[msg("selected instance index is out of range")]
+    InvalidInstanceSelection,
+    #[msg("supplied bin arrays do not cover the swap's price range")]
+    InsufficientBinArrays,
+    #[msg("computed profit exceeds the plausible maximum")]
+    ImplausibleProfit,
+    #[msg("pool state does not match the supplied vaults or mints")]
+    PoolStateMismatch,
+    #[msg("estimated compute units of the path exceed the budget")]
+    ComputeBudgetExceeded,
+    #[msg("path trades back and forth through the same pool")]
+    DegeneratePath,
+    #[msg("amount or profit does not fit its integer type")]
+    MathOverflow,
+    #[msg("path does not record the expected output of every edge")]
+    MissingExpectedAmount,
+    #[msg("swap of a path edge failed")]
+    EdgeExecutionFailed,
+    #[msg("oracle account is not a live price feed")]
+    InvalidOracle,
+    #[msg("pool cannot pay out the quoted amount")]
+    InsufficientLiquidity,
+    #[msg("no edge prices the start token in the profit mint")]
+    NoPriceForProfitMint,
+    #[msg("token program does not own the mint it is supplied for")]
+    TokenProgramMismatch,
+    #[msg("quoted rate strays too far from the pool's marginal price")]
+    ImplausibleQuote,
+    #[msg("pool has a coin creator but its vault accounts were not supplied")]
+    MissingCoinCreatorVault,
+    #[msg("event authority is not the venue program's event authority PDA")]
+    InvalidEventAuthority,
+    #[msg("vault is not a token account")]
+    VaultParseFailed,
+    #[msg("pool account data is too short or malformed for its venue")]
+    PoolDeserializeFailed,
+    #[msg("mint account could not be loaded")]
+    MintLoadFailed,
+    #[msg("venue could not quote the swap")]
+    QuoteFailed,
+    #[msg("more edges than the on-chain search can go through")]
+    TooManyEdges,
+    #[msg("AMM config account is missing or not the pool's AMM config")]
+    MissingAmmConfig,
+    #[msg("payer account did not sign the transaction")]
+    PayerNotSigner,
+    #[msg("arbitrage path does not end in the token it starts from")]
+    PathNotClosed,
+    #[msg("no instance carries the associated token program to create a token account")]
+    MissingAssociatedTokenProgram,
+    #[msg("quote crosses more bins than the instruction allows")]
+    QuoteTooDeep,
+    #[msg("swap input is below the smallest amount the pool pays anything out for")]
+    SwapTooSmall,
+    #[msg("start tokens of different mints need a profit mint to compare their profits in")]
+    ProfitMintRequired,
 }
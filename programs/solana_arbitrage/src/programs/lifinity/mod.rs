@@ -0,0 +1,1077 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    account_info::next_account_info,
+    instruction::{AccountMeta, Instruction},
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::arbitrage::base::EdgeSide;
+use crate::math::{constant_product_out, mul_div, Rounding};
+use crate::programs::programs::{
+    check_recorded_keys, check_vault_mints, quote_exact_out_by_search, read_mint_decimals,
+    PRICE_SCALE,
+};
+use crate::programs::{PriceSource, ProgramMeta, SolarBError};
+use crate::utils::utils::{parse_mint, resolve_swap_accounts, ResolvedAccounts, SwapUserAccounts};
+
+// Borsh offsets (including the 8-byte discriminator) into the Lifinity v2 amm account
+const AMM_FREEZE_TRADE_OFFSET: usize = 122;
+const AMM_TOKEN_A_ACCOUNT_OFFSET: usize = 158;
+const AMM_TOKEN_B_ACCOUNT_OFFSET: usize = 190;
+const AMM_POOL_MINT_OFFSET: usize = 222;
+const AMM_TOKEN_A_MINT_OFFSET: usize = 254;
+const AMM_TOKEN_B_MINT_OFFSET: usize = 286;
+const AMM_FEE_ACCOUNT_OFFSET: usize = 318;
+const AMM_ORACLE_MAIN_ACCOUNT_OFFSET: usize = 350;
+const AMM_ORACLE_SUB_ACCOUNT_OFFSET: usize = 382;
+const AMM_ORACLE_PC_ACCOUNT_OFFSET: usize = 414;
+const AMM_TRADE_FEE_NUMERATOR_OFFSET: usize = 446;
+const AMM_TRADE_FEE_DENOMINATOR_OFFSET: usize = 454;
+const AMM_OWNER_TRADE_FEE_NUMERATOR_OFFSET: usize = 462;
+const AMM_OWNER_TRADE_FEE_DENOMINATOR_OFFSET: usize = 470;
+const AMM_CURVE_PARAMETERS_OFFSET: usize = 511;
+
+// Header and aggregate price of a Pyth v2 price account
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+const PYTH_PRICE_ACCOUNT_TYPE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+const PYTH_ACCOUNT_TYPE_OFFSET: usize = 8;
+const PYTH_EXPONENT_OFFSET: usize = 20;
+const PYTH_AGGREGATE_PRICE_OFFSET: usize = 208;
+const PYTH_AGGREGATE_STATUS_OFFSET: usize = 224;
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    let bytes = data
+        .get(offset..offset + N)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    Ok(bytes.try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(data, offset)?))
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    Ok(Pubkey::new_from_array(read_bytes(data, offset)?))
+}
+
+/// The part of the amm account needed to quote a swap
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AmmState {
+    pub freeze_trade: u8,
+    pub trade_fee_numerator: u64,
+    pub trade_fee_denominator: u64,
+    pub owner_trade_fee_numerator: u64,
+    pub owner_trade_fee_denominator: u64,
+    /// How tightly the curve's liquidity sits around the oracle price; 1 (or 0) is a plain
+    /// constant product
+    pub concentration: u64,
+}
+
+impl AmmState {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Ok(AmmState {
+            freeze_trade: read_bytes::<1>(data, AMM_FREEZE_TRADE_OFFSET)?[0],
+            trade_fee_numerator: read_u64(data, AMM_TRADE_FEE_NUMERATOR_OFFSET)?,
+            trade_fee_denominator: read_u64(data, AMM_TRADE_FEE_DENOMINATOR_OFFSET)?,
+            owner_trade_fee_numerator: read_u64(data, AMM_OWNER_TRADE_FEE_NUMERATOR_OFFSET)?,
+            owner_trade_fee_denominator: read_u64(data, AMM_OWNER_TRADE_FEE_DENOMINATOR_OFFSET)?,
+            concentration: read_u64(data, AMM_CURVE_PARAMETERS_OFFSET)?,
+        })
+    }
+
+    /// Share of the input kept as the trade and owner fees
+    pub fn fee_rate(&self) -> f64 {
+        let rate = |numerator: u64, denominator: u64| {
+            if denominator == 0 {
+                0.0
+            } else {
+                numerator as f64 / denominator as f64
+            }
+        };
+        rate(self.trade_fee_numerator, self.trade_fee_denominator)
+            + rate(
+                self.owner_trade_fee_numerator,
+                self.owner_trade_fee_denominator,
+            )
+    }
+
+    /// Input that reaches the curve once both fees, each rounded down, are taken
+    pub fn amount_after_fees(&self, amount_in: u64) -> Option<u128> {
+        let fee = |numerator: u64, denominator: u64| {
+            if denominator == 0 {
+                return Some(0);
+            }
+            (amount_in as u128)
+                .checked_mul(numerator as u128)?
+                .checked_div(denominator as u128)
+        };
+        (amount_in as u128)
+            .checked_sub(fee(self.trade_fee_numerator, self.trade_fee_denominator)?)?
+            .checked_sub(fee(
+                self.owner_trade_fee_numerator,
+                self.owner_trade_fee_denominator,
+            )?)
+    }
+}
+
+/// Aggregate price of a Pyth v2 price account: `price * 10^exponent` quote tokens per base
+/// token
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub exponent: i32,
+}
+
+impl OraclePrice {
+    /// Fails with `InvalidOracle` unless the account is a Pyth price account trading at a
+    /// positive price
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let magic = u32::from_le_bytes(read_bytes(data, 0)?);
+        let account_type = u32::from_le_bytes(read_bytes(data, PYTH_ACCOUNT_TYPE_OFFSET)?);
+        let status = u32::from_le_bytes(read_bytes(data, PYTH_AGGREGATE_STATUS_OFFSET)?);
+        let price = i64::from_le_bytes(read_bytes(data, PYTH_AGGREGATE_PRICE_OFFSET)?);
+        if magic != PYTH_MAGIC
+            || account_type != PYTH_PRICE_ACCOUNT_TYPE
+            || status != PYTH_STATUS_TRADING
+            || price <= 0
+        {
+            msg!(
+                "Oracle is not a live Pyth price: magic={:#x}, type={}, status={}, price={}",
+                magic,
+                account_type,
+                status,
+                price
+            );
+            return Err(error!(SolarBError::InvalidOracle));
+        }
+        Ok(OraclePrice {
+            price,
+            exponent: i32::from_le_bytes(read_bytes(data, PYTH_EXPONENT_OFFSET)?),
+        })
+    }
+
+    /// Price in token atoms as `(numerator, denominator)`: quote atoms per base atom
+    pub fn raw_price(&self, base_decimals: u8, quote_decimals: u8) -> Option<(u128, u128)> {
+        let exponent = self.exponent + quote_decimals as i32 - base_decimals as i32;
+        let scale = 10u128.checked_pow(exponent.unsigned_abs())?;
+        let price = self.price as u128;
+        if exponent >= 0 {
+            Some((price.checked_mul(scale)?, 1))
+        } else {
+            Some((price, scale))
+        }
+    }
+
+    pub fn to_f64(self, base_decimals: u8, quote_decimals: u8) -> f64 {
+        let exponent = self.exponent + quote_decimals as i32 - base_decimals as i32;
+        self.price as f64 * 10f64.powi(exponent)
+    }
+}
+
+/// Lifinity v2 pool.
+///
+/// The curve is a constant product re-centred on the Pyth price in `oracle_main_account`:
+/// the input side's reserve is multiplied by the pool's concentration into a virtual
+/// reserve, and the output side's virtual reserve sits at the oracle price against it.
+/// The higher the concentration, the less a swap moves the price away from the oracle's.
+pub struct LifinityV2<'info> {
+    pub program_id: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+    pub pool_id: AccountInfo<'info>,
+    pub base_vault: AccountInfo<'info>,
+    pub quote_vault: AccountInfo<'info>,
+    pub base_token: AccountInfo<'info>,
+    pub quote_token: AccountInfo<'info>,
+    pub pool_mint: AccountInfo<'info>,
+    pub fee_account: AccountInfo<'info>,
+    pub oracle_main_account: AccountInfo<'info>,
+    pub oracle_sub_account: AccountInfo<'info>,
+    pub oracle_pc_account: AccountInfo<'info>,
+}
+
+impl<'info> ProgramMeta for LifinityV2<'info> {
+    fn get_id(&self) -> &Pubkey {
+        &Self::PROGRAM_ID
+    }
+
+    fn program_label(&self) -> &'static str {
+        "LifinityV2"
+    }
+
+    fn pool_key(&self) -> &Pubkey {
+        self.pool_id.key
+    }
+
+    fn get_vaults(&self) -> (&AccountInfo<'_>, &AccountInfo<'_>) {
+        unsafe {
+            (
+                &*(&self.base_vault as *const AccountInfo<'info> as *const AccountInfo<'_>),
+                &*(&self.quote_vault as *const AccountInfo<'info> as *const AccountInfo<'_>),
+            )
+        }
+    }
+
+    fn get_mints(&self) -> (&Pubkey, &Pubkey) {
+        (self.base_token.key, self.quote_token.key)
+    }
+
+    fn mint_decimals(&self) -> (u8, u8) {
+        read_mint_decimals(&self.base_token, &self.quote_token)
+    }
+
+    /// At the margin the curve trades at the oracle price, whatever the reserves
+    fn compute_price_swap_base_in(&self, _base_amount: u128, _quote_amount: u128) -> Result<f64> {
+        let fee_rate = self.amm_state()?.fee_rate();
+        Ok(self.oracle_price_f64()? * (1.0 - fee_rate))
+    }
+
+    fn compute_price_swap_base_out(&self, _base_amount: u128, _quote_amount: u128) -> Result<f64> {
+        let fee_rate = self.amm_state()?.fee_rate();
+        Ok(self.oracle_price_f64()?.recip() * (1.0 - fee_rate))
+    }
+
+    fn swap_base_in(&self, input_mint: Pubkey, amount_in: u64, _clock: Clock) -> Result<u64> {
+        self.quote_exact_in(input_mint, amount_in, None)
+    }
+
+    /// Searched through the exact-in quote, whose oracle-centred curve has no closed-form
+    /// inverse
+    fn swap_base_out(&self, input_mint: Pubkey, amount_out: u64, _clock: Clock) -> Result<u64> {
+        quote_exact_out_by_search(amount_out, |amount_in| {
+            self.quote_exact_in(input_mint, amount_in, None)
+        })
+    }
+
+    fn invoke_swap_base_in<'a>(
+        &self,
+        input_mint: Pubkey,
+        max_amount_in: u64,
+        amount_out: Option<u64>,
+        authority: AccountInfo<'a>,
+        user_mint_1_token_account: AccountInfo<'a>,
+        user_mint_2_token_account: AccountInfo<'a>,
+        mint_1_account: AccountInfo<'a>,
+        mint_2_account: AccountInfo<'a>,
+        mint_1_token_program: AccountInfo<'a>,
+        mint_2_token_program: AccountInfo<'a>,
+    ) -> Result<()> {
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            input_mint,
+            max_amount_in,
+            amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
+    }
+
+    fn invoke_swap_base_out<'a>(
+        &self,
+        input_mint: Pubkey,
+        amount_in: u64,
+        min_amount_out: Option<u64>,
+        authority: AccountInfo<'a>,
+        user_mint_1_token_account: AccountInfo<'a>,
+        user_mint_2_token_account: AccountInfo<'a>,
+        mint_1_account: AccountInfo<'a>,
+        mint_2_account: AccountInfo<'a>,
+        mint_1_token_program: AccountInfo<'a>,
+        mint_2_token_program: AccountInfo<'a>,
+    ) -> Result<()> {
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            input_mint,
+            amount_in,
+            min_amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
+    }
+
+    /// `swap` of `amount_in` from the user's input token account to their output one
+    fn build_swap_ix(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        user_accounts: &SwapUserAccounts,
+    ) -> Result<Instruction> {
+        let (source, destination, swap_source, swap_destination, token_program) = match direction {
+            EdgeSide::LeftToRight => (
+                user_accounts.user_base_token_account,
+                user_accounts.user_quote_token_account,
+                &self.base_vault,
+                &self.quote_vault,
+                user_accounts.base_token_program,
+            ),
+            EdgeSide::RightToLeft => (
+                user_accounts.user_quote_token_account,
+                user_accounts.user_base_token_account,
+                &self.quote_vault,
+                &self.base_vault,
+                user_accounts.quote_token_program,
+            ),
+        };
+
+        let mut data = Self::SWAP_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&min_out.to_le_bytes());
+        Ok(Instruction {
+            program_id: *self.program_id.key,
+            accounts: vec![
+                AccountMeta::new_readonly(*self.authority.key, false),
+                AccountMeta::new(*self.pool_id.key, false),
+                AccountMeta::new_readonly(user_accounts.authority, true),
+                AccountMeta::new(source, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new(*swap_source.key, false),
+                AccountMeta::new(*swap_destination.key, false),
+                AccountMeta::new(*self.pool_mint.key, false),
+                AccountMeta::new(*self.fee_account.key, false),
+                AccountMeta::new_readonly(token_program, false),
+                AccountMeta::new_readonly(*self.oracle_main_account.key, false),
+                AccountMeta::new_readonly(*self.oracle_sub_account.key, false),
+                AccountMeta::new_readonly(*self.oracle_pc_account.key, false),
+            ],
+            data,
+        })
+    }
+
+    fn estimate_compute_units(&self) -> u32 {
+        Self::SWAP_COMPUTE_UNITS
+    }
+
+    /// Frozen pools and pools whose oracle isn't trading reject swaps
+    fn is_tradable(&self, _clock: &Clock) -> Result<bool> {
+        if self.amm_state()?.freeze_trade != 0 {
+            return Ok(false);
+        }
+        Ok(OraclePrice::parse(&self.oracle_main_account.try_borrow_data()?).is_ok())
+    }
+
+    fn validate_accounts(&self) -> Result<()> {
+        check_vault_mints(self)?;
+        let amm_data = self.pool_id.try_borrow_data()?;
+        check_recorded_keys(
+            self.pool_id.key,
+            &[
+                (
+                    "token_a_mint",
+                    read_pubkey(&amm_data, AMM_TOKEN_A_MINT_OFFSET)?,
+                    self.base_token.key,
+                ),
+                (
+                    "token_b_mint",
+                    read_pubkey(&amm_data, AMM_TOKEN_B_MINT_OFFSET)?,
+                    self.quote_token.key,
+                ),
+                (
+                    "token_a_account",
+                    read_pubkey(&amm_data, AMM_TOKEN_A_ACCOUNT_OFFSET)?,
+                    self.base_vault.key,
+                ),
+                (
+                    "token_b_account",
+                    read_pubkey(&amm_data, AMM_TOKEN_B_ACCOUNT_OFFSET)?,
+                    self.quote_vault.key,
+                ),
+                (
+                    "pool_mint",
+                    read_pubkey(&amm_data, AMM_POOL_MINT_OFFSET)?,
+                    self.pool_mint.key,
+                ),
+                (
+                    "fee_account",
+                    read_pubkey(&amm_data, AMM_FEE_ACCOUNT_OFFSET)?,
+                    self.fee_account.key,
+                ),
+                (
+                    "oracle_main_account",
+                    read_pubkey(&amm_data, AMM_ORACLE_MAIN_ACCOUNT_OFFSET)?,
+                    self.oracle_main_account.key,
+                ),
+                (
+                    "oracle_sub_account",
+                    read_pubkey(&amm_data, AMM_ORACLE_SUB_ACCOUNT_OFFSET)?,
+                    self.oracle_sub_account.key,
+                ),
+                (
+                    "oracle_pc_account",
+                    read_pubkey(&amm_data, AMM_ORACLE_PC_ACCOUNT_OFFSET)?,
+                    self.oracle_pc_account.key,
+                ),
+            ],
+        )
+    }
+
+    fn log_accounts(&self) -> Result<()> {
+        msg!(
+            "Lifinity v2 accounts: amm={}, authority={}, base_vault={}, quote_vault={}, base_token={}, quote_token={}, pool_mint={}, fee_account={}, oracle_main={}, oracle_sub={}, oracle_pc={}",
+            self.pool_id.key,
+            self.authority.key,
+            self.base_vault.key,
+            self.quote_vault.key,
+            self.base_token.key,
+            self.quote_token.key,
+            self.pool_mint.key,
+            self.fee_account.key,
+            self.oracle_main_account.key,
+            self.oracle_sub_account.key,
+            self.oracle_pc_account.key,
+        );
+        Ok(())
+    }
+}
+
+impl<'info> LifinityV2<'info> {
+    pub const PROGRAM_ID: Pubkey =
+        Pubkey::from_str_const("2wT8Yq49kHgDzXuPxZSaeLaH1qbmGXtEyPy64bL7aD3c");
+    /// Shortest span `find_program_instance` accepts, program id included
+    pub const EXPECTED_ACCOUNTS: usize = 12;
+    /// Typical compute units of one swap CPI, oracle reads included
+    pub const SWAP_COMPUTE_UNITS: u32 = 60_000;
+    const SWAP_DISCRIMINATOR: [u8; 8] = [0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x75, 0x87, 0xc8];
+
+    pub fn new(accounts: &[AccountInfo<'info>]) -> Result<Self> {
+        let mut iter = accounts.iter();
+        let program_id = next_account_info(&mut iter)?; // 0
+        let authority = next_account_info(&mut iter)?; // 1
+        let pool_id = next_account_info(&mut iter)?; // 2
+        let base_vault = next_account_info(&mut iter)?; // 3
+        let quote_vault = next_account_info(&mut iter)?; // 4
+        let base_token = next_account_info(&mut iter)?; // 5
+        let quote_token = next_account_info(&mut iter)?; // 6
+        let pool_mint = next_account_info(&mut iter)?; // 7
+        let fee_account = next_account_info(&mut iter)?; // 8
+        let oracle_main_account = next_account_info(&mut iter)?; // 9
+        let oracle_sub_account = next_account_info(&mut iter)?; // 10
+        let oracle_pc_account = next_account_info(&mut iter)?; // 11
+
+        Ok(LifinityV2 {
+            program_id: program_id.clone(),
+            authority: authority.clone(),
+            pool_id: pool_id.clone(),
+            base_vault: base_vault.clone(),
+            quote_vault: quote_vault.clone(),
+            base_token: base_token.clone(),
+            quote_token: quote_token.clone(),
+            pool_mint: pool_mint.clone(),
+            fee_account: fee_account.clone(),
+            oracle_main_account: oracle_main_account.clone(),
+            oracle_sub_account: oracle_sub_account.clone(),
+            oracle_pc_account: oracle_pc_account.clone(),
+        })
+    }
+
+    pub fn amm_state(&self) -> Result<AmmState> {
+        AmmState::parse(&self.pool_id.try_borrow_data()?)
+    }
+
+    /// Oracle price as quote atoms per base atom, `(numerator, denominator)`
+    pub fn oracle_price(&self) -> Result<(u128, u128)> {
+        let oracle = OraclePrice::parse(&self.oracle_main_account.try_borrow_data()?)?;
+        oracle
+            .raw_price(
+                parse_mint(&self.base_token)?.decimals,
+                parse_mint(&self.quote_token)?.decimals,
+            )
+            .ok_or(error!(SolarBError::MathOverflow))
+    }
+
+    fn oracle_price_f64(&self) -> Result<f64> {
+        let oracle = OraclePrice::parse(&self.oracle_main_account.try_borrow_data()?)?;
+        Ok(oracle.to_f64(
+            parse_mint(&self.base_token)?.decimals,
+            parse_mint(&self.quote_token)?.decimals,
+        ))
+    }
+
+    /// Exact-in quote: fees come off the input, then the constant product over the virtual
+    /// reserves prices the rest. Fails with `InsufficientLiquidity` when the output is more
+    /// than the pool holds.
+    ///
+    /// The curve centres on `price_source`'s price of the base mint, or on the oracle
+    /// account's when `None`.
+    pub fn quote_exact_in(
+        &self,
+        input_mint: Pubkey,
+        amount_in: u64,
+        price_source: Option<&dyn PriceSource>,
+    ) -> Result<u64> {
+        let amm = self.amm_state()?;
+        let price = price_source.unwrap_or(self).price(self.base_token.key)?;
+        let (base_reserve, quote_reserve) = self.vault_reserves()?;
+        let concentration = amm.concentration.max(1) as u128;
+
+        let amount = amm
+            .amount_after_fees(amount_in)
+            .ok_or(error!(SolarBError::MathOverflow))?;
+        let virtual_reserves = if input_mint == *self.base_token.key {
+            base_reserve
+                .checked_mul(concentration)
+                .and_then(|virtual_base| {
+                    let virtual_quote = mul_div(virtual_base, price, PRICE_SCALE, Rounding::Down)?;
+                    Some((virtual_base, virtual_quote, quote_reserve))
+                })
+        } else {
+            quote_reserve
+                .checked_mul(concentration)
+                .and_then(|virtual_quote| {
+                    let virtual_base = mul_div(virtual_quote, PRICE_SCALE, price, Rounding::Down)?;
+                    Some((virtual_quote, virtual_base, base_reserve))
+                })
+        };
+        let (virtual_in, virtual_out, reserve_out) =
+            virtual_reserves.ok_or(error!(SolarBError::MathOverflow))?;
+
+        let amount_out = constant_product_out(amount, virtual_in, virtual_out, 0)
+            .ok_or(error!(SolarBError::MathOverflow))?;
+        require!(
+            amount_out <= reserve_out,
+            SolarBError::InsufficientLiquidity
+        );
+        u64::try_from(amount_out).map_err(|_| error!(SolarBError::MathOverflow))
+    }
+
+    fn invoke_swap<'a>(
+        &self,
+        input_mint: Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        authority: AccountInfo<'a>,
+        accounts: ResolvedAccounts<'a>,
+    ) -> Result<()> {
+        let direction = if input_mint == *self.base_token.key {
+            EdgeSide::LeftToRight
+        } else {
+            EdgeSide::RightToLeft
+        };
+        let swap_ix = self.build_swap_ix(
+            direction.clone(),
+            amount_in,
+            minimum_amount_out,
+            &accounts.keys(authority.key),
+        )?;
+        let token_program = match direction {
+            EdgeSide::LeftToRight => accounts.base_token_program,
+            EdgeSide::RightToLeft => accounts.quote_token_program,
+        };
+
+        // Same lifetime cast as the other adapters: the pool's accounts outlive the swap
+        let mut accounts_vec: Vec<AccountInfo<'info>> = vec![
+            self.authority.to_account_info(),
+            self.pool_id.to_account_info(),
+            self.base_vault.to_account_info(),
+            self.quote_vault.to_account_info(),
+            self.pool_mint.to_account_info(),
+            self.fee_account.to_account_info(),
+            self.oracle_main_account.to_account_info(),
+            self.oracle_sub_account.to_account_info(),
+            self.oracle_pc_account.to_account_info(),
+            self.program_id.to_account_info(),
+        ];
+        for account in [
+            authority,
+            accounts.user_base_token_account,
+            accounts.user_quote_token_account,
+            token_program,
+        ] {
+            accounts_vec.push(unsafe {
+                std::mem::transmute::<AccountInfo<'a>, AccountInfo<'info>>(account)
+            });
+        }
+
+        unsafe {
+            let accounts =
+                std::mem::transmute::<&[AccountInfo<'info>], &[AccountInfo<'a>]>(&accounts_vec);
+            invoke(&swap_ix, accounts)?;
+        }
+        Ok(())
+    }
+}
+
+/// The pool's Pyth price, for its base mint only
+impl PriceSource for LifinityV2<'_> {
+    fn price(&self, mint: &Pubkey) -> Result<u128> {
+        require_keys_eq!(*mint, *self.base_token.key, SolarBError::InvalidOracle);
+        let (numerator, denominator) = self.oracle_price()?;
+        mul_div(numerator, PRICE_SCALE, denominator, Rounding::Down)
+            .ok_or(error!(SolarBError::MathOverflow))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::programs::test_utils::{mint_data, token_account_data};
+    use anchor_lang::solana_program::system_program;
+    use std::cell::Cell;
+
+    const TOKEN_PROGRAM: Pubkey =
+        Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+    // Helper function to create a mock AccountInfo
+    fn create_mock_account_info(
+        key: Pubkey,
+        owner: Pubkey,
+        account_data: Option<Vec<u8>>,
+    ) -> AccountInfo<'static> {
+        let data = if let Some(provided_data) = account_data {
+            Box::leak(Box::new(provided_data))
+        } else {
+            Box::leak(Box::new(Vec::new()))
+        };
+        let lamports = Box::leak(Box::new(0u64));
+        let owner_static = Box::leak(Box::new(owner));
+        let key_static = Box::leak(Box::new(key));
+
+        AccountInfo::new(
+            key_static,
+            false,
+            false,
+            lamports,
+            data,
+            owner_static,
+            false,
+            0,
+        )
+    }
+
+    pub(crate) fn pyth_price_data(price: i64, exponent: i32, status: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 3312];
+        data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[PYTH_ACCOUNT_TYPE_OFFSET..PYTH_ACCOUNT_TYPE_OFFSET + 4]
+            .copy_from_slice(&PYTH_PRICE_ACCOUNT_TYPE.to_le_bytes());
+        data[PYTH_EXPONENT_OFFSET..PYTH_EXPONENT_OFFSET + 4]
+            .copy_from_slice(&exponent.to_le_bytes());
+        data[PYTH_AGGREGATE_PRICE_OFFSET..PYTH_AGGREGATE_PRICE_OFFSET + 8]
+            .copy_from_slice(&price.to_le_bytes());
+        data[PYTH_AGGREGATE_STATUS_OFFSET..PYTH_AGGREGATE_STATUS_OFFSET + 4]
+            .copy_from_slice(&status.to_le_bytes());
+        data
+    }
+
+    fn amm_data(state: AmmState) -> Vec<u8> {
+        let mut data = vec![0u8; 912];
+        data[AMM_FREEZE_TRADE_OFFSET] = state.freeze_trade;
+        for (offset, value) in [
+            (AMM_TRADE_FEE_NUMERATOR_OFFSET, state.trade_fee_numerator),
+            (
+                AMM_TRADE_FEE_DENOMINATOR_OFFSET,
+                state.trade_fee_denominator,
+            ),
+            (
+                AMM_OWNER_TRADE_FEE_NUMERATOR_OFFSET,
+                state.owner_trade_fee_numerator,
+            ),
+            (
+                AMM_OWNER_TRADE_FEE_DENOMINATOR_OFFSET,
+                state.owner_trade_fee_denominator,
+            ),
+            (AMM_CURVE_PARAMETERS_OFFSET, state.concentration),
+        ] {
+            data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    // 0.2% trade fee plus 0.05% to the owner
+    fn amm_state(concentration: u64) -> AmmState {
+        AmmState {
+            freeze_trade: 0,
+            trade_fee_numerator: 20,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10_000,
+            concentration,
+        }
+    }
+
+    /// Pool over mints of `decimals` base/quote decimals, with its oracle account data
+    pub(crate) fn create_pool_accounts(
+        (base_reserve, quote_reserve): (u64, u64),
+        decimals: (u8, u8),
+        state: AmmState,
+        oracle: Vec<u8>,
+    ) -> Vec<AccountInfo<'static>> {
+        let owner = system_program::id();
+        let (base_mint, quote_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let accounts = vec![
+            create_mock_account_info(LifinityV2::PROGRAM_ID, owner, None),
+            create_mock_account_info(Pubkey::new_unique(), owner, None),
+            create_mock_account_info(Pubkey::new_unique(), owner, Some(amm_data(state))),
+            create_mock_account_info(
+                Pubkey::new_unique(),
+                TOKEN_PROGRAM,
+                Some(token_account_data(base_mint, base_reserve)),
+            ),
+            create_mock_account_info(
+                Pubkey::new_unique(),
+                TOKEN_PROGRAM,
+                Some(token_account_data(quote_mint, quote_reserve)),
+            ),
+            create_mock_account_info(base_mint, TOKEN_PROGRAM, Some(mint_data(0, decimals.0))),
+            create_mock_account_info(quote_mint, TOKEN_PROGRAM, Some(mint_data(0, decimals.1))),
+            create_mock_account_info(Pubkey::new_unique(), TOKEN_PROGRAM, Some(mint_data(0, 6))),
+            create_mock_account_info(Pubkey::new_unique(), TOKEN_PROGRAM, None),
+            create_mock_account_info(Pubkey::new_unique(), owner, Some(oracle)),
+            create_mock_account_info(Pubkey::new_unique(), owner, None),
+            create_mock_account_info(Pubkey::new_unique(), owner, None),
+        ];
+        record_keys(&accounts);
+        accounts
+    }
+
+    // Writes the vaults, mints and oracles the amm records, as on-chain
+    fn record_keys(accounts: &[AccountInfo]) {
+        let mut amm_data = accounts[2].try_borrow_mut_data().unwrap();
+        for (offset, key) in [
+            (AMM_TOKEN_A_ACCOUNT_OFFSET, accounts[3].key),
+            (AMM_TOKEN_B_ACCOUNT_OFFSET, accounts[4].key),
+            (AMM_TOKEN_A_MINT_OFFSET, accounts[5].key),
+            (AMM_TOKEN_B_MINT_OFFSET, accounts[6].key),
+            (AMM_POOL_MINT_OFFSET, accounts[7].key),
+            (AMM_FEE_ACCOUNT_OFFSET, accounts[8].key),
+            (AMM_ORACLE_MAIN_ACCOUNT_OFFSET, accounts[9].key),
+            (AMM_ORACLE_SUB_ACCOUNT_OFFSET, accounts[10].key),
+            (AMM_ORACLE_PC_ACCOUNT_OFFSET, accounts[11].key),
+        ] {
+            amm_data[offset..offset + 32].copy_from_slice(key.as_ref());
+        }
+    }
+
+    /// USDC/USDT-like pool: 1M of each 6-decimal token, oracle at 1.0, concentration 10
+    pub(crate) fn create_test_accounts() -> Vec<AccountInfo<'static>> {
+        create_pool_accounts(
+            (1_000_000_000_000, 1_000_000_000_000),
+            (6, 6),
+            amm_state(10),
+            pyth_price_data(100_000_000, -8, PYTH_STATUS_TRADING),
+        )
+    }
+
+    #[test]
+    fn test_lifinity_new_insufficient_accounts() {
+        let accounts = create_test_accounts();
+        assert!(LifinityV2::new(&accounts[..11]).is_err());
+    }
+
+    #[test]
+    fn test_lifinity_new_sufficient_accounts() {
+        let accounts = create_test_accounts();
+        let lifinity = LifinityV2::new(&accounts).unwrap();
+
+        assert_eq!(*lifinity.program_id.key, LifinityV2::PROGRAM_ID);
+        assert_eq!(lifinity.pool_key(), accounts[2].key);
+        assert_eq!(lifinity.get_mints(), (accounts[5].key, accounts[6].key));
+        assert_eq!(lifinity.oracle_main_account.key, accounts[9].key);
+        assert_eq!(lifinity.amm_state().unwrap(), amm_state(10));
+    }
+
+    #[test]
+    fn test_oracle_raw_price_scales_by_decimals() {
+        // $150.00000000 SOL (9 decimals) in USDC (6 decimals): 0.15 USDC atoms per lamport
+        let oracle = OraclePrice::parse(&pyth_price_data(15_000_000_000, -8, 1)).unwrap();
+        assert_eq!(
+            oracle.raw_price(9, 6),
+            Some((15_000_000_000, 100_000_000_000))
+        );
+        assert_eq!(oracle.to_f64(9, 6), 0.15);
+        // A positive exponent scales the numerator instead
+        let oracle = OraclePrice {
+            price: 3,
+            exponent: 2,
+        };
+        assert_eq!(oracle.raw_price(6, 6), Some((300, 1)));
+    }
+
+    #[test]
+    fn test_oracle_rejects_non_trading_price() {
+        for data in [
+            pyth_price_data(100_000_000, -8, 0),
+            pyth_price_data(0, -8, PYTH_STATUS_TRADING),
+            vec![0u8; 3312],
+        ] {
+            assert_eq!(
+                OraclePrice::parse(&data).unwrap_err(),
+                error!(SolarBError::InvalidOracle)
+            );
+        }
+
+        let accounts = create_pool_accounts(
+            (1_000_000_000_000, 1_000_000_000_000),
+            (6, 6),
+            amm_state(10),
+            pyth_price_data(100_000_000, -8, 0),
+        );
+        let lifinity = LifinityV2::new(&accounts).unwrap();
+        assert!(!lifinity.is_tradable(&Clock::default()).unwrap());
+        assert_eq!(
+            lifinity
+                .swap_base_in(*accounts[5].key, 1_000_000, Clock::default())
+                .unwrap_err(),
+            error!(SolarBError::InvalidOracle)
+        );
+    }
+
+    #[test]
+    fn test_frozen_pool_is_not_tradable() {
+        let frozen = AmmState {
+            freeze_trade: 1,
+            ..amm_state(10)
+        };
+        let accounts = create_pool_accounts(
+            (1_000_000_000_000, 1_000_000_000_000),
+            (6, 6),
+            frozen,
+            pyth_price_data(100_000_000, -8, PYTH_STATUS_TRADING),
+        );
+        let lifinity = LifinityV2::new(&accounts).unwrap();
+        assert!(!lifinity.is_tradable(&Clock::default()).unwrap());
+    }
+
+    #[test]
+    fn test_concentration_tightens_output_versus_constant_product() {
+        let reserves = (1_000_000_000_000, 1_000_000_000_000);
+        let amount_in = 10_000_000_000;
+        let quote = |concentration: u64| {
+            let accounts = create_pool_accounts(
+                reserves,
+                (6, 6),
+                amm_state(concentration),
+                pyth_price_data(100_000_000, -8, PYTH_STATUS_TRADING),
+            );
+            let lifinity = LifinityV2::new(&accounts).unwrap();
+            lifinity
+                .swap_base_in(*accounts[5].key, amount_in, Clock::default())
+                .unwrap()
+        };
+
+        // Without concentration and the oracle at the reserve ratio, it is a plain xy=k pool
+        let after_fees = amm_state(1).amount_after_fees(amount_in).unwrap();
+        assert_eq!(after_fees, 9_975_000_000);
+        let vanilla = constant_product_out(after_fees, reserves.0 as u128, reserves.1 as u128, 0)
+            .unwrap() as u64;
+        assert_eq!(quote(1), vanilla);
+        assert_eq!(quote(0), vanilla);
+
+        // Concentrating the liquidity cuts the slippage, but never beats the oracle price
+        let (concentrated, tighter) = (quote(10), quote(100));
+        assert!(vanilla < concentrated && concentrated < tighter);
+        assert!(tighter < after_fees as u64);
+        // 1e13 * 9_975_000_000 / (1e13 + 9_975_000_000) on the 10x virtual reserves
+        assert_eq!(concentrated, 9_965_059_852);
+    }
+
+    #[test]
+    fn test_quote_follows_oracle_price() {
+        // SOL/USDC with the oracle at $150 but the reserves at $140
+        let accounts = create_pool_accounts(
+            (1_000_000_000_000, 140_000_000_000),
+            (9, 6),
+            amm_state(1),
+            pyth_price_data(15_000_000_000, -8, PYTH_STATUS_TRADING),
+        );
+        let lifinity = LifinityV2::new(&accounts).unwrap();
+        let (sol, usdc) = (*accounts[5].key, *accounts[6].key);
+
+        // Selling 1 SOL prices off the oracle's $150, less fees and slippage
+        let usdc_out = lifinity
+            .swap_base_in(sol, 1_000_000_000, Clock::default())
+            .unwrap();
+        assert!(usdc_out > 149_000_000 && usdc_out < 150_000_000);
+        // Buying SOL with $150 gets just under 1 SOL
+        let sol_out = lifinity
+            .swap_base_in(usdc, 150_000_000, Clock::default())
+            .unwrap();
+        assert!(sol_out > 990_000_000 && sol_out < 1_000_000_000);
+
+        let price = lifinity.compute_price_swap_base_in(0, 0).unwrap();
+        assert!((price - 0.15 * (1.0 - 0.0025)).abs() < 1e-12);
+    }
+
+    // Price source a test sets directly, in place of the oracle account
+    struct FixedPrice(Cell<u128>);
+
+    impl PriceSource for FixedPrice {
+        fn price(&self, _mint: &Pubkey) -> Result<u128> {
+            Ok(self.0.get())
+        }
+    }
+
+    #[test]
+    fn test_quote_follows_injected_price() {
+        let accounts = create_test_accounts();
+        let lifinity = LifinityV2::new(&accounts).unwrap();
+        let (base, quote) = (*accounts[5].key, *accounts[6].key);
+        let account_data = |accounts: &[AccountInfo]| -> Vec<Vec<u8>> {
+            accounts
+                .iter()
+                .map(|account| account.try_borrow_data().unwrap().to_vec())
+                .collect()
+        };
+        let before = account_data(&accounts);
+        let quote_with = |source: &FixedPrice, input_mint: Pubkey| {
+            lifinity
+                .quote_exact_in(input_mint, 1_000_000, Some(source))
+                .unwrap()
+        };
+
+        // The oracle account's own price, injected, quotes as the account does
+        let source = FixedPrice(Cell::new(lifinity.price(&base).unwrap()));
+        assert_eq!(source.0.get(), PRICE_SCALE);
+        for input_mint in [base, quote] {
+            assert_eq!(
+                quote_with(&source, input_mint),
+                lifinity
+                    .swap_base_in(input_mint, 1_000_000, Clock::default())
+                    .unwrap()
+            );
+        }
+        let (base_sold, quote_sold) = (quote_with(&source, base), quote_with(&source, quote));
+
+        // Doubling the base's price about doubles what a base token sells for, and halves
+        // what a quote token buys
+        source.0.set(2 * PRICE_SCALE);
+        let doubled = quote_with(&source, base);
+        assert!(
+            doubled.abs_diff(2 * base_sold) <= 2,
+            "{} vs {}",
+            doubled,
+            base_sold
+        );
+        let halved = quote_with(&source, quote);
+        assert!(
+            halved.abs_diff(quote_sold / 2) <= 1,
+            "{} vs {}",
+            halved,
+            quote_sold
+        );
+
+        assert_eq!(account_data(&accounts), before);
+    }
+
+    #[test]
+    fn test_price_source_prices_only_the_base_mint() {
+        let accounts = create_test_accounts();
+        let lifinity = LifinityV2::new(&accounts).unwrap();
+        assert_eq!(
+            lifinity.price(accounts[6].key).unwrap_err(),
+            error!(SolarBError::InvalidOracle)
+        );
+    }
+
+    #[test]
+    fn test_quote_rejects_output_beyond_reserve() {
+        // Deep concentration prices a swap the thin quote side can't pay out
+        let accounts = create_pool_accounts(
+            (1_000_000_000_000, 1_000_000),
+            (6, 6),
+            amm_state(10),
+            pyth_price_data(100_000_000, -8, PYTH_STATUS_TRADING),
+        );
+        let lifinity = LifinityV2::new(&accounts).unwrap();
+        assert_eq!(
+            lifinity
+                .swap_base_in(*accounts[5].key, 10_000_000, Clock::default())
+                .unwrap_err(),
+            error!(SolarBError::InsufficientLiquidity)
+        );
+    }
+
+    #[test]
+    fn test_validate_accounts() {
+        let accounts = create_test_accounts();
+        assert!(LifinityV2::new(&accounts)
+            .unwrap()
+            .validate_accounts()
+            .is_ok());
+
+        // Some other price account in the oracle slot
+        let mut foreign = accounts.clone();
+        foreign[9] = create_mock_account_info(
+            Pubkey::new_unique(),
+            system_program::id(),
+            Some(pyth_price_data(100_000_000, -8, PYTH_STATUS_TRADING)),
+        );
+        let err = LifinityV2::new(&foreign)
+            .unwrap()
+            .validate_accounts()
+            .unwrap_err();
+        assert_eq!(err, error!(SolarBError::PoolStateMismatch));
+    }
+
+    #[test]
+    fn test_build_swap_ix_follows_direction() {
+        let accounts = create_test_accounts();
+        let lifinity = LifinityV2::new(&accounts).unwrap();
+        let user = create_mock_accounts_for_swap(&accounts);
+        let payer = Pubkey::new_unique();
+
+        for direction in [EdgeSide::LeftToRight, EdgeSide::RightToLeft] {
+            let resolved = resolve_swap_accounts(
+                accounts[5].key,
+                user[0].clone(),
+                user[1].clone(),
+                user[2].clone(),
+                user[2].clone(),
+                user[3].clone(),
+                user[4].clone(),
+            )
+            .unwrap();
+            let ix = lifinity
+                .build_swap_ix(direction.clone(), 1_000, 990, &resolved.keys(&payer))
+                .unwrap();
+
+            assert_eq!(ix.program_id, LifinityV2::PROGRAM_ID);
+            assert_eq!(ix.accounts.len(), 13);
+            assert_eq!(ix.accounts[2], AccountMeta::new_readonly(payer, true));
+            let (source, destination, swap_source, swap_destination) = match direction {
+                EdgeSide::LeftToRight => {
+                    (user[3].key, user[4].key, accounts[3].key, accounts[4].key)
+                }
+                EdgeSide::RightToLeft => {
+                    (user[4].key, user[3].key, accounts[4].key, accounts[3].key)
+                }
+            };
+            let keys: Vec<&Pubkey> = ix.accounts[3..7].iter().map(|meta| &meta.pubkey).collect();
+            assert_eq!(keys, [source, destination, swap_source, swap_destination]);
+            assert_eq!(ix.accounts[10].pubkey, *accounts[9].key);
+
+            assert_eq!(ix.data[..8], LifinityV2::SWAP_DISCRIMINATOR);
+            assert_eq!(ix.data[8..16], 1_000u64.to_le_bytes());
+            assert_eq!(ix.data[16..], 990u64.to_le_bytes());
+        }
+    }
+
+    // User-side mints, token program and token accounts for the pool's two mints
+    fn create_mock_accounts_for_swap(pool: &[AccountInfo<'static>]) -> Vec<AccountInfo<'static>> {
+        let owner = system_program::id();
+        vec![
+            pool[5].clone(),
+            pool[6].clone(),
+            create_mock_account_info(TOKEN_PROGRAM, owner, None),
+            create_mock_account_info(Pubkey::new_unique(), TOKEN_PROGRAM, None),
+            create_mock_account_info(Pubkey::new_unique(), TOKEN_PROGRAM, None),
+        ]
+    }
+}
@@ -1,20 +1,199 @@
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::{account_info::next_account_info, pubkey::Pubkey};
-use anchor_spl::token_interface::TokenAccount;
+use anchor_lang::solana_program::{
+    account_info::next_account_info,
+    instruction::{AccountMeta, Instruction},
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
 
+use crate::arbitrage::base::EdgeSide;
+use crate::programs::programs::{
+    check_recorded_keys, check_vault_mints, marginal_price, quote_exact_out_by_search,
+    read_mint_decimals,
+};
 use crate::programs::ProgramMeta;
+use crate::utils::utils::{
+    parse_mint, parse_token_account, resolve_swap_accounts, ResolvedAccounts, SwapUserAccounts,
+};
 
+/// Denominator used by the dynamic vault to release locked profit over time
+pub const LOCKED_PROFIT_DEGRADATION_DENOMINATOR: u128 = 1_000_000_000_000;
+
+// Borsh offsets (including the 8-byte discriminator) into the dynamic AMM pool account
+const POOL_TOKEN_A_MINT_OFFSET: usize = 40;
+const POOL_TOKEN_B_MINT_OFFSET: usize = 72;
+const POOL_A_VAULT_OFFSET: usize = 104;
+const POOL_B_VAULT_OFFSET: usize = 136;
+const POOL_ENABLED_OFFSET: usize = 233;
+const POOL_TRADE_FEE_NUMERATOR_OFFSET: usize = 330;
+const POOL_TRADE_FEE_DENOMINATOR_OFFSET: usize = 338;
+const POOL_PROTOCOL_FEE_NUMERATOR_OFFSET: usize = 346;
+const POOL_PROTOCOL_FEE_DENOMINATOR_OFFSET: usize = 354;
+
+// Borsh offsets (including the 8-byte discriminator) into the dynamic vault account
+const VAULT_TOTAL_AMOUNT_OFFSET: usize = 11;
+const VAULT_TOKEN_VAULT_OFFSET: usize = 19;
+const VAULT_LAST_UPDATED_LOCKED_PROFIT_OFFSET: usize = 1203;
+const VAULT_LAST_REPORT_OFFSET: usize = 1211;
+const VAULT_LOCKED_PROFIT_DEGRADATION_OFFSET: usize = 1219;
+
+/// Anchor discriminator of the dynamic AMM `swap` instruction
+const SWAP_DISCRIMINATOR: [u8; 8] = [0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x75, 0x87, 0xc8];
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    let bytes = data
+        .get(offset..offset + 8)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    let bytes = data
+        .get(offset..offset + 32)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    Ok(Pubkey::new_from_array(bytes.try_into().unwrap()))
+}
+
+/// Trading fee configuration stored on the pool
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PoolFees {
+    pub trade_fee_numerator: u64,
+    pub trade_fee_denominator: u64,
+    pub protocol_trade_fee_numerator: u64,
+    pub protocol_trade_fee_denominator: u64,
+}
+
+impl PoolFees {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Ok(PoolFees {
+            trade_fee_numerator: read_u64(data, POOL_TRADE_FEE_NUMERATOR_OFFSET)?,
+            trade_fee_denominator: read_u64(data, POOL_TRADE_FEE_DENOMINATOR_OFFSET)?,
+            protocol_trade_fee_numerator: read_u64(data, POOL_PROTOCOL_FEE_NUMERATOR_OFFSET)?,
+            protocol_trade_fee_denominator: read_u64(data, POOL_PROTOCOL_FEE_DENOMINATOR_OFFSET)?,
+        })
+    }
+
+    /// Share of the input taken as the trade fee; the protocol fee is a cut of it
+    pub fn trade_fee_rate(&self) -> f64 {
+        if self.trade_fee_denominator == 0 {
+            return 0.0;
+        }
+        self.trade_fee_numerator as f64 / self.trade_fee_denominator as f64
+    }
+
+    pub fn trading_fee(&self, amount: u128) -> Option<u128> {
+        calculate_fee(amount, self.trade_fee_numerator, self.trade_fee_denominator)
+    }
+
+    pub fn protocol_trading_fee(&self, trading_fee: u128) -> Option<u128> {
+        calculate_fee(
+            trading_fee,
+            self.protocol_trade_fee_numerator,
+            self.protocol_trade_fee_denominator,
+        )
+    }
+}
+
+/// Fee rounding used by the pool: any non-zero fee on a non-zero amount is at least 1
+fn calculate_fee(amount: u128, numerator: u64, denominator: u64) -> Option<u128> {
+    if numerator == 0 || amount == 0 {
+        return Some(0);
+    }
+    let fee = amount
+        .checked_mul(numerator as u128)?
+        .checked_div(denominator as u128)?;
+    if fee == 0 {
+        Some(1)
+    } else {
+        Some(fee)
+    }
+}
+
+/// The part of a dynamic vault needed to convert LP shares to underlying tokens
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VaultState {
+    pub total_amount: u64,
+    pub last_updated_locked_profit: u64,
+    pub last_report: u64,
+    pub locked_profit_degradation: u64,
+}
+
+impl VaultState {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Ok(VaultState {
+            total_amount: read_u64(data, VAULT_TOTAL_AMOUNT_OFFSET)?,
+            last_updated_locked_profit: read_u64(data, VAULT_LAST_UPDATED_LOCKED_PROFIT_OFFSET)?,
+            last_report: read_u64(data, VAULT_LAST_REPORT_OFFSET)?,
+            locked_profit_degradation: read_u64(data, VAULT_LOCKED_PROFIT_DEGRADATION_OFFSET)?,
+        })
+    }
+
+    /// Profit from the last strategy report that has not been released yet
+    pub fn locked_profit(&self, current_time: u64) -> Option<u64> {
+        let duration = current_time.checked_sub(self.last_report)? as u128;
+        let locked_fund_ratio = duration.checked_mul(self.locked_profit_degradation as u128)?;
+        if locked_fund_ratio > LOCKED_PROFIT_DEGRADATION_DENOMINATOR {
+            return Some(0);
+        }
+        let locked_profit = (self.last_updated_locked_profit as u128)
+            .checked_mul(LOCKED_PROFIT_DEGRADATION_DENOMINATOR - locked_fund_ratio)?
+            .checked_div(LOCKED_PROFIT_DEGRADATION_DENOMINATOR)?;
+        u64::try_from(locked_profit).ok()
+    }
+
+    pub fn unlocked_amount(&self, current_time: u64) -> Option<u64> {
+        self.total_amount
+            .checked_sub(self.locked_profit(current_time)?)
+    }
+
+    /// Underlying tokens owned by `share` LP tokens out of `total_supply`
+    pub fn amount_by_share(&self, current_time: u64, share: u64, total_supply: u64) -> Option<u64> {
+        if total_supply == 0 {
+            return Some(0);
+        }
+        let amount = (share as u128)
+            .checked_mul(self.unlocked_amount(current_time)? as u128)?
+            .checked_div(total_supply as u128)?;
+        u64::try_from(amount).ok()
+    }
+
+    /// LP tokens minted (or burned) for `amount` underlying tokens
+    pub fn unmint_amount(&self, current_time: u64, amount: u64, total_supply: u64) -> Option<u64> {
+        let unlocked = self.unlocked_amount(current_time)?;
+        if unlocked == 0 {
+            return None;
+        }
+        let share = (amount as u128)
+            .checked_mul(total_supply as u128)?
+            .checked_div(unlocked as u128)?;
+        u64::try_from(share).ok()
+    }
+}
+
+/// Meteora dynamic AMM (v1) pool.
+///
+/// Pool reserves are not held directly in token accounts: the pool owns LP tokens of two
+/// dynamic vaults (`a_vault_lp`/`b_vault_lp`), and the vaults hold the underlying tokens
+/// in `a_token_vault`/`b_token_vault` shared with every other depositor. Quoting converts
+/// the pool's vault LP balance back to underlying tokens. Only the constant-product curve
+/// is handled.
 pub struct MeteoraDammV1<'info> {
+    pub program_id: AccountInfo<'info>,
     pub pool_id: AccountInfo<'info>,
-    pub base_vault: AccountInfo<'info>,
-    pub quote_vault: AccountInfo<'info>,
+    pub a_vault: AccountInfo<'info>,
+    pub b_vault: AccountInfo<'info>,
+    pub a_token_vault: AccountInfo<'info>,
+    pub b_token_vault: AccountInfo<'info>,
     pub base_token: AccountInfo<'info>,
     pub quote_token: AccountInfo<'info>,
-    pub oracle: AccountInfo<'info>,
-    pub host_fee_in: AccountInfo<'info>,
-    pub bitmap_extension: AccountInfo<'info>,
-    pub memo: AccountInfo<'info>,
-    pub event_authority: AccountInfo<'info>,
+    pub a_vault_lp_mint: AccountInfo<'info>,
+    pub b_vault_lp_mint: AccountInfo<'info>,
+    pub a_vault_lp: AccountInfo<'info>,
+    pub b_vault_lp: AccountInfo<'info>,
+    pub protocol_token_a_fee: AccountInfo<'info>,
+    pub protocol_token_b_fee: AccountInfo<'info>,
+    pub vault_program: AccountInfo<'info>,
 }
 
 impl<'info> ProgramMeta for MeteoraDammV1<'info> {
@@ -22,21 +201,55 @@ impl<'info> ProgramMeta for MeteoraDammV1<'info> {
         &Self::PROGRAM_ID
     }
 
+    fn program_label(&self) -> &'static str {
+        "MeteoraDammV1"
+    }
+
+    fn pool_key(&self) -> &Pubkey {
+        self.pool_id.key
+    }
+
+    /// The vaults' token accounts are shared with other depositors, so their balances are
+    /// only used for the mints; reserves and quotes go through the vault LP.
     fn get_vaults(&self) -> (&AccountInfo<'_>, &AccountInfo<'_>) {
         unsafe {
             (
-                &*(&self.base_vault as *const AccountInfo<'info> as *const AccountInfo<'_>),
-                &*(&self.quote_vault as *const AccountInfo<'info> as *const AccountInfo<'_>),
+                &*(&self.a_token_vault as *const AccountInfo<'info> as *const AccountInfo<'_>),
+                &*(&self.b_token_vault as *const AccountInfo<'info> as *const AccountInfo<'_>),
             )
         }
     }
 
+    fn get_mints(&self) -> (&Pubkey, &Pubkey) {
+        (self.base_token.key, self.quote_token.key)
+    }
+
+    fn mint_decimals(&self) -> (u8, u8) {
+        read_mint_decimals(&self.base_token, &self.quote_token)
+    }
+
+    /// The pool's LP share of each vault, not the vault balances other pools also hold
+    fn reserves(&self, clock: &Clock) -> Result<(u128, u128)> {
+        let (token_a_amount, token_b_amount) = self.pool_reserves(clock.unix_timestamp as u64)?;
+        Ok((token_a_amount as u128, token_b_amount as u128))
+    }
+
+    fn compute_price_swap_base_in(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        let fee_rate = PoolFees::parse(&self.pool_id.try_borrow_data()?)?.trade_fee_rate();
+        Ok(marginal_price(base_amount, quote_amount, fee_rate))
+    }
+
+    fn compute_price_swap_base_out(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        let fee_rate = PoolFees::parse(&self.pool_id.try_borrow_data()?)?.trade_fee_rate();
+        Ok(marginal_price(quote_amount, base_amount, fee_rate))
+    }
+
     fn swap_base_in(&self, input_mint: Pubkey, amount_in: u64, clock: Clock) -> Result<u64> {
         self.swap_base_in_impl(input_mint, amount_in, clock)
     }
 
-    fn swap_base_out(&self, input_mint: Pubkey, amount_in: u64, clock: Clock) -> Result<u64> {
-        self.swap_base_out_impl(input_mint, amount_in, clock)
+    fn swap_base_out(&self, input_mint: Pubkey, amount_out: u64, clock: Clock) -> Result<u64> {
+        self.swap_base_out_impl(input_mint, amount_out, clock)
     }
 
     fn invoke_swap_base_in<'a>(
@@ -44,7 +257,7 @@ impl<'info> ProgramMeta for MeteoraDammV1<'info> {
         input_mint: Pubkey,
         max_amount_in: u64,
         amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -56,7 +269,7 @@ impl<'info> ProgramMeta for MeteoraDammV1<'info> {
             input_mint,
             max_amount_in,
             amount_out,
-            payer,
+            authority,
             user_mint_1_token_account,
             user_mint_2_token_account,
             mint_1_account,
@@ -71,7 +284,7 @@ impl<'info> ProgramMeta for MeteoraDammV1<'info> {
         input_mint: Pubkey,
         amount_in: u64,
         min_amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -83,7 +296,7 @@ impl<'info> ProgramMeta for MeteoraDammV1<'info> {
             input_mint,
             amount_in,
             min_amount_out,
-            payer,
+            authority,
             user_mint_1_token_account,
             user_mint_2_token_account,
             mint_1_account,
@@ -93,19 +306,128 @@ impl<'info> ProgramMeta for MeteoraDammV1<'info> {
         )
     }
 
+    /// `swap` is exact-in both ways; the protocol fee goes to the input side's fee account
+    fn build_swap_ix(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        user_accounts: &SwapUserAccounts,
+    ) -> Result<Instruction> {
+        let (user_source, user_destination, protocol_token_fee, token_program) = match direction {
+            EdgeSide::LeftToRight => (
+                user_accounts.user_base_token_account,
+                user_accounts.user_quote_token_account,
+                self.protocol_token_a_fee.key,
+                user_accounts.base_token_program,
+            ),
+            EdgeSide::RightToLeft => (
+                user_accounts.user_quote_token_account,
+                user_accounts.user_base_token_account,
+                self.protocol_token_b_fee.key,
+                user_accounts.quote_token_program,
+            ),
+        };
+
+        let mut data = SWAP_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&min_out.to_le_bytes());
+
+        Ok(Instruction {
+            program_id: Self::PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*self.pool_id.key, false),
+                AccountMeta::new(user_source, false),
+                AccountMeta::new(user_destination, false),
+                AccountMeta::new(*self.a_vault.key, false),
+                AccountMeta::new(*self.b_vault.key, false),
+                AccountMeta::new(*self.a_token_vault.key, false),
+                AccountMeta::new(*self.b_token_vault.key, false),
+                AccountMeta::new(*self.a_vault_lp_mint.key, false),
+                AccountMeta::new(*self.b_vault_lp_mint.key, false),
+                AccountMeta::new(*self.a_vault_lp.key, false),
+                AccountMeta::new(*self.b_vault_lp.key, false),
+                AccountMeta::new(*protocol_token_fee, false),
+                AccountMeta::new_readonly(user_accounts.authority, true),
+                AccountMeta::new_readonly(*self.vault_program.key, false),
+                AccountMeta::new_readonly(token_program, false),
+            ],
+            data,
+        })
+    }
+
+    fn estimate_compute_units(&self) -> u32 {
+        Self::SWAP_COMPUTE_UNITS
+    }
+
+    fn is_tradable(&self, _clock: &Clock) -> Result<bool> {
+        let pool_data = self.pool_id.try_borrow_data()?;
+        let enabled = pool_data
+            .get(POOL_ENABLED_OFFSET)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        Ok(*enabled != 0)
+    }
+
+    fn validate_accounts(&self) -> Result<()> {
+        check_vault_mints(self)?;
+        let pool_data = self.pool_id.try_borrow_data()?;
+        let a_vault_data = self.a_vault.try_borrow_data()?;
+        let b_vault_data = self.b_vault.try_borrow_data()?;
+        // The pool records the dynamic vaults, which in turn record their token accounts
+        check_recorded_keys(
+            self.pool_id.key,
+            &[
+                (
+                    "token_a_mint",
+                    read_pubkey(&pool_data, POOL_TOKEN_A_MINT_OFFSET)?,
+                    self.base_token.key,
+                ),
+                (
+                    "token_b_mint",
+                    read_pubkey(&pool_data, POOL_TOKEN_B_MINT_OFFSET)?,
+                    self.quote_token.key,
+                ),
+                (
+                    "a_vault",
+                    read_pubkey(&pool_data, POOL_A_VAULT_OFFSET)?,
+                    self.a_vault.key,
+                ),
+                (
+                    "b_vault",
+                    read_pubkey(&pool_data, POOL_B_VAULT_OFFSET)?,
+                    self.b_vault.key,
+                ),
+                (
+                    "a_vault token_vault",
+                    read_pubkey(&a_vault_data, VAULT_TOKEN_VAULT_OFFSET)?,
+                    self.a_token_vault.key,
+                ),
+                (
+                    "b_vault token_vault",
+                    read_pubkey(&b_vault_data, VAULT_TOKEN_VAULT_OFFSET)?,
+                    self.b_token_vault.key,
+                ),
+            ],
+        )
+    }
+
     fn log_accounts(&self) -> Result<()> {
         msg!(
-            "Meteora DAMM v1 accounts: pool={}, base_vault={}, quote_vault={}, base_token={}, quote_token={}, oracle={}, host_fee_in={}, bitmap_extension={}, memo={}, event_authority={}",
+            "Meteora DAMM v1 accounts: pool={}, a_vault={}, b_vault={}, a_token_vault={}, b_token_vault={}, base_token={}, quote_token={}, a_vault_lp_mint={}, b_vault_lp_mint={}, a_vault_lp={}, b_vault_lp={}, protocol_token_a_fee={}, protocol_token_b_fee={}, vault_program={}",
             self.pool_id.key,
-            self.base_vault.key,
-            self.quote_vault.key,
+            self.a_vault.key,
+            self.b_vault.key,
+            self.a_token_vault.key,
+            self.b_token_vault.key,
             self.base_token.key,
             self.quote_token.key,
-            self.oracle.key,
-            self.host_fee_in.key,
-            self.bitmap_extension.key,
-            self.memo.key,
-            self.event_authority.key,
+            self.a_vault_lp_mint.key,
+            self.b_vault_lp_mint.key,
+            self.a_vault_lp.key,
+            self.b_vault_lp.key,
+            self.protocol_token_a_fee.key,
+            self.protocol_token_b_fee.key,
+            self.vault_program.key,
         );
         Ok(())
     }
@@ -113,81 +435,747 @@ impl<'info> ProgramMeta for MeteoraDammV1<'info> {
 
 impl<'info> MeteoraDammV1<'info> {
     pub const PROGRAM_ID: Pubkey =
-        Pubkey::from_str_const("dbcij3LWUppWqq96dh6gJWwBifmcGfLSB5D4DuSMaqN");
+        Pubkey::from_str_const("Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB");
+    /// Shortest span `find_program_instance` accepts, program id included
+    pub const EXPECTED_ACCOUNTS: usize = 15;
+    pub const VAULT_PROGRAM_ID: Pubkey =
+        Pubkey::from_str_const("24Uqj9JCLxUeoC3hGfh5W3s9FM9uCHDS2SG3LYwBpyTi");
+    /// Typical compute units of one swap CPI, including the deposit and withdraw CPIs into
+    /// both dynamic vaults
+    pub const SWAP_COMPUTE_UNITS: u32 = 100_000;
+
     pub fn new(accounts: &[AccountInfo<'info>]) -> Result<Self> {
         let mut iter = accounts.iter();
-        let pool_id = next_account_info(&mut iter)?;
-        let base_vault = next_account_info(&mut iter)?;
-        let quote_vault = next_account_info(&mut iter)?;
-        let base_token = next_account_info(&mut iter)?;
-        let quote_token = next_account_info(&mut iter)?;
-        let oracle = next_account_info(&mut iter)?;
-        let host_fee_in = next_account_info(&mut iter)?;
-        let bitmap_extension = next_account_info(&mut iter)?;
-        let memo = next_account_info(&mut iter)?;
-        let event_authority = next_account_info(&mut iter)?;
+        let program_id = next_account_info(&mut iter)?; // 0
+        let pool_id = next_account_info(&mut iter)?; // 1
+        let a_vault = next_account_info(&mut iter)?; // 2
+        let b_vault = next_account_info(&mut iter)?; // 3
+        let a_token_vault = next_account_info(&mut iter)?; // 4
+        let b_token_vault = next_account_info(&mut iter)?; // 5
+        let base_token = next_account_info(&mut iter)?; // 6
+        let quote_token = next_account_info(&mut iter)?; // 7
+        let a_vault_lp_mint = next_account_info(&mut iter)?; // 8
+        let b_vault_lp_mint = next_account_info(&mut iter)?; // 9
+        let a_vault_lp = next_account_info(&mut iter)?; // 10
+        let b_vault_lp = next_account_info(&mut iter)?; // 11
+        let protocol_token_a_fee = next_account_info(&mut iter)?; // 12
+        let protocol_token_b_fee = next_account_info(&mut iter)?; // 13
+        let vault_program = next_account_info(&mut iter)?; // 14
 
         Ok(MeteoraDammV1 {
+            program_id: program_id.clone(),
             pool_id: pool_id.clone(),
-            base_vault: base_vault.clone(),
-            quote_vault: quote_vault.clone(),
+            a_vault: a_vault.clone(),
+            b_vault: b_vault.clone(),
+            a_token_vault: a_token_vault.clone(),
+            b_token_vault: b_token_vault.clone(),
             base_token: base_token.clone(),
             quote_token: quote_token.clone(),
-            oracle: oracle.clone(),
-            host_fee_in: host_fee_in.clone(),
-            bitmap_extension: bitmap_extension.clone(),
-            memo: memo.clone(),
-            event_authority: event_authority.clone(),
+            a_vault_lp_mint: a_vault_lp_mint.clone(),
+            b_vault_lp_mint: b_vault_lp_mint.clone(),
+            a_vault_lp: a_vault_lp.clone(),
+            b_vault_lp: b_vault_lp.clone(),
+            protocol_token_a_fee: protocol_token_a_fee.clone(),
+            protocol_token_b_fee: protocol_token_b_fee.clone(),
+            vault_program: vault_program.clone(),
         })
     }
 
+    /// Load the vault state, the pool's LP balance in that vault and the vault LP supply
+    fn load_vault_side(
+        vault: &AccountInfo<'info>,
+        vault_lp: &AccountInfo<'info>,
+        vault_lp_mint: &AccountInfo<'info>,
+    ) -> Result<(VaultState, u64, u64)> {
+        let vault_state = VaultState::parse(&vault.try_borrow_data()?)?;
+        let pool_lp_amount = parse_token_account(vault_lp)?.amount;
+        let lp_supply = parse_mint(vault_lp_mint)?.supply;
+        Ok((vault_state, pool_lp_amount, lp_supply))
+    }
+
+    /// Underlying (token_a, token_b) reserves owned by the pool at `current_time`
+    pub fn pool_reserves(&self, current_time: u64) -> Result<(u64, u64)> {
+        let (a_vault, a_pool_lp, a_lp_supply) =
+            Self::load_vault_side(&self.a_vault, &self.a_vault_lp, &self.a_vault_lp_mint)?;
+        let (b_vault, b_pool_lp, b_lp_supply) =
+            Self::load_vault_side(&self.b_vault, &self.b_vault_lp, &self.b_vault_lp_mint)?;
+        let token_a_amount = a_vault
+            .amount_by_share(current_time, a_pool_lp, a_lp_supply)
+            .ok_or(ProgramError::InvalidArgument)?;
+        let token_b_amount = b_vault
+            .amount_by_share(current_time, b_pool_lp, b_lp_supply)
+            .ok_or(ProgramError::InvalidArgument)?;
+        Ok((token_a_amount, token_b_amount))
+    }
+
+    /// Exact-in quote following the on-chain swap: the protocol fee is taken first, the
+    /// rest is deposited into the input vault, the trade fee is removed from what the
+    /// deposit is worth, and the curve output is rounded through the output vault's LP.
+    pub fn quote_exact_in(&self, input_mint: Pubkey, amount_in: u64, clock: &Clock) -> Result<u64> {
+        let current_time = clock.unix_timestamp as u64;
+        let fees = PoolFees::parse(&self.pool_id.try_borrow_data()?)?;
+
+        let (a_vault, a_pool_lp, a_lp_supply) =
+            Self::load_vault_side(&self.a_vault, &self.a_vault_lp, &self.a_vault_lp_mint)?;
+        let (b_vault, b_pool_lp, b_lp_supply) =
+            Self::load_vault_side(&self.b_vault, &self.b_vault_lp, &self.b_vault_lp_mint)?;
+
+        let a_to_b = input_mint == *self.base_token.key;
+        let (mut in_vault, in_pool_lp, in_lp_supply, out_vault, out_pool_lp, out_lp_supply) =
+            if a_to_b {
+                (
+                    a_vault,
+                    a_pool_lp,
+                    a_lp_supply,
+                    b_vault,
+                    b_pool_lp,
+                    b_lp_supply,
+                )
+            } else {
+                (
+                    b_vault,
+                    b_pool_lp,
+                    b_lp_supply,
+                    a_vault,
+                    a_pool_lp,
+                    a_lp_supply,
+                )
+            };
+
+        let in_reserve = in_vault
+            .amount_by_share(current_time, in_pool_lp, in_lp_supply)
+            .ok_or(ProgramError::InvalidArgument)?;
+        let out_reserve = out_vault
+            .amount_by_share(current_time, out_pool_lp, out_lp_supply)
+            .ok_or(ProgramError::InvalidArgument)?;
+
+        let trade_fee = fees
+            .trading_fee(amount_in as u128)
+            .ok_or(ProgramError::InvalidArgument)?;
+        let protocol_fee = fees
+            .protocol_trading_fee(trade_fee)
+            .ok_or(ProgramError::InvalidArgument)?;
+        let trade_fee = trade_fee
+            .checked_sub(protocol_fee)
+            .ok_or(ProgramError::InvalidArgument)?;
+        let in_amount_after_protocol_fee = (amount_in as u128)
+            .checked_sub(protocol_fee)
+            .ok_or(ProgramError::InvalidArgument)?
+            as u64;
+
+        // Deposit into the input vault and measure what the pool's share is worth afterwards
+        let in_lp = in_vault
+            .unmint_amount(current_time, in_amount_after_protocol_fee, in_lp_supply)
+            .ok_or(ProgramError::InvalidArgument)?;
+        in_vault.total_amount = in_vault
+            .total_amount
+            .checked_add(in_amount_after_protocol_fee)
+            .ok_or(ProgramError::InvalidArgument)?;
+        let after_in_reserve = in_vault
+            .amount_by_share(
+                current_time,
+                in_pool_lp
+                    .checked_add(in_lp)
+                    .ok_or(ProgramError::InvalidArgument)?,
+                in_lp_supply
+                    .checked_add(in_lp)
+                    .ok_or(ProgramError::InvalidArgument)?,
+            )
+            .ok_or(ProgramError::InvalidArgument)?;
+        let actual_in_amount = after_in_reserve
+            .checked_sub(in_reserve)
+            .ok_or(ProgramError::InvalidArgument)? as u128;
+        let actual_in_amount_after_fee = actual_in_amount
+            .checked_sub(trade_fee)
+            .ok_or(ProgramError::InvalidArgument)?;
+
+        // Constant product: out = out_reserve * in / (in_reserve + in)
+        let destination_amount = (out_reserve as u128)
+            .checked_mul(actual_in_amount_after_fee)
+            .and_then(|x| {
+                x.checked_div((in_reserve as u128).checked_add(actual_in_amount_after_fee)?)
+            })
+            .ok_or(ProgramError::InvalidArgument)? as u64;
+
+        // Withdrawals burn whole LP tokens, so the output is rounded down through the vault
+        let out_lp = out_vault
+            .unmint_amount(current_time, destination_amount, out_lp_supply)
+            .ok_or(ProgramError::InvalidArgument)?;
+        let amount_out = out_vault
+            .amount_by_share(current_time, out_lp, out_lp_supply)
+            .ok_or(ProgramError::InvalidArgument)?;
+        Ok(amount_out)
+    }
+
     pub fn swap_base_in_impl(
         &self,
-        _input_mint: Pubkey,
-        _amount_in: u64,
-        _clock: Clock,
+        input_mint: Pubkey,
+        amount_in: u64,
+        clock: Clock,
     ) -> Result<u64> {
-        Ok(0)
+        self.quote_exact_in(input_mint, amount_in, &clock)
     }
 
+    /// Exact-out quote, searched through the exact-in one: the vault and LP rounding have no
+    /// closed-form inverse
     pub fn swap_base_out_impl(
         &self,
-        _input_mint: Pubkey,
-        _amount_in: u64,
-        _clock: Clock,
+        input_mint: Pubkey,
+        amount_out: u64,
+        clock: Clock,
     ) -> Result<u64> {
-        Ok(0)
+        quote_exact_out_by_search(amount_out, |amount_in| {
+            self.quote_exact_in(input_mint, amount_in, &clock)
+        })
     }
 
     pub fn invoke_swap_base_in_impl<'a>(
         &self,
         _input_mint: Pubkey,
-        _max_amount_in: u64,
-        _amount_out: Option<u64>,
-        _payer: AccountInfo<'a>,
-        _user_mint_1_token_account: AccountInfo<'a>,
-        _user_mint_2_token_account: AccountInfo<'a>,
-        _mint_1_account: AccountInfo<'a>,
-        _mint_2_account: AccountInfo<'a>,
-        _mint_1_token_program: AccountInfo<'a>,
-        _mint_2_token_program: AccountInfo<'a>,
+        max_amount_in: u64,
+        amount_out: Option<u64>,
+        authority: AccountInfo<'a>,
+        user_mint_1_token_account: AccountInfo<'a>,
+        user_mint_2_token_account: AccountInfo<'a>,
+        mint_1_account: AccountInfo<'a>,
+        mint_2_account: AccountInfo<'a>,
+        mint_1_token_program: AccountInfo<'a>,
+        mint_2_token_program: AccountInfo<'a>,
     ) -> Result<()> {
-        Ok(())
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            EdgeSide::RightToLeft,
+            max_amount_in,
+            amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
     }
 
     pub fn invoke_swap_base_out_impl<'a>(
         &self,
         _input_mint: Pubkey,
-        _amount_in: u64,
-        _min_amount_out: Option<u64>,
-        _payer: AccountInfo<'a>,
-        _user_mint_1_token_account: AccountInfo<'a>,
-        _user_mint_2_token_account: AccountInfo<'a>,
-        _mint_1_account: AccountInfo<'a>,
-        _mint_2_account: AccountInfo<'a>,
-        _mint_1_token_program: AccountInfo<'a>,
-        _mint_2_token_program: AccountInfo<'a>,
+        amount_in: u64,
+        min_amount_out: Option<u64>,
+        authority: AccountInfo<'a>,
+        user_mint_1_token_account: AccountInfo<'a>,
+        user_mint_2_token_account: AccountInfo<'a>,
+        mint_1_account: AccountInfo<'a>,
+        mint_2_account: AccountInfo<'a>,
+        mint_1_token_program: AccountInfo<'a>,
+        mint_2_token_program: AccountInfo<'a>,
+    ) -> Result<()> {
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            EdgeSide::LeftToRight,
+            amount_in,
+            min_amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
+    }
+
+    fn invoke_swap<'a>(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        authority: AccountInfo<'a>,
+        accounts: ResolvedAccounts<'a>,
     ) -> Result<()> {
+        let swap_ix =
+            self.build_swap_ix(direction, amount_in, min_out, &accounts.keys(authority.key))?;
+
+        // Same lifetime cast as the other adapters: 'a outlives 'info when called from
+        // execute_arbitrage_path
+        let mut accounts_vec: Vec<AccountInfo<'info>> = vec![
+            self.program_id.clone(),
+            self.pool_id.clone(),
+            self.a_vault.clone(),
+            self.b_vault.clone(),
+            self.a_token_vault.clone(),
+            self.b_token_vault.clone(),
+            self.a_vault_lp_mint.clone(),
+            self.b_vault_lp_mint.clone(),
+            self.a_vault_lp.clone(),
+            self.b_vault_lp.clone(),
+            self.protocol_token_a_fee.clone(),
+            self.protocol_token_b_fee.clone(),
+            self.vault_program.clone(),
+        ];
+        for account in [
+            authority,
+            accounts.user_base_token_account,
+            accounts.user_quote_token_account,
+            accounts.base_token_program,
+            accounts.quote_token_program,
+        ] {
+            accounts_vec.push(unsafe {
+                std::mem::transmute::<AccountInfo<'a>, AccountInfo<'info>>(account)
+            });
+        }
+
+        unsafe {
+            let accounts: &[AccountInfo<'a>] = std::mem::transmute(accounts_vec.as_slice());
+            invoke(&swap_ix, accounts)?;
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::programs::test_utils::{mint_data, token_account_data};
+    use crate::programs::SolarBError;
+    use anchor_lang::solana_program::{account_info::AccountInfo, pubkey::Pubkey, system_program};
+
+    const TOKEN_PROGRAM: Pubkey =
+        Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+    const SOL_MINT: Pubkey = Pubkey::from_str_const("So11111111111111111111111111111111111111112");
+    const USDC_MINT: Pubkey =
+        Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+    // Quote time of the tests, 250s after the SOL vault's last strategy report
+    const CURRENT_TIME: i64 = 1_700_000_250;
+
+    // Helper function to create a mock AccountInfo
+    fn create_mock_account_info(
+        key: Pubkey,
+        owner: Pubkey,
+        account_data: Option<Vec<u8>>,
+    ) -> AccountInfo<'static> {
+        let data = if let Some(provided_data) = account_data {
+            Box::leak(Box::new(provided_data))
+        } else {
+            Box::leak(Box::new(Vec::new()))
+        };
+        let lamports = Box::leak(Box::new(0u64));
+        let owner_static = Box::leak(Box::new(owner));
+        let key_static = Box::leak(Box::new(key));
+
+        AccountInfo::new(
+            key_static,
+            false,
+            false,
+            lamports,
+            data,
+            owner_static,
+            false,
+            0,
+        )
+    }
+
+    // Dynamic vault `Vault` packed field by field in upstream order, rather than through the
+    // offsets the parser reads
+    fn vault_data(
+        state: VaultState,
+        token_vault: Pubkey,
+        token_mint: Pubkey,
+        lp_mint: Pubkey,
+    ) -> Vec<u8> {
+        // Discriminator, enabled and the vault and token vault bumps
+        let mut data = vec![0u8; 8];
+        data.extend_from_slice(&[1, 0, 0]);
+        data.extend_from_slice(&state.total_amount.to_le_bytes());
+        // Token vault, fee vault, token mint, LP mint, 30 strategies, base, admin and operator
+        let mut keys = vec![token_vault, Pubkey::new_unique(), token_mint, lp_mint];
+        keys.extend([Pubkey::default(); 30]);
+        keys.extend([
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ]);
+        for key in keys {
+            data.extend_from_slice(key.as_ref());
+        }
+        // Locked profit tracker
+        for value in [
+            state.last_updated_locked_profit,
+            state.last_report,
+            state.locked_profit_degradation,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    // Dynamic AMM `Pool` packed field by field in upstream order up to its fees, with the
+    // rest (pool type, stake, bootstrapping, partner info, padding and curve type) zeroed.
+    // `keys` are the two mints, the two dynamic vaults and the pool's LP accounts in them.
+    fn pool_data(fees: PoolFees, keys: [Pubkey; 6]) -> Vec<u8> {
+        let [mint_a, mint_b, a_vault, b_vault, a_vault_lp, b_vault_lp] = keys;
+        let mut data = vec![0u8; 8];
+        // LP mint, the mints, the vaults and the vault LP accounts
+        for key in [
+            Pubkey::new_unique(),
+            mint_a,
+            mint_b,
+            a_vault,
+            b_vault,
+            a_vault_lp,
+            b_vault_lp,
+        ] {
+            data.extend_from_slice(key.as_ref());
+        }
+        // Vault LP bump and enabled
+        data.extend_from_slice(&[0, 1]);
+        // Protocol fee accounts, the fee update time and its padding
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(&[0u8; 32]);
+        for value in [
+            fees.trade_fee_numerator,
+            fees.trade_fee_denominator,
+            fees.protocol_trade_fee_numerator,
+            fees.protocol_trade_fee_denominator,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.resize(944, 0);
+        data
+    }
+
+    fn sol_vault() -> VaultState {
+        VaultState {
+            total_amount: 1_250_000_000_000,
+            last_updated_locked_profit: 2_000_000_000,
+            last_report: 1_700_000_000,
+            locked_profit_degradation: 1_000_000_000,
+        }
+    }
+
+    fn usdc_vault() -> VaultState {
+        VaultState {
+            total_amount: 150_000_000_000,
+            last_updated_locked_profit: 0,
+            last_report: 0,
+            locked_profit_degradation: 0,
+        }
+    }
+
+    pub(crate) fn test_clock() -> Clock {
+        Clock {
+            unix_timestamp: CURRENT_TIME,
+            ..Clock::default()
+        }
+    }
+
+    // SOL/USDC pool: the pool owns 20% of the SOL vault LP and 25% of the USDC vault LP,
+    // 0.25% trade fee of which 20% goes to the protocol
+    pub(crate) fn create_test_accounts() -> Vec<AccountInfo<'static>> {
+        let owner = system_program::id();
+        let fees = PoolFees {
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10_000,
+            protocol_trade_fee_numerator: 20,
+            protocol_trade_fee_denominator: 100,
+        };
+        let a_lp_mint = Pubkey::new_unique();
+        let b_lp_mint = Pubkey::new_unique();
+        let [a_vault, b_vault, a_token_vault, b_token_vault, a_vault_lp, b_vault_lp] =
+            std::array::from_fn(|_| Pubkey::new_unique());
+        let pool_keys = [
+            SOL_MINT, USDC_MINT, a_vault, b_vault, a_vault_lp, b_vault_lp,
+        ];
+        vec![
+            create_mock_account_info(MeteoraDammV1::PROGRAM_ID, owner, None),
+            create_mock_account_info(
+                Pubkey::new_unique(),
+                owner,
+                Some(pool_data(fees, pool_keys)),
+            ),
+            create_mock_account_info(
+                a_vault,
+                owner,
+                Some(vault_data(sol_vault(), a_token_vault, SOL_MINT, a_lp_mint)),
+            ),
+            create_mock_account_info(
+                b_vault,
+                owner,
+                Some(vault_data(
+                    usdc_vault(),
+                    b_token_vault,
+                    USDC_MINT,
+                    b_lp_mint,
+                )),
+            ),
+            create_mock_account_info(
+                a_token_vault,
+                TOKEN_PROGRAM,
+                Some(token_account_data(SOL_MINT, 1_000_000_000_000)),
+            ),
+            create_mock_account_info(
+                b_token_vault,
+                TOKEN_PROGRAM,
+                Some(token_account_data(USDC_MINT, 120_000_000_000)),
+            ),
+            create_mock_account_info(SOL_MINT, TOKEN_PROGRAM, Some(mint_data(0, 9))),
+            create_mock_account_info(USDC_MINT, TOKEN_PROGRAM, Some(mint_data(0, 6))),
+            create_mock_account_info(
+                a_lp_mint,
+                TOKEN_PROGRAM,
+                Some(mint_data(1_100_000_000_000, 9)),
+            ),
+            create_mock_account_info(
+                b_lp_mint,
+                TOKEN_PROGRAM,
+                Some(mint_data(144_000_000_000, 6)),
+            ),
+            create_mock_account_info(
+                a_vault_lp,
+                TOKEN_PROGRAM,
+                Some(token_account_data(a_lp_mint, 220_000_000_000)),
+            ),
+            create_mock_account_info(
+                b_vault_lp,
+                TOKEN_PROGRAM,
+                Some(token_account_data(b_lp_mint, 36_000_000_000)),
+            ),
+            create_mock_account_info(Pubkey::new_unique(), owner, None),
+            create_mock_account_info(Pubkey::new_unique(), owner, None),
+            create_mock_account_info(MeteoraDammV1::VAULT_PROGRAM_ID, owner, None),
+        ]
+    }
+
+    #[test]
+    fn test_meteora_damm_v1_new_insufficient_accounts() {
+        let accounts = create_test_accounts();
+        let result = MeteoraDammV1::new(&accounts[..14]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_meteora_damm_v1_new_sufficient_accounts() {
+        let accounts = create_test_accounts();
+        let meteora = MeteoraDammV1::new(&accounts).unwrap();
+
+        assert_eq!(*meteora.program_id.key, MeteoraDammV1::PROGRAM_ID);
+        assert_eq!(meteora.pool_id.key, accounts[1].key);
+        assert_eq!(meteora.a_vault.key, accounts[2].key);
+        assert_eq!(meteora.b_vault.key, accounts[3].key);
+        assert_eq!(meteora.get_mints(), (&SOL_MINT, &USDC_MINT));
+        assert_eq!(*meteora.vault_program.key, MeteoraDammV1::VAULT_PROGRAM_ID);
+    }
+
+    #[test]
+    fn test_validate_accounts() {
+        let accounts = create_test_accounts();
+        assert!(MeteoraDammV1::new(&accounts)
+            .unwrap()
+            .validate_accounts()
+            .is_ok());
+
+        // A token account on the wrong mint for its side
+        let mut mismatched = accounts.clone();
+        mismatched[4] = create_mock_account_info(
+            Pubkey::new_unique(),
+            TOKEN_PROGRAM,
+            Some(token_account_data(USDC_MINT, 1_000_000_000_000)),
+        );
+        let err = MeteoraDammV1::new(&mismatched)
+            .unwrap()
+            .validate_accounts()
+            .unwrap_err();
+        assert_eq!(err, error!(SolarBError::AccountMismatch));
+
+        // Right mint, but not the token account the SOL dynamic vault records
+        let mut foreign = accounts.clone();
+        foreign[4] = create_mock_account_info(
+            Pubkey::new_unique(),
+            TOKEN_PROGRAM,
+            Some(token_account_data(SOL_MINT, 1_000_000_000_000)),
+        );
+        let err = MeteoraDammV1::new(&foreign)
+            .unwrap()
+            .validate_accounts()
+            .unwrap_err();
+        assert_eq!(err, error!(SolarBError::PoolStateMismatch));
+    }
+
+    #[test]
+    fn test_parse_upstream_layout() {
+        let accounts = create_test_accounts();
+        let fees = PoolFees::parse(&accounts[1].try_borrow_data().unwrap()).unwrap();
+        assert_eq!(fees.trade_fee_numerator, 25);
+        assert_eq!(fees.trade_fee_denominator, 10_000);
+        assert_eq!(fees.protocol_trade_fee_numerator, 20);
+        assert_eq!(fees.protocol_trade_fee_denominator, 100);
+        assert!(MeteoraDammV1::new(&accounts)
+            .unwrap()
+            .is_tradable(&test_clock())
+            .unwrap());
+
+        assert_eq!(
+            VaultState::parse(&accounts[2].try_borrow_data().unwrap()).unwrap(),
+            sol_vault()
+        );
+        assert_eq!(
+            VaultState::parse(&accounts[3].try_borrow_data().unwrap()).unwrap(),
+            usdc_vault()
+        );
+    }
+
+    #[test]
+    fn test_vault_locked_profit_degradation() {
+        let vault = sol_vault();
+        // 250s * 1e9 / 1e12 = 25% of the locked profit has been released
+        assert_eq!(vault.locked_profit(1_700_000_250), Some(1_500_000_000));
+        assert_eq!(
+            vault.unlocked_amount(1_700_000_250),
+            Some(1_248_500_000_000)
+        );
+        // Fully released after 1000s
+        assert_eq!(vault.locked_profit(1_700_001_001), Some(0));
+        assert_eq!(
+            vault.unlocked_amount(1_700_001_001),
+            Some(1_250_000_000_000)
+        );
+        // Clock behind the last report
+        assert_eq!(vault.locked_profit(1_699_999_999), None);
+    }
+
+    #[test]
+    fn test_vault_share_conversion_round_trip() {
+        let vault = usdc_vault();
+        let supply = 144_000_000_000;
+        assert_eq!(
+            vault.amount_by_share(0, 36_000_000_000, supply),
+            Some(37_500_000_000)
+        );
+        assert_eq!(
+            vault.unmint_amount(0, 37_500_000_000, supply),
+            Some(36_000_000_000)
+        );
+        assert_eq!(vault.amount_by_share(0, 1, 0), Some(0));
+    }
+
+    #[test]
+    fn test_pool_reserves_use_vault_lp_share() {
+        let accounts = create_test_accounts();
+        let meteora = MeteoraDammV1::new(&accounts).unwrap();
+
+        let (token_a_amount, token_b_amount) = meteora.pool_reserves(CURRENT_TIME as u64).unwrap();
+        // 220e9 / 1.1e12 of the 1_248.5 SOL unlocked in the vault
+        assert_eq!(token_a_amount, 249_700_000_000);
+        // 36e9 / 144e9 of the 150_000 USDC in the vault
+        assert_eq!(token_b_amount, 37_500_000_000);
+
+        // The shared token vault balances are not the pool's reserves
+        let (a_token_vault, b_token_vault) = meteora.parse_vaults().unwrap();
+        assert_ne!(a_token_vault.amount, token_a_amount);
+        assert_ne!(b_token_vault.amount, token_b_amount);
+        // and the edges are priced off the pool's share
+        assert_eq!(
+            meteora.reserves(&test_clock()).unwrap(),
+            (token_a_amount as u128, token_b_amount as u128)
+        );
+    }
+
+    #[test]
+    fn test_swap_base_in_a_to_b() {
+        let accounts = create_test_accounts();
+        let meteora = MeteoraDammV1::new(&accounts).unwrap();
+
+        let amount_out = meteora
+            .swap_base_in(SOL_MINT, 1_000_000_000, test_clock())
+            .unwrap();
+        assert_eq!(amount_out, 149_208_706);
+    }
+
+    #[test]
+    fn test_swap_base_out_b_to_a() {
+        let accounts = create_test_accounts();
+        let meteora = MeteoraDammV1::new(&accounts).unwrap();
+
+        // 150 USDC buys 992_343_547 lamports exact-in, so about 150 USDC buys them exact-out
+        let amount_in = meteora
+            .swap_base_out(USDC_MINT, 992_343_547, test_clock())
+            .unwrap();
+        assert!(amount_in <= 150_000_000 && amount_in > 149_999_000);
+        let quote = |amount_in| meteora.swap_base_in(USDC_MINT, amount_in, test_clock());
+        assert!(quote(amount_in).unwrap() >= 992_343_547);
+        assert!(quote(amount_in - 1).unwrap() < 992_343_547);
+    }
+
+    #[test]
+    fn test_swap_zero_amount() {
+        let accounts = create_test_accounts();
+        let meteora = MeteoraDammV1::new(&accounts).unwrap();
+
+        let amount_out = meteora.swap_base_in(SOL_MINT, 0, test_clock()).unwrap();
+        assert_eq!(amount_out, 0);
+    }
+
+    #[test]
+    fn test_swap_base_in_deterministic() {
+        let accounts = create_test_accounts();
+        let meteora = MeteoraDammV1::new(&accounts).unwrap();
+
+        let first = meteora
+            .swap_base_in(SOL_MINT, 1_000_000_000, test_clock())
+            .unwrap();
+        let second = meteora
+            .swap_base_in(SOL_MINT, 1_000_000_000, test_clock())
+            .unwrap();
+        assert_eq!(first.to_le_bytes(), second.to_le_bytes());
+    }
+
+    #[test]
+    fn test_build_swap_ix_follows_direction() {
+        let accounts = create_test_accounts();
+        let meteora = MeteoraDammV1::new(&accounts).unwrap();
+        let user = SwapUserAccounts {
+            authority: Pubkey::new_unique(),
+            base_token_program: TOKEN_PROGRAM,
+            quote_token_program: TOKEN_PROGRAM,
+            user_base_token_account: Pubkey::new_unique(),
+            user_quote_token_account: Pubkey::new_unique(),
+        };
+
+        for (direction, source, destination, protocol_fee) in [
+            (
+                EdgeSide::LeftToRight,
+                user.user_base_token_account,
+                user.user_quote_token_account,
+                *accounts[12].key,
+            ),
+            (
+                EdgeSide::RightToLeft,
+                user.user_quote_token_account,
+                user.user_base_token_account,
+                *accounts[13].key,
+            ),
+        ] {
+            let ix = meteora.build_swap_ix(direction, 1_000, 990, &user).unwrap();
+            assert_eq!(ix.program_id, MeteoraDammV1::PROGRAM_ID);
+            assert_eq!(ix.accounts.len(), 15);
+            assert_eq!(ix.data[..8], SWAP_DISCRIMINATOR);
+            assert_eq!(ix.data[8..16], 1_000u64.to_le_bytes());
+            assert_eq!(ix.data[16..24], 990u64.to_le_bytes());
+            assert_eq!(ix.accounts[1].pubkey, source);
+            assert_eq!(ix.accounts[2].pubkey, destination);
+            assert_eq!(ix.accounts[11].pubkey, protocol_fee);
+            assert_eq!(
+                ix.accounts[12],
+                AccountMeta::new_readonly(user.authority, true)
+            );
+            assert_eq!(ix.accounts[13].pubkey, MeteoraDammV1::VAULT_PROGRAM_ID);
+        }
+    }
+}
@@ -17,8 +17,7 @@ pub struct ActivationParams {
 }
 
 impl ActivationParams {
-    pub fn validate(&self) -> Result<()> {
-        let clock = Clock::get()?;
+    pub fn validate(&self, clock: &Clock) -> Result<()> {
         let activation_type = ActivationType::try_from(self.activation_type)
             .map_err(|_| PoolError::InvalidActivationType)?;
         let TimingConstraint {
@@ -28,7 +27,7 @@ impl ActivationParams {
             pre_activation_swap_duration,
             last_join_buffer,
             ..
-        } = get_timing_constraint_by_activation_type(activation_type, &clock);
+        } = get_timing_constraint_by_activation_type(activation_type, clock);
 
         if self.has_alpha_vault {
             // Must specify activation point to prevent "unable" create alpha vault
@@ -34,48 +34,50 @@ pub struct ActivationHandler {
 }
 
 impl ActivationHandler {
-    pub fn get_current_point(activation_type: u8) -> Result<u64> {
+    pub fn get_current_point(activation_type: u8, clock: &Clock) -> Result<u64> {
         let activation_type = ActivationType::try_from(activation_type)
             .map_err(|_| PoolError::InvalidActivationType)?;
         let current_point = match activation_type {
-            ActivationType::Slot => Clock::get()?.slot,
-            ActivationType::Timestamp => Clock::get()?.unix_timestamp as u64,
+            ActivationType::Slot => clock.slot,
+            ActivationType::Timestamp => clock.unix_timestamp as u64,
         };
         Ok(current_point)
     }
 
-    pub fn get_current_point_and_max_vesting_duration(activation_type: u8) -> Result<(u64, u64)> {
+    pub fn get_current_point_and_max_vesting_duration(
+        activation_type: u8,
+        clock: &Clock,
+    ) -> Result<(u64, u64)> {
         let activation_type = ActivationType::try_from(activation_type)
             .map_err(|_| PoolError::InvalidActivationType)?;
         let (curr_point, max_vesting_duration) = match activation_type {
-            ActivationType::Slot => (Clock::get()?.slot, MAX_VESTING_SLOT_DURATION),
-            ActivationType::Timestamp => (
-                Clock::get()?.unix_timestamp as u64,
-                MAX_VESTING_TIME_DURATION,
-            ),
+            ActivationType::Slot => (clock.slot, MAX_VESTING_SLOT_DURATION),
+            ActivationType::Timestamp => (clock.unix_timestamp as u64, MAX_VESTING_TIME_DURATION),
         };
         Ok((curr_point, max_vesting_duration))
     }
 
-    pub fn get_current_point_and_buffer_duration(activation_type: u8) -> Result<(u64, u64)> {
+    pub fn get_current_point_and_buffer_duration(
+        activation_type: u8,
+        clock: &Clock,
+    ) -> Result<(u64, u64)> {
         let activation_type = ActivationType::try_from(activation_type)
             .map_err(|_| PoolError::InvalidActivationType)?;
         let (curr_point, buffer_duration) = match activation_type {
-            ActivationType::Slot => (Clock::get()?.slot, SLOT_BUFFER),
-            ActivationType::Timestamp => (Clock::get()?.unix_timestamp as u64, TIME_BUFFER),
+            ActivationType::Slot => (clock.slot, SLOT_BUFFER),
+            ActivationType::Timestamp => (clock.unix_timestamp as u64, TIME_BUFFER),
         };
         Ok((curr_point, buffer_duration))
     }
 
-    pub fn get_max_activation_point(activation_type: u8) -> Result<u64> {
+    pub fn get_max_activation_point(activation_type: u8, clock: &Clock) -> Result<u64> {
         let activation_type = ActivationType::try_from(activation_type)
             .map_err(|_| PoolError::InvalidActivationType)?;
         let (curr_point, max_activation_duration) = match activation_type {
-            ActivationType::Slot => (Clock::get()?.slot, MAX_ACTIVATION_SLOT_DURATION),
-            ActivationType::Timestamp => (
-                Clock::get()?.unix_timestamp as u64,
-                MAX_ACTIVATION_TIME_DURATION,
-            ),
+            ActivationType::Slot => (clock.slot, MAX_ACTIVATION_SLOT_DURATION),
+            ActivationType::Timestamp => {
+                (clock.unix_timestamp as u64, MAX_ACTIVATION_TIME_DURATION)
+            }
         };
         Ok(curr_point.safe_add(max_activation_duration)?)
     }
@@ -1,14 +1,29 @@
-use super::super::programs::ProgramMeta;
+use super::super::programs::{
+    programs::{check_recorded_keys, check_vault_mints, marginal_price, read_mint_decimals},
+    ProgramMeta, SolarBError,
+};
+use crate::arbitrage::base::EdgeSide;
+use crate::utils::utils::{
+    check_event_authority, resolve_swap_accounts, ResolvedAccounts, SwapUserAccounts,
+};
+use crate::LiquidityData;
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
-    account_info::next_account_info, program_error::ProgramError, pubkey::Pubkey,
+    account_info::next_account_info,
+    instruction::{AccountMeta, Instruction},
+    program::invoke,
+    pubkey::Pubkey,
 };
 use bytemuck;
+#[cfg(test)]
+use std::cell::Cell;
+use std::cell::RefCell;
 
 // Expose the damm_v2 module
 pub mod damm_v2;
 
 // Re-export the MeteoraDammV2 struct from lib.rs
+use damm_v2::state::pool::PoolStatus;
 pub use damm_v2::{ActivationType, FeeMode, Pool, TradeDirection};
 
 pub fn get_current_point(
@@ -30,6 +45,133 @@ pub fn get_current_point(
     Ok(current_point)
 }
 
+/// Share of the input taken at the pool's cliff fee
+fn base_fee_rate(pool: &Pool) -> f64 {
+    pool.pool_fees.base_fee.cliff_fee_numerator as f64
+        / damm_v2::constants::fee::FEE_DENOMINATOR as f64
+}
+
+/// Side of `pool` the swap goes in on, its token A being `base_mint`
+fn trade_direction(base_mint: &Pubkey, input_mint: Pubkey) -> TradeDirection {
+    if input_mint == *base_mint {
+        TradeDirection::AtoB
+    } else {
+        TradeDirection::BtoA
+    }
+}
+
+/// Exact-in quote of `pool` at `clock`, net of the trading fee
+///
+/// `has_referral` only splits the protocol's share of the fee with the referrer, so the
+/// quote is the same with or without a referral account.
+fn quote_exact_input(
+    pool: &Pool,
+    base_mint: &Pubkey,
+    has_referral: bool,
+    input_mint: Pubkey,
+    amount_in: u64,
+    clock: &Clock,
+) -> Result<u64> {
+    let trade_direction = trade_direction(base_mint, input_mint);
+    let current_timestamp = clock.unix_timestamp as u64;
+    let current_slot = clock.slot;
+
+    let current_point = get_current_point(pool.activation_type, current_slot, current_timestamp)?;
+
+    let fee_mode = FeeMode::get_fee_mode(pool.collect_fee_mode, trade_direction, has_referral)?;
+    // The trading fee is the base fee scheduled for `current_point` plus the dynamic fee
+    // of the pool's stored volatility accumulator, as on-chain: the pre-swap update there
+    // only moves the volatility reference, which this swap's fee doesn't read
+    let results = pool.get_swap_result_from_exact_input(
+        amount_in,
+        &fee_mode,
+        trade_direction,
+        current_point,
+    )?;
+
+    Ok(results.output_amount)
+}
+
+/// Exact-out quote of `pool` at `clock`: the input needed to receive `amount_out`
+fn quote_exact_output(
+    pool: &Pool,
+    base_mint: &Pubkey,
+    has_referral: bool,
+    input_mint: Pubkey,
+    amount_out: u64,
+    clock: &Clock,
+) -> Result<u64> {
+    let trade_direction = trade_direction(base_mint, input_mint);
+    let current_timestamp = clock.unix_timestamp as u64;
+    let current_slot = clock.slot;
+
+    let current_point = get_current_point(pool.activation_type, current_slot, current_timestamp)?;
+
+    let fee_mode = FeeMode::get_fee_mode(pool.collect_fee_mode, trade_direction, has_referral)?;
+    let results = pool.get_swap_result_from_exact_output(
+        amount_out,
+        &fee_mode,
+        trade_direction,
+        current_point,
+    )?;
+
+    // Return the input amount needed to get the desired output
+    Ok(results.excluded_fee_input_amount)
+}
+
+/// A DAMM v2 pool held as its deserialized state and vault reserves rather than accounts,
+/// for quoting off-chain; see `MeteoraDammV2::from_state`
+#[cfg(feature = "client")]
+#[derive(Clone, Copy, Debug)]
+pub struct MeteoraDammV2State {
+    pub pool: Pool,
+    /// Base and quote vault reserves
+    pub vaults: (u128, u128),
+    /// Base (token A) and quote (token B) mints
+    pub mints: (Pubkey, Pubkey),
+}
+
+#[cfg(feature = "client")]
+impl MeteoraDammV2State {
+    pub fn get_mints(&self) -> (&Pubkey, &Pubkey) {
+        (&self.mints.0, &self.mints.1)
+    }
+
+    /// `ProgramMeta::compute_price_swap_base_in` at the held reserves
+    pub fn compute_price_swap_base_in(&self) -> f64 {
+        marginal_price(self.vaults.0, self.vaults.1, base_fee_rate(&self.pool))
+    }
+
+    /// `ProgramMeta::compute_price_swap_base_out` at the held reserves
+    pub fn compute_price_swap_base_out(&self) -> f64 {
+        marginal_price(self.vaults.1, self.vaults.0, base_fee_rate(&self.pool))
+    }
+
+    /// Same quote as `ProgramMeta::swap_base_in`, without a referral account
+    pub fn swap_base_in(&self, input_mint: Pubkey, amount_in: u64, clock: &Clock) -> Result<u64> {
+        quote_exact_input(
+            &self.pool,
+            &self.mints.0,
+            false,
+            input_mint,
+            amount_in,
+            clock,
+        )
+    }
+
+    /// Same quote as `ProgramMeta::swap_base_out`, without a referral account
+    pub fn swap_base_out(&self, input_mint: Pubkey, amount_out: u64, clock: &Clock) -> Result<u64> {
+        quote_exact_output(
+            &self.pool,
+            &self.mints.0,
+            false,
+            input_mint,
+            amount_out,
+            clock,
+        )
+    }
+}
+
 #[derive(Clone)]
 pub struct MeteoraDammV2<'info> {
     pub program_id: AccountInfo<'info>,
@@ -40,13 +182,26 @@ pub struct MeteoraDammV2<'info> {
     pub quote_token: AccountInfo<'info>,
     pub pool_authority: AccountInfo<'info>,
     pub event_authority: AccountInfo<'info>,
-    pub referral_token_account: AccountInfo<'info>,
+    /// Receives the referral fee; absent for 8-account spans or a zero key in slot 8
+    pub referral_token_account: Option<AccountInfo<'info>>,
+    /// Pool deserialized on first use, shared by every quote on this edge
+    pool_state: RefCell<Option<Pool>>,
+    #[cfg(test)]
+    pool_state_loads: Cell<u32>,
 }
 
 impl<'info> ProgramMeta for MeteoraDammV2<'info> {
     fn get_id(&self) -> &Pubkey {
         &Self::PROGRAM_ID
     }
+
+    fn program_label(&self) -> &'static str {
+        "MeteoraDammV2"
+    }
+
+    fn pool_key(&self) -> &Pubkey {
+        self.pool_id.key
+    }
     fn get_vaults(&self) -> (&AccountInfo<'_>, &AccountInfo<'_>) {
         unsafe {
             (
@@ -56,6 +211,30 @@ impl<'info> ProgramMeta for MeteoraDammV2<'info> {
         }
     }
 
+    fn get_mints(&self) -> (&Pubkey, &Pubkey) {
+        (self.base_token.key, self.quote_token.key)
+    }
+
+    fn mint_decimals(&self) -> (u8, u8) {
+        read_mint_decimals(&self.base_token, &self.quote_token)
+    }
+
+    fn compute_price_swap_base_in(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        Ok(marginal_price(
+            base_amount,
+            quote_amount,
+            self.base_fee_rate()?,
+        ))
+    }
+
+    fn compute_price_swap_base_out(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        Ok(marginal_price(
+            quote_amount,
+            base_amount,
+            self.base_fee_rate()?,
+        ))
+    }
+
     fn swap_base_in(&self, input_mint: Pubkey, amount_in: u64, clock: Clock) -> Result<u64> {
         self.swap_base_in_impl(input_mint, amount_in, clock)
     }
@@ -69,7 +248,7 @@ impl<'info> ProgramMeta for MeteoraDammV2<'info> {
         input_mint: Pubkey,
         max_amount_in: u64,
         amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -81,7 +260,7 @@ impl<'info> ProgramMeta for MeteoraDammV2<'info> {
             input_mint,
             max_amount_in,
             amount_out,
-            payer,
+            authority,
             user_mint_1_token_account,
             user_mint_2_token_account,
             mint_1_account,
@@ -96,7 +275,7 @@ impl<'info> ProgramMeta for MeteoraDammV2<'info> {
         input_mint: Pubkey,
         amount_in: u64,
         min_amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -108,7 +287,7 @@ impl<'info> ProgramMeta for MeteoraDammV2<'info> {
             input_mint,
             amount_in,
             min_amount_out,
-            payer,
+            authority,
             user_mint_1_token_account,
             user_mint_2_token_account,
             mint_1_account,
@@ -118,9 +297,80 @@ impl<'info> ProgramMeta for MeteoraDammV2<'info> {
         )
     }
 
+    fn build_swap_ix(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        user_accounts: &SwapUserAccounts,
+    ) -> Result<Instruction> {
+        let (user_input, user_output) = match direction {
+            EdgeSide::LeftToRight => (
+                user_accounts.user_base_token_account,
+                user_accounts.user_quote_token_account,
+            ),
+            EdgeSide::RightToLeft => (
+                user_accounts.user_quote_token_account,
+                user_accounts.user_base_token_account,
+            ),
+        };
+        let mut data = Self::SWAP_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&min_out.to_le_bytes());
+        Ok(Instruction {
+            program_id: *self.program_id.key,
+            accounts: vec![
+                AccountMeta::new_readonly(*self.pool_authority.key, false),
+                AccountMeta::new(*self.pool_id.key, false),
+                AccountMeta::new(user_input, false),
+                AccountMeta::new(user_output, false),
+                AccountMeta::new(*self.base_vault.key, false),
+                AccountMeta::new(*self.quote_vault.key, false),
+                AccountMeta::new_readonly(*self.base_token.key, false),
+                AccountMeta::new_readonly(*self.quote_token.key, false),
+                AccountMeta::new(user_accounts.authority, true),
+                AccountMeta::new_readonly(user_accounts.base_token_program, false),
+                AccountMeta::new_readonly(user_accounts.quote_token_program, false),
+                self.referral_meta(),
+                AccountMeta::new_readonly(*self.event_authority.key, false),
+                AccountMeta::new_readonly(*self.program_id.key, false),
+            ],
+            data,
+        })
+    }
+
+    fn estimate_compute_units(&self) -> u32 {
+        Self::SWAP_COMPUTE_UNITS
+    }
+
+    fn is_tradable(&self, clock: &Clock) -> Result<bool> {
+        let pool = self.pool_state()?;
+        // Swaps revert on a disabled pool and, outside the whitelisted vault, before activation
+        let current_point = get_current_point(
+            pool.activation_type,
+            clock.slot,
+            clock.unix_timestamp as u64,
+        )?;
+        Ok(pool.pool_status == PoolStatus::Enable as u8 && current_point >= pool.activation_point)
+    }
+
+    fn validate_accounts(&self) -> Result<()> {
+        check_vault_mints(self)?;
+        let pool = self.pool_state()?;
+        check_recorded_keys(
+            self.pool_id.key,
+            &[
+                ("token_a_mint", pool.token_a_mint, self.base_token.key),
+                ("token_b_mint", pool.token_b_mint, self.quote_token.key),
+                ("token_a_vault", pool.token_a_vault, self.base_vault.key),
+                ("token_b_vault", pool.token_b_vault, self.quote_vault.key),
+            ],
+        )
+    }
+
     fn log_accounts(&self) -> Result<()> {
         msg!(
-            "Meteora DAMM v2 accounts: pool={}, base_vault={}, quote_vault={}, base_token={}, quote_token={}, pool_authority={}, event_authority={}, referral_token_account={}",
+            "Meteora DAMM v2 accounts: pool={}, base_vault={}, quote_vault={}, base_token={}, quote_token={}, pool_authority={}, event_authority={}, referral_token_account={:?}",
             self.pool_id.key,
             self.base_vault.key,
             self.quote_vault.key,
@@ -128,7 +378,7 @@ impl<'info> ProgramMeta for MeteoraDammV2<'info> {
             self.quote_token.key,
             self.pool_authority.key,
             self.event_authority.key,
-            self.referral_token_account.key,
+            self.referral_token_account.as_ref().map(|referral| referral.key),
         );
         Ok(())
     }
@@ -137,6 +387,19 @@ impl<'info> ProgramMeta for MeteoraDammV2<'info> {
 impl<'info> MeteoraDammV2<'info> {
     pub const PROGRAM_ID: Pubkey =
         Pubkey::from_str_const("cpamdpZCGKUy5JxQXB4dcpGPiikHawvSWAd6mEn1sGG");
+    /// Shortest span `find_program_instance` accepts, program id included; a referral
+    /// token account may follow
+    pub const EXPECTED_ACCOUNTS: usize = 8;
+    /// Typical compute units of one swap CPI
+    pub const SWAP_COMPUTE_UNITS: u32 = 50_000;
+    /// Anchor discriminator of `swap`
+    pub const SWAP_DISCRIMINATOR: [u8; 8] = [0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x75, 0x87, 0xc8];
+    /// Anchor discriminator of `add_liquidity`
+    pub const ADD_LIQUIDITY_DISCRIMINATOR: [u8; 8] =
+        [0xb5, 0x9d, 0x59, 0x43, 0x8f, 0xb6, 0x34, 0x48];
+    /// Anchor discriminator of `remove_liquidity`
+    pub const REMOVE_LIQUIDITY_DISCRIMINATOR: [u8; 8] =
+        [0x50, 0x55, 0xd1, 0x48, 0x18, 0xce, 0xb1, 0x6c];
 
     pub fn new(accounts: &[AccountInfo<'info>]) -> Result<Self> {
         let mut iter = accounts.iter();
@@ -148,7 +411,10 @@ impl<'info> MeteoraDammV2<'info> {
         let quote_token = next_account_info(&mut iter)?; // 5
         let pool_authority = next_account_info(&mut iter)?; // 6
         let event_authority = next_account_info(&mut iter)?; // 7
-        let referral_token_account = next_account_info(&mut iter)?; // 8
+        check_event_authority(&Self::PROGRAM_ID, event_authority)?;
+        let referral_token_account = iter
+            .next() // 8, optional
+            .filter(|referral| *referral.key != Pubkey::default());
 
         Ok(MeteoraDammV2 {
             program_id: program_id.clone(),
@@ -159,49 +425,75 @@ impl<'info> MeteoraDammV2<'info> {
             quote_token: quote_token.clone(),
             pool_authority: pool_authority.clone(),
             event_authority: event_authority.clone(),
-            referral_token_account: referral_token_account.clone(),
+            referral_token_account: referral_token_account.cloned(),
+            pool_state: RefCell::new(None),
+            #[cfg(test)]
+            pool_state_loads: Cell::new(0),
         })
     }
 
+    /// Quote-only view of a pool whose state and reserves were fetched already, so
+    /// off-chain callers don't have to build its accounts
+    #[cfg(feature = "client")]
+    pub fn from_state(
+        pool: Pool,
+        vaults: (u128, u128),
+        mints: (Pubkey, Pubkey),
+    ) -> MeteoraDammV2State {
+        MeteoraDammV2State {
+            pool,
+            vaults,
+            mints,
+        }
+    }
+
+    /// Pool state, read from `pool_id` once and cached until the pool is swapped against
+    pub fn pool_state(&self) -> Result<Pool> {
+        if let Some(pool) = *self.pool_state.borrow() {
+            return Ok(pool);
+        }
+
+        let data = self.pool_id.try_borrow_data()?;
+        let pool_data = data
+            .get(8..8 + std::mem::size_of::<Pool>())
+            .ok_or(error!(SolarBError::PoolDeserializeFailed))?;
+        let pool = bytemuck::pod_read_unaligned::<Pool>(pool_data);
+        #[cfg(test)]
+        self.pool_state_loads.set(self.pool_state_loads.get() + 1);
+
+        *self.pool_state.borrow_mut() = Some(pool);
+        Ok(pool)
+    }
+
+    /// Referral slot of the swap CPI. Anchor reads an optional account passed as the program
+    /// id as `None`, which keeps the event authority and program metas in place.
+    fn referral_meta(&self) -> AccountMeta {
+        match &self.referral_token_account {
+            Some(referral) => AccountMeta::new(*referral.key, false),
+            None => AccountMeta::new_readonly(*self.program_id.key, false),
+        }
+    }
+
+    /// Share of the input taken at the pool's cliff fee; pricing has no clock, so the fee
+    /// scheduler's decay and the dynamic fee are left out
+    pub fn base_fee_rate(&self) -> Result<f64> {
+        Ok(base_fee_rate(&self.pool_state()?))
+    }
+
     pub fn swap_base_in_impl(
         &self,
         input_mint: Pubkey,
         amount_in: u64,
         clock: Clock,
     ) -> Result<u64> {
-        use damm_v2::{FeeMode, Pool, TradeDirection};
-
-        let data = self.pool_id.try_borrow_data()?;
-        let pool: &Pool = bytemuck::try_from_bytes::<Pool>(&data[8..])
-            .map_err(|_| ProgramError::InvalidAccountData)?;
-
-        // Determine trade direction based on input_mint
-        let trade_direction = if input_mint == self.base_token.key() {
-            TradeDirection::AtoB
-        } else {
-            TradeDirection::BtoA
-        };
-        let current_timestamp = clock.unix_timestamp as u64;
-        let current_slot = clock.slot as u64;
-
-        let current_point =
-            get_current_point(pool.activation_type, current_slot, current_timestamp)?;
-
-        let has_referral = !self.referral_token_account.key.eq(&Pubkey::default());
-        let fee_mode = FeeMode::get_fee_mode(pool.collect_fee_mode, trade_direction, has_referral)?;
-        eprintln!("fee_mode: {:?}", fee_mode);
-        eprintln!("current_point: {}", current_point);
-        eprintln!("amount_in: {}", amount_in);
-        let results = pool.get_swap_result_from_exact_input(
+        quote_exact_input(
+            &self.pool_state()?,
+            self.base_token.key,
+            self.referral_token_account.is_some(),
+            input_mint,
             amount_in,
-            &fee_mode,
-            trade_direction,
-            current_point,
-        )?;
-
-        eprintln!("results: {:?}", results);
-
-        Ok(results.output_amount)
+            &clock,
+        )
     }
 
     pub fn swap_base_out_impl(
@@ -210,37 +502,14 @@ impl<'info> MeteoraDammV2<'info> {
         amount_out: u64,
         clock: Clock,
     ) -> Result<u64> {
-        use damm_v2::{FeeMode, Pool, TradeDirection};
-
-        let data = self.pool_id.try_borrow_data()?;
-        let pool: &Pool = bytemuck::try_from_bytes::<Pool>(&data[8..])
-            .map_err(|_| ProgramError::InvalidAccountData)?;
-
-        // Determine trade direction based on input_mint
-        let trade_direction = if input_mint == self.base_token.key() {
-            TradeDirection::AtoB
-        } else {
-            TradeDirection::BtoA
-        };
-        let current_timestamp = clock.unix_timestamp as u64;
-        let current_slot = clock.slot as u64;
-
-        let current_point =
-            get_current_point(pool.activation_type, current_slot, current_timestamp)?;
-
-        let has_referral = !self.referral_token_account.key.eq(&Pubkey::default());
-        let fee_mode = FeeMode::get_fee_mode(pool.collect_fee_mode, trade_direction, has_referral)?;
-        let results = pool.get_swap_result_from_exact_output(
+        quote_exact_output(
+            &self.pool_state()?,
+            self.base_token.key,
+            self.referral_token_account.is_some(),
+            input_mint,
             amount_out,
-            &fee_mode,
-            trade_direction,
-            current_point,
-        )?;
-
-        eprintln!("results: {:?}", results);
-
-        // Return the input amount needed to get the desired output
-        Ok(results.excluded_fee_input_amount)
+            &clock,
+        )
     }
 
     pub fn invoke_swap_base_in_impl<'a>(
@@ -248,7 +517,7 @@ impl<'info> MeteoraDammV2<'info> {
         _input_mint: Pubkey,
         max_amount_in: u64,
         amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -256,61 +525,65 @@ impl<'info> MeteoraDammV2<'info> {
         mint_1_token_program: AccountInfo<'a>,
         mint_2_token_program: AccountInfo<'a>,
     ) -> Result<()> {
-        use anchor_lang::solana_program::{
-            instruction::{AccountMeta, Instruction},
-            program::invoke,
-        };
-
-        let (
-            base_token_program,
-            quote_token_program,
-            user_base_token_account,
-            user_quote_token_account,
-        ) = if mint_1_account.key == self.base_token.key {
-            (
-                mint_1_token_program,
-                mint_2_token_program,
-                user_mint_1_token_account,
-                user_mint_2_token_account,
-            )
-        } else if mint_2_account.key == self.base_token.key {
-            (
-                mint_2_token_program,
-                mint_1_token_program,
-                user_mint_2_token_account,
-                user_mint_1_token_account,
-            )
-        } else {
-            return Err(ProgramError::InvalidAccountData.into());
-        };
-
-        let amount_out_value = amount_out.unwrap_or(0);
-        let metas = vec![
-            AccountMeta::new_readonly(*self.pool_authority.key, false),
-            AccountMeta::new(*self.pool_id.key, false),
-            AccountMeta::new(*user_quote_token_account.key, false),
-            AccountMeta::new(*user_base_token_account.key, false),
-            AccountMeta::new(*self.base_vault.key, false),
-            AccountMeta::new(*self.quote_vault.key, false),
-            AccountMeta::new_readonly(*self.base_token.key, false),
-            AccountMeta::new_readonly(*self.quote_token.key, false),
-            AccountMeta::new(*payer.key, true),
-            AccountMeta::new_readonly(*base_token_program.key, false),
-            AccountMeta::new_readonly(*quote_token_program.key, false),
-            AccountMeta::new_readonly(*self.referral_token_account.key, false),
-            AccountMeta::new_readonly(*self.event_authority.key, false),
-            AccountMeta::new_readonly(*self.program_id.key, false),
-        ];
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            EdgeSide::RightToLeft,
+            max_amount_in,
+            amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
+    }
 
-        let mut data = vec![0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x75, 0x87, 0xc8];
-        data.extend_from_slice(&max_amount_in.to_le_bytes());
-        data.extend_from_slice(&amount_out_value.to_le_bytes());
+    pub fn invoke_swap_base_out_impl<'a>(
+        &self,
+        _input_mint: Pubkey,
+        amount_in: u64,
+        min_amount_out: Option<u64>,
+        authority: AccountInfo<'a>,
+        user_mint_1_token_account: AccountInfo<'a>,
+        user_mint_2_token_account: AccountInfo<'a>,
+        mint_1_account: AccountInfo<'a>,
+        mint_2_account: AccountInfo<'a>,
+        mint_1_token_program: AccountInfo<'a>,
+        mint_2_token_program: AccountInfo<'a>,
+    ) -> Result<()> {
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            EdgeSide::LeftToRight,
+            amount_in,
+            min_amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
+    }
 
-        let swap_ix = Instruction {
-            program_id: *self.program_id.key,
-            accounts: metas,
-            data,
-        };
+    fn invoke_swap<'a>(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        authority: AccountInfo<'a>,
+        accounts: ResolvedAccounts<'a>,
+    ) -> Result<()> {
+        let swap_ix =
+            self.build_swap_ix(direction, amount_in, min_out, &accounts.keys(authority.key))?;
 
         // Collect AccountInfo into a vector and use unsafe to cast lifetimes
         // This is safe because 'a outlives 'info in practice when called from execute_arbitrage_path
@@ -321,128 +594,146 @@ impl<'info> MeteoraDammV2<'info> {
             self.quote_vault.to_account_info(),
             self.base_token.to_account_info(),
             self.quote_token.to_account_info(),
-            self.referral_token_account.to_account_info(),
             self.event_authority.to_account_info(),
             self.program_id.to_account_info(),
         ];
-        // Cast parameter AccountInfo<'a> to AccountInfo<'info> to add to vector
-        accounts_vec
-            .push(unsafe { std::mem::transmute(user_quote_token_account.to_account_info()) });
-        accounts_vec
-            .push(unsafe { std::mem::transmute(user_base_token_account.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(payer.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(base_token_program.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(quote_token_program.to_account_info()) });
+        accounts_vec.extend(self.referral_token_account.clone());
+        for account in [
+            authority,
+            accounts.user_base_token_account,
+            accounts.user_quote_token_account,
+            accounts.base_token_program,
+            accounts.quote_token_program,
+        ] {
+            accounts_vec.push(unsafe {
+                std::mem::transmute::<AccountInfo<'a>, AccountInfo<'info>>(account)
+            });
+        }
 
         // Cast entire vector to AccountInfo<'a> for invoke
         unsafe {
             let accounts: &[AccountInfo<'a>] = std::mem::transmute(accounts_vec.as_slice());
             invoke(&swap_ix, accounts)?;
         }
-
+        // The swap moved the pool's reserves, so later quotes must re-read it
+        self.pool_state.take();
         Ok(())
     }
 
-    pub fn invoke_swap_base_out_impl<'a>(
+    /// `add_liquidity` or `remove_liquidity` of `data.liquidity_delta` on the position,
+    /// against this pool's vaults and mints. Token A is the base token.
+    pub fn build_liquidity_ix(
         &self,
-        _input_mint: Pubkey,
-        amount_in: u64,
-        min_amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
-        user_mint_1_token_account: AccountInfo<'a>,
-        user_mint_2_token_account: AccountInfo<'a>,
-        mint_1_account: AccountInfo<'a>,
-        mint_2_account: AccountInfo<'a>,
-        mint_1_token_program: AccountInfo<'a>,
-        mint_2_token_program: AccountInfo<'a>,
-    ) -> Result<()> {
-        use anchor_lang::solana_program::{
-            instruction::{AccountMeta, Instruction},
-            program::invoke,
-        };
-
-        let (
-            base_token_program,
-            quote_token_program,
-            user_base_token_account,
-            user_quote_token_account,
-        ) = if mint_1_account.key == self.base_token.key {
+        data: &LiquidityData,
+        position: &PositionAccounts<'_>,
+    ) -> Instruction {
+        let (discriminator, mut accounts) = if data.remove {
             (
-                mint_1_token_program,
-                mint_2_token_program,
-                user_mint_1_token_account,
-                user_mint_2_token_account,
-            )
-        } else if mint_2_account.key == self.base_token.key {
-            (
-                mint_2_token_program,
-                mint_1_token_program,
-                user_mint_2_token_account,
-                user_mint_1_token_account,
+                Self::REMOVE_LIQUIDITY_DISCRIMINATOR,
+                vec![AccountMeta::new_readonly(*self.pool_authority.key, false)],
             )
         } else {
-            return Err(ProgramError::InvalidAccountData.into());
+            (Self::ADD_LIQUIDITY_DISCRIMINATOR, Vec::new())
         };
-
-        let min_amount_out_value = min_amount_out.unwrap_or(0);
-        let metas = vec![
-            AccountMeta::new_readonly(*self.pool_authority.key, false),
+        accounts.extend([
             AccountMeta::new(*self.pool_id.key, false),
-            AccountMeta::new(*user_base_token_account.key, false),
-            AccountMeta::new(*user_quote_token_account.key, false),
+            AccountMeta::new(*position.position.key, false),
+            AccountMeta::new(*position.user_base_token_account.key, false),
+            AccountMeta::new(*position.user_quote_token_account.key, false),
             AccountMeta::new(*self.base_vault.key, false),
             AccountMeta::new(*self.quote_vault.key, false),
             AccountMeta::new_readonly(*self.base_token.key, false),
             AccountMeta::new_readonly(*self.quote_token.key, false),
-            AccountMeta::new(*payer.key, true),
-            AccountMeta::new_readonly(*base_token_program.key, false),
-            AccountMeta::new_readonly(*quote_token_program.key, false),
-            AccountMeta::new_readonly(*self.referral_token_account.key, false),
+            AccountMeta::new_readonly(*position.position_nft_account.key, false),
+            AccountMeta::new_readonly(*position.owner.key, true),
+            AccountMeta::new_readonly(*position.base_token_program.key, false),
+            AccountMeta::new_readonly(*position.quote_token_program.key, false),
             AccountMeta::new_readonly(*self.event_authority.key, false),
             AccountMeta::new_readonly(*self.program_id.key, false),
-        ];
-        let mut data = vec![0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x75, 0x87, 0xc8];
-        data.extend_from_slice(&amount_in.to_le_bytes());
-        data.extend_from_slice(&min_amount_out_value.to_le_bytes());
-
-        let swap_ix = Instruction {
+        ]);
+        let mut ix_data = discriminator.to_vec();
+        ix_data.extend_from_slice(&data.liquidity_delta.to_le_bytes());
+        ix_data.extend_from_slice(&data.token_a_amount_threshold.to_le_bytes());
+        ix_data.extend_from_slice(&data.token_b_amount_threshold.to_le_bytes());
+        Instruction {
             program_id: *self.program_id.key,
-            accounts: metas,
-            data,
-        };
-
-        // Collect AccountInfo into a vector and use unsafe to cast lifetimes
-        let mut accounts_vec: Vec<AccountInfo<'info>> = vec![
-            self.pool_authority.to_account_info(),
-            self.pool_id.to_account_info(),
-            self.base_vault.to_account_info(),
-            self.quote_vault.to_account_info(),
-            self.base_token.to_account_info(),
-            self.quote_token.to_account_info(),
-            self.referral_token_account.to_account_info(),
-            self.event_authority.to_account_info(),
-            self.program_id.to_account_info(),
-        ];
-        accounts_vec
-            .push(unsafe { std::mem::transmute(user_base_token_account.to_account_info()) });
-        accounts_vec
-            .push(unsafe { std::mem::transmute(user_quote_token_account.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(payer.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(base_token_program.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(quote_token_program.to_account_info()) });
-
-        unsafe {
-            let accounts: &[AccountInfo<'a>] = std::mem::transmute(accounts_vec.as_slice());
-            invoke(&swap_ix, accounts)?;
+            accounts,
+            data: ix_data,
         }
+    }
+
+    pub fn invoke_liquidity(
+        &self,
+        data: &LiquidityData,
+        position: &PositionAccounts<'info>,
+    ) -> Result<()> {
+        let ix = self.build_liquidity_ix(data, position);
+        invoke(
+            &ix,
+            &[
+                self.pool_authority.clone(),
+                self.pool_id.clone(),
+                position.position.clone(),
+                position.user_base_token_account.clone(),
+                position.user_quote_token_account.clone(),
+                self.base_vault.clone(),
+                self.quote_vault.clone(),
+                self.base_token.clone(),
+                self.quote_token.clone(),
+                position.position_nft_account.clone(),
+                position.owner.clone(),
+                position.base_token_program.clone(),
+                position.quote_token_program.clone(),
+                self.event_authority.clone(),
+                self.program_id.clone(),
+            ],
+        )?;
+        // The deposit or withdrawal moved the pool's reserves and liquidity
+        self.pool_state.take();
         Ok(())
     }
 }
 
+/// Accounts of the position `add_liquidity` and `remove_liquidity` act on, besides the
+/// pool's own
+pub struct PositionAccounts<'info> {
+    /// Owner of the position NFT, signing the transaction
+    pub owner: AccountInfo<'info>,
+    pub position: AccountInfo<'info>,
+    /// Token account holding the position NFT
+    pub position_nft_account: AccountInfo<'info>,
+    pub user_base_token_account: AccountInfo<'info>,
+    pub user_quote_token_account: AccountInfo<'info>,
+    pub base_token_program: AccountInfo<'info>,
+    pub quote_token_program: AccountInfo<'info>,
+}
+
+impl<'info> PositionAccounts<'info> {
+    /// Number of accounts `new` reads
+    pub const LEN: usize = 7;
+
+    /// Read from `accounts` in field order
+    pub fn new(accounts: &[AccountInfo<'info>]) -> Result<Self> {
+        let mut iter = accounts.iter();
+        Ok(PositionAccounts {
+            owner: next_account_info(&mut iter)?.clone(),
+            position: next_account_info(&mut iter)?.clone(),
+            position_nft_account: next_account_info(&mut iter)?.clone(),
+            user_base_token_account: next_account_info(&mut iter)?.clone(),
+            user_quote_token_account: next_account_info(&mut iter)?.clone(),
+            base_token_program: next_account_info(&mut iter)?.clone(),
+            quote_token_program: next_account_info(&mut iter)?.clone(),
+        })
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
 
     use super::*;
+    use crate::programs::test_utils::token_account_data;
+    use crate::programs::SolarBError;
+    use crate::utils::utils::derive_event_authority;
     use anchor_lang::solana_program::{
         account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, system_program,
     };
@@ -476,9 +767,31 @@ mod tests {
         )
     }
 
+    // Helper function to create a pool AccountInfo whose data after the discriminator is
+    // aligned for `Pool`, so bytemuck::try_from_bytes succeeds on the host as well
+    fn create_aligned_pool_account(key: Pubkey, pool: &Pool) -> AccountInfo<'static> {
+        let pool_bytes = bytemuck::bytes_of(pool);
+        let words = (16 + pool_bytes.len()).div_ceil(16);
+        let buffer: &'static mut [u128] = Box::leak(vec![0u128; words].into_boxed_slice());
+        let bytes: &'static mut [u8] = bytemuck::cast_slice_mut(buffer);
+        let data = &mut bytes[8..16 + pool_bytes.len()];
+        data[8..].copy_from_slice(pool_bytes);
+
+        AccountInfo::new(
+            Box::leak(Box::new(key)),
+            false,
+            false,
+            Box::leak(Box::new(0u64)),
+            data,
+            Box::leak(Box::new(system_program::id())),
+            false,
+            0,
+        )
+    }
+
     // Helper function to create a Pool from actual pool data
     // Pool data from pool_data.txt (Python bytes literal converted to Rust)
-    fn create_test_pool() -> Pool {
+    pub(crate) fn create_test_pool() -> Pool {
         // Actual pool data bytes (from pool_data.txt, skipping 8-byte discriminator)
         // This is the raw pool account data starting after the discriminator
         let pool_data_bytes = include_bytes!("pool_data.bin");
@@ -521,14 +834,11 @@ mod tests {
 
     #[test]
     fn test_meteora_damm_v2_program_id() {
-        let expected_bytes = [
-            202, 173, 213, 232, 67, 75, 181, 53, 88, 180, 220, 112, 105, 107, 171, 119, 215, 173,
-            214, 67, 75, 181, 53, 88, 180, 220, 112, 105, 107, 171, 119, 215,
-        ];
-        let mut arr = [0u8; 32];
-        arr.copy_from_slice(&expected_bytes);
-        let expected_id = Pubkey::new_from_array(arr);
-        assert_eq!(MeteoraDammV2::PROGRAM_ID, expected_id);
+        // Compared as base58 so a hand-written byte array can't drift from the mainnet id
+        assert_eq!(
+            MeteoraDammV2::PROGRAM_ID.to_string(),
+            "cpamdpZCGKUy5JxQXB4dcpGPiikHawvSWAd6mEn1sGG"
+        );
     }
 
     #[test]
@@ -547,7 +857,7 @@ mod tests {
         let base_token = Pubkey::new_unique();
         let quote_token = Pubkey::new_unique();
         let pool_authority = Pubkey::new_unique();
-        let event_authority = Pubkey::new_unique();
+        let event_authority = derive_event_authority(&MeteoraDammV2::PROGRAM_ID);
         let referral_token_account = Pubkey::new_unique();
 
         let accounts = vec![
@@ -572,6 +882,65 @@ mod tests {
         assert_eq!(*meteora.quote_vault.key, quote_vault);
     }
 
+    #[test]
+    fn test_new_without_referral_account() {
+        let pool = create_test_pool();
+        let mut accounts = create_swap_test_accounts(&pool);
+        accounts.truncate(8);
+        let meteora = MeteoraDammV2::new(&accounts).unwrap();
+
+        assert!(meteora.referral_token_account.is_none());
+        // The referral slot carries the program id, Anchor's marker for an absent account
+        let referral_meta = meteora.referral_meta();
+        assert_eq!(referral_meta.pubkey, MeteoraDammV2::PROGRAM_ID);
+        assert!(!referral_meta.is_writable);
+        assert!(meteora
+            .swap_base_in(pool.token_a_mint, 1_000_000, activated_clock())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_new_checks_event_authority() {
+        let pool = create_test_pool();
+        let mut accounts = create_swap_test_accounts(&pool);
+        assert_eq!(
+            *accounts[7].key,
+            Pubkey::find_program_address(&[b"__event_authority"], &MeteoraDammV2::PROGRAM_ID).0
+        );
+        assert!(MeteoraDammV2::new(&accounts).is_ok());
+
+        accounts[7] = create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
+        assert_eq!(
+            MeteoraDammV2::new(&accounts).err(),
+            Some(error!(SolarBError::InvalidEventAuthority))
+        );
+    }
+
+    #[test]
+    fn test_new_with_referral_account() {
+        let pool = create_test_pool();
+        let mut accounts = create_swap_test_accounts(&pool);
+        let referral_token_account = Pubkey::new_unique();
+        accounts[8] = create_mock_account_info(referral_token_account, system_program::id(), None);
+        let meteora = MeteoraDammV2::new(&accounts).unwrap();
+
+        assert_eq!(
+            meteora
+                .referral_token_account
+                .as_ref()
+                .map(|referral| *referral.key),
+            Some(referral_token_account)
+        );
+        let referral_meta = meteora.referral_meta();
+        assert_eq!(referral_meta.pubkey, referral_token_account);
+        assert!(referral_meta.is_writable);
+
+        // A zero key in slot 8 still means no referral
+        let accounts = create_swap_test_accounts(&pool);
+        let meteora = MeteoraDammV2::new(&accounts).unwrap();
+        assert!(meteora.referral_token_account.is_none());
+    }
+
     #[test]
     fn test_swap_base_in_basic() {
         let pool = create_test_pool();
@@ -590,7 +959,7 @@ mod tests {
         let base_token = Pubkey::new_unique();
         let quote_token = Pubkey::new_unique();
         let pool_authority = Pubkey::new_unique();
-        let event_authority = Pubkey::new_unique();
+        let event_authority = derive_event_authority(&MeteoraDammV2::PROGRAM_ID);
         let referral_token_account = Pubkey::new_unique();
 
         let accounts = vec![
@@ -626,7 +995,7 @@ mod tests {
         let base_token = pool.token_a_mint;
         let quote_token = pool.token_b_mint;
         let pool_authority = Pubkey::new_unique(); // This might need to be calculated properly
-        let event_authority = Pubkey::new_unique();
+        let event_authority = derive_event_authority(&MeteoraDammV2::PROGRAM_ID);
         let referral_token_account = Pubkey::default(); // Use default for no referral
 
         let correct_accounts = vec![
@@ -683,7 +1052,7 @@ mod tests {
         let base_token = Pubkey::new_unique();
         let quote_token = Pubkey::new_unique();
         let pool_authority = Pubkey::new_unique();
-        let event_authority = Pubkey::new_unique();
+        let event_authority = derive_event_authority(&MeteoraDammV2::PROGRAM_ID);
         let referral_token_account = Pubkey::new_unique();
 
         let accounts = vec![
@@ -742,7 +1111,7 @@ mod tests {
         let base_token = Pubkey::new_unique();
         let quote_token = Pubkey::new_unique();
         let pool_authority = Pubkey::new_unique();
-        let event_authority = Pubkey::new_unique();
+        let event_authority = derive_event_authority(&MeteoraDammV2::PROGRAM_ID);
         // Use a non-default referral token account
         let referral_token_account = Pubkey::new_unique();
 
@@ -793,7 +1162,7 @@ mod tests {
         let base_token = Pubkey::new_unique();
         let quote_token = Pubkey::new_unique();
         let pool_authority = Pubkey::new_unique();
-        let event_authority = Pubkey::new_unique();
+        let event_authority = derive_event_authority(&MeteoraDammV2::PROGRAM_ID);
         // Use default (zero) referral token account
         let referral_token_account = Pubkey::default();
 
@@ -827,6 +1196,73 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_referral_leaves_quote_unchanged() {
+        let mut pool = create_test_pool();
+        // Mainnet DAMM v2 configs send 20% of the trading fee to the protocol, and 20%
+        // of that to a referrer
+        pool.pool_fees.protocol_fee_percent = 20;
+        pool.pool_fees.referral_fee_percent = 20;
+        let without = create_swap_test_accounts(&pool);
+        let mut with = without.clone();
+        with[8] = create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
+        let without = MeteoraDammV2::new(&without).unwrap();
+        let with = MeteoraDammV2::new(&with).unwrap();
+        assert!(without.referral_token_account.is_none());
+        assert!(with.referral_token_account.is_some());
+
+        // cp_amm pays the referral out of the protocol's share of the trading fee, so a
+        // referral account changes who receives the fee but not what the swapper gets
+        for input_mint in [pool.token_a_mint, pool.token_b_mint] {
+            let amount_out = without
+                .swap_base_in(input_mint, 1_000_000, activated_clock())
+                .unwrap();
+            assert!(amount_out > 0);
+            assert_eq!(
+                with.swap_base_in(input_mint, 1_000_000, activated_clock())
+                    .unwrap(),
+                amount_out
+            );
+            assert_eq!(
+                with.swap_base_out(input_mint, amount_out, activated_clock())
+                    .unwrap(),
+                without
+                    .swap_base_out(input_mint, amount_out, activated_clock())
+                    .unwrap()
+            );
+        }
+
+        // The referral does take its cut, just from the protocol fee
+        let current_point = get_current_point(
+            pool.activation_type,
+            activated_clock().slot,
+            activated_clock().unix_timestamp as u64,
+        )
+        .unwrap();
+        let swap = |has_referral| {
+            let trade_direction = trade_direction(&pool.token_a_mint, pool.token_a_mint);
+            let fee_mode =
+                FeeMode::get_fee_mode(pool.collect_fee_mode, trade_direction, has_referral)
+                    .unwrap();
+            pool.get_swap_result_from_exact_input(
+                1_000_000_000,
+                &fee_mode,
+                trade_direction,
+                current_point,
+            )
+            .unwrap()
+        };
+        let (plain, referred) = (swap(false), swap(true));
+        assert_eq!(plain.referral_fee, 0);
+        assert!(referred.referral_fee > 0);
+        assert_eq!(
+            referred.protocol_fee + referred.referral_fee,
+            plain.protocol_fee
+        );
+        assert_eq!(referred.trading_fee, plain.trading_fee);
+        assert_eq!(referred.output_amount, plain.output_amount);
+    }
+
     #[test]
     fn test_program_meta_implementation() {
         let program_id = MeteoraDammV2::PROGRAM_ID;
@@ -836,7 +1272,7 @@ mod tests {
         let base_token = Pubkey::new_unique();
         let quote_token = Pubkey::new_unique();
         let pool_authority = Pubkey::new_unique();
-        let event_authority = Pubkey::new_unique();
+        let event_authority = derive_event_authority(&MeteoraDammV2::PROGRAM_ID);
         let referral_token_account = Pubkey::new_unique();
 
         let accounts = vec![
@@ -861,4 +1297,402 @@ mod tests {
         assert_eq!(*vault1.key, *meteora.base_vault.key);
         assert_eq!(*vault2.key, *meteora.quote_vault.key);
     }
+
+    // Accounts for a MeteoraDammV2 instance over the recorded pool
+    pub(crate) fn create_swap_test_accounts(pool: &Pool) -> Vec<AccountInfo<'static>> {
+        vec![
+            create_mock_account_info(MeteoraDammV2::PROGRAM_ID, system_program::id(), None),
+            create_aligned_pool_account(Pubkey::new_unique(), pool),
+            create_mock_account_info(
+                pool.token_a_vault,
+                system_program::id(),
+                Some(token_account_data(pool.token_a_mint, 1_000_000_000)),
+            ),
+            create_mock_account_info(
+                pool.token_b_vault,
+                system_program::id(),
+                Some(token_account_data(pool.token_b_mint, 1_000_000_000)),
+            ),
+            create_mock_account_info(pool.token_a_mint, system_program::id(), None),
+            create_mock_account_info(pool.token_b_mint, system_program::id(), None),
+            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None),
+            create_mock_account_info(
+                derive_event_authority(&MeteoraDammV2::PROGRAM_ID),
+                system_program::id(),
+                None,
+            ),
+            create_mock_account_info(Pubkey::default(), system_program::id(), None),
+        ]
+    }
+
+    // Past the recorded pool's activation point
+    pub(crate) fn activated_clock() -> Clock {
+        Clock {
+            slot: 400_000_000,
+            epoch_start_timestamp: 0,
+            epoch: 900,
+            leader_schedule_epoch: 0,
+            unix_timestamp: 1_800_000_000,
+        }
+    }
+
+    #[test]
+    fn test_is_tradable_checks_status_and_activation() {
+        let mut pool = create_test_pool();
+        pool.activation_type = ActivationType::Timestamp as u8;
+        pool.activation_point = 1_700_000_000;
+        pool.pool_status = PoolStatus::Enable as u8;
+        let accounts = create_swap_test_accounts(&pool);
+        let meteora = MeteoraDammV2::new(&accounts).unwrap();
+
+        let before_activation = Clock {
+            unix_timestamp: 1_699_999_999,
+            ..activated_clock()
+        };
+        assert!(!meteora.is_tradable(&before_activation).unwrap());
+        assert!(meteora.is_tradable(&activated_clock()).unwrap());
+
+        pool.pool_status = PoolStatus::Disable as u8;
+        let accounts = create_swap_test_accounts(&pool);
+        let meteora = MeteoraDammV2::new(&accounts).unwrap();
+        assert!(!meteora.is_tradable(&activated_clock()).unwrap());
+    }
+
+    #[test]
+    fn test_swap_base_in_deterministic() {
+        let pool = create_test_pool();
+        let accounts = create_swap_test_accounts(&pool);
+        let meteora = MeteoraDammV2::new(&accounts).unwrap();
+
+        let first = meteora
+            .swap_base_in(pool.token_a_mint, 1_000_000, activated_clock())
+            .unwrap();
+        let second = meteora
+            .swap_base_in(pool.token_a_mint, 1_000_000, activated_clock())
+            .unwrap();
+        assert!(first > 0);
+        assert_eq!(first.to_le_bytes(), second.to_le_bytes());
+    }
+
+    #[test]
+    fn test_pool_state_deserialized_once_per_edge() {
+        let pool = create_test_pool();
+        let accounts = create_swap_test_accounts(&pool);
+        let meteora = MeteoraDammV2::new(&accounts).unwrap();
+        assert_eq!(meteora.pool_state_loads.get(), 0);
+
+        // Quoting both directions of the edge reads the pool account only once
+        meteora
+            .swap_base_in(pool.token_a_mint, 1_000_000, activated_clock())
+            .unwrap();
+        meteora
+            .swap_base_in(pool.token_b_mint, 1_000_000, activated_clock())
+            .unwrap();
+        assert_eq!(meteora.pool_state_loads.get(), 1);
+
+        // Dropping the cache, as a swap CPI does, forces the next quote to re-read the pool
+        meteora.pool_state.take();
+        meteora
+            .swap_base_in(pool.token_a_mint, 1_000_000, activated_clock())
+            .unwrap();
+        assert_eq!(meteora.pool_state_loads.get(), 2);
+    }
+
+    #[test]
+    fn test_truncated_pool_fails_to_deserialize() {
+        let pool = create_test_pool();
+        let mut accounts = create_swap_test_accounts(&pool);
+        accounts[1] =
+            create_mock_account_info(*accounts[1].key, system_program::id(), Some(vec![0u8; 64]));
+        let meteora = MeteoraDammV2::new(&accounts).unwrap();
+        assert_eq!(
+            meteora
+                .swap_base_in(pool.token_a_mint, 1_000_000, activated_clock())
+                .err(),
+            Some(error!(SolarBError::PoolDeserializeFailed))
+        );
+    }
+
+    #[test]
+    fn test_build_swap_ix_follows_direction() {
+        let pool = create_test_pool();
+        let accounts = create_swap_test_accounts(&pool);
+        let meteora = MeteoraDammV2::new(&accounts).unwrap();
+        let user = SwapUserAccounts {
+            authority: Pubkey::new_unique(),
+            base_token_program: anchor_spl::token::ID,
+            quote_token_program: anchor_spl::token::ID,
+            user_base_token_account: Pubkey::new_unique(),
+            user_quote_token_account: Pubkey::new_unique(),
+        };
+
+        for (direction, input, output) in [
+            (
+                EdgeSide::LeftToRight,
+                user.user_base_token_account,
+                user.user_quote_token_account,
+            ),
+            (
+                EdgeSide::RightToLeft,
+                user.user_quote_token_account,
+                user.user_base_token_account,
+            ),
+        ] {
+            let ix = meteora.build_swap_ix(direction, 1_000, 990, &user).unwrap();
+            assert_eq!(ix.program_id, MeteoraDammV2::PROGRAM_ID);
+            assert_eq!(ix.accounts.len(), 14);
+            assert_eq!(ix.data[..8], MeteoraDammV2::SWAP_DISCRIMINATOR);
+            assert_eq!(ix.data[8..16], 1_000u64.to_le_bytes());
+            assert_eq!(ix.data[16..24], 990u64.to_le_bytes());
+            assert_eq!(ix.accounts[2].pubkey, input);
+            assert_eq!(ix.accounts[3].pubkey, output);
+            assert_eq!(ix.accounts[8], AccountMeta::new(user.authority, true));
+        }
+    }
+
+    #[test]
+    fn test_swap_base_in_applies_scheduled_and_dynamic_fee() {
+        use damm_v2::state::fee::BaseFeeMode;
+
+        let clock = activated_clock();
+        let mut pool = create_test_pool();
+        // A linear scheduler from a 5% cliff, 0.4% lower every minute, two minutes in
+        pool.activation_type = ActivationType::Timestamp as u8;
+        pool.activation_point = clock.unix_timestamp as u64 - 120;
+        pool.pool_fees.base_fee.base_fee_mode = BaseFeeMode::FeeSchedulerLinear as u8;
+        pool.pool_fees.base_fee.cliff_fee_numerator = 50_000_000;
+        pool.pool_fees.base_fee.first_factor = 10;
+        pool.pool_fees.base_fee.second_factor = 60u64.to_le_bytes();
+        pool.pool_fees.base_fee.third_factor = 4_000_000;
+        pool.pool_fees.dynamic_fee.initialized = 0;
+
+        let quote = |pool: &Pool| {
+            let accounts = create_swap_test_accounts(pool);
+            let meteora = MeteoraDammV2::new(&accounts).unwrap();
+            meteora
+                .swap_base_in(pool.token_a_mint, 1_000_000_000, clock.clone())
+                .unwrap()
+        };
+        let fee_numerator = |pool: &Pool| {
+            pool.pool_fees
+                .get_total_trading_fee_from_included_fee_amount(
+                    clock.unix_timestamp as u64,
+                    pool.activation_point,
+                    1_000_000_000,
+                    damm_v2::TradeDirection::AtoB,
+                    u64::MAX,
+                )
+                .unwrap()
+        };
+        let without_dynamic_fee = quote(&pool);
+        assert_eq!(fee_numerator(&pool), 42_000_000);
+
+        // Volatility 100_000 on 10 bps bins at a 100_000 fee control adds
+        // (100_000 * 10)^2 * 100_000 / 1e11 = 1_000_000, another 0.1%
+        pool.pool_fees.dynamic_fee.initialized = 1;
+        pool.pool_fees.dynamic_fee.bin_step = 10;
+        pool.pool_fees.dynamic_fee.variable_fee_control = 100_000;
+        pool.pool_fees.dynamic_fee.volatility_accumulator = 100_000;
+        let with_dynamic_fee = quote(&pool);
+        assert_eq!(fee_numerator(&pool), 43_000_000);
+
+        assert!(with_dynamic_fee < without_dynamic_fee);
+        // The fee comes off one side of the swap, so the quotes differ by about the extra 0.1%
+        let gap = (without_dynamic_fee - with_dynamic_fee) as f64 / without_dynamic_fee as f64;
+        assert!((gap - 0.001 / 0.958).abs() < 1e-4, "gap {}", gap);
+    }
+
+    #[test]
+    fn test_swap_base_in_takes_fee_on_collect_fee_mode_side() {
+        use damm_v2::state::fee::BaseFeeMode;
+        use damm_v2::state::pool::CollectFeeMode;
+
+        let clock = activated_clock();
+        let mut pool = create_test_pool();
+        // A flat 1% fee: no scheduler periods and no dynamic fee
+        pool.activation_type = ActivationType::Timestamp as u8;
+        pool.activation_point = clock.unix_timestamp as u64 - 120;
+        pool.pool_fees.base_fee.base_fee_mode = BaseFeeMode::FeeSchedulerLinear as u8;
+        pool.pool_fees.base_fee.cliff_fee_numerator = 10_000_000;
+        pool.pool_fees.base_fee.first_factor = 0;
+        pool.pool_fees.base_fee.second_factor = 0u64.to_le_bytes();
+        pool.pool_fees.base_fee.third_factor = 0;
+        pool.pool_fees.dynamic_fee.initialized = 0;
+        let mut fee_free = pool;
+        fee_free.pool_fees.base_fee.cliff_fee_numerator = 0;
+
+        let quote = |pool: &Pool, collect_fee_mode: CollectFeeMode, input_mint, amount_in| {
+            let mut pool = *pool;
+            pool.collect_fee_mode = collect_fee_mode as u8;
+            let accounts = create_swap_test_accounts(&pool);
+            let meteora = MeteoraDammV2::new(&accounts).unwrap();
+            meteora
+                .swap_base_in(input_mint, amount_in, clock.clone())
+                .unwrap()
+        };
+        let amount_in = 1_000_000_000;
+        let fee_on_input = amount_in / 100;
+
+        // B to A: OnlyB takes the fee off the B going in, BothToken off the A coming out
+        let token_b = pool.token_b_mint;
+        let only_b = quote(&pool, CollectFeeMode::OnlyB, token_b, amount_in);
+        let reduced_in = amount_in - fee_on_input;
+        assert_eq!(
+            only_b,
+            quote(&fee_free, CollectFeeMode::OnlyB, token_b, reduced_in)
+        );
+        let both_token = quote(&pool, CollectFeeMode::BothToken, token_b, amount_in);
+        let fee_free_out = quote(&fee_free, CollectFeeMode::BothToken, token_b, amount_in);
+        assert_eq!(both_token, fee_free_out - fee_free_out.div_ceil(100));
+        // Less input moves the price less, so the fee on input leaves more output
+        assert!(only_b > both_token);
+
+        // A to B: both modes take the fee off the B coming out
+        let token_a = pool.token_a_mint;
+        let fee_free_out = quote(&fee_free, CollectFeeMode::OnlyB, token_a, amount_in);
+        for collect_fee_mode in [CollectFeeMode::OnlyB, CollectFeeMode::BothToken] {
+            assert_eq!(
+                quote(&pool, collect_fee_mode, token_a, amount_in),
+                fee_free_out - fee_free_out.div_ceil(100)
+            );
+        }
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_from_state_quotes_like_the_accounts() {
+        let clock = activated_clock();
+        let pool = create_test_pool();
+        let accounts = create_swap_test_accounts(&pool);
+        let meteora = MeteoraDammV2::new(&accounts).unwrap();
+        let state = MeteoraDammV2::from_state(
+            pool,
+            (1_000_000_000, 1_000_000_000),
+            (pool.token_a_mint, pool.token_b_mint),
+        );
+
+        assert_eq!(state.get_mints(), meteora.get_mints());
+        assert_eq!(
+            state.compute_price_swap_base_in(),
+            meteora
+                .compute_price_swap_base_in(1_000_000_000, 1_000_000_000)
+                .unwrap()
+        );
+        assert_eq!(
+            state.compute_price_swap_base_out(),
+            meteora
+                .compute_price_swap_base_out(1_000_000_000, 1_000_000_000)
+                .unwrap()
+        );
+        for input_mint in [pool.token_a_mint, pool.token_b_mint] {
+            assert_eq!(
+                state.swap_base_in(input_mint, 1_000_000, &clock).unwrap(),
+                meteora
+                    .swap_base_in(input_mint, 1_000_000, clock.clone())
+                    .unwrap()
+            );
+            assert_eq!(
+                state.swap_base_out(input_mint, 1_000_000, &clock).unwrap(),
+                meteora
+                    .swap_base_out(input_mint, 1_000_000, clock.clone())
+                    .unwrap()
+            );
+        }
+    }
+
+    fn position_test_accounts() -> Vec<AccountInfo<'static>> {
+        (0..PositionAccounts::LEN)
+            .map(|_| create_mock_account_info(Pubkey::new_unique(), system_program::id(), None))
+            .collect()
+    }
+
+    #[test]
+    fn test_liquidity_discriminators_match_cp_amm() {
+        use const_crypto::sha2::Sha256;
+
+        for (name, discriminator) in [
+            ("swap", MeteoraDammV2::SWAP_DISCRIMINATOR),
+            ("add_liquidity", MeteoraDammV2::ADD_LIQUIDITY_DISCRIMINATOR),
+            (
+                "remove_liquidity",
+                MeteoraDammV2::REMOVE_LIQUIDITY_DISCRIMINATOR,
+            ),
+        ] {
+            let preimage = format!("global:{}", name);
+            assert_eq!(
+                Sha256::new().update(preimage.as_bytes()).finalize()[..8],
+                discriminator,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_liquidity_ix() {
+        let pool = create_test_pool();
+        let accounts = create_swap_test_accounts(&pool);
+        let meteora = MeteoraDammV2::new(&accounts).unwrap();
+        let position_accounts = position_test_accounts();
+        let position = PositionAccounts::new(&position_accounts).unwrap();
+        let mut data = LiquidityData {
+            remove: false,
+            liquidity_delta: 1 << 70,
+            token_a_amount_threshold: 1_000,
+            token_b_amount_threshold: 2_000,
+        };
+
+        let add = meteora.build_liquidity_ix(&data, &position);
+        assert_eq!(add.program_id, MeteoraDammV2::PROGRAM_ID);
+        assert_eq!(add.data[..8], MeteoraDammV2::ADD_LIQUIDITY_DISCRIMINATOR);
+        assert_eq!(add.data[8..24], (1u128 << 70).to_le_bytes());
+        assert_eq!(add.data[24..32], 1_000u64.to_le_bytes());
+        assert_eq!(add.data[32..40], 2_000u64.to_le_bytes());
+        assert_eq!(add.data.len(), 40);
+        assert_eq!(
+            add.accounts,
+            vec![
+                AccountMeta::new(*accounts[1].key, false),
+                AccountMeta::new(*position.position.key, false),
+                AccountMeta::new(*position.user_base_token_account.key, false),
+                AccountMeta::new(*position.user_quote_token_account.key, false),
+                AccountMeta::new(pool.token_a_vault, false),
+                AccountMeta::new(pool.token_b_vault, false),
+                AccountMeta::new_readonly(pool.token_a_mint, false),
+                AccountMeta::new_readonly(pool.token_b_mint, false),
+                AccountMeta::new_readonly(*position.position_nft_account.key, false),
+                AccountMeta::new_readonly(*position.owner.key, true),
+                AccountMeta::new_readonly(*position.base_token_program.key, false),
+                AccountMeta::new_readonly(*position.quote_token_program.key, false),
+                AccountMeta::new_readonly(*accounts[7].key, false),
+                AccountMeta::new_readonly(MeteoraDammV2::PROGRAM_ID, false),
+            ]
+        );
+
+        // Removing takes the same accounts behind the pool authority
+        data.remove = true;
+        let remove = meteora.build_liquidity_ix(&data, &position);
+        assert_eq!(
+            remove.data[..8],
+            MeteoraDammV2::REMOVE_LIQUIDITY_DISCRIMINATOR
+        );
+        assert_eq!(remove.data[8..], add.data[8..]);
+        assert_eq!(
+            remove.accounts[0],
+            AccountMeta::new_readonly(*accounts[6].key, false)
+        );
+        assert_eq!(remove.accounts[1..], add.accounts[..]);
+    }
+
+    #[test]
+    fn test_position_accounts_need_every_account() {
+        let accounts = position_test_accounts();
+        assert_eq!(
+            PositionAccounts::new(&accounts[..PositionAccounts::LEN - 1]).err(),
+            Some(ProgramError::NotEnoughAccountKeys.into())
+        );
+        let position = PositionAccounts::new(&accounts).unwrap();
+        assert_eq!(position.owner.key, accounts[0].key);
+        assert_eq!(position.quote_token_program.key, accounts[6].key);
+    }
 }
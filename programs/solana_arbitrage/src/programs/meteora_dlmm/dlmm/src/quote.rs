@@ -44,13 +44,37 @@ fn validate_swap_activation(
     Ok(())
 }
 
+/// A bin array the swap reaches was not supplied, so the quote would be a partial fill
+#[derive(Debug)]
+pub struct BinArrayNotFound(pub Pubkey);
+
+impl std::fmt::Display for BinArrayNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Bin array {} not found", self.0)
+    }
+}
+
+impl std::error::Error for BinArrayNotFound {}
+
+/// An exact-in swap crosses more bins than the quote was allowed to
+#[derive(Debug)]
+pub struct BinLimitExceeded(pub usize);
+
+impl std::fmt::Display for BinLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Swap crosses more than {} bins", self.0)
+    }
+}
+
+impl std::error::Error for BinLimitExceeded {}
+
 #[allow(clippy::too_many_arguments)]
 pub fn quote_exact_out<'a>(
     lb_pair_pubkey: Pubkey,
     lb_pair: &LbPair,
     mut amount_out: u64,
     swap_for_y: bool,
-    bin_arrays: &HashMap<Pubkey, BinArray>,
+    bin_arrays: Vec<AccountInfo<'a>>,
     bitmap_extension: Option<&BinArrayBitmapExtension>,
     clock: &Clock,
     mint_x_account: &InterfaceAccount<'a, anchor_spl::token_interface::Mint>,
@@ -77,6 +101,13 @@ pub fn quote_exact_out<'a>(
     amount_out =
         calculate_transfer_fee_included_amount(out_mint_account, amount_out, epoch)?.amount;
 
+    // Same in-place bin reads as quote_exact_in, so no BinArray is deserialized whole
+    const BIN_ARRAY_HEADER_SIZE: usize = 56;
+    const BIN_SIZE: usize = 144;
+
+    let bin_arrays_map: HashMap<Pubkey, &AccountInfo> =
+        bin_arrays.iter().map(|acc| (*acc.key, acc)).collect();
+
     while amount_out > 0 {
         let active_bin_array_pubkey = get_bin_array_pubkeys_for_swap(
             lb_pair_pubkey,
@@ -88,16 +119,28 @@ pub fn quote_exact_out<'a>(
         .pop()
         .context("Pool out of liquidity")?;
 
-        let active_bin_array = bin_arrays
+        // Unlike an exact-in quote, stopping short would understate the input
+        let active_bin_array_account = bin_arrays_map
             .get(&active_bin_array_pubkey)
-            .context("Active bin array not found")?;
+            .ok_or(BinArrayNotFound(active_bin_array_pubkey))?;
 
-        // Use only the index for range checking (stack-safe, 8 bytes)
-        let bin_array_index = active_bin_array.index as i32;
-
-        // Cache range calculation once per bin array (doesn't change within inner loop)
+        let bin_array_data = active_bin_array_account.try_borrow_data()?;
+        let bin_array_index: i64 = bytemuck::pod_read_unaligned(
+            bin_array_data
+                .get(8..16)
+                .context("Bin array account too short")?,
+        );
         let (lower_bin_id, upper_bin_id) =
-            BinArray::get_bin_array_lower_upper_bin_id(bin_array_index)?;
+            BinArray::get_bin_array_lower_upper_bin_id(bin_array_index as i32)?;
+
+        // The bitmap skips empty arrays, so enter the next one from its near edge
+        if lb_pair.active_id < lower_bin_id || lb_pair.active_id > upper_bin_id {
+            lb_pair.active_id = if swap_for_y {
+                upper_bin_id
+            } else {
+                lower_bin_id
+            };
+        }
 
         loop {
             // Early exit checks
@@ -116,16 +159,14 @@ pub fn quote_exact_out<'a>(
                 .active_id
                 .checked_sub(lower_bin_id)
                 .context("MathOverflow")?;
-            let bin_index_usize = bin_index_in_array as usize;
+            let bin_offset = BIN_ARRAY_HEADER_SIZE + (bin_index_in_array as usize * BIN_SIZE);
 
-            ensure!(
-                bin_index_usize < MAX_BIN_PER_ARRAY,
-                "Bin index out of bounds"
+            // Read single bin from account data (only ~144 bytes on stack)
+            let mut active_bin: Bin = bytemuck::pod_read_unaligned(
+                bin_array_data
+                    .get(bin_offset..bin_offset + BIN_SIZE)
+                    .context("Bin array account too short")?,
             );
-
-            // Clone only the specific bin we need (~144 bytes, acceptable on stack)
-            // This avoids cloning the entire BinArray (~10KB)
-            let mut active_bin = active_bin_array.bins[bin_index_usize];
             let price = active_bin.get_or_store_bin_price(lb_pair.active_id, lb_pair.bin_step)?;
 
             if !active_bin.is_empty(!swap_for_y) {
@@ -176,6 +217,9 @@ pub fn quote_exact_out<'a>(
     })
 }
 
+/// Exact-in quote through the supplied `bin_arrays`. Fails with `BinArrayNotFound` when the
+/// swap reaches a bin array that wasn't supplied, rather than quoting a partial fill, and
+/// with `BinLimitExceeded` once it crosses more than `max_bins` bins (0 for no limit).
 #[allow(clippy::too_many_arguments)]
 pub fn quote_exact_in<'a>(
     lb_pair_pubkey: Pubkey,
@@ -187,6 +231,7 @@ pub fn quote_exact_in<'a>(
     clock: &Clock,
     mint_x_account: &InterfaceAccount<'a, anchor_spl::token_interface::Mint>,
     mint_y_account: &InterfaceAccount<'a, anchor_spl::token_interface::Mint>,
+    max_bins: usize,
 ) -> anyhow::Result<SwapExactInQuote> {
     let current_timestamp: u64 = clock.unix_timestamp as u64;
     let current_slot = clock.slot;
@@ -205,6 +250,7 @@ pub fn quote_exact_in<'a>(
     let transfer_fee_excluded_amount_in =
         calculate_transfer_fee_excluded_amount(in_mint_account, amount_in, epoch)?.amount;
     let mut amount_left = transfer_fee_excluded_amount_in;
+    let mut bins: usize = 0;
 
     // Constants moved outside loop for better performance
     const BIN_ARRAY_HEADER_SIZE: usize = 56;
@@ -224,34 +270,31 @@ pub fn quote_exact_in<'a>(
         )?
         .pop()
         .context("Pool out of liquidity")?;
-        // msg!("12: {}", active_bin_array_pubkey);
-        let active_bin_array_account = match bin_arrays_map.get(&active_bin_array_pubkey) {
-            Some(account) => *account,
-            None => {
-                // msg!("12: ERROR - Required bin array {} not found in provided accounts, insufficient liquidity", active_bin_array_pubkey);
-                // msg!(
-                //     "12: Current amount_left: {}, total_amount_out so far: {}",
-                //     amount_left,
-                //     total_amount_out
-                // );
-                // // We don't have the required bin array account - stop the swap
-                // // This means we've exhausted the available bin arrays
-                // // Return partial result if we made some progress, otherwise it's an error
-                // if total_amount_out == 0 {
-                //     return Err(anyhow::anyhow!(
-                //         "Insufficient liquidity: required bin array not available"
-                //     ));
-                // }
-                break;
-            }
-        };
+        // Stopping short of an array the swap reaches would quote a partial fill
+        let active_bin_array_account = bin_arrays_map
+            .get(&active_bin_array_pubkey)
+            .ok_or(BinArrayNotFound(active_bin_array_pubkey))?;
 
         let bin_array_data = active_bin_array_account.try_borrow_data()?;
         // Read only the index field (offset 8, size 8) to avoid deserializing entire BinArray
-        let bin_array_index: i64 = bytemuck::pod_read_unaligned(&bin_array_data[8..16]);        // Cache range calculation once per bin array (doesn't change within inner loop)
+        let bin_array_index: i64 = bytemuck::pod_read_unaligned(
+            bin_array_data
+                .get(8..16)
+                .context("Bin array account too short")?,
+        );
+        // Cache range calculation once per bin array (doesn't change within inner loop)
         let (lower_bin_id, upper_bin_id) =
             BinArray::get_bin_array_lower_upper_bin_id(bin_array_index as i32)?;
 
+        // The bitmap skips empty arrays, so enter the next one from its near edge
+        if lb_pair.active_id < lower_bin_id || lb_pair.active_id > upper_bin_id {
+            lb_pair.active_id = if swap_for_y {
+                upper_bin_id
+            } else {
+                lower_bin_id
+            };
+        }
+
         loop {
             // Early exit checks
             if amount_left == 0 {
@@ -261,6 +304,10 @@ pub fn quote_exact_in<'a>(
             if lb_pair.active_id < lower_bin_id || lb_pair.active_id > upper_bin_id {
                 break;
             }
+            bins += 1;
+            if max_bins > 0 && bins > max_bins {
+                return Err(BinLimitExceeded(max_bins).into());
+            }
             lb_pair.update_volatility_accumulator()?;
 
             // Calculate bin index within array
@@ -270,12 +317,14 @@ pub fn quote_exact_in<'a>(
                 .context("MathOverflow")?;
             let bin_index_usize = bin_index_in_array as usize;
             // msg!("15");
-            // Calculate bin offset (bounds check removed - validated by range check above)
             let bin_offset = BIN_ARRAY_HEADER_SIZE + (bin_index_usize * BIN_SIZE);
 
             // Read single bin from account data (only ~144 bytes on stack)
-            let mut active_bin: Bin =
-                bytemuck::pod_read_unaligned(&bin_array_data[bin_offset..bin_offset + BIN_SIZE]);
+            let mut active_bin: Bin = bytemuck::pod_read_unaligned(
+                bin_array_data
+                    .get(bin_offset..bin_offset + BIN_SIZE)
+                    .context("Bin array account too short")?,
+            );
 
             let price = active_bin.get_or_store_bin_price(lb_pair.active_id, lb_pair.bin_step)?;
 
@@ -502,18 +551,7 @@ mod tests {
             .await
             .unwrap();
 
-        // Create HashMap for quote_exact_out (which still uses HashMap)
-        let bin_arrays = accounts
-            .iter()
-            .zip(bin_array_pubkeys.iter())
-            .filter_map(|(account_opt, key)| {
-                account_opt
-                    .as_ref()
-                    .map(|account| (*key, bytemuck::pod_read_unaligned(&account.data[8..])))
-            })
-            .collect::<HashMap<_, _>>();
-
-        // Create Vec<AccountInfo> for quote_exact_in (stack-safe approach)
+        // Create Vec<AccountInfo> for both quotes (stack-safe approach)
         let bin_array_account_infos: Vec<AccountInfo> = accounts
             .into_iter()
             .zip(bin_array_pubkeys.into_iter())
@@ -533,7 +571,7 @@ mod tests {
             &lb_pair,
             out_sol_amount,
             false,
-            &bin_arrays,
+            bin_array_account_infos.clone(),
             None,
             &clock,
             &mint_x_account,
@@ -558,6 +596,7 @@ mod tests {
             &clock,
             &mint_x_account,
             &mint_y_account,
+            0,
         )
         .unwrap();
 
@@ -574,7 +613,7 @@ mod tests {
             &lb_pair,
             out_usdc_amount,
             true,
-            &bin_arrays,
+            bin_array_account_infos.clone(),
             None,
             &clock,
             &mint_x_account,
@@ -599,6 +638,7 @@ mod tests {
             &clock,
             &mint_x_account,
             &mint_y_account,
+            0,
         )
         .unwrap();
 
@@ -674,6 +714,7 @@ mod tests {
             &clock,
             &mint_x_account,
             &mint_y_account,
+            0,
         )
         .unwrap();
 
@@ -695,6 +736,7 @@ mod tests {
             &clock,
             &mint_x_account,
             &mint_y_account,
+            0,
         )
         .unwrap();
 
@@ -1,4 +1,11 @@
-use super::super::programs::ProgramMeta;
+use super::super::programs::{
+    programs::{check_recorded_keys, check_vault_mints, marginal_price, read_mint_decimals},
+    ProgramMeta, SolarBError,
+};
+use crate::arbitrage::base::EdgeSide;
+use crate::utils::utils::{
+    check_event_authority, resolve_swap_accounts, ResolvedAccounts, SwapUserAccounts,
+};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
     account_info::next_account_info,
@@ -8,10 +15,44 @@ use anchor_lang::solana_program::{
     pubkey::Pubkey,
 };
 use anchor_spl::token::spl_token::native_mint;
-use dlmm::dlmm::accounts::{BinArrayBitmapExtension, LbPair};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_hook, StateWithExtensions},
+};
+use anchor_spl::token_interface::Mint;
+use dlmm::dlmm::accounts::{BinArray, BinArrayBitmapExtension, LbPair};
+use dlmm::dlmm::types::{Bin, PairStatus};
+use dlmm::extensions::{BinArrayExtension, BinExtension, LbPairExtension};
 use dlmm::pda;
-use dlmm::quote::quote_exact_in;
+use dlmm::quote::{quote_exact_in, quote_exact_out, BinArrayNotFound, BinLimitExceeded};
 use dlmm::token::load_mint;
+use std::cell::OnceCell;
+
+/// Seed of the PDA a transfer hook program reads its extra account metas from
+const EXTRA_ACCOUNT_METAS_SEED: &[u8] = b"extra-account-metas";
+
+/// `AccountsType` of the `remaining_accounts_info` slices carrying the X and Y mint hooks
+const ACCOUNTS_TYPE_TRANSFER_HOOK_X: u8 = 0;
+const ACCOUNTS_TYPE_TRANSFER_HOOK_Y: u8 = 1;
+
+/// Transfer hook program of a Token-2022 `mint` and the extra account metas PDA it
+/// validates against, or `None` for a mint without a hook
+fn transfer_hook_accounts(mint: &AccountInfo) -> Result<Option<[Pubkey; 2]>> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(None);
+    }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)
+        .map_err(|_| error!(SolarBError::MintLoadFailed))?;
+    let Some(hook_program) = transfer_hook::get_program_id(&state) else {
+        return Ok(None);
+    };
+    let (extra_account_metas, _) = Pubkey::find_program_address(
+        &[EXTRA_ACCOUNT_METAS_SEED, mint.key.as_ref()],
+        &hook_program,
+    );
+    Ok(Some([hook_program, extra_account_metas]))
+}
 
 #[derive(Clone)]
 pub struct MeteoraDlmm<'info> {
@@ -30,13 +71,30 @@ pub struct MeteoraDlmm<'info> {
     // pub bitmap_extension: AccountInfo<'info>,
     // pub bin_arrays_buy: Option<Vec<AccountInfo<'info>>>,
     // pub bin_arrays_sell: Option<Vec<AccountInfo<'info>>>,
+    /// Buy then sell bin arrays in the span's tail, when the instruction says how many;
+    /// without them the tail is split at the SOL mint separator
+    pub bin_array_counts: Option<(u8, u8)>,
+    /// Bins an exact-in quote may cross before failing with `QuoteTooDeep`, 0 for no limit
+    pub max_bins: usize,
+    mints: OnceCell<MintPair<'info>>,
 }
 
+/// Base then quote mint, as `quote_exact_in` and `quote_exact_out` take them
+pub type MintPair<'info> = (InterfaceAccount<'info, Mint>, InterfaceAccount<'info, Mint>);
+
 impl<'info> ProgramMeta for MeteoraDlmm<'info> {
     fn get_id(&self) -> &Pubkey {
         &Self::PROGRAM_ID
     }
 
+    fn program_label(&self) -> &'static str {
+        "MeteoraDlmm"
+    }
+
+    fn pool_key(&self) -> &Pubkey {
+        self.pool_id.key
+    }
+
     fn get_vaults(&self) -> (&AccountInfo<'_>, &AccountInfo<'_>) {
         unsafe {
             (
@@ -46,12 +104,46 @@ impl<'info> ProgramMeta for MeteoraDlmm<'info> {
         }
     }
 
+    fn get_mints(&self) -> (&Pubkey, &Pubkey) {
+        (self.base_token.key, self.quote_token.key)
+    }
+
+    fn mint_decimals(&self) -> (u8, u8) {
+        read_mint_decimals(&self.base_token, &self.quote_token)
+    }
+
+    /// The reserve balances less the protocol fees the pair has accrued in them
+    fn reserves(&self, _clock: &Clock) -> Result<(u128, u128)> {
+        let protocol_fee = self.lb_pair()?.protocol_fee;
+        let (base_amount, quote_amount) = self.vault_reserves()?;
+        Ok((
+            base_amount.saturating_sub(protocol_fee.amount_x as u128),
+            quote_amount.saturating_sub(protocol_fee.amount_y as u128),
+        ))
+    }
+
+    fn compute_price_swap_base_in(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        Ok(marginal_price(
+            base_amount,
+            quote_amount,
+            self.total_fee_rate()?,
+        ))
+    }
+
+    fn compute_price_swap_base_out(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        Ok(marginal_price(
+            quote_amount,
+            base_amount,
+            self.total_fee_rate()?,
+        ))
+    }
+
     fn swap_base_in(&self, input_mint: Pubkey, amount_in: u64, clock: Clock) -> Result<u64> {
         self.swap_base_in_impl(input_mint, amount_in, clock)
     }
 
-    fn swap_base_out(&self, input_mint: Pubkey, amount_in: u64, clock: Clock) -> Result<u64> {
-        self.swap_base_in_impl(input_mint, amount_in, clock)
+    fn swap_base_out(&self, input_mint: Pubkey, amount_out: u64, clock: Clock) -> Result<u64> {
+        self.swap_base_out_impl(input_mint, amount_out, clock)
     }
 
     fn invoke_swap_base_in<'a>(
@@ -59,7 +151,7 @@ impl<'info> ProgramMeta for MeteoraDlmm<'info> {
         input_mint: Pubkey,
         max_amount_in: u64,
         amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -71,7 +163,7 @@ impl<'info> ProgramMeta for MeteoraDlmm<'info> {
             input_mint,
             max_amount_in,
             amount_out,
-            payer,
+            authority,
             user_mint_1_token_account,
             user_mint_2_token_account,
             mint_1_account,
@@ -86,7 +178,7 @@ impl<'info> ProgramMeta for MeteoraDlmm<'info> {
         input_mint: Pubkey,
         amount_in: u64,
         min_amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -98,7 +190,7 @@ impl<'info> ProgramMeta for MeteoraDlmm<'info> {
             input_mint,
             amount_in,
             min_amount_out,
-            payer,
+            authority,
             user_mint_1_token_account,
             user_mint_2_token_account,
             mint_1_account,
@@ -108,6 +200,105 @@ impl<'info> ProgramMeta for MeteoraDlmm<'info> {
         )
     }
 
+    /// Both directions run `swap2` of `amount_in` for at least `min_out`, sells through the
+    /// buy bin arrays and buys through the sell ones
+    fn build_swap_ix(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        user_accounts: &SwapUserAccounts,
+    ) -> Result<Instruction> {
+        let oracle = &self.accounts[6];
+        let host_fee_in = &self.accounts[7];
+        let memo = &self.accounts[8];
+        let event_authority = &self.accounts[9];
+        let bitmap_extension = &self.accounts[10];
+
+        let bin_arrays = match direction {
+            EdgeSide::LeftToRight => self.get_bin_arrays_buy(),
+            EdgeSide::RightToLeft => self.get_bin_arrays_sell(),
+        };
+        let mut metas = vec![
+            AccountMeta::new(*self.pool_id.key, false),
+            AccountMeta::new(*bitmap_extension.key, false),
+            AccountMeta::new(*self.base_vault.key, false),
+            AccountMeta::new(*self.quote_vault.key, false),
+            AccountMeta::new(user_accounts.user_base_token_account, false),
+            AccountMeta::new(user_accounts.user_quote_token_account, false),
+            AccountMeta::new_readonly(*self.base_token.key, false),
+            AccountMeta::new_readonly(*self.quote_token.key, false),
+            AccountMeta::new(*oracle.key, false),
+            AccountMeta::new(*host_fee_in.key, false),
+            AccountMeta::new(user_accounts.authority, true),
+            AccountMeta::new_readonly(user_accounts.base_token_program, false),
+            AccountMeta::new_readonly(user_accounts.quote_token_program, false),
+            AccountMeta::new_readonly(*memo.key, false),
+            AccountMeta::new_readonly(*event_authority.key, false),
+            AccountMeta::new_readonly(Self::PROGRAM_ID, false),
+        ];
+        // The transfer hook accounts come first in the remaining accounts, each mint's in the
+        // slice `remaining_accounts_info` describes, then the bin arrays
+        let hook_slices = self.transfer_hook_slices()?;
+        for (_, hook_accounts) in &hook_slices {
+            metas.extend(
+                hook_accounts
+                    .iter()
+                    .map(|key| AccountMeta::new_readonly(*key, false)),
+            );
+        }
+        metas.extend(
+            bin_arrays
+                .unwrap_or_default()
+                .iter()
+                .map(|account| AccountMeta::new(*account.key, false)),
+        );
+
+        let mut data = Self::SWAP2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&min_out.to_le_bytes());
+        // RemainingAccountsInfo { slices: Vec<RemainingAccountsSlice { accounts_type, length }> }
+        data.extend_from_slice(&(hook_slices.len() as u32).to_le_bytes());
+        for (accounts_type, hook_accounts) in &hook_slices {
+            data.push(*accounts_type);
+            data.push(hook_accounts.len() as u8);
+        }
+
+        Ok(Instruction {
+            program_id: Self::PROGRAM_ID,
+            accounts: metas,
+            data,
+        })
+    }
+
+    fn estimate_compute_units(&self) -> u32 {
+        // The direction is not known yet, so budget for the side with more bin arrays
+        let bin_arrays = self
+            .get_bin_arrays_buy()
+            .map_or(0, |arrays| arrays.len())
+            .max(self.get_bin_arrays_sell().map_or(0, |arrays| arrays.len()));
+        Self::SWAP_COMPUTE_UNITS
+            .saturating_add(Self::BIN_ARRAY_COMPUTE_UNITS.saturating_mul(bin_arrays as u32))
+    }
+
+    fn is_tradable(&self, _clock: &Clock) -> Result<bool> {
+        Ok(matches!(self.lb_pair()?.status(), Ok(PairStatus::Enabled)))
+    }
+
+    fn validate_accounts(&self) -> Result<()> {
+        check_vault_mints(self)?;
+        let lb_pair = self.lb_pair()?;
+        check_recorded_keys(
+            self.pool_id.key,
+            &[
+                ("token_x_mint", lb_pair.token_x_mint, self.base_token.key),
+                ("token_y_mint", lb_pair.token_y_mint, self.quote_token.key),
+                ("reserve_x", lb_pair.reserve_x, self.base_vault.key),
+                ("reserve_y", lb_pair.reserve_y, self.quote_vault.key),
+            ],
+        )
+    }
+
     fn log_accounts(&self) -> Result<()> {
         let stored_accounts = self.accounts.clone();
         let program_id = &stored_accounts[0];
@@ -160,7 +351,25 @@ impl<'info> ProgramMeta for MeteoraDlmm<'info> {
 impl<'info> MeteoraDlmm<'info> {
     pub const PROGRAM_ID: Pubkey =
         Pubkey::from_str_const("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo");
+    /// Shortest span `find_program_instance` accepts, program id included: the 11 fixed
+    /// accounts and a bin array on each side, more of which may follow
+    pub const EXPECTED_ACCOUNTS: usize = 13;
+    /// Typical compute units of one swap CPI, excluding the bin arrays it walks
+    pub const SWAP_COMPUTE_UNITS: u32 = 50_000;
+    /// Additional compute units for each supplied bin array the swap may load and cross
+    pub const BIN_ARRAY_COMPUTE_UNITS: u32 = 20_000;
+    /// Anchor discriminator of `swap2`
+    pub const SWAP2_DISCRIMINATOR: [u8; 8] = [65, 75, 63, 76, 235, 91, 91, 136];
     pub fn new(accounts: &[AccountInfo<'info>]) -> Result<Self> {
+        Self::new_with_bin_array_counts(accounts, None)
+    }
+
+    /// `new` over a span whose tail holds exactly `bin_array_counts` buy then sell bin
+    /// arrays, besides any transfer hook accounts, so no SOL mint separator is needed
+    pub fn new_with_bin_array_counts(
+        accounts: &[AccountInfo<'info>],
+        bin_array_counts: Option<(u8, u8)>,
+    ) -> Result<Self> {
         let mut iter = accounts.iter();
         let program_id = next_account_info(&mut iter)?; // 0
         let pool_id = next_account_info(&mut iter)?; // 1
@@ -174,13 +383,18 @@ impl<'info> MeteoraDlmm<'info> {
                                                          // let event_authority = next_account_info(&mut iter)?; // 9
                                                          // let bin_array_bitmap_extension = next_account_info(&mut iter)?; // 10
 
-        // Handle bin_arrays: they are split by SOL MINT account
+        let event_authority = accounts.get(9).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        check_event_authority(&Self::PROGRAM_ID, event_authority)?;
+
+        // Handle bin_arrays: they are split by SOL MINT account, or by the explicit counts
         // Structure: [fixed accounts] [bin_arrays_buy...] [SOL_MINT] [bin_arrays_sell...]
         // We've consumed 11 accounts (0-10), so remaining start at index 11
+        // Transfer hook accounts of Token-2022 mints may also sit in the tail; they are
+        // skipped when picking the bin arrays
         // let bin_arrays_buy = self.get_bin_arrays_buy();
         // let bin_arrays_sell = self.get_bin_arrays_sell();
-        
-        Ok(MeteoraDlmm {
+
+        let meteora = MeteoraDlmm {
             accounts: accounts.to_vec(),
             program_id: program_id.clone(),
             pool_id: pool_id.clone(),
@@ -196,71 +410,237 @@ impl<'info> MeteoraDlmm<'info> {
             // bitmap_extension: bin_array_bitmap_extension.clone(),
             // bin_arrays_buy: bin_arrays_buy.clone(),
             // bin_arrays_sell: bin_arrays_sell.clone(),
-        })
+            bin_array_counts,
+            max_bins: 0,
+            mints: OnceCell::new(),
+        };
+        if let Some((buy, sell)) = bin_array_counts {
+            let tail_len = meteora.bin_array_tail().len();
+            if tail_len != buy as usize + sell as usize {
+                msg!(
+                    "DLMM pool {} carries {} bin arrays, expected {} buy and {} sell",
+                    pool_id.key,
+                    tail_len,
+                    buy,
+                    sell
+                );
+                return Err(error!(SolarBError::AccountSpanMismatch));
+            }
+        }
+        Ok(meteora)
     }
 
-    /// Extract bin arrays for buying from accounts starting at index 11
-    /// Structure: [fixed accounts] [bin_arrays_buy...] [SOL_MINT] [bin_arrays_sell...]
-    fn get_bin_arrays_buy(&self) -> Option<Vec<AccountInfo<'info>>> {
-        if self.accounts.len() <= 11 {
-            return None;
+    /// Transfer hook accounts of the X then the Y mint, each with the `AccountsType` of its
+    /// `remaining_accounts_info` slice; mints without a hook are left out
+    fn transfer_hook_slices(&self) -> Result<Vec<(u8, [Pubkey; 2])>> {
+        let mut slices = Vec::new();
+        for (accounts_type, mint) in [
+            (ACCOUNTS_TYPE_TRANSFER_HOOK_X, &self.base_token),
+            (ACCOUNTS_TYPE_TRANSFER_HOOK_Y, &self.quote_token),
+        ] {
+            if let Some(hook_accounts) = transfer_hook_accounts(mint)? {
+                slices.push((accounts_type, hook_accounts));
+            }
         }
+        Ok(slices)
+    }
 
-        let remaining = &self.accounts[11..];
-        let sol_mint = native_mint::id();
-
-        // Find position of SOL MINT separator
-        let sol_mint_pos = remaining.iter().position(|acc| *acc.key == sol_mint);
+    /// Accounts after the fixed ones, less the transfer hook accounts the span carries
+    /// for the swap CPI; what is left are the bin arrays and the SOL mint separator
+    fn bin_array_tail(&self) -> Vec<AccountInfo<'info>> {
+        if self.accounts.len() <= 11 {
+            return Vec::new();
+        }
+        let hook_keys: Vec<Pubkey> = self
+            .transfer_hook_slices()
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|(_, hook_accounts)| hook_accounts)
+            .collect();
+        self.accounts[11..]
+            .iter()
+            .filter(|account| !hook_keys.contains(account.key))
+            .cloned()
+            .collect()
+    }
 
-        match sol_mint_pos {
-            Some(pos) => {
-                // Split at SOL MINT position - buy arrays are before SOL MINT
-                let buy_slice = &remaining[..pos];
-                if buy_slice.is_empty() {
-                    None
-                } else {
-                    Some(buy_slice.iter().cloned().collect())
-                }
-            }
-            None => {
-                // No SOL MINT found, all remaining are buy arrays
-                if remaining.is_empty() {
-                    None
-                } else {
-                    Some(remaining.iter().cloned().collect())
+    /// Buy and sell bin arrays of the tail, split by `bin_array_counts` when set
+    /// Structure: [fixed accounts] [bin_arrays_buy...] [SOL_MINT] [bin_arrays_sell...]
+    /// or, with the counts: [fixed accounts] [bin_arrays_buy...] [bin_arrays_sell...]
+    fn split_bin_arrays(&self) -> (Vec<AccountInfo<'info>>, Vec<AccountInfo<'info>>) {
+        let mut buy = self.bin_array_tail();
+        let sell = match self.bin_array_counts {
+            // `new_with_bin_array_counts` checked the tail holds exactly that many
+            Some((buy_count, _)) => buy.split_off((buy_count as usize).min(buy.len())),
+            // Find position of SOL MINT separator; without one, all remaining are buy arrays
+            None => match buy.iter().position(|acc| *acc.key == native_mint::id()) {
+                Some(pos) => {
+                    let sell = buy.split_off(pos + 1); // Skip SOL MINT itself
+                    buy.pop();
+                    sell
                 }
-            }
-        }
+                None => Vec::new(),
+            },
+        };
+        (buy, sell)
+    }
+
+    /// Extract bin arrays for buying from accounts starting at index 11
+    fn get_bin_arrays_buy(&self) -> Option<Vec<AccountInfo<'info>>> {
+        let (buy, _) = self.split_bin_arrays();
+        (!buy.is_empty()).then_some(buy)
     }
 
     /// Extract bin arrays for selling from accounts starting at index 11
-    /// Structure: [fixed accounts] [bin_arrays_buy...] [SOL_MINT] [bin_arrays_sell...]
     fn get_bin_arrays_sell(&self) -> Option<Vec<AccountInfo<'info>>> {
-        if self.accounts.len() <= 11 {
-            return None;
+        let (_, sell) = self.split_bin_arrays();
+        (!sell.is_empty()).then_some(sell)
+    }
+
+    /// Deserialize the bin array bitmap extension at index 10, if one was supplied
+    fn load_bitmap_extension(&self) -> Result<Option<BinArrayBitmapExtension>> {
+        let bitmap_extension_account = &self.accounts[10];
+        if *bitmap_extension_account.key != Self::PROGRAM_ID
+            && bitmap_extension_account.data_len() > 8
+        {
+            Ok(Some(bytemuck::pod_read_unaligned(
+                &bitmap_extension_account.try_borrow_data()?[8..],
+            )))
+        } else {
+            Ok(None)
         }
+    }
 
-        let remaining = &self.accounts[11..];
-        let sol_mint = native_mint::id();
+    /// Number of bin arrays an exact-in swap of `amount_in` traverses, starting at the active
+    /// bin and following the pair bitmap (and bitmap extension) in the swap direction.
+    /// Liquidity is read from the supplied bin arrays; the walk stops at the first missing
+    /// array, so the count is a lower bound in that case. Quotes don't need this, since
+    /// `quote_exact_in` fails on the first missing array itself.
+    pub fn required_bin_arrays(
+        &self,
+        lb_pair: &LbPair,
+        amount_in: u64,
+        swap_for_y: bool,
+    ) -> Result<usize> {
+        const BIN_ARRAY_HEADER_SIZE: usize = 56;
+        const BIN_SIZE: usize = 144;
 
-        // Find position of SOL MINT separator
-        let sol_mint_pos = remaining.iter().position(|acc| *acc.key == sol_mint);
+        let bitmap_extension = self.load_bitmap_extension()?;
+        let bin_arrays = if swap_for_y {
+            self.get_bin_arrays_buy().unwrap_or_default()
+        } else {
+            self.get_bin_arrays_sell().unwrap_or_default()
+        };
+        let lb_pair_key = *self.pool_id.key;
+        let mut lb_pair = *lb_pair;
+        let mut amount_left = amount_in;
+        let mut required: usize = 0;
+
+        while amount_left > 0 {
+            let Some(bin_array_key) = get_bin_array_pubkeys_for_swap(
+                lb_pair_key,
+                &lb_pair,
+                bitmap_extension.as_ref(),
+                swap_for_y,
+                1,
+            )
+            .map_err(|_| error!(SolarBError::QuoteFailed))?
+            .pop() else {
+                // No liquidity left in the swap direction, nothing more to cover
+                break;
+            };
+            required += 1;
+
+            let Some(bin_array) = bin_arrays.iter().find(|acc| *acc.key == bin_array_key) else {
+                return Ok(required);
+            };
+            let bin_array_data = bin_array.try_borrow_data()?;
+            let bin_array_index: i64 = bytemuck::pod_read_unaligned(
+                bin_array_data
+                    .get(8..16)
+                    .ok_or(error!(SolarBError::QuoteFailed))?,
+            );
+            let (lower_bin_id, upper_bin_id) =
+                BinArray::get_bin_array_lower_upper_bin_id(bin_array_index as i32)
+                    .map_err(|_| error!(SolarBError::QuoteFailed))?;
+
+            // The bitmap may skip empty arrays, so enter the next one from its near edge
+            if lb_pair.active_id < lower_bin_id || lb_pair.active_id > upper_bin_id {
+                lb_pair.active_id = if swap_for_y { upper_bin_id } else { lower_bin_id };
+            }
 
-        match sol_mint_pos {
-            Some(pos) => {
-                // Split at SOL MINT position - sell arrays are after SOL MINT
-                let after_sol = &remaining[pos + 1..]; // Skip SOL MINT itself
-                if after_sol.is_empty() {
-                    None
-                } else {
-                    Some(after_sol.iter().cloned().collect())
+            while amount_left > 0
+                && lb_pair.active_id >= lower_bin_id
+                && lb_pair.active_id <= upper_bin_id
+            {
+                lb_pair
+                    .update_volatility_accumulator()
+                    .map_err(|_| error!(SolarBError::QuoteFailed))?;
+                let bin_offset = BIN_ARRAY_HEADER_SIZE
+                    + (lb_pair.active_id - lower_bin_id) as usize * BIN_SIZE;
+                let mut bin: Bin = bytemuck::pod_read_unaligned(
+                    bin_array_data
+                        .get(bin_offset..bin_offset + BIN_SIZE)
+                        .ok_or(error!(SolarBError::QuoteFailed))?,
+                );
+                if !bin.is_empty(!swap_for_y) {
+                    let price = bin
+                        .get_or_store_bin_price(lb_pair.active_id, lb_pair.bin_step)
+                        .map_err(|_| error!(SolarBError::QuoteFailed))?;
+                    let result = bin
+                        .swap(amount_left, price, swap_for_y, &lb_pair, None)
+                        .map_err(|_| error!(SolarBError::QuoteFailed))?;
+                    amount_left = amount_left.saturating_sub(result.amount_in_with_fees);
+                }
+                if amount_left > 0 && lb_pair.advance_active_bin(swap_for_y).is_err() {
+                    return Ok(required);
                 }
             }
-            None => {
-                // No SOL MINT found, no sell arrays
-                None
-            }
         }
+
+        Ok(required)
+    }
+
+    pub fn with_max_bins(mut self, max_bins: usize) -> Self {
+        self.max_bins = max_bins;
+        self
+    }
+
+    /// Base and quote mints as the quotes take them, loaded on the first quote and shared by
+    /// both directions after that. `InterfaceAccount` borrows its account for `'info`, which
+    /// `self` can't lend, so each mint's `AccountInfo` is cloned and leaked instead; the
+    /// on-chain bump allocator never frees anyway.
+    pub fn mints(&self) -> Result<&MintPair<'info>> {
+        if let Some(mints) = self.mints.get() {
+            return Ok(mints);
+        }
+        let load = |mint: &AccountInfo<'info>| {
+            let mint: &'info AccountInfo<'info> = Box::leak(Box::new(mint.clone()));
+            load_mint(mint).map_err(|e| {
+                msg!("ERROR loading mint {}: {:?}", mint.key, e);
+                error!(SolarBError::MintLoadFailed)
+            })
+        };
+        let mints = (load(&self.base_token)?, load(&self.quote_token)?);
+        Ok(self.mints.get_or_init(|| mints))
+    }
+
+    /// Pair state read from `pool_id`
+    pub fn lb_pair(&self) -> Result<LbPair> {
+        let pool_data = self.pool_id.try_borrow_data()?;
+        let lb_pair_data = pool_data
+            .get(8..8 + std::mem::size_of::<LbPair>())
+            .ok_or(error!(SolarBError::PoolDeserializeFailed))?;
+        Ok(bytemuck::pod_read_unaligned(lb_pair_data))
+    }
+
+    /// Share of the input taken as base plus variable fee at the pair's current volatility
+    pub fn total_fee_rate(&self) -> Result<f64> {
+        let total_fee = self
+            .lb_pair()?
+            .get_total_fee()
+            .map_err(|_| ProgramError::ArithmeticOverflow)?;
+        Ok(total_fee as f64 / dlmm::FEE_PRECISION as f64)
     }
 
     pub fn swap_base_in_impl(
@@ -269,33 +649,11 @@ impl<'info> MeteoraDlmm<'info> {
         amount_in: u64,
         clock: Clock,
     ) -> Result<u64> {
-        // self.log_accounts()?;
-    
-        let pool_data = self.pool_id.try_borrow_data()?;
-        if pool_data.len() < 8 {
-            return Err(anchor_lang::error::Error::from(
-                anchor_lang::error::ErrorCode::AccountDiscriminatorNotFound,
-            ));
-        }
-        let pool_data_slice = &pool_data[8..];
-        let lb_pair_size = std::mem::size_of::<LbPair>();
-
-        let pool_id_state: LbPair = bytemuck::pod_read_unaligned(pool_data_slice);
+        let pool_id_state = self.lb_pair()?;
         let pool_id_key = *self.pool_id.key;
 
         let swap_for_y = input_mint == pool_id_state.token_x_mint;
-        // Deserialize bitmap extension if available
-        let bitmap_extension_account = &self.accounts[10];
-        let bitmap_extension: Option<BinArrayBitmapExtension> = if *bitmap_extension_account.key
-            != Self::PROGRAM_ID
-            && bitmap_extension_account.data_len() > 8
-        {
-            Some(bytemuck::pod_read_unaligned(
-                &bitmap_extension_account.try_borrow_data()?[8..],
-            ))
-        } else {
-            None
-        };
+        let bitmap_extension = self.load_bitmap_extension()?;
 
         let bin_arrays = if swap_for_y {
             // Keep bin_array_accounts alive in the same scope where it's used
@@ -306,77 +664,46 @@ impl<'info> MeteoraDlmm<'info> {
             bin_arrays
         };
 
-        // Helper to load mints and call quote_exact_in, working around lifetime variance
-        // Safe because InterfaceAccount just wraps AccountInfo and we're only changing
-        // the lifetime annotation, not the actual data or memory layout
-        let quote = {
-            // Work around lifetime variance: cast references to AccountInfo to match expected lifetime
-            let base_token_ref: &AccountInfo<'info> =
-                unsafe { &*(&self.base_token as *const AccountInfo<'info>) };
-            let quote_token_ref: &AccountInfo<'info> =
-                unsafe { &*(&self.quote_token as *const AccountInfo<'info>) };
-
-            let mint_x_account = load_mint(base_token_ref).map_err(|_e| {
-                anchor_lang::error::Error::from(anchor_lang::error::ErrorCode::ConstraintOwner)
-            })?;
-            let mint_y_account = load_mint(quote_token_ref).map_err(|_e| {
-                anchor_lang::error::Error::from(anchor_lang::error::ErrorCode::ConstraintOwner)
-            })?;
-
-            unsafe {
-                let mint_x_ref: &InterfaceAccount<'_, anchor_spl::token_interface::Mint> =
-                    &*(&mint_x_account
-                        as *const InterfaceAccount<'_, anchor_spl::token_interface::Mint>);
-                let mint_y_ref: &InterfaceAccount<'_, anchor_spl::token_interface::Mint> =
-                    &*(&mint_y_account
-                        as *const InterfaceAccount<'_, anchor_spl::token_interface::Mint>);
-                quote_exact_in(
-                    pool_id_key,
-                    &pool_id_state,
-                    amount_in,
-                    swap_for_y, // swap_for_y
-                    bin_arrays,
-                    bitmap_extension.as_ref(),
-                    &clock,
-                    mint_x_ref,
-                    mint_y_ref,
-                )
+        let (mint_x, mint_y) = self.mints()?;
+        let quote = quote_exact_in(
+            pool_id_key,
+            &pool_id_state,
+            amount_in,
+            swap_for_y, // swap_for_y
+            bin_arrays,
+            bitmap_extension.as_ref(),
+            &clock,
+            mint_x,
+            mint_y,
+            self.max_bins,
+        )
+        .map_err(|e| {
+            msg!("ERROR in quote_exact_in: {:?}", e);
+            if e.is::<BinArrayNotFound>() {
+                return error!(SolarBError::InsufficientBinArrays);
             }
-        }
-        .map_err(|_e| {
-            anchor_lang::error::Error::from(anchor_lang::error::ErrorCode::ConstraintOwner)
+            if e.is::<BinLimitExceeded>() {
+                return error!(SolarBError::QuoteTooDeep);
+            }
+            error!(SolarBError::QuoteFailed)
         })?;
         Ok(quote.amount_out)
     }
 
+    /// Exact-out quote: amount of `input_mint` needed to receive `amount_out` of the other
+    /// token, fees and transfer fees included
     pub fn swap_base_out_impl(
         &self,
         input_mint: Pubkey,
-        amount_in: u64,
+        amount_out: u64,
         clock: Clock,
     ) -> Result<u64> {
-        // self.log_accounts()?;
-        let pool_data = self.pool_id.try_borrow_data()?;
-        let pool_data_slice = &pool_data[8..];
-        let lb_pair_size = std::mem::size_of::<LbPair>();
-
-        let lb_pair_state: LbPair = bytemuck::pod_read_unaligned(pool_data_slice);
+        let lb_pair_state = self.lb_pair()?;
         let lb_pair_key = *self.pool_id.key;
 
         let swap_for_y = input_mint == lb_pair_state.token_x_mint;
 
-        // Deserialize bitmap extension if available
-        let bitmap_extension_account = &self.accounts[10];
-        let bitmap_extension: Option<BinArrayBitmapExtension> = if *bitmap_extension_account.key
-            != Self::PROGRAM_ID
-            && bitmap_extension_account.data_len() > 8
-        {
-            Some(bytemuck::pod_read_unaligned(
-                &bitmap_extension_account.try_borrow_data()?[8..],
-            ))
-        } else {
-            None
-        };
+        let bitmap_extension = self.load_bitmap_extension()?;
 
         let bin_arrays = if swap_for_y {
             // Keep bin_array_accounts alive in the same scope where it's used
@@ -387,65 +714,34 @@ impl<'info> MeteoraDlmm<'info> {
             bin_arrays
         };
 
-        let quote = {
-            // Work around lifetime variance: cast references to AccountInfo to match expected lifetime
-            let base_token_ref: &AccountInfo<'info> =
-                unsafe { &*(&self.base_token as *const AccountInfo<'info>) };
-            let quote_token_ref: &AccountInfo<'info> =
-                unsafe { &*(&self.quote_token as *const AccountInfo<'info>) };
-
-            let mint_x_account = load_mint(base_token_ref).map_err(|e| {
-                msg!(
-                    "ERROR loading base_token mint {}: {:?}",
-                    base_token_ref.key,
-                    e
-                );
-                anchor_lang::error::Error::from(anchor_lang::error::ErrorCode::ConstraintOwner)
-            })?;
-            let mint_y_account = load_mint(quote_token_ref).map_err(|e| {
-                msg!(
-                    "ERROR loading quote_token mint {}: {:?}",
-                    quote_token_ref.key,
-                    e
-                );
-                anchor_lang::error::Error::from(anchor_lang::error::ErrorCode::ConstraintOwner)
-            })?;
-
-            unsafe {
-                let mint_x_ref: &InterfaceAccount<'_, anchor_spl::token_interface::Mint> =
-                    &*(&mint_x_account
-                        as *const InterfaceAccount<'_, anchor_spl::token_interface::Mint>);
-                let mint_y_ref: &InterfaceAccount<'_, anchor_spl::token_interface::Mint> =
-                    &*(&mint_y_account
-                        as *const InterfaceAccount<'_, anchor_spl::token_interface::Mint>);
-
-                quote_exact_in(
-                    lb_pair_key,
-                    &lb_pair_state,
-                    amount_in,
-                    swap_for_y, // swap_for_y = false means swapping FOR X (base token), so we need buy arrays
-                    bin_arrays,
-                    bitmap_extension.as_ref(),
-                    &clock,
-                    mint_x_ref,
-                    mint_y_ref,
-                )
-            }
-        }
+        let (mint_x, mint_y) = self.mints()?;
+        let quote = quote_exact_out(
+            lb_pair_key,
+            &lb_pair_state,
+            amount_out,
+            swap_for_y,
+            bin_arrays,
+            bitmap_extension.as_ref(),
+            &clock,
+            mint_x,
+            mint_y,
+        )
         .map_err(|e| {
-            msg!("ERROR in quote_exact_in: {:?}", e);
-            // Try to preserve the original error if possible, otherwise use ConstraintOwner
-            anchor_lang::error::Error::from(anchor_lang::error::ErrorCode::ConstraintOwner)
+            msg!("ERROR in quote_exact_out: {:?}", e);
+            if e.is::<BinArrayNotFound>() {
+                return error!(SolarBError::InsufficientBinArrays);
+            }
+            error!(SolarBError::QuoteFailed)
         })?;
-        Ok(quote.amount_out)
+        Ok(quote.amount_in)
     }
 
     pub fn invoke_swap_base_in_impl<'a>(
         &self,
-        input_mint: Pubkey,
+        _input_mint: Pubkey,
         amount_in: u64,
         amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -453,130 +749,30 @@ impl<'info> MeteoraDlmm<'info> {
         mint_1_token_program: AccountInfo<'a>,
         mint_2_token_program: AccountInfo<'a>,
     ) -> Result<()> {
-        let (
-            base_token_program,
-            quote_token_program,
-            user_base_token_account,
-            user_quote_token_account,
-        ) = if mint_1_account.key == self.base_token.key {
-            (
-                mint_1_token_program,
-                mint_2_token_program,
-                user_mint_1_token_account,
-                user_mint_2_token_account,
-            )
-        } else if mint_2_account.key == self.base_token.key {
-            (
-                mint_2_token_program,
-                mint_1_token_program,
-                user_mint_2_token_account,
-                user_mint_1_token_account,
-            )
-        } else {
-            return Err(ProgramError::InvalidAccountData.into());
-        };
-
-        let amount_out_value = amount_out.unwrap_or(0);
-
-        // Get stored accounts from self.accounts - these are the accounts stored in the struct
-        let stored_accounts = self.accounts.clone();
-        let program_id_stored = &stored_accounts[0];
-        let pool_id = &stored_accounts[1];
-        let base_vault = &stored_accounts[2];
-        let quote_vault = &stored_accounts[3];
-        let base_token = &stored_accounts[4];
-        let quote_token = &stored_accounts[5];
-        let oracle = &stored_accounts[6];
-        let host_fee_in = &stored_accounts[7];
-        let memo = &stored_accounts[8];
-        let event_authority = &stored_accounts[9];
-        let bitmap_extension = &stored_accounts[10];
-        
-        let swap_for_y = input_mint == *base_token.key;
-
-        let bin_arrays = if swap_for_y {
-            // Keep bin_array_accounts alive in the same scope where it's used
-            let bin_arrays: Vec<AccountInfo<'_>> = self.get_bin_arrays_buy().unwrap_or_default();
-            bin_arrays
-        } else {
-            let bin_arrays: Vec<AccountInfo<'_>> = self.get_bin_arrays_sell().unwrap_or_default();
-            bin_arrays
-        };
-
-
-        let mut metas = vec![
-            AccountMeta::new(*pool_id.key, false),
-            AccountMeta::new_readonly(*bitmap_extension.key, false),
-            AccountMeta::new(*base_vault.key, false),
-            AccountMeta::new(*quote_vault.key, false),
-            AccountMeta::new(*user_base_token_account.key, false),
-            AccountMeta::new(*user_quote_token_account.key, false),
-            AccountMeta::new_readonly(*base_token.key, false),
-            AccountMeta::new_readonly(*quote_token.key, false),
-            AccountMeta::new(*oracle.key, false),
-            AccountMeta::new(*host_fee_in.key, false),
-            AccountMeta::new(*payer.key, true),
-            AccountMeta::new_readonly(*base_token_program.key, false),
-            AccountMeta::new_readonly(*quote_token_program.key, false),
-            AccountMeta::new_readonly(*memo.key, false),
-            AccountMeta::new_readonly(*event_authority.key, false),
-            AccountMeta::new_readonly(Self::PROGRAM_ID, false),
-        ];
-        // Add bin arrays (buy arrays for swap_base_in)
-        for account in bin_arrays.clone() {
-                metas.push(AccountMeta::new(*account.key, false));
-        }
-
-        let mut data = vec![43, 215, 247, 132, 137, 60, 243, 81]; // TODO: Add proper instruction discriminator
-        data.extend_from_slice(&amount_in.to_le_bytes());
-        data.extend_from_slice(&amount_out_value.to_le_bytes());
-        data.extend_from_slice(&0u32.to_le_bytes()); // Empty vec
-
-        let swap_ix = Instruction {
-            program_id: Self::PROGRAM_ID,
-            accounts: metas,
-            data,
-        };
-
-        // Collect AccountInfo into a vector and use unsafe to cast lifetimes
-        // Order must match metas order exactly
-        let mut accounts_vec: Vec<AccountInfo<'info>> = vec![
-            pool_id.clone(),          // 0: pool_id
-            bitmap_extension.clone(), // 1: bitmap_extension (readonly)
-            base_vault.clone(),       // 2: base_vault
-            quote_vault.clone(),      // 3: quote_vault
-            unsafe { std::mem::transmute(user_base_token_account.to_account_info()) }, // 4: user_base_token_account
-            unsafe { std::mem::transmute(user_quote_token_account.to_account_info()) }, // 5: user_quote_token_account
-            base_token.clone(),  // 6: base_token (readonly)
-            quote_token.clone(), // 7: quote_token (readonly)
-            oracle.clone(),      // 8: oracle (readonly)
-            host_fee_in.clone(), // 9: host_fee_in
-            unsafe { std::mem::transmute(payer.to_account_info()) }, // 10: payer (signer)
-            unsafe { std::mem::transmute(base_token_program.to_account_info()) }, // 11: base_token_program (readonly)
-            unsafe { std::mem::transmute(quote_token_program.to_account_info()) }, // 12: quote_token_program (readonly)
-            memo.clone(),              // 13: memo (readonly)
-            event_authority.clone(),   // 14: event_authority (readonly)
-            program_id_stored.clone(), // 15: program_id (readonly)
-        ];
-        // Add bin arrays (buy arrays for swap_base_in)
-        for account in bin_arrays {
-            accounts_vec.push(account);
-        }
-        
-
-        unsafe {
-            let accounts: &[AccountInfo<'a>] = std::mem::transmute(accounts_vec.as_slice());
-            invoke(&swap_ix, accounts)?;
-        }
-        Ok(())
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            EdgeSide::RightToLeft,
+            amount_in,
+            amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
     }
 
     pub fn invoke_swap_base_out_impl<'a>(
         &self,
-        input_mint: Pubkey,
+        _input_mint: Pubkey,
         amount_in: u64,
         min_amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -584,120 +780,48 @@ impl<'info> MeteoraDlmm<'info> {
         mint_1_token_program: AccountInfo<'a>,
         mint_2_token_program: AccountInfo<'a>,
     ) -> Result<()> {
-        let (
-            base_token_program,
-            quote_token_program,
-            user_base_token_account,
-            user_quote_token_account,
-        ) = if mint_1_account.key == self.base_token.key {
-            (
-                mint_1_token_program,
-                mint_2_token_program,
-                user_mint_1_token_account,
-                user_mint_2_token_account,
-            )
-        } else if mint_2_account.key == self.base_token.key {
-            (
-                mint_2_token_program,
-                mint_1_token_program,
-                user_mint_2_token_account,
-                user_mint_1_token_account,
-            )
-        } else {
-            return Err(ProgramError::InvalidAccountData.into());
-        };
-
-        let min_amount_out_value = min_amount_out.unwrap_or(0);
-
-        // Get stored accounts from self.accounts - these are the accounts stored in the struct
-        let stored_accounts = self.accounts.clone();
-        let program_id_stored = &stored_accounts[0];
-        let pool_id = &stored_accounts[1];
-        let base_vault = &stored_accounts[2];
-        let quote_vault = &stored_accounts[3];
-        let base_token = &stored_accounts[4];
-        let quote_token = &stored_accounts[5];
-        let oracle = &stored_accounts[6];
-        let host_fee_in = &stored_accounts[7];
-        let memo = &stored_accounts[8];
-        let event_authority = &stored_accounts[9];
-        let bitmap_extension = &stored_accounts[10];
-
-        let swap_for_y = input_mint == *base_token.key;
-
-        let bin_arrays = if swap_for_y {
-            // Keep bin_array_accounts alive in the same scope where it's used
-            let bin_arrays: Vec<AccountInfo<'_>> = self.get_bin_arrays_buy().unwrap_or_default();
-            bin_arrays
-        } else {
-            let bin_arrays: Vec<AccountInfo<'_>> = self.get_bin_arrays_sell().unwrap_or_default();
-            bin_arrays
-        };
-
-        let mut metas = vec![
-            AccountMeta::new(*pool_id.key, false),
-            AccountMeta::new(*bitmap_extension.key, false),
-            AccountMeta::new(*base_vault.key, false),
-            AccountMeta::new(*quote_vault.key, false),
-            AccountMeta::new(*user_base_token_account.key, false),
-            AccountMeta::new(*user_quote_token_account.key, false),
-            AccountMeta::new_readonly(*base_token.key, false),
-            AccountMeta::new_readonly(*quote_token.key, false),
-            AccountMeta::new(*oracle.key, false),
-            AccountMeta::new(*host_fee_in.key, false),
-            AccountMeta::new(*payer.key, true),
-            AccountMeta::new_readonly(*base_token_program.key, false),
-            AccountMeta::new_readonly(*quote_token_program.key, false),
-            AccountMeta::new_readonly(*memo.key, false),
-            AccountMeta::new_readonly(*event_authority.key, false),
-            AccountMeta::new_readonly(Self::PROGRAM_ID, false),
-        ];
-        // Add bin arrays (sell arrays for swap_base_out)
-
-        for account in bin_arrays.clone() {
-            metas.push(AccountMeta::new(*account.key, false));
-        }
-        
-
-        // swap2 instruction discriminator: [65, 75, 63, 76, 235, 91, 91, 136]
-        let mut data = vec![65, 75, 63, 76, 235, 91, 91, 136];
-        data.extend_from_slice(&amount_in.to_le_bytes());
-        data.extend_from_slice(&min_amount_out_value.to_le_bytes());
-
-        // RemainingAccountsInfo: { slices: Vec<RemainingAccountsSlice> }
-        // For basic swaps without transfer hooks, slices is empty
-        // Serialize Vec length as u32 (Anchor uses u32 for Vec length)
-        data.extend_from_slice(&0u32.to_le_bytes()); // Empty vec
-
-        let swap_ix = Instruction {
-            program_id: Self::PROGRAM_ID,
-            accounts: metas,
-            data,
-        };
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            EdgeSide::LeftToRight,
+            amount_in,
+            min_amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
+    }
 
-        // Collect AccountInfo into a vector and use unsafe to cast lifetimes
-        // Order must match metas order exactly
-        let mut accounts_vec: Vec<AccountInfo<'info>> = vec![
-            pool_id.clone(),          // 0: pool_id
-            bitmap_extension.clone(), // 1: bitmap_extension
-            base_vault.clone(),       // 2: base_vault
-            quote_vault.clone(),      // 3: quote_vault
-            unsafe { std::mem::transmute(user_base_token_account.to_account_info()) }, // 4: user_base_token_account
-            unsafe { std::mem::transmute(user_quote_token_account.to_account_info()) }, // 5: user_quote_token_account
-            base_token.clone(),  // 6: base_token (readonly)
-            quote_token.clone(), // 7: quote_token (readonly)
-            oracle.clone(),      // 8: oracle (readonly)
-            host_fee_in.clone(), // 9: host_fee_in
-            unsafe { std::mem::transmute(payer.to_account_info()) }, // 10: payer (signer)
-            unsafe { std::mem::transmute(base_token_program.to_account_info()) }, // 11: base_token_program (readonly)
-            unsafe { std::mem::transmute(quote_token_program.to_account_info()) }, // 12: quote_token_program (readonly)
-            memo.clone(),              // 13: memo (readonly)
-            event_authority.clone(),   // 14: event_authority (readonly)
-            program_id_stored.clone(), // 15: program_id (readonly)
-        ];
-        // Add bin arrays (sell arrays for swap_base_out)
-        for account in bin_arrays {
-            accounts_vec.push(account);
+    fn invoke_swap<'a>(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        authority: AccountInfo<'a>,
+        accounts: ResolvedAccounts<'a>,
+    ) -> Result<()> {
+        let swap_ix =
+            self.build_swap_ix(direction, amount_in, min_out, &accounts.keys(authority.key))?;
+
+        // The span's own accounts, bin arrays included, then the user's; invoke matches them
+        // to the metas by key. Same lifetime cast as the other adapters.
+        let mut accounts_vec: Vec<AccountInfo<'info>> = self.accounts.clone();
+        for account in [
+            authority,
+            accounts.user_base_token_account,
+            accounts.user_quote_token_account,
+            accounts.base_token_program,
+            accounts.quote_token_program,
+        ] {
+            accounts_vec.push(unsafe {
+                std::mem::transmute::<AccountInfo<'a>, AccountInfo<'info>>(account)
+            });
         }
 
         unsafe {
@@ -712,8 +836,9 @@ impl<'info> MeteoraDlmm<'info> {
 pub use dlmm::quote::get_bin_array_pubkeys_for_swap;
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::utils::utils::derive_event_authority;
     use anchor_lang::prelude::{Clock, InterfaceAccount};
     use anchor_lang::solana_program::{account_info::AccountInfo, pubkey::Pubkey, system_program};
     use anchor_spl::token_interface::Mint;
@@ -1040,7 +1165,7 @@ mod tests {
         };
 
         let amount_out_2 = meteora_dlmm
-            .swap_base_out(other_mint, 9517577807, clock_2)
+            .swap_base_in(other_mint, 9517577807, clock_2)
             .unwrap();
         eprintln!(
             "Step 1: {} SOL -> {} TOKEN",
@@ -1149,4 +1274,829 @@ mod tests {
         //     quote_result.amount_out as f64 / 1_000_000_000.0
         // );
     }
+
+    #[tokio::test]
+    #[ignore = "queries devnet"]
+    async fn test_dlmm_swap_quote_exact_out_round_trip() {
+        use anchor_client::Cluster;
+        use solana_client::nonblocking::rpc_client::RpcClient;
+
+        let rpc_client = RpcClient::new(Cluster::Devnet.url().to_string());
+        let pool_id = Pubkey::from_str_const("FT8ueq7bP7DpBoP6b3QSsos3TkRY9JYCbGLCLKA3tgUn");
+        let lb_pair_account = rpc_client.get_account(&pool_id).await.unwrap();
+        let lb_pair: LbPair = bytemuck::pod_read_unaligned(&lb_pair_account.data[8..]);
+
+        let program_id_account =
+            create_mock_account_info_with_data(MeteoraDlmm::PROGRAM_ID, system_program::id(), None);
+        let (bitmap_extension_key, _) = pda::derive_bin_array_bitmap_extension(pool_id);
+        let bitmap_extension_account =
+            try_fetch_account_info_from_rpc(&rpc_client, bitmap_extension_key)
+                .await
+                .unwrap_or_else(|| program_id_account.clone());
+        let (event_authority_key, _) = pda::derive_event_authority_pda();
+
+        let mut accounts = vec![
+            program_id_account,
+            account_to_account_info(pool_id, lb_pair_account),
+            fetch_account_info_from_rpc(&rpc_client, lb_pair.reserve_x).await,
+            fetch_account_info_from_rpc(&rpc_client, lb_pair.reserve_y).await,
+            fetch_account_info_from_rpc(&rpc_client, lb_pair.token_x_mint).await,
+            fetch_account_info_from_rpc(&rpc_client, lb_pair.token_y_mint).await,
+            fetch_account_info_from_rpc(&rpc_client, lb_pair.oracle).await,
+            // host_fee_in, memo and event_authority are not read by the quotes
+            create_mock_account_info_with_data(Pubkey::default(), system_program::id(), None),
+            create_mock_account_info_with_data(
+                anchor_spl::associated_token::ID,
+                system_program::id(),
+                None,
+            ),
+            create_mock_account_info_with_data(event_authority_key, system_program::id(), None),
+            bitmap_extension_account,
+        ];
+
+        // Buy arrays, then the SOL mint separator, then sell arrays
+        for key in get_bin_array_pubkeys_for_swap(pool_id, &lb_pair, None, true, 3).unwrap() {
+            if let Some(account) = try_fetch_account_info_from_rpc(&rpc_client, key).await {
+                accounts.push(account);
+            }
+        }
+        accounts.push(create_mock_account_info_with_data(
+            native_mint::id(),
+            system_program::id(),
+            None,
+        ));
+        for key in get_bin_array_pubkeys_for_swap(pool_id, &lb_pair, None, false, 3).unwrap() {
+            if let Some(account) = try_fetch_account_info_from_rpc(&rpc_client, key).await {
+                accounts.push(account);
+            }
+        }
+
+        let clock = get_clock(&rpc_client).await.unwrap();
+        let meteora_dlmm = MeteoraDlmm::new(&accounts).unwrap();
+
+        for input_mint in [lb_pair.token_x_mint, lb_pair.token_y_mint] {
+            let amount_in = 1_000_000;
+            let amount_out = meteora_dlmm
+                .swap_base_in(input_mint, amount_in, clock.clone())
+                .unwrap();
+            let required = meteora_dlmm
+                .swap_base_out(input_mint, amount_out, clock.clone())
+                .unwrap();
+
+            // Only the partially filled last bin rounds: its output is floored on the way in
+            // and the fee rounded up on the way back, so the inverse is off by at most one
+            assert!(required <= amount_in + 1);
+            assert!(
+                meteora_dlmm
+                    .swap_base_in(input_mint, required, clock.clone())
+                    .unwrap()
+                    >= amount_out
+            );
+        }
+    }
+
+    // Pool with liquidity in a single bin of each of the bin arrays 0, -1, -2 and -3:
+    // bins 0, -1, -71 and -141 each hold 1_000_000 of token Y
+    pub(crate) fn create_bin_array_test_accounts(
+        supplied_bin_array_indexes: &[i64],
+    ) -> Vec<AccountInfo<'static>> {
+        create_bin_array_test_accounts_for_mints(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            supplied_bin_array_indexes,
+        )
+    }
+
+    // Same pool between the given mints
+    fn create_bin_array_test_accounts_for_mints(
+        token_x_mint: Pubkey,
+        token_y_mint: Pubkey,
+        supplied_bin_array_indexes: &[i64],
+    ) -> Vec<AccountInfo<'static>> {
+        use anchor_spl::token::spl_token;
+
+        let owner = MeteoraDlmm::PROGRAM_ID;
+        let lb_pair_key = Pubkey::new_unique();
+        let reserve_x = Pubkey::new_unique();
+        let reserve_y = Pubkey::new_unique();
+
+        let mut lb_pair: LbPair = bytemuck::Zeroable::zeroed();
+        lb_pair.active_id = 0;
+        lb_pair.bin_step = 10;
+        lb_pair.token_x_mint = token_x_mint;
+        lb_pair.token_y_mint = token_y_mint;
+        lb_pair.reserve_x = reserve_x;
+        lb_pair.reserve_y = reserve_y;
+        // Bitmap offset is bin_array_index + 512: arrays 0, -1, -2, -3 -> bits 512, 511, 510, 509
+        lb_pair.bin_array_bitmap[8] = 1;
+        lb_pair.bin_array_bitmap[7] = 0b111 << 61;
+        let mut lb_pair_data = vec![0u8; 8];
+        lb_pair_data.extend_from_slice(bytemuck::bytes_of(&lb_pair));
+
+        let mut mint_data = vec![0u8; 82];
+        mint_data[44] = 6; // decimals
+        mint_data[45] = 1; // is_initialized
+
+        // SPL token account: mint (32) + owner (32) + amount (8) + delegate (36) + state (1)
+        let reserve_data = |mint: Pubkey| {
+            let mut data = vec![0u8; 165];
+            data[0..32].copy_from_slice(mint.as_ref());
+            data[64..72].copy_from_slice(&1_000_000u64.to_le_bytes());
+            data[108] = 1; // Initialized
+            data
+        };
+
+        let mut accounts = vec![
+            create_mock_account_info_with_data(MeteoraDlmm::PROGRAM_ID, owner, None),
+            create_mock_account_info_with_data(lb_pair_key, owner, Some(lb_pair_data)),
+            create_mock_account_info_with_data(
+                reserve_x,
+                spl_token::ID,
+                Some(reserve_data(token_x_mint)),
+            ),
+            create_mock_account_info_with_data(
+                reserve_y,
+                spl_token::ID,
+                Some(reserve_data(token_y_mint)),
+            ),
+            create_mock_account_info_with_data(
+                token_x_mint,
+                spl_token::ID,
+                Some(mint_data.clone()),
+            ),
+            create_mock_account_info_with_data(token_y_mint, spl_token::ID, Some(mint_data)),
+        ];
+        for _ in 6..9 {
+            accounts.push(create_mock_account_info_with_data(
+                Pubkey::new_unique(),
+                owner,
+                None,
+            ));
+        }
+        accounts.push(create_mock_account_info_with_data(
+            pda::derive_event_authority_pda().0,
+            owner,
+            None,
+        ));
+        // No bitmap extension
+        accounts.push(create_mock_account_info_with_data(
+            MeteoraDlmm::PROGRAM_ID,
+            owner,
+            None,
+        ));
+
+        for &bin_array_index in supplied_bin_array_indexes {
+            let mut data = vec![0u8; 56 + 70 * 144];
+            data[8..16].copy_from_slice(&bin_array_index.to_le_bytes());
+            data[24..56].copy_from_slice(&lb_pair_key.to_bytes());
+            // Liquidity sits in the top bin of array 0 (bin 0) or the top bin of the others
+            let bin_in_array: usize = if bin_array_index == 0 { 0 } else { 69 };
+            let bin_offset = 56 + bin_in_array * 144;
+            data[bin_offset + 8..bin_offset + 16].copy_from_slice(&1_000_000u64.to_le_bytes());
+            let key = pda::derive_bin_array_pda(lb_pair_key, bin_array_index).0;
+            accounts.push(create_mock_account_info_with_data(key, owner, Some(data)));
+        }
+        accounts
+    }
+
+    // Same pool with 1_000_000 of token X added to the active bin, and bin array 0 also
+    // supplied after the SOL mint separator so both swap directions can be quoted
+    pub(crate) fn create_two_sided_test_accounts() -> Vec<AccountInfo<'static>> {
+        let mut accounts = create_bin_array_test_accounts(&[0, -1, -2, -3]);
+        let active_bin_array = accounts[11].clone();
+        active_bin_array.try_borrow_mut_data().unwrap()[56..64]
+            .copy_from_slice(&1_000_000u64.to_le_bytes());
+        accounts.push(create_mock_account_info_with_data(
+            native_mint::id(),
+            system_program::id(),
+            None,
+        ));
+        accounts.push(active_bin_array);
+        accounts
+    }
+
+    #[test]
+    fn test_bin_array_counts_split_pool_without_sol() {
+        let usdc = Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+        let usdt = Pubkey::from_str_const("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB");
+        // Two-sided USDC/USDT pool, bin array 0 supplied again for selling with no SOL
+        // mint before it
+        let mut accounts = create_bin_array_test_accounts_for_mints(usdc, usdt, &[0, -1, -2, -3]);
+        let active_bin_array = accounts[11].clone();
+        active_bin_array.try_borrow_mut_data().unwrap()[56..64]
+            .copy_from_slice(&1_000_000u64.to_le_bytes());
+        accounts.push(active_bin_array);
+        let keys = |arrays: Option<Vec<AccountInfo>>| -> Vec<Pubkey> {
+            arrays
+                .unwrap_or_default()
+                .iter()
+                .map(|account| *account.key)
+                .collect()
+        };
+
+        let meteora = MeteoraDlmm::new_with_bin_array_counts(&accounts, Some((4, 1))).unwrap();
+        assert_eq!(
+            keys(meteora.get_bin_arrays_buy()),
+            keys(Some(accounts[11..15].to_vec()))
+        );
+        assert_eq!(keys(meteora.get_bin_arrays_sell()), vec![*accounts[15].key]);
+        assert!(
+            meteora
+                .swap_base_in(usdc, 500_000, Clock::default())
+                .unwrap()
+                > 0
+        );
+        assert!(
+            meteora
+                .swap_base_in(usdt, 500_000, Clock::default())
+                .unwrap()
+                > 0
+        );
+
+        // Without the counts no separator is found and every array is taken for buying
+        let separated = MeteoraDlmm::new(&accounts).unwrap();
+        assert_eq!(separated.get_bin_arrays_buy().unwrap().len(), 5);
+        assert!(separated.get_bin_arrays_sell().is_none());
+        assert_eq!(
+            separated
+                .swap_base_in(usdt, 500_000, Clock::default())
+                .unwrap_err(),
+            SolarBError::InsufficientBinArrays.into()
+        );
+    }
+
+    #[test]
+    fn test_bin_array_counts_must_cover_the_tail() {
+        let accounts = create_bin_array_test_accounts(&[0, -1]);
+        for counts in [(1, 0), (2, 1), (0, 3)] {
+            assert_eq!(
+                MeteoraDlmm::new_with_bin_array_counts(&accounts, Some(counts)).err(),
+                Some(error!(SolarBError::AccountSpanMismatch))
+            );
+        }
+        let meteora = MeteoraDlmm::new_with_bin_array_counts(&accounts, Some((0, 2))).unwrap();
+        assert!(meteora.get_bin_arrays_buy().is_none());
+        assert_eq!(meteora.get_bin_arrays_sell().unwrap().len(), 2);
+    }
+
+    fn load_lb_pair(meteora: &MeteoraDlmm) -> LbPair {
+        bytemuck::pod_read_unaligned(&meteora.pool_id.try_borrow_data().unwrap()[8..])
+    }
+
+    #[test]
+    fn test_required_bin_arrays_small_swap() {
+        let accounts = create_bin_array_test_accounts(&[0, -1, -2, -3]);
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+        let lb_pair = load_lb_pair(&meteora);
+
+        // Fits in the active bin
+        assert_eq!(
+            meteora.required_bin_arrays(&lb_pair, 500_000, true).unwrap(),
+            1
+        );
+        assert_eq!(meteora.required_bin_arrays(&lb_pair, 0, true).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_required_bin_arrays_large_swap() {
+        let accounts = create_bin_array_test_accounts(&[0, -1, -2, -3]);
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+        let lb_pair = load_lb_pair(&meteora);
+
+        // Drains bins 0, -1 and -71 and finishes in bin -141
+        assert_eq!(
+            meteora
+                .required_bin_arrays(&lb_pair, 3_500_000, true)
+                .unwrap(),
+            4
+        );
+        let amount_out = meteora
+            .swap_base_in(lb_pair.token_x_mint, 3_500_000, Clock::default())
+            .unwrap();
+        assert!(amount_out > 3_000_000);
+        assert!(amount_out < 4_000_000);
+    }
+
+    #[test]
+    fn test_swap_with_insufficient_bin_arrays() {
+        let accounts = create_bin_array_test_accounts(&[0, -1]);
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+        let lb_pair = load_lb_pair(&meteora);
+
+        // Only the first two of the four arrays are supplied, the walk stops at the third
+        assert_eq!(
+            meteora
+                .required_bin_arrays(&lb_pair, 3_500_000, true)
+                .unwrap(),
+            3
+        );
+        let result = meteora.swap_base_in(lb_pair.token_x_mint, 3_500_000, Clock::default());
+        assert_eq!(
+            result.unwrap_err(),
+            SolarBError::InsufficientBinArrays.into()
+        );
+        let result = meteora.swap_base_out(lb_pair.token_x_mint, 3_500_000, Clock::default());
+        assert_eq!(
+            result.unwrap_err(),
+            SolarBError::InsufficientBinArrays.into()
+        );
+
+        // A swap that stays within the supplied arrays still quotes
+        assert!(meteora
+            .swap_base_in(lb_pair.token_x_mint, 1_500_000, Clock::default())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_swap_base_in_deterministic() {
+        let accounts = create_bin_array_test_accounts(&[0, -1, -2, -3]);
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+        let lb_pair = load_lb_pair(&meteora);
+        let clock = Clock {
+            slot: 300_000_000,
+            epoch_start_timestamp: 0,
+            epoch: 700,
+            leader_schedule_epoch: 0,
+            unix_timestamp: 1_700_000_000,
+        };
+
+        // Crosses several bin arrays, so the bin array lookup order is exercised too
+        let first = meteora
+            .swap_base_in(lb_pair.token_x_mint, 3_500_000, clock.clone())
+            .unwrap();
+        let second = meteora
+            .swap_base_in(lb_pair.token_x_mint, 3_500_000, clock)
+            .unwrap();
+        assert!(first > 0);
+        assert_eq!(first.to_le_bytes(), second.to_le_bytes());
+    }
+
+    #[test]
+    fn test_swap_base_out_inverts_swap_base_in() {
+        let accounts = create_two_sided_test_accounts();
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+        let lb_pair = load_lb_pair(&meteora);
+
+        // Selling X crosses up to four bins, buying X stays in the active bin
+        for (input_mint, amount_in) in [
+            (lb_pair.token_x_mint, 500_000),
+            (lb_pair.token_x_mint, 3_500_000),
+            (lb_pair.token_y_mint, 500_000),
+        ] {
+            let amount_out = meteora
+                .swap_base_in(input_mint, amount_in, Clock::default())
+                .unwrap();
+            let required = meteora
+                .swap_base_out(input_mint, amount_out, Clock::default())
+                .unwrap();
+            // The exact-in leg floors its output in the last bin, so the inverse may
+            // need slightly less, but never more
+            assert!(required <= amount_in);
+            assert!(amount_in - required <= 2, "{amount_in} -> {required}");
+        }
+    }
+
+    #[test]
+    fn test_mints_load_once_for_both_directions() {
+        let accounts = create_two_sided_test_accounts();
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+        let lb_pair = load_lb_pair(&meteora);
+        assert!(meteora.mints.get().is_none());
+
+        meteora
+            .swap_base_in(lb_pair.token_x_mint, 500_000, Clock::default())
+            .unwrap();
+        let loaded: *const MintPair = meteora.mints.get().unwrap();
+        meteora
+            .swap_base_in(lb_pair.token_y_mint, 500_000, Clock::default())
+            .unwrap();
+        meteora
+            .swap_base_out(lb_pair.token_x_mint, 1_000, Clock::default())
+            .unwrap();
+        meteora
+            .swap_base_out(lb_pair.token_y_mint, 1_000, Clock::default())
+            .unwrap();
+        let mints = meteora.mints().unwrap();
+        assert!(std::ptr::eq(mints, loaded));
+        assert_eq!(mints.0.key(), lb_pair.token_x_mint);
+        assert_eq!(mints.1.key(), lb_pair.token_y_mint);
+    }
+
+    #[test]
+    fn test_max_bins_stops_deep_quotes() {
+        let accounts = create_two_sided_test_accounts();
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+        let token_x = load_lb_pair(&meteora).token_x_mint;
+        let quote = |max_bins: usize, amount_in: u64| {
+            meteora.clone().with_max_bins(max_bins).swap_base_in(
+                token_x,
+                amount_in,
+                Clock::default(),
+            )
+        };
+
+        // Selling 3_500_000 X walks from bin 0 down to bin -141, through 142 bins
+        let unlimited = quote(0, 3_500_000).unwrap();
+        assert_eq!(quote(142, 3_500_000).unwrap(), unlimited);
+        for max_bins in [1, 50, 141] {
+            assert_eq!(
+                quote(max_bins, 3_500_000).unwrap_err(),
+                error!(SolarBError::QuoteTooDeep)
+            );
+        }
+        // A swap the active bin fills stays within even a single bin
+        assert!(quote(1, 500_000).is_ok());
+    }
+
+    #[test]
+    fn test_swap_base_in_charges_variable_fee() {
+        let calm = create_bin_array_test_accounts(&[0, -1, -2, -3]);
+        let volatile = create_bin_array_test_accounts(&[0, -1, -2, -3]);
+        let quote = |accounts: &[AccountInfo<'static>], volatility_reference: u32| {
+            {
+                let mut data = accounts[1].try_borrow_mut_data().unwrap();
+                let mut lb_pair: LbPair = bytemuck::pod_read_unaligned(&data[8..]);
+                lb_pair.parameters.base_factor = 10_000;
+                lb_pair.parameters.filter_period = 30;
+                lb_pair.parameters.decay_period = 600;
+                lb_pair.parameters.reduction_factor = 5_000;
+                lb_pair.parameters.variable_fee_control = 400_000;
+                lb_pair.parameters.max_volatility_accumulator = 350_000;
+                lb_pair.v_parameters.volatility_reference = volatility_reference;
+                data[8..].copy_from_slice(bytemuck::bytes_of(&lb_pair));
+            }
+            let meteora = MeteoraDlmm::new(accounts).unwrap();
+            let lb_pair = load_lb_pair(&meteora);
+            meteora
+                .swap_base_in(lb_pair.token_x_mint, 500_000, Clock::default())
+                .unwrap()
+        };
+
+        // Within the filter period the reference carries over, so the accumulator starts
+        // at it: a 0.1% base fee alone, then 0.1% base plus 0.4% variable
+        assert_eq!(quote(&calm, 0), 499_500);
+        assert_eq!(quote(&volatile, 100_000), 497_500);
+    }
+
+    #[test]
+    fn test_reserves_leave_out_protocol_fees() {
+        let accounts = create_bin_array_test_accounts(&[0]);
+        {
+            let mut data = accounts[1].try_borrow_mut_data().unwrap();
+            let mut lb_pair: LbPair = bytemuck::pod_read_unaligned(&data[8..]);
+            lb_pair.protocol_fee.amount_x = 1_000;
+            lb_pair.protocol_fee.amount_y = 2_000;
+            data[8..].copy_from_slice(bytemuck::bytes_of(&lb_pair));
+        }
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+
+        assert_eq!(meteora.vault_reserves().unwrap(), (1_000_000, 1_000_000));
+        // The fees sit in the reserve accounts until claimed, but no swap can take them
+        assert_eq!(
+            meteora.reserves(&Clock::default()).unwrap(),
+            (999_000, 998_000)
+        );
+    }
+
+    // Compares the quote against a simulated swap of a mainnet pool that trades often
+    // enough to carry a variable fee. DLMM_SIM_OWNER must hold token X and an ATA for
+    // token Y; SOLANA_RPC_URL overrides the public mainnet endpoint.
+    #[tokio::test]
+    #[ignore = "queries mainnet; needs DLMM_POOL and DLMM_SIM_OWNER"]
+    async fn test_swap_base_in_matches_simulated_swap_on_volatile_pool() {
+        use anchor_client::Cluster;
+        use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+        use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+        use solana_client::nonblocking::rpc_client::RpcClient;
+        use solana_client::rpc_config::{
+            RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+        };
+        use solana_sdk::{message::Message, transaction::Transaction};
+
+        let env_key = |name: &str| -> Pubkey { std::env::var(name).expect(name).parse().unwrap() };
+        let memo_program = Pubkey::from_str_const("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+        let pool_id = env_key("DLMM_POOL");
+        let owner = env_key("DLMM_SIM_OWNER");
+        let rpc_url =
+            std::env::var("SOLANA_RPC_URL").unwrap_or_else(|_| Cluster::Mainnet.url().to_string());
+        let rpc_client = RpcClient::new(rpc_url);
+
+        let lb_pair_account = rpc_client.get_account(&pool_id).await.unwrap();
+        let lb_pair: LbPair = bytemuck::pod_read_unaligned(&lb_pair_account.data[8..]);
+        assert!(
+            lb_pair.parameters.variable_fee_control > 0,
+            "pool has no variable fee"
+        );
+
+        let program_id_account =
+            create_mock_account_info_with_data(MeteoraDlmm::PROGRAM_ID, system_program::id(), None);
+        let (bitmap_extension_key, _) = pda::derive_bin_array_bitmap_extension(pool_id);
+        let bitmap_extension_account =
+            try_fetch_account_info_from_rpc(&rpc_client, bitmap_extension_key)
+                .await
+                .unwrap_or_else(|| program_id_account.clone());
+        let (event_authority_key, _) = pda::derive_event_authority_pda();
+        let mint_x_account = fetch_account_info_from_rpc(&rpc_client, lb_pair.token_x_mint).await;
+        let mint_y_account = fetch_account_info_from_rpc(&rpc_client, lb_pair.token_y_mint).await;
+        let (token_x_program, token_y_program) = (*mint_x_account.owner, *mint_y_account.owner);
+
+        let mut accounts = vec![
+            program_id_account,
+            account_to_account_info(pool_id, lb_pair_account),
+            fetch_account_info_from_rpc(&rpc_client, lb_pair.reserve_x).await,
+            fetch_account_info_from_rpc(&rpc_client, lb_pair.reserve_y).await,
+            mint_x_account,
+            mint_y_account,
+            fetch_account_info_from_rpc(&rpc_client, lb_pair.oracle).await,
+            create_mock_account_info_with_data(MeteoraDlmm::PROGRAM_ID, system_program::id(), None),
+            create_mock_account_info_with_data(memo_program, system_program::id(), None),
+            create_mock_account_info_with_data(event_authority_key, system_program::id(), None),
+            bitmap_extension_account.clone(),
+        ];
+        let bin_array_keys =
+            get_bin_array_pubkeys_for_swap(pool_id, &lb_pair, None, true, 3).unwrap();
+        for key in &bin_array_keys {
+            if let Some(account) = try_fetch_account_info_from_rpc(&rpc_client, *key).await {
+                accounts.push(account);
+            }
+        }
+
+        let clock = get_clock(&rpc_client).await.unwrap();
+        let amount_in = 1_000_000;
+        let quoted = MeteoraDlmm::new(&accounts)
+            .unwrap()
+            .swap_base_in(lb_pair.token_x_mint, amount_in, clock)
+            .unwrap();
+
+        let user_x = get_associated_token_address_with_program_id(
+            &owner,
+            &lb_pair.token_x_mint,
+            &token_x_program,
+        );
+        let user_y = get_associated_token_address_with_program_id(
+            &owner,
+            &lb_pair.token_y_mint,
+            &token_y_program,
+        );
+        let mut metas = vec![
+            AccountMeta::new(pool_id, false),
+            AccountMeta::new_readonly(*bitmap_extension_account.key, false),
+            AccountMeta::new(lb_pair.reserve_x, false),
+            AccountMeta::new(lb_pair.reserve_y, false),
+            AccountMeta::new(user_x, false),
+            AccountMeta::new(user_y, false),
+            AccountMeta::new_readonly(lb_pair.token_x_mint, false),
+            AccountMeta::new_readonly(lb_pair.token_y_mint, false),
+            AccountMeta::new(lb_pair.oracle, false),
+            AccountMeta::new_readonly(MeteoraDlmm::PROGRAM_ID, false),
+            AccountMeta::new(owner, true),
+            AccountMeta::new_readonly(token_x_program, false),
+            AccountMeta::new_readonly(token_y_program, false),
+            AccountMeta::new_readonly(memo_program, false),
+            AccountMeta::new_readonly(event_authority_key, false),
+            AccountMeta::new_readonly(MeteoraDlmm::PROGRAM_ID, false),
+        ];
+        metas.extend(
+            bin_array_keys
+                .iter()
+                .map(|key| AccountMeta::new(*key, false)),
+        );
+        let mut data = vec![65, 75, 63, 76, 235, 91, 91, 136];
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        let swap_ix = Instruction {
+            program_id: MeteoraDlmm::PROGRAM_ID,
+            accounts: metas,
+            data,
+        };
+
+        let balance = |data: &[u8]| u64::from_le_bytes(data[64..72].try_into().unwrap());
+        let before = balance(&rpc_client.get_account(&user_y).await.unwrap().data);
+        let transaction = Transaction::new_unsigned(Message::new(&[swap_ix], Some(&owner)));
+        let simulation = rpc_client
+            .simulate_transaction_with_config(
+                &transaction,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    accounts: Some(RpcSimulateTransactionAccountsConfig {
+                        encoding: None,
+                        addresses: vec![user_y.to_string()],
+                    }),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap()
+            .value;
+        assert!(simulation.err.is_none(), "{:?}", simulation.logs);
+        let after = simulation.accounts.unwrap()[0]
+            .as_ref()
+            .and_then(|account| account.data.decode())
+            .map(|data| balance(&data))
+            .unwrap();
+        let simulated = after - before;
+
+        // The pool may trade between the fetch and the simulation, so allow 10 bps
+        assert!(quoted.abs_diff(simulated) <= simulated / 1_000);
+    }
+
+    #[test]
+    fn test_estimate_compute_units_grows_with_bin_arrays() {
+        let accounts = create_bin_array_test_accounts(&[0, -1, -2, -3, -4, -5]);
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+        assert_eq!(
+            meteora.estimate_compute_units(),
+            MeteoraDlmm::SWAP_COMPUTE_UNITS + 6 * MeteoraDlmm::BIN_ARRAY_COMPUTE_UNITS
+        );
+
+        let single = create_bin_array_test_accounts(&[0]);
+        let single = MeteoraDlmm::new(&single).unwrap();
+        assert!(meteora.estimate_compute_units() > single.estimate_compute_units());
+    }
+
+    #[test]
+    fn test_new_checks_event_authority() {
+        let mut accounts = create_bin_array_test_accounts(&[0]);
+        assert_eq!(
+            derive_event_authority(&MeteoraDlmm::PROGRAM_ID),
+            pda::derive_event_authority_pda().0
+        );
+        assert!(MeteoraDlmm::new(&accounts).is_ok());
+
+        accounts[9] = create_mock_account_info_with_data(
+            Pubkey::new_unique(),
+            MeteoraDlmm::PROGRAM_ID,
+            None,
+        );
+        assert_eq!(
+            MeteoraDlmm::new(&accounts).err(),
+            Some(error!(SolarBError::InvalidEventAuthority))
+        );
+    }
+
+    #[test]
+    fn test_build_swap_ix_follows_direction() {
+        let accounts = create_two_sided_test_accounts();
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+        let user = SwapUserAccounts {
+            authority: Pubkey::new_unique(),
+            base_token_program: anchor_spl::token::ID,
+            quote_token_program: anchor_spl::token::ID,
+            user_base_token_account: Pubkey::new_unique(),
+            user_quote_token_account: Pubkey::new_unique(),
+        };
+
+        // Both directions are exact-in, so the buy leg gets the whole quote, not the minimum
+        for (direction, bin_arrays) in [
+            (
+                EdgeSide::LeftToRight,
+                meteora.get_bin_arrays_buy().unwrap_or_default(),
+            ),
+            (
+                EdgeSide::RightToLeft,
+                meteora.get_bin_arrays_sell().unwrap_or_default(),
+            ),
+        ] {
+            let ix = meteora.build_swap_ix(direction, 1_000, 990, &user).unwrap();
+            assert_eq!(ix.program_id, MeteoraDlmm::PROGRAM_ID);
+            assert_eq!(ix.accounts.len(), 16 + bin_arrays.len());
+            assert_eq!(ix.data[..8], MeteoraDlmm::SWAP2_DISCRIMINATOR);
+            assert_eq!(ix.data[8..16], 1_000u64.to_le_bytes());
+            assert_eq!(ix.data[16..24], 990u64.to_le_bytes());
+            assert_eq!(ix.accounts[10], AccountMeta::new(user.authority, true));
+            for (meta, bin_array) in ix.accounts[16..].iter().zip(&bin_arrays) {
+                assert_eq!(meta.pubkey, *bin_array.key);
+            }
+        }
+    }
+
+    // Token-2022 mint at `key` whose transfers call `hook_program`
+    fn create_transfer_hook_mint(key: Pubkey, hook_program: Pubkey) -> AccountInfo<'static> {
+        use anchor_spl::token_2022::spl_token_2022::extension::{
+            transfer_hook::TransferHook, BaseStateWithExtensionsMut, ExtensionType,
+            StateWithExtensionsMut,
+        };
+
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+            ExtensionType::TransferHook,
+        ])
+        .unwrap();
+        let mut data = vec![0u8; space];
+        let mut state =
+            StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data)
+                .unwrap();
+        state
+            .init_extension::<TransferHook>(true)
+            .unwrap()
+            .program_id = Some(hook_program).try_into().unwrap();
+        state.base.decimals = 6;
+        state.base.is_initialized = true;
+        state.pack_base();
+        state.init_account_type().unwrap();
+        create_mock_account_info_with_data(key, spl_token_2022::ID, Some(data))
+    }
+
+    #[test]
+    fn test_build_swap_ix_appends_transfer_hook_accounts() {
+        let mut accounts = create_two_sided_test_accounts();
+        let hook_program = Pubkey::new_unique();
+        let (extra_account_metas, _) = Pubkey::find_program_address(
+            &[EXTRA_ACCOUNT_METAS_SEED, accounts[4].key.as_ref()],
+            &hook_program,
+        );
+        let plain = MeteoraDlmm::new(&accounts).unwrap();
+        accounts[4] = create_transfer_hook_mint(*accounts[4].key, hook_program);
+        // The span carries the hook accounts for the CPI, after the bin arrays
+        for key in [hook_program, extra_account_metas] {
+            accounts.push(create_mock_account_info_with_data(
+                key,
+                system_program::id(),
+                None,
+            ));
+        }
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+        let user = SwapUserAccounts {
+            authority: Pubkey::new_unique(),
+            base_token_program: spl_token_2022::ID,
+            quote_token_program: anchor_spl::token::ID,
+            user_base_token_account: Pubkey::new_unique(),
+            user_quote_token_account: Pubkey::new_unique(),
+        };
+
+        // The hook accounts are not mistaken for bin arrays
+        let bin_array_keys = |arrays: Option<Vec<AccountInfo>>| -> Vec<Pubkey> {
+            arrays
+                .unwrap_or_default()
+                .iter()
+                .map(|account| *account.key)
+                .collect()
+        };
+        assert_eq!(
+            bin_array_keys(meteora.get_bin_arrays_buy()),
+            bin_array_keys(plain.get_bin_arrays_buy())
+        );
+        assert_eq!(
+            bin_array_keys(meteora.get_bin_arrays_sell()),
+            bin_array_keys(plain.get_bin_arrays_sell())
+        );
+
+        for (direction, bin_arrays) in [
+            (
+                EdgeSide::LeftToRight,
+                bin_array_keys(meteora.get_bin_arrays_buy()),
+            ),
+            (
+                EdgeSide::RightToLeft,
+                bin_array_keys(meteora.get_bin_arrays_sell()),
+            ),
+        ] {
+            let ix = meteora.build_swap_ix(direction, 1_000, 990, &user).unwrap();
+            assert_eq!(ix.accounts.len(), 18 + bin_arrays.len());
+            assert_eq!(
+                ix.accounts[16..18],
+                [
+                    AccountMeta::new_readonly(hook_program, false),
+                    AccountMeta::new_readonly(extra_account_metas, false),
+                ]
+            );
+            let tail: Vec<Pubkey> = ix.accounts[18..].iter().map(|meta| meta.pubkey).collect();
+            assert_eq!(tail, bin_arrays);
+            // One TransferHookX slice of two accounts
+            assert_eq!(
+                ix.data[24..],
+                [1, 0, 0, 0, ACCOUNTS_TYPE_TRANSFER_HOOK_X, 2]
+            );
+        }
+
+        // Without a hook the data ends with an empty slice list
+        let ix = plain
+            .build_swap_ix(EdgeSide::LeftToRight, 1_000, 990, &user)
+            .unwrap();
+        assert_eq!(ix.data[24..], 0u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_truncated_pool_fails_to_deserialize() {
+        let mut accounts = create_bin_array_test_accounts(&[0]);
+        let token_x_mint = *accounts[4].key;
+        accounts[1] = create_mock_account_info_with_data(
+            *accounts[1].key,
+            MeteoraDlmm::PROGRAM_ID,
+            Some(vec![0u8; 64]),
+        );
+        let meteora = MeteoraDlmm::new(&accounts).unwrap();
+        let clock = Clock::default();
+        assert_eq!(
+            meteora
+                .swap_base_in(token_x_mint, 1_000, clock.clone())
+                .err(),
+            Some(error!(SolarBError::PoolDeserializeFailed))
+        );
+        assert_eq!(
+            meteora.swap_base_out(token_x_mint, 1_000, clock).err(),
+            Some(error!(SolarBError::PoolDeserializeFailed))
+        );
+    }
 }
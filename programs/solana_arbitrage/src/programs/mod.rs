@@ -1,18 +1,55 @@
 pub mod constants;
 pub mod errors;
+#[cfg(feature = "lifinity")]
+pub mod lifinity;
+#[cfg(feature = "damm-v1")]
 pub mod meteora_damm_v1;
+#[cfg(feature = "damm-v2")]
 pub mod meteora_damm_v2;
+#[cfg(feature = "dlmm")]
 pub mod meteora_dlmm;
+#[cfg(feature = "phoenix")]
+pub mod phoenix;
 pub mod programs;
+#[cfg(feature = "pump-amm")]
 pub mod pump_amm;
+#[cfg(feature = "raydium-cpmm")]
 pub mod raydium_cpmm;
+#[cfg(feature = "saber")]
+pub mod saber;
+#[cfg(all(
+    test,
+    any(
+        feature = "damm-v1",
+        feature = "damm-v2",
+        feature = "lifinity",
+        feature = "phoenix",
+        feature = "saber"
+    )
+))]
+pub(crate) mod test_utils;
 pub mod types;
 
 pub use errors::SolarBError;
+#[cfg(feature = "lifinity")]
+pub use lifinity::LifinityV2;
+#[cfg(feature = "damm-v1")]
 pub use meteora_damm_v1::MeteoraDammV1;
+#[cfg(feature = "damm-v2")]
 pub use meteora_damm_v2::MeteoraDammV2;
+#[cfg(all(feature = "client", feature = "damm-v2"))]
+pub use meteora_damm_v2::MeteoraDammV2State;
+#[cfg(feature = "dlmm")]
 pub use meteora_dlmm::MeteoraDlmm;
-pub use programs::ProgramMeta;
+#[cfg(feature = "phoenix")]
+pub use phoenix::Phoenix;
+pub use programs::{PriceSource, ProgramMeta};
+#[cfg(feature = "pump-amm")]
 pub use pump_amm::PumpAmm;
+#[cfg(all(feature = "client", feature = "pump-amm"))]
+pub use pump_amm::PumpAmmState;
+#[cfg(feature = "raydium-cpmm")]
 pub use raydium_cpmm::RaydiumCPMM;
+#[cfg(feature = "saber")]
+pub use saber::Saber;
 pub use types::*;
@@ -0,0 +1,1168 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    account_info::next_account_info,
+    instruction::{AccountMeta, Instruction},
+    program::invoke,
+    pubkey::Pubkey,
+};
+
+use crate::arbitrage::base::EdgeSide;
+use crate::programs::programs::{
+    check_recorded_keys, check_vault_mints, quote_exact_out_by_search, read_mint_decimals,
+};
+use crate::programs::{ProgramMeta, SolarBError};
+use crate::utils::utils::{resolve_swap_accounts, ResolvedAccounts, SwapUserAccounts};
+
+// Offsets into the market account: the 576-byte MarketHeader, then the FIFOMarket
+const MARKET_STATUS_OFFSET: usize = 8;
+const MARKET_BIDS_SIZE_OFFSET: usize = 16;
+const MARKET_ASKS_SIZE_OFFSET: usize = 24;
+const MARKET_BASE_MINT_OFFSET: usize = 48;
+const MARKET_BASE_VAULT_OFFSET: usize = 80;
+const MARKET_BASE_LOT_SIZE_OFFSET: usize = 112;
+const MARKET_QUOTE_MINT_OFFSET: usize = 128;
+const MARKET_QUOTE_VAULT_OFFSET: usize = 160;
+const MARKET_QUOTE_LOT_SIZE_OFFSET: usize = 192;
+const MARKET_BASE_LOTS_PER_BASE_UNIT_OFFSET: usize = 832;
+const MARKET_TICK_SIZE_OFFSET: usize = 840;
+const MARKET_TAKER_FEE_BPS_OFFSET: usize = 856;
+const MARKET_BIDS_OFFSET: usize = 880;
+const MARKET_STATUS_ACTIVE: u64 = 1;
+
+// A sokoban red-black tree: the root (4) and padding (12), then its allocator's size (8),
+// bump index (4) and free list head (4) ahead of the nodes. Nodes are addressed from 1, as
+// 0 is the null node.
+const TREE_NODES_OFFSET: usize = 32;
+const NODE_SIZE: usize = 64;
+// Within a node: the left and right child registers, then the FIFOOrderId key and the
+// FIFORestingOrder value
+const NODE_LEFT_OFFSET: usize = 0;
+const NODE_RIGHT_OFFSET: usize = 4;
+const NODE_PRICE_IN_TICKS_OFFSET: usize = 16;
+const NODE_NUM_BASE_LOTS_OFFSET: usize = 40;
+const NODE_LAST_VALID_SLOT_OFFSET: usize = 48;
+const NODE_LAST_VALID_TIMESTAMP_OFFSET: usize = 56;
+
+const SEAT_MARKET_OFFSET: usize = 8;
+
+// `Swap` instruction tag, then the borsh `OrderPacket::ImmediateOrCancel` it carries
+const SWAP_INSTRUCTION_TAG: u8 = 0;
+const ORDER_PACKET_IMMEDIATE_OR_CANCEL: u8 = 2;
+const SIDE_BID: u8 = 0;
+const SIDE_ASK: u8 = 1;
+const SELF_TRADE_ABORT: u8 = 0;
+
+const BPS_DENOMINATOR: u128 = 10_000;
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    let bytes = data
+        .get(offset..offset + N)
+        .ok_or(error!(SolarBError::PoolDeserializeFailed))?;
+    Ok(bytes.try_into().unwrap())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(data, offset)?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(data, offset)?))
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    Ok(Pubkey::new_from_array(read_bytes(data, offset)?))
+}
+
+/// One resting order: `num_base_lots` offered at `price_in_ticks`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RestingOrder {
+    pub price_in_ticks: u64,
+    pub num_base_lots: u64,
+}
+
+/// The part of the market account needed to quote a market order
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MarketState {
+    pub status: u64,
+    pub bids_size: u64,
+    pub asks_size: u64,
+    /// Base atoms per base lot
+    pub base_lot_size: u64,
+    /// Quote atoms per quote lot
+    pub quote_lot_size: u64,
+    pub base_lots_per_base_unit: u64,
+    pub tick_size_in_quote_lots_per_base_unit: u64,
+    pub taker_fee_bps: u64,
+}
+
+impl MarketState {
+    /// Fails with `PoolDeserializeFailed` on a short account or one with a zero lot or tick
+    /// size, which no quote could be priced in
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let state = MarketState {
+            status: read_u64(data, MARKET_STATUS_OFFSET)?,
+            bids_size: read_u64(data, MARKET_BIDS_SIZE_OFFSET)?,
+            asks_size: read_u64(data, MARKET_ASKS_SIZE_OFFSET)?,
+            base_lot_size: read_u64(data, MARKET_BASE_LOT_SIZE_OFFSET)?,
+            quote_lot_size: read_u64(data, MARKET_QUOTE_LOT_SIZE_OFFSET)?,
+            base_lots_per_base_unit: read_u64(data, MARKET_BASE_LOTS_PER_BASE_UNIT_OFFSET)?,
+            tick_size_in_quote_lots_per_base_unit: read_u64(data, MARKET_TICK_SIZE_OFFSET)?,
+            taker_fee_bps: read_u64(data, MARKET_TAKER_FEE_BPS_OFFSET)?,
+        };
+        require!(
+            state.base_lot_size > 0
+                && state.quote_lot_size > 0
+                && state.base_lots_per_base_unit > 0
+                && state.tick_size_in_quote_lots_per_base_unit > 0,
+            SolarBError::PoolDeserializeFailed
+        );
+        Ok(state)
+    }
+
+    /// Offset of the asks tree, right after the bids
+    fn asks_offset(&self) -> Result<usize> {
+        usize::try_from(self.bids_size)
+            .ok()
+            .and_then(|size| size.checked_mul(NODE_SIZE))
+            .and_then(|nodes| nodes.checked_add(MARKET_BIDS_OFFSET + TREE_NODES_OFFSET))
+            .ok_or(error!(SolarBError::PoolDeserializeFailed))
+    }
+
+    /// Quote lots `num_base_lots` trade for at `price_in_ticks`, rounded down
+    fn quote_lots(&self, price_in_ticks: u64, num_base_lots: u64) -> Option<u128> {
+        (num_base_lots as u128)
+            .checked_mul(price_in_ticks as u128)?
+            .checked_mul(self.tick_size_in_quote_lots_per_base_unit as u128)?
+            .checked_div(self.base_lots_per_base_unit as u128)
+    }
+
+    /// Quote atoms per base atom at `price_in_ticks`
+    pub fn price(&self, price_in_ticks: u64) -> f64 {
+        price_in_ticks as f64
+            * self.tick_size_in_quote_lots_per_base_unit as f64
+            * self.quote_lot_size as f64
+            / (self.base_lots_per_base_unit as f64 * self.base_lot_size as f64)
+    }
+
+    fn fee_rate(&self) -> f64 {
+        self.taker_fee_bps as f64 / BPS_DENOMINATOR as f64
+    }
+
+    /// Quote lots a market sell of `num_base_lots` receives, net of the taker fee, walking
+    /// `bids` best first. Fails with `InsufficientLiquidity` when the bids run out first.
+    pub fn sell_base(&self, bids: &[RestingOrder], num_base_lots: u64) -> Result<u64> {
+        let mut remaining = num_base_lots;
+        let mut quote_lots = 0u128;
+        for order in bids {
+            if remaining == 0 {
+                break;
+            }
+            let filled = remaining.min(order.num_base_lots);
+            quote_lots = self
+                .quote_lots(order.price_in_ticks, filled)
+                .and_then(|lots| quote_lots.checked_add(lots))
+                .ok_or(error!(SolarBError::MathOverflow))?;
+            remaining -= filled;
+        }
+        require!(remaining == 0, SolarBError::InsufficientLiquidity);
+
+        // The fee is rounded up, as the market does
+        let fee = (quote_lots * self.taker_fee_bps as u128).div_ceil(BPS_DENOMINATOR);
+        u64::try_from(quote_lots - fee).map_err(|_| error!(SolarBError::MathOverflow))
+    }
+
+    /// Base lots a market buy spending `num_quote_lots`, taker fee included, receives,
+    /// walking `asks` best first. Fails with `InsufficientLiquidity` when the asks run out
+    /// before the budget does.
+    pub fn buy_base(&self, asks: &[RestingOrder], num_quote_lots: u64) -> Result<u64> {
+        // The fee is charged on top of the matched quote, so the budget is what matches
+        let mut budget = num_quote_lots as u128 * BPS_DENOMINATOR
+            / (BPS_DENOMINATOR + self.taker_fee_bps as u128);
+        let mut base_lots = 0u64;
+        for order in asks {
+            let cost = self
+                .quote_lots(order.price_in_ticks, order.num_base_lots)
+                .ok_or(error!(SolarBError::MathOverflow))?;
+            if cost <= budget {
+                base_lots = base_lots
+                    .checked_add(order.num_base_lots)
+                    .ok_or(error!(SolarBError::MathOverflow))?;
+                budget -= cost;
+                continue;
+            }
+            // The level is deeper than the budget: take the whole lots it pays for
+            let lot_cost =
+                order.price_in_ticks as u128 * self.tick_size_in_quote_lots_per_base_unit as u128;
+            let affordable = budget * self.base_lots_per_base_unit as u128 / lot_cost.max(1);
+            return base_lots
+                .checked_add(affordable as u64)
+                .ok_or(error!(SolarBError::MathOverflow));
+        }
+        require!(budget == 0, SolarBError::InsufficientLiquidity);
+        Ok(base_lots)
+    }
+}
+
+/// Orders of the tree at `offset` holding up to `capacity` nodes, in tree order. Orders
+/// past their last valid slot or timestamp at `clock` are left out; without a clock every
+/// order is kept.
+fn resting_orders(
+    data: &[u8],
+    offset: usize,
+    capacity: u64,
+    clock: Option<&Clock>,
+) -> Result<Vec<RestingOrder>> {
+    let node_offset = |address: u32| -> Result<usize> {
+        require!(
+            address as u64 <= capacity,
+            SolarBError::PoolDeserializeFailed
+        );
+        Ok(offset + TREE_NODES_OFFSET + (address as usize - 1) * NODE_SIZE)
+    };
+
+    let mut orders = Vec::new();
+    let mut stack = Vec::new();
+    let mut address = read_u32(data, offset)?;
+    // Iterative in-order walk; a tree can't hold more nodes than its capacity, so a cycle in
+    // malformed data stops there
+    while address != 0 || !stack.is_empty() {
+        while address != 0 {
+            require!(
+                (stack.len() + orders.len()) as u64 <= capacity,
+                SolarBError::PoolDeserializeFailed
+            );
+            stack.push(address);
+            address = read_u32(data, node_offset(address)? + NODE_LEFT_OFFSET)?;
+        }
+        let node = node_offset(stack.pop().unwrap())?;
+        let last_valid_slot = read_u64(data, node + NODE_LAST_VALID_SLOT_OFFSET)?;
+        let last_valid_timestamp = read_u64(data, node + NODE_LAST_VALID_TIMESTAMP_OFFSET)?;
+        let expired = clock.is_some_and(|clock| {
+            (last_valid_slot != 0 && last_valid_slot < clock.slot)
+                || (last_valid_timestamp != 0
+                    && (last_valid_timestamp as i64) < clock.unix_timestamp)
+        });
+        if !expired {
+            orders.push(RestingOrder {
+                price_in_ticks: read_u64(data, node + NODE_PRICE_IN_TICKS_OFFSET)?,
+                num_base_lots: read_u64(data, node + NODE_NUM_BASE_LOTS_OFFSET)?,
+            });
+        }
+        address = read_u32(data, node + NODE_RIGHT_OFFSET)?;
+    }
+    Ok(orders)
+}
+
+/// Phoenix v1 market, an on-chain limit order book.
+///
+/// Quotes fill a market order against the resting orders in the market account, best
+/// price first, so they hold only for the book as it stands when the transaction runs.
+/// Swaps are `Swap` instructions carrying an immediate-or-cancel order; input below one
+/// lot isn't traded and stays with the user.
+pub struct Phoenix<'info> {
+    pub program_id: AccountInfo<'info>,
+    pub log_authority: AccountInfo<'info>,
+    pub market: AccountInfo<'info>,
+    /// The authority's seat on the market; `Swap` doesn't read it, but it ties the span
+    /// to the market the user trades on
+    pub seat: AccountInfo<'info>,
+    pub base_vault: AccountInfo<'info>,
+    pub quote_vault: AccountInfo<'info>,
+    pub base_token: AccountInfo<'info>,
+    pub quote_token: AccountInfo<'info>,
+}
+
+impl<'info> ProgramMeta for Phoenix<'info> {
+    fn get_id(&self) -> &Pubkey {
+        &Self::PROGRAM_ID
+    }
+
+    fn program_label(&self) -> &'static str {
+        "Phoenix"
+    }
+
+    fn pool_key(&self) -> &Pubkey {
+        self.market.key
+    }
+
+    fn get_vaults(&self) -> (&AccountInfo<'_>, &AccountInfo<'_>) {
+        unsafe {
+            (
+                &*(&self.base_vault as *const AccountInfo<'info> as *const AccountInfo<'_>),
+                &*(&self.quote_vault as *const AccountInfo<'info> as *const AccountInfo<'_>),
+            )
+        }
+    }
+
+    fn get_mints(&self) -> (&Pubkey, &Pubkey) {
+        (self.base_token.key, self.quote_token.key)
+    }
+
+    fn mint_decimals(&self) -> (u8, u8) {
+        read_mint_decimals(&self.base_token, &self.quote_token)
+    }
+
+    /// A sell fills at the best bid first, whatever the vaults hold; 0 on an empty side
+    fn compute_price_swap_base_in(&self, _base_amount: u128, _quote_amount: u128) -> Result<f64> {
+        let market = self.market_state()?;
+        Ok(self.bids(None)?.first().map_or(0.0, |best| {
+            market.price(best.price_in_ticks) * (1.0 - market.fee_rate())
+        }))
+    }
+
+    /// A buy fills at the best ask first; 0 on an empty side
+    fn compute_price_swap_base_out(&self, _base_amount: u128, _quote_amount: u128) -> Result<f64> {
+        let market = self.market_state()?;
+        Ok(self.asks(None)?.first().map_or(0.0, |best| {
+            market.price(best.price_in_ticks).recip() * (1.0 - market.fee_rate())
+        }))
+    }
+
+    fn swap_base_in(&self, input_mint: Pubkey, amount_in: u64, clock: Clock) -> Result<u64> {
+        self.swap_base_in_impl(input_mint, amount_in, &clock)
+    }
+
+    /// Searched through the exact-in fill, as lot rounding makes some outputs unreachable
+    fn swap_base_out(&self, input_mint: Pubkey, amount_out: u64, clock: Clock) -> Result<u64> {
+        quote_exact_out_by_search(amount_out, |amount_in| {
+            self.swap_base_in_impl(input_mint, amount_in, &clock)
+        })
+    }
+
+    fn invoke_swap_base_in<'a>(
+        &self,
+        input_mint: Pubkey,
+        max_amount_in: u64,
+        amount_out: Option<u64>,
+        authority: AccountInfo<'a>,
+        user_mint_1_token_account: AccountInfo<'a>,
+        user_mint_2_token_account: AccountInfo<'a>,
+        mint_1_account: AccountInfo<'a>,
+        mint_2_account: AccountInfo<'a>,
+        mint_1_token_program: AccountInfo<'a>,
+        mint_2_token_program: AccountInfo<'a>,
+    ) -> Result<()> {
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap_base_in_impl(
+            input_mint,
+            max_amount_in,
+            amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
+    }
+
+    fn invoke_swap_base_out<'a>(
+        &self,
+        input_mint: Pubkey,
+        amount_in: u64,
+        min_amount_out: Option<u64>,
+        authority: AccountInfo<'a>,
+        user_mint_1_token_account: AccountInfo<'a>,
+        user_mint_2_token_account: AccountInfo<'a>,
+        mint_1_account: AccountInfo<'a>,
+        mint_2_account: AccountInfo<'a>,
+        mint_1_token_program: AccountInfo<'a>,
+        mint_2_token_program: AccountInfo<'a>,
+    ) -> Result<()> {
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap_base_in_impl(
+            input_mint,
+            amount_in,
+            min_amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
+    }
+
+    /// `Swap` with an immediate-or-cancel order: an ask of the whole base lots in
+    /// `amount_in`, or a bid spending its whole quote lots, filling at least `min_out`
+    fn build_swap_ix(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        user_accounts: &SwapUserAccounts,
+    ) -> Result<Instruction> {
+        let market = self.market_state()?;
+        let (side, num_base_lots, num_quote_lots, min_base_lots, min_quote_lots) = match direction {
+            EdgeSide::LeftToRight => (
+                SIDE_ASK,
+                amount_in / market.base_lot_size,
+                0,
+                0,
+                min_out.div_ceil(market.quote_lot_size),
+            ),
+            EdgeSide::RightToLeft => (
+                SIDE_BID,
+                0,
+                amount_in / market.quote_lot_size,
+                min_out.div_ceil(market.base_lot_size),
+                0,
+            ),
+        };
+
+        // No limit price, match limit or expiry
+        let mut data = vec![
+            SWAP_INSTRUCTION_TAG,
+            ORDER_PACKET_IMMEDIATE_OR_CANCEL,
+            side,
+            0,
+        ];
+        for lots in [num_base_lots, num_quote_lots, min_base_lots, min_quote_lots] {
+            data.extend_from_slice(&lots.to_le_bytes());
+        }
+        data.extend_from_slice(&[SELF_TRADE_ABORT, 0]);
+        data.extend_from_slice(&0u128.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0]);
+
+        Ok(Instruction {
+            program_id: *self.program_id.key,
+            accounts: vec![
+                AccountMeta::new_readonly(*self.program_id.key, false),
+                AccountMeta::new_readonly(*self.log_authority.key, false),
+                AccountMeta::new(*self.market.key, false),
+                AccountMeta::new_readonly(user_accounts.authority, true),
+                AccountMeta::new(user_accounts.user_base_token_account, false),
+                AccountMeta::new(user_accounts.user_quote_token_account, false),
+                AccountMeta::new(*self.base_vault.key, false),
+                AccountMeta::new(*self.quote_vault.key, false),
+                AccountMeta::new_readonly(user_accounts.base_token_program, false),
+            ],
+            data,
+        })
+    }
+
+    fn estimate_compute_units(&self) -> u32 {
+        Self::SWAP_COMPUTE_UNITS
+    }
+
+    /// Only an active market matches taker orders
+    fn is_tradable(&self, _clock: &Clock) -> Result<bool> {
+        Ok(self.market_state()?.status == MARKET_STATUS_ACTIVE)
+    }
+
+    fn validate_accounts(&self) -> Result<()> {
+        check_vault_mints(self)?;
+        let market_data = self.market.try_borrow_data()?;
+        check_recorded_keys(
+            self.market.key,
+            &[
+                (
+                    "base_mint",
+                    read_pubkey(&market_data, MARKET_BASE_MINT_OFFSET)?,
+                    self.base_token.key,
+                ),
+                (
+                    "quote_mint",
+                    read_pubkey(&market_data, MARKET_QUOTE_MINT_OFFSET)?,
+                    self.quote_token.key,
+                ),
+                (
+                    "base_vault",
+                    read_pubkey(&market_data, MARKET_BASE_VAULT_OFFSET)?,
+                    self.base_vault.key,
+                ),
+                (
+                    "quote_vault",
+                    read_pubkey(&market_data, MARKET_QUOTE_VAULT_OFFSET)?,
+                    self.quote_vault.key,
+                ),
+            ],
+        )?;
+        check_recorded_keys(
+            self.seat.key,
+            &[(
+                "market",
+                read_pubkey(&self.seat.try_borrow_data()?, SEAT_MARKET_OFFSET)?,
+                self.market.key,
+            )],
+        )
+    }
+
+    fn log_accounts(&self) -> Result<()> {
+        msg!(
+            "Phoenix accounts: market={}, seat={}, log_authority={}, base_vault={}, quote_vault={}, base_token={}, quote_token={}",
+            self.market.key,
+            self.seat.key,
+            self.log_authority.key,
+            self.base_vault.key,
+            self.quote_vault.key,
+            self.base_token.key,
+            self.quote_token.key,
+        );
+        Ok(())
+    }
+}
+
+impl<'info> Phoenix<'info> {
+    pub const PROGRAM_ID: Pubkey =
+        Pubkey::from_str_const("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY");
+    /// Shortest span `find_program_instance` accepts, program id included
+    pub const EXPECTED_ACCOUNTS: usize = 8;
+    /// The program's `log` PDA, which signs the events its instructions emit
+    pub const LOG_AUTHORITY: Pubkey =
+        Pubkey::from_str_const("7aDTsspkQNGKmrexAN7FLx9oxU3iPczSSvHNggyuqYkR");
+    /// Typical compute units of one swap CPI matching a handful of resting orders
+    pub const SWAP_COMPUTE_UNITS: u32 = 50_000;
+
+    pub fn new(accounts: &[AccountInfo<'info>]) -> Result<Self> {
+        let mut iter = accounts.iter();
+        let program_id = next_account_info(&mut iter)?; // 0
+        let log_authority = next_account_info(&mut iter)?; // 1
+        let market = next_account_info(&mut iter)?; // 2
+        let seat = next_account_info(&mut iter)?; // 3
+        let base_vault = next_account_info(&mut iter)?; // 4
+        let quote_vault = next_account_info(&mut iter)?; // 5
+        let base_token = next_account_info(&mut iter)?; // 6
+        let quote_token = next_account_info(&mut iter)?; // 7
+
+        // Phoenix's log authority plays the part of an Anchor event authority
+        require!(
+            *log_authority.key == Self::LOG_AUTHORITY,
+            SolarBError::InvalidEventAuthority
+        );
+
+        Ok(Phoenix {
+            program_id: program_id.clone(),
+            log_authority: log_authority.clone(),
+            market: market.clone(),
+            seat: seat.clone(),
+            base_vault: base_vault.clone(),
+            quote_vault: quote_vault.clone(),
+            base_token: base_token.clone(),
+            quote_token: quote_token.clone(),
+        })
+    }
+
+    pub fn market_state(&self) -> Result<MarketState> {
+        MarketState::parse(&self.market.try_borrow_data()?)
+    }
+
+    /// Bids live at `clock`, best (highest) first
+    pub fn bids(&self, clock: Option<&Clock>) -> Result<Vec<RestingOrder>> {
+        let market = self.market_state()?;
+        let data = self.market.try_borrow_data()?;
+        let mut bids = resting_orders(&data, MARKET_BIDS_OFFSET, market.bids_size, clock)?;
+        bids.sort_by(|a, b| b.price_in_ticks.cmp(&a.price_in_ticks));
+        Ok(bids)
+    }
+
+    /// Asks live at `clock`, best (lowest) first
+    pub fn asks(&self, clock: Option<&Clock>) -> Result<Vec<RestingOrder>> {
+        let market = self.market_state()?;
+        let data = self.market.try_borrow_data()?;
+        let mut asks = resting_orders(&data, market.asks_offset()?, market.asks_size, clock)?;
+        asks.sort_by_key(|order| order.price_in_ticks);
+        Ok(asks)
+    }
+
+    /// Exact-in quote of a market order: selling base walks the bids, buying it walks the
+    /// asks, each best price first and net of the taker fee. Fails with
+    /// `InsufficientLiquidity` when the book can't fill the whole order.
+    pub fn swap_base_in_impl(
+        &self,
+        input_mint: Pubkey,
+        amount_in: u64,
+        clock: &Clock,
+    ) -> Result<u64> {
+        let market = self.market_state()?;
+        let amount_out = if input_mint == *self.base_token.key {
+            let quote_lots =
+                market.sell_base(&self.bids(Some(clock))?, amount_in / market.base_lot_size)?;
+            (quote_lots as u128) * market.quote_lot_size as u128
+        } else {
+            let base_lots =
+                market.buy_base(&self.asks(Some(clock))?, amount_in / market.quote_lot_size)?;
+            (base_lots as u128) * market.base_lot_size as u128
+        };
+        u64::try_from(amount_out).map_err(|_| error!(SolarBError::MathOverflow))
+    }
+
+    pub fn invoke_swap_base_in_impl<'a>(
+        &self,
+        input_mint: Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+        authority: AccountInfo<'a>,
+        accounts: ResolvedAccounts<'a>,
+    ) -> Result<()> {
+        let direction = if input_mint == *self.base_token.key {
+            EdgeSide::LeftToRight
+        } else {
+            EdgeSide::RightToLeft
+        };
+        let swap_ix = self.build_swap_ix(
+            direction,
+            amount_in,
+            min_amount_out,
+            &accounts.keys(authority.key),
+        )?;
+
+        // Same lifetime cast as the other adapters: the market's accounts outlive the swap
+        let mut accounts_vec: Vec<AccountInfo<'info>> = vec![
+            self.program_id.to_account_info(),
+            self.log_authority.to_account_info(),
+            self.market.to_account_info(),
+            self.base_vault.to_account_info(),
+            self.quote_vault.to_account_info(),
+        ];
+        for account in [
+            authority,
+            accounts.user_base_token_account,
+            accounts.user_quote_token_account,
+            accounts.base_token_program,
+        ] {
+            accounts_vec.push(unsafe {
+                std::mem::transmute::<AccountInfo<'a>, AccountInfo<'info>>(account)
+            });
+        }
+
+        unsafe {
+            let accounts =
+                std::mem::transmute::<&[AccountInfo<'info>], &[AccountInfo<'a>]>(&accounts_vec);
+            invoke(&swap_ix, accounts)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::programs::test_utils::token_account_data;
+    use anchor_lang::solana_program::system_program;
+
+    const TOKEN_PROGRAM: Pubkey =
+        Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+    // Helper function to create a mock AccountInfo
+    fn create_mock_account_info(
+        key: Pubkey,
+        owner: Pubkey,
+        account_data: Option<Vec<u8>>,
+    ) -> AccountInfo<'static> {
+        let data = if let Some(provided_data) = account_data {
+            Box::leak(Box::new(provided_data))
+        } else {
+            Box::leak(Box::new(Vec::new()))
+        };
+        let lamports = Box::leak(Box::new(0u64));
+        let owner_static = Box::leak(Box::new(owner));
+        let key_static = Box::leak(Box::new(key));
+
+        AccountInfo::new(
+            key_static,
+            false,
+            false,
+            lamports,
+            data,
+            owner_static,
+            false,
+            0,
+        )
+    }
+
+    fn order(price_in_ticks: u64, num_base_lots: u64) -> RestingOrder {
+        RestingOrder {
+            price_in_ticks,
+            num_base_lots,
+        }
+    }
+
+    /// Bytes written field by field in the order of the upstream structs, so the fixtures
+    /// don't go through the offsets the parser reads
+    #[derive(Default)]
+    struct AccountWriter(Vec<u8>);
+
+    impl AccountWriter {
+        fn u32(&mut self, value: u32) -> &mut Self {
+            self.0.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        fn u64(&mut self, value: u64) -> &mut Self {
+            self.0.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        fn pubkey(&mut self, key: &Pubkey) -> &mut Self {
+            self.0.extend_from_slice(key.as_ref());
+            self
+        }
+
+        fn zeros(&mut self, len: usize) -> &mut Self {
+            self.0.resize(self.0.len() + len, 0);
+            self
+        }
+    }
+
+    /// Keys a market account records
+    struct MarketKeys {
+        base_mint: Pubkey,
+        base_vault: Pubkey,
+        quote_mint: Pubkey,
+        quote_vault: Pubkey,
+    }
+
+    // A sokoban `RedBlackTree<FIFOOrderId, FIFORestingOrder, capacity>` holding `orders` as
+    // nodes 1..=n, each the right child of the one before: a valid, if unbalanced, tree
+    // whose order the quotes mustn't rely on
+    fn write_tree(out: &mut AccountWriter, capacity: u64, orders: &[RestingOrder]) {
+        let root = if orders.is_empty() { 0 } else { 1 };
+        let len = orders.len() as u32;
+        // Root and padding, then the allocator's size, bump index and free list head
+        out.u32(root).zeros(12).u64(len as u64).u32(len + 1).u32(0);
+        for (i, order) in (1..).zip(orders) {
+            let right = if i < len { i + 1 } else { 0 };
+            // Left, right, parent and color registers
+            out.u32(0).u32(right).u32(i - 1).u32(0);
+            // FIFOOrderId: price and sequence number
+            out.u64(order.price_in_ticks).u64(i as u64);
+            // FIFORestingOrder: trader index, size, last valid slot and timestamp
+            out.u64(1).u64(order.num_base_lots).u64(0).u64(0);
+        }
+        out.zeros((capacity as usize - orders.len()) * 64);
+    }
+
+    // `MarketHeader`, then `FIFOMarket` up to its asks tree; the traders tree after it isn't
+    // read
+    fn market_data(
+        state: MarketState,
+        keys: &MarketKeys,
+        bids: &[RestingOrder],
+        asks: &[RestingOrder],
+    ) -> Vec<u8> {
+        let mut out = AccountWriter::default();
+        // Discriminant, status and size params: bids, asks and seats
+        out.u64(0)
+            .u64(state.status)
+            .u64(state.bids_size)
+            .u64(state.asks_size)
+            .u64(1);
+        // Base token params: decimals, vault bump, mint and vault
+        out.u32(9)
+            .u32(0)
+            .pubkey(&keys.base_mint)
+            .pubkey(&keys.base_vault)
+            .u64(state.base_lot_size);
+        out.u32(6)
+            .u32(0)
+            .pubkey(&keys.quote_mint)
+            .pubkey(&keys.quote_vault)
+            .u64(state.quote_lot_size);
+        // Tick size in quote atoms, authority, fee recipient, sequence number, successor,
+        // raw base units per base unit and padding
+        out.u64(state.tick_size_in_quote_lots_per_base_unit * state.quote_lot_size)
+            .pubkey(&Pubkey::new_unique())
+            .pubkey(&Pubkey::new_unique())
+            .u64(0)
+            .pubkey(&Pubkey::new_unique())
+            .u32(1)
+            .zeros(4 + 256);
+
+        // FIFOMarket: padding, then the lot and fee params, the order sequence number and
+        // the collected and unclaimed fees
+        out.zeros(256)
+            .u64(state.base_lots_per_base_unit)
+            .u64(state.tick_size_in_quote_lots_per_base_unit)
+            .u64(0)
+            .u64(state.taker_fee_bps)
+            .u64(0)
+            .u64(0);
+        write_tree(&mut out, state.bids_size, bids);
+        write_tree(&mut out, state.asks_size, asks);
+        out.0
+    }
+
+    /// The SOL/USDC market's parameters: 0.001 SOL lots, 0.000001 USDC lots, 0.001 USDC
+    /// ticks and a 2 bps taker fee
+    pub(crate) fn sol_usdc_state() -> MarketState {
+        MarketState {
+            status: MARKET_STATUS_ACTIVE,
+            bids_size: 16,
+            asks_size: 16,
+            base_lot_size: 1_000_000,
+            quote_lot_size: 1,
+            base_lots_per_base_unit: 1_000,
+            tick_size_in_quote_lots_per_base_unit: 1_000,
+            taker_fee_bps: 2,
+        }
+    }
+
+    // SOL/USDC book around $150, each side listed out of price order
+    fn sol_usdc_book() -> (Vec<RestingOrder>, Vec<RestingOrder>) {
+        (
+            vec![
+                order(149_900, 3_000),
+                order(150_000, 2_000),
+                order(149_500, 5_000),
+            ],
+            vec![order(150_200, 4_000), order(150_100, 1_000)],
+        )
+    }
+
+    /// Market over two fresh mints with the given book, its vaults holding `reserves`
+    pub(crate) fn create_market_accounts(
+        state: MarketState,
+        bids: &[RestingOrder],
+        asks: &[RestingOrder],
+        (base_reserve, quote_reserve): (u64, u64),
+    ) -> Vec<AccountInfo<'static>> {
+        let owner = system_program::id();
+        let market = Pubkey::new_unique();
+        let keys = MarketKeys {
+            base_mint: Pubkey::new_unique(),
+            base_vault: Pubkey::new_unique(),
+            quote_mint: Pubkey::new_unique(),
+            quote_vault: Pubkey::new_unique(),
+        };
+        // Seat: discriminant, then the market it's on
+        let mut seat = AccountWriter::default();
+        seat.u64(0).pubkey(&market).zeros(88);
+        vec![
+            create_mock_account_info(Phoenix::PROGRAM_ID, owner, None),
+            create_mock_account_info(Phoenix::LOG_AUTHORITY, owner, None),
+            create_mock_account_info(
+                market,
+                Phoenix::PROGRAM_ID,
+                Some(market_data(state, &keys, bids, asks)),
+            ),
+            create_mock_account_info(Pubkey::new_unique(), Phoenix::PROGRAM_ID, Some(seat.0)),
+            create_mock_account_info(
+                keys.base_vault,
+                TOKEN_PROGRAM,
+                Some(token_account_data(keys.base_mint, base_reserve)),
+            ),
+            create_mock_account_info(
+                keys.quote_vault,
+                TOKEN_PROGRAM,
+                Some(token_account_data(keys.quote_mint, quote_reserve)),
+            ),
+            create_mock_account_info(keys.base_mint, TOKEN_PROGRAM, None),
+            create_mock_account_info(keys.quote_mint, TOKEN_PROGRAM, None),
+        ]
+    }
+
+    /// USDC/USDT-like market: 6-decimal tokens, 0.001 base lots, 0.0001 ticks, quoted at
+    /// 0.9999 / 1.0001 with 10k of each side resting
+    pub(crate) fn create_test_accounts() -> Vec<AccountInfo<'static>> {
+        let state = MarketState {
+            base_lot_size: 1_000,
+            tick_size_in_quote_lots_per_base_unit: 100,
+            ..sol_usdc_state()
+        };
+        create_market_accounts(
+            state,
+            &[order(9_999, 10_000_000)],
+            &[order(10_001, 10_000_000)],
+            (1_000_000_000_000, 1_000_000_000_000),
+        )
+    }
+
+    fn create_sol_usdc_accounts() -> Vec<AccountInfo<'static>> {
+        let (bids, asks) = sol_usdc_book();
+        create_market_accounts(
+            sol_usdc_state(),
+            &bids,
+            &asks,
+            (10_000_000_000, 1_500_000_000),
+        )
+    }
+
+    #[test]
+    fn test_phoenix_new_insufficient_accounts() {
+        let accounts = create_test_accounts();
+        assert!(Phoenix::new(&accounts[..7]).is_err());
+    }
+
+    #[test]
+    fn test_phoenix_new_sufficient_accounts() {
+        let accounts = create_test_accounts();
+        let phoenix = Phoenix::new(&accounts).unwrap();
+
+        assert_eq!(*phoenix.program_id.key, Phoenix::PROGRAM_ID);
+        assert_eq!(phoenix.pool_key(), accounts[2].key);
+        assert_eq!(phoenix.seat.key, accounts[3].key);
+        assert_eq!(phoenix.get_mints(), (accounts[6].key, accounts[7].key));
+        assert_eq!(phoenix.market_state().unwrap().base_lot_size, 1_000);
+    }
+
+    #[test]
+    fn test_market_state_reads_upstream_layout() {
+        let accounts = create_sol_usdc_accounts();
+        let phoenix = Phoenix::new(&accounts).unwrap();
+        assert_eq!(phoenix.market_state().unwrap(), sol_usdc_state());
+        // The mints, vaults and seat market are where the market records them
+        assert!(phoenix.validate_accounts().is_ok());
+        assert_eq!(phoenix.bids(None).unwrap().len(), 3);
+        assert_eq!(phoenix.asks(None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_new_checks_log_authority() {
+        assert_eq!(
+            Phoenix::LOG_AUTHORITY,
+            Pubkey::find_program_address(&[b"log"], &Phoenix::PROGRAM_ID).0
+        );
+
+        let mut accounts = create_test_accounts();
+        accounts[1] = create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
+        assert_eq!(
+            Phoenix::new(&accounts).err(),
+            Some(error!(SolarBError::InvalidEventAuthority))
+        );
+    }
+
+    #[test]
+    fn test_book_sides_sort_best_first() {
+        let accounts = create_sol_usdc_accounts();
+        let phoenix = Phoenix::new(&accounts).unwrap();
+        let prices = |orders: Vec<RestingOrder>| -> Vec<u64> {
+            orders.iter().map(|order| order.price_in_ticks).collect()
+        };
+        assert_eq!(
+            prices(phoenix.bids(None).unwrap()),
+            [150_000, 149_900, 149_500]
+        );
+        assert_eq!(prices(phoenix.asks(None).unwrap()), [150_100, 150_200]);
+    }
+
+    #[test]
+    fn test_sell_fills_bids_level_by_level() {
+        let accounts = create_sol_usdc_accounts();
+        let phoenix = Phoenix::new(&accounts).unwrap();
+        let sol = *accounts[6].key;
+
+        // 4 SOL takes the 2 SOL bid at $150.000, then 2 of the 3 SOL at $149.900:
+        // 599.8 USDC, less the 2 bps fee rounded up
+        let usdc_out = phoenix
+            .swap_base_in(sol, 4_000_000_000, Clock::default())
+            .unwrap();
+        assert_eq!(usdc_out, 599_800_000 - 119_960);
+        assert!(usdc_out < 4 * 150_000_000);
+
+        // A partial lot isn't sold
+        assert_eq!(
+            phoenix
+                .swap_base_in(sol, 4_000_999_999, Clock::default())
+                .unwrap(),
+            usdc_out
+        );
+
+        let price = phoenix.compute_price_swap_base_in(0, 0).unwrap();
+        assert!((price - 0.15 * (1.0 - 0.0002)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_buy_fills_asks_level_by_level() {
+        let accounts = create_sol_usdc_accounts();
+        let phoenix = Phoenix::new(&accounts).unwrap();
+        let usdc = *accounts[7].key;
+
+        // $300 less the fee buys the 1 SOL at $150.100, and the $149.84 left buys 997 lots
+        // at $150.200
+        let sol_out = phoenix
+            .swap_base_in(usdc, 300_000_000, Clock::default())
+            .unwrap();
+        assert_eq!(sol_out, 1_997_000_000);
+
+        let price = phoenix.compute_price_swap_base_out(0, 0).unwrap();
+        assert!((price - (1.0 - 0.0002) / 0.1501).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_quote_rejects_order_beyond_book() {
+        let accounts = create_sol_usdc_accounts();
+        let phoenix = Phoenix::new(&accounts).unwrap();
+        let (sol, usdc) = (*accounts[6].key, *accounts[7].key);
+
+        // 10 SOL of bids and $750.90 of asks rest on the book
+        assert!(phoenix
+            .swap_base_in(sol, 10_000_000_000, Clock::default())
+            .is_ok());
+        for (mint, amount) in [(sol, 11_000_000_000), (usdc, 1_000_000_000)] {
+            assert_eq!(
+                phoenix
+                    .swap_base_in(mint, amount, Clock::default())
+                    .unwrap_err(),
+                error!(SolarBError::InsufficientLiquidity)
+            );
+        }
+    }
+
+    #[test]
+    fn test_expired_orders_are_skipped() {
+        let accounts = create_sol_usdc_accounts();
+        // The $150.000 bid, node 2, is good until slot 100
+        {
+            let mut data = accounts[2].try_borrow_mut_data().unwrap();
+            let node = MARKET_BIDS_OFFSET + TREE_NODES_OFFSET + NODE_SIZE;
+            data[node + NODE_LAST_VALID_SLOT_OFFSET..node + NODE_LAST_VALID_SLOT_OFFSET + 8]
+                .copy_from_slice(&100u64.to_le_bytes());
+        }
+        let phoenix = Phoenix::new(&accounts).unwrap();
+        let sol = *accounts[6].key;
+        let at_slot = |slot: u64| Clock {
+            slot,
+            ..Clock::default()
+        };
+
+        assert_eq!(
+            phoenix
+                .swap_base_in(sol, 1_000_000_000, at_slot(50))
+                .unwrap(),
+            150_000_000 - 30_000
+        );
+        assert_eq!(
+            phoenix
+                .swap_base_in(sol, 1_000_000_000, at_slot(200))
+                .unwrap(),
+            149_900_000 - 29_980
+        );
+    }
+
+    #[test]
+    fn test_inactive_market_is_not_tradable() {
+        let (bids, asks) = sol_usdc_book();
+        // Post-only, paused and closed markets don't take orders
+        for status in [2, 3, 4] {
+            let state = MarketState {
+                status,
+                ..sol_usdc_state()
+            };
+            let accounts = create_market_accounts(state, &bids, &asks, (1, 1));
+            let phoenix = Phoenix::new(&accounts).unwrap();
+            assert!(!phoenix.is_tradable(&Clock::default()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_validate_accounts() {
+        let accounts = create_test_accounts();
+        assert!(Phoenix::new(&accounts).unwrap().validate_accounts().is_ok());
+
+        // A seat on some other market
+        let mut foreign = accounts.clone();
+        let mut seat = vec![0u8; 128];
+        seat[SEAT_MARKET_OFFSET..SEAT_MARKET_OFFSET + 32]
+            .copy_from_slice(Pubkey::new_unique().as_ref());
+        foreign[3] =
+            create_mock_account_info(Pubkey::new_unique(), Phoenix::PROGRAM_ID, Some(seat));
+        let err = Phoenix::new(&foreign)
+            .unwrap()
+            .validate_accounts()
+            .unwrap_err();
+        assert_eq!(err, error!(SolarBError::PoolStateMismatch));
+    }
+
+    #[test]
+    fn test_truncated_market_fails_to_deserialize() {
+        let mut accounts = create_test_accounts();
+        accounts[2] = create_mock_account_info(
+            *accounts[2].key,
+            Phoenix::PROGRAM_ID,
+            Some(vec![0u8; MARKET_BIDS_OFFSET]),
+        );
+        let phoenix = Phoenix::new(&accounts).unwrap();
+        assert_eq!(
+            phoenix
+                .swap_base_in(*accounts[6].key, 1_000_000, Clock::default())
+                .unwrap_err(),
+            error!(SolarBError::PoolDeserializeFailed)
+        );
+    }
+
+    #[test]
+    fn test_build_swap_ix_follows_direction() {
+        let accounts = create_sol_usdc_accounts();
+        let phoenix = Phoenix::new(&accounts).unwrap();
+        let user = create_mock_accounts_for_swap(&accounts);
+        let payer = Pubkey::new_unique();
+
+        for direction in [EdgeSide::LeftToRight, EdgeSide::RightToLeft] {
+            let resolved = resolve_swap_accounts(
+                accounts[6].key,
+                user[0].clone(),
+                user[1].clone(),
+                user[2].clone(),
+                user[2].clone(),
+                user[3].clone(),
+                user[4].clone(),
+            )
+            .unwrap();
+            let ix = phoenix
+                .build_swap_ix(
+                    direction.clone(),
+                    2_500_000_000,
+                    370_000_000,
+                    &resolved.keys(&payer),
+                )
+                .unwrap();
+
+            assert_eq!(ix.program_id, Phoenix::PROGRAM_ID);
+            assert_eq!(ix.accounts.len(), 9);
+            assert_eq!(ix.accounts[1].pubkey, Phoenix::LOG_AUTHORITY);
+            assert_eq!(ix.accounts[2], AccountMeta::new(*accounts[2].key, false));
+            assert_eq!(ix.accounts[3], AccountMeta::new_readonly(payer, true));
+            // Base accounts come first in both directions
+            let keys: Vec<&Pubkey> = ix.accounts[4..8].iter().map(|meta| &meta.pubkey).collect();
+            assert_eq!(
+                keys,
+                [user[3].key, user[4].key, accounts[4].key, accounts[5].key]
+            );
+
+            assert_eq!(
+                ix.data[..2],
+                [SWAP_INSTRUCTION_TAG, ORDER_PACKET_IMMEDIATE_OR_CANCEL]
+            );
+            let lots: Vec<u64> = ix.data[4..36]
+                .chunks(8)
+                .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+                .collect();
+            match direction {
+                // Ask 2_500 SOL lots for at least 370 USDC in quote lots
+                EdgeSide::LeftToRight => {
+                    assert_eq!(ix.data[2], SIDE_ASK);
+                    assert_eq!(lots, [2_500, 0, 0, 370_000_000]);
+                }
+                // Bid $2_500 of quote lots for at least 370 SOL lots
+                EdgeSide::RightToLeft => {
+                    assert_eq!(ix.data[2], SIDE_BID);
+                    assert_eq!(lots, [0, 2_500_000_000, 370, 0]);
+                }
+            }
+            // Self-trade behavior, no match limit, client order id, deposited funds and
+            // expiry flags
+            assert_eq!(ix.data.len(), 36 + 2 + 16 + 3);
+        }
+    }
+
+    // User-side mints, token program and token accounts for the market's two mints
+    fn create_mock_accounts_for_swap(pool: &[AccountInfo<'static>]) -> Vec<AccountInfo<'static>> {
+        let owner = system_program::id();
+        vec![
+            pool[6].clone(),
+            pool[7].clone(),
+            create_mock_account_info(TOKEN_PROGRAM, owner, None),
+            create_mock_account_info(Pubkey::new_unique(), TOKEN_PROGRAM, None),
+            create_mock_account_info(Pubkey::new_unique(), TOKEN_PROGRAM, None),
+        ]
+    }
+}
@@ -6,4 +6,13 @@ pub const PUMP_AMM_GLOBAL : Pubkey = Pubkey::from_str_const("ADyA8hdefvWN2dbGGWF
 pub const PUMP_AMM_FEE_CONFIG: Pubkey = Pubkey::from_str_const("AVmoTthdrX6tKt4nDjco2D775W2YK3sDhxPcMmzUAmTY");
 pub const PUMP_AMM_FEE_TOKEN_ACCOUNT : Pubkey = Pubkey::from_str_const("FGptqdxjahafaCzpZ1T6EDtCzYMv7Dyn5MgBLyB3VUFW");
 pub const PUMP_AMM_EVENT_AUTHORITY: Pubkey = Pubkey::from_str_const("GS4CU59F31iL7aR2Q8zVS8DRrcRnXX1yjQ66TqNVQnaR");
-pub const PUMP_AMM_FEE_PROGRAM: Pubkey = Pubkey::from_str_const("pfeeUxB6jkeY1Hxd7CsFCAjcbHA9rWtchMGdZ6VojVZ");
\ No newline at end of file
+pub const PUMP_AMM_FEE_PROGRAM: Pubkey = Pubkey::from_str_const("pfeeUxB6jkeY1Hxd7CsFCAjcbHA9rWtchMGdZ6VojVZ");
+
+/// Default LP fee charged on the quote side of every swap, in basis points; the live rate is
+/// read from the fee_config account when it is supplied
+pub const LP_FEE_BPS: u128 = 20;
+/// Default protocol fee charged on the quote side of every swap, in basis points
+pub const PROTOCOL_FEE_BPS: u128 = 5;
+/// Default fee paid to the coin creator, in basis points, by pools that have one
+pub const COIN_CREATOR_FEE_BPS: u128 = 5;
+pub const FEE_DENOMINATOR: u128 = 10_000;
@@ -1,5 +1,9 @@
+use crate::arbitrage::base::EdgeSide;
+use crate::math::{constant_product_in, constant_product_out, mul_div, Rounding};
+use crate::programs::errors::SolarBError;
+use crate::programs::programs::{check_vault_mints, marginal_price, read_mint_decimals};
 use crate::programs::ProgramMeta;
-use crate::utils::utils::{parse_token_account, amount_with_slippage};
+use crate::utils::utils::{resolve_swap_accounts, ResolvedAccounts, SwapUserAccounts};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
     account_info::next_account_info,
@@ -8,8 +12,274 @@ use anchor_lang::solana_program::{
     program_error::ProgramError,
     pubkey::Pubkey,
 };
+use anchor_spl::associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent;
 mod constants;
 
+use self::constants::{COIN_CREATOR_FEE_BPS, FEE_DENOMINATOR, LP_FEE_BPS, PROTOCOL_FEE_BPS};
+
+// Borsh offsets (including the 8-byte discriminator) into the pump AMM pool account
+const POOL_BASE_MINT_OFFSET: usize = 43;
+const POOL_QUOTE_MINT_OFFSET: usize = 75;
+const POOL_BASE_TOKEN_ACCOUNT_OFFSET: usize = 139;
+const POOL_QUOTE_TOKEN_ACCOUNT_OFFSET: usize = 171;
+const POOL_COIN_CREATOR_OFFSET: usize = 211;
+
+// Borsh offsets (including the 8-byte discriminator) into the fee program's fee_config account:
+// bump (u8), admin (Pubkey), then the flat fees
+const FEE_CONFIG_LP_FEE_BPS_OFFSET: usize = 41;
+const FEE_CONFIG_PROTOCOL_FEE_BPS_OFFSET: usize = 49;
+const FEE_CONFIG_COIN_CREATOR_FEE_BPS_OFFSET: usize = 57;
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    let bytes = data
+        .get(offset..offset + 32)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    Ok(Pubkey::new_from_array(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    let bytes = data
+        .get(offset..offset + 8)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Flat swap fees published by the pump fee program's `fee_config` account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PumpFees {
+    pub lp_fee_bps: u128,
+    pub protocol_fee_bps: u128,
+    /// Only charged by pools with a coin creator; see `for_coin_creator`
+    pub coin_creator_fee_bps: u128,
+}
+
+impl Default for PumpFees {
+    fn default() -> Self {
+        PumpFees {
+            lp_fee_bps: LP_FEE_BPS,
+            protocol_fee_bps: PROTOCOL_FEE_BPS,
+            coin_creator_fee_bps: COIN_CREATOR_FEE_BPS,
+        }
+    }
+}
+
+impl PumpFees {
+    /// Reads the flat fees from `fee_config` account data, using the default rates when the
+    /// account is empty
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.is_empty() {
+            return Ok(Self::default());
+        }
+        let fees = PumpFees {
+            lp_fee_bps: read_u64(data, FEE_CONFIG_LP_FEE_BPS_OFFSET)? as u128,
+            protocol_fee_bps: read_u64(data, FEE_CONFIG_PROTOCOL_FEE_BPS_OFFSET)? as u128,
+            coin_creator_fee_bps: read_u64(data, FEE_CONFIG_COIN_CREATOR_FEE_BPS_OFFSET)? as u128,
+        };
+        if fees.total_fee_bps() >= FEE_DENOMINATOR {
+            return Err(ProgramError::InvalidAccountData.into());
+        }
+        Ok(fees)
+    }
+
+    /// The fees a pool with `coin_creator` charges: a pool without one (the default key)
+    /// pays no coin creator fee
+    pub fn for_coin_creator(self, coin_creator: &Pubkey) -> Self {
+        if *coin_creator != Pubkey::default() {
+            return self;
+        }
+        PumpFees {
+            coin_creator_fee_bps: 0,
+            ..self
+        }
+    }
+
+    /// Lp, protocol and coin creator fees together, in basis points
+    pub fn total_fee_bps(&self) -> u128 {
+        self.lp_fee_bps + self.protocol_fee_bps + self.coin_creator_fee_bps
+    }
+
+    /// Share of a quote amount taken as lp, protocol and coin creator fees
+    pub fn fee_rate(&self) -> f64 {
+        self.total_fee_bps() as f64 / FEE_DENOMINATOR as f64
+    }
+
+    /// Fees the program takes on a quote amount; each fee is rounded up on its own
+    fn quote_fees(&self, quote_amount: u128) -> Option<u128> {
+        self.fees_bps()
+            .into_iter()
+            .try_fold(0u128, |total, fee_bps| {
+                let fee = mul_div(quote_amount, fee_bps, FEE_DENOMINATOR, Rounding::Up)?;
+                total.checked_add(fee)
+            })
+    }
+
+    fn fees_bps(&self) -> [u128; 3] {
+        [
+            self.lp_fee_bps,
+            self.protocol_fee_bps,
+            self.coin_creator_fee_bps,
+        ]
+    }
+
+    /// Base received for `quote_in`; fees come out of the quote before it reaches the pool
+    pub fn buy_quote_input(
+        &self,
+        base_reserve: u128,
+        quote_reserve: u128,
+        quote_in: u128,
+    ) -> Option<u128> {
+        let effective_quote = mul_div(
+            quote_in,
+            FEE_DENOMINATOR,
+            FEE_DENOMINATOR + self.total_fee_bps(),
+            Rounding::Down,
+        )?;
+        constant_product_out(effective_quote, quote_reserve, base_reserve, 0)
+    }
+
+    /// Quote (fees included) needed to buy exactly `base_out`, the constant-product input
+    /// rounded up plus each fee on it rounded up, as `buy` charges it. `buy_quote_input`
+    /// lets the fees share one rounding, so this can ask up to one unit per charged fee
+    /// beyond the first more than the least input that buys `base_out` there; quoting
+    /// less would have `buy` fail its `max_quote_amount_in`.
+    pub fn buy_base_output(
+        &self,
+        base_reserve: u128,
+        quote_reserve: u128,
+        base_out: u128,
+    ) -> Option<u128> {
+        let quote_in = constant_product_in(base_out, quote_reserve, base_reserve, 0)?;
+        quote_in.checked_add(self.quote_fees(quote_in)?)
+    }
+
+    /// Quote received for `base_in`, after fees are taken from the pool's output
+    pub fn sell_base_input(
+        &self,
+        base_reserve: u128,
+        quote_reserve: u128,
+        base_in: u128,
+    ) -> Option<u128> {
+        let quote_out = constant_product_out(base_in, base_reserve, quote_reserve, 0)?;
+        quote_out.checked_sub(self.quote_fees(quote_out)?)
+    }
+
+    /// Base needed to receive exactly `quote_out` after fees
+    pub fn sell_quote_output(
+        &self,
+        base_reserve: u128,
+        quote_reserve: u128,
+        quote_out: u128,
+    ) -> Option<u128> {
+        if quote_out == 0 {
+            return Some(0);
+        }
+        // Each fee rounds up on its own, so together they can take up to one unit per fee
+        // more than their share of the pool's output. Grossing up by those units as well
+        // leaves at least `quote_out` after fees for this output or any larger one.
+        let charged_fees = self
+            .fees_bps()
+            .iter()
+            .filter(|fee_bps| **fee_bps > 0)
+            .count();
+        let raw_quote_out = mul_div(
+            quote_out.checked_add(charged_fees.saturating_sub(1) as u128)?,
+            FEE_DENOMINATOR,
+            FEE_DENOMINATOR.checked_sub(self.total_fee_bps())?,
+            Rounding::Up,
+        )?;
+        constant_product_in(raw_quote_out, base_reserve, quote_reserve, 0)
+    }
+
+    /// Exact-in quote: output for `amount_in`, selling base when `base_in` and buying it
+    /// with quote otherwise
+    pub fn quote_exact_input(
+        &self,
+        (base_reserve, quote_reserve): (u128, u128),
+        base_in: bool,
+        amount_in: u64,
+    ) -> Result<u64> {
+        let amount_out = if base_in {
+            self.sell_base_input(base_reserve, quote_reserve, amount_in as u128)
+        } else {
+            self.buy_quote_input(base_reserve, quote_reserve, amount_in as u128)
+        }
+        .ok_or(ProgramError::InvalidArgument)?;
+
+        u64::try_from(amount_out).map_err(|_| ProgramError::InvalidArgument.into())
+    }
+
+    /// Exact-out quote: input needed to receive `amount_out` of the other token, base going
+    /// in when `base_in`
+    pub fn quote_exact_output(
+        &self,
+        (base_reserve, quote_reserve): (u128, u128),
+        base_in: bool,
+        amount_out: u64,
+    ) -> Result<u64> {
+        let amount_in = if base_in {
+            self.sell_quote_output(base_reserve, quote_reserve, amount_out as u128)
+        } else {
+            self.buy_base_output(base_reserve, quote_reserve, amount_out as u128)
+        }
+        .ok_or(ProgramError::InvalidArgument)?;
+
+        u64::try_from(amount_in).map_err(|_| ProgramError::InvalidArgument.into())
+    }
+
+    /// Marginal price of selling base, the fees coming off the quote received
+    pub fn price_base_in(&self, base_amount: u128, quote_amount: u128) -> f64 {
+        marginal_price(base_amount, quote_amount, self.fee_rate())
+    }
+
+    /// Buying base adds the fees on top of the quote that reaches the pool, so a unit of
+    /// quote input only trades `1 / (1 + fee)` of itself
+    pub fn price_base_out(&self, base_amount: u128, quote_amount: u128) -> f64 {
+        let fee_rate = self.fee_rate();
+        marginal_price(quote_amount, base_amount, fee_rate / (1.0 + fee_rate))
+    }
+}
+
+/// A Pump AMM pool held as its fees and vault reserves rather than accounts, for quoting
+/// off-chain; see `PumpAmm::from_state`
+#[cfg(feature = "client")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PumpAmmState {
+    pub fees: PumpFees,
+    /// Base and quote vault reserves
+    pub vaults: (u128, u128),
+    /// Base and quote mints
+    pub mints: (Pubkey, Pubkey),
+}
+
+#[cfg(feature = "client")]
+impl PumpAmmState {
+    pub fn get_mints(&self) -> (&Pubkey, &Pubkey) {
+        (&self.mints.0, &self.mints.1)
+    }
+
+    /// `ProgramMeta::compute_price_swap_base_in` at the held reserves
+    pub fn compute_price_swap_base_in(&self) -> f64 {
+        self.fees.price_base_in(self.vaults.0, self.vaults.1)
+    }
+
+    /// `ProgramMeta::compute_price_swap_base_out` at the held reserves
+    pub fn compute_price_swap_base_out(&self) -> f64 {
+        self.fees.price_base_out(self.vaults.0, self.vaults.1)
+    }
+
+    /// Same quote as `ProgramMeta::swap_base_in`
+    pub fn swap_base_in(&self, input_mint: Pubkey, amount_in: u64) -> Result<u64> {
+        self.fees
+            .quote_exact_input(self.vaults, input_mint == self.mints.0, amount_in)
+    }
+
+    /// Same quote as `ProgramMeta::swap_base_out`
+    pub fn swap_base_out(&self, input_mint: Pubkey, amount_out: u64) -> Result<u64> {
+        self.fees
+            .quote_exact_output(self.vaults, input_mint == self.mints.0, amount_out)
+    }
+}
+
 pub struct PumpAmm<'info> {
     pub accounts: Vec<AccountInfo<'info>>,
     pub program_id: AccountInfo<'info>,
@@ -25,6 +295,14 @@ impl<'info> ProgramMeta for PumpAmm<'info> {
         &Self::PROGRAM_ID
     }
 
+    fn program_label(&self) -> &'static str {
+        "PumpAmm"
+    }
+
+    fn pool_key(&self) -> &Pubkey {
+        self.pool_id.key
+    }
+
     fn get_vaults(&self) -> (&AccountInfo<'_>, &AccountInfo<'_>) {
         unsafe {
             (
@@ -38,6 +316,21 @@ impl<'info> ProgramMeta for PumpAmm<'info> {
         (self.base_token.key, self.quote_token.key)
     }
 
+    fn mint_decimals(&self) -> (u8, u8) {
+        read_mint_decimals(&self.base_token, &self.quote_token)
+    }
+
+    /// Selling base takes the fees out of the quote received
+    fn compute_price_swap_base_in(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        Ok(self.parse_fees()?.price_base_in(base_amount, quote_amount))
+    }
+
+    /// Buying base adds the fees on top of the quote that reaches the pool, so a unit of
+    /// quote input only trades `1 / (1 + fee)` of itself
+    fn compute_price_swap_base_out(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        Ok(self.parse_fees()?.price_base_out(base_amount, quote_amount))
+    }
+
     fn swap_base_in(&self, input_mint: Pubkey, amount_in: u64, clock: Clock) -> Result<u64> {
         self.swap_base_in_impl(input_mint, amount_in, clock)
     }
@@ -51,7 +344,7 @@ impl<'info> ProgramMeta for PumpAmm<'info> {
         input_mint: Pubkey,
         max_amount_in: u64,
         amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -63,7 +356,7 @@ impl<'info> ProgramMeta for PumpAmm<'info> {
             input_mint,
             max_amount_in,
             amount_out,
-            payer,
+            authority,
             user_mint_1_token_account,
             user_mint_2_token_account,
             mint_1_account,
@@ -78,7 +371,7 @@ impl<'info> ProgramMeta for PumpAmm<'info> {
         input_mint: Pubkey,
         amount_in: u64,
         min_amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -90,7 +383,7 @@ impl<'info> ProgramMeta for PumpAmm<'info> {
             input_mint,
             amount_in,
             min_amount_out,
-            payer,
+            authority,
             user_mint_1_token_account,
             user_mint_2_token_account,
             mint_1_account,
@@ -100,6 +393,142 @@ impl<'info> ProgramMeta for PumpAmm<'info> {
         )
     }
 
+    /// Sells run `sell` of `amount_in` base; buys run `buy` of the base `amount_in` quote
+    /// pays for at the pool's current state, never less than `min_out`, for at most
+    /// `amount_in` quote
+    fn build_swap_ix(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        user_accounts: &SwapUserAccounts,
+    ) -> Result<Instruction> {
+        self.check_pool_state()?;
+
+        let protocol_fee_recipient = &self.accounts[6];
+        let protocol_fee_token_account = &self.accounts[7];
+        let event_authority = &self.accounts[8];
+        let fee_config = &self.accounts[9];
+        let fee_program = &self.accounts[10];
+        let user_volume_accumulator = &self.accounts[11];
+        let pump_amm_global = &self.accounts[12];
+        let system_program = &self.accounts[13];
+        let associated_token_program = &self.accounts[14];
+        let global_vol_accumulator = &self.accounts[15];
+
+        let mut metas = vec![
+            AccountMeta::new(*self.pool_id.key, false),
+            AccountMeta::new(user_accounts.authority, true),
+            AccountMeta::new_readonly(*pump_amm_global.key, false),
+            AccountMeta::new_readonly(*self.base_token.key, false),
+            AccountMeta::new_readonly(*self.quote_token.key, false),
+            AccountMeta::new(user_accounts.user_base_token_account, false),
+            AccountMeta::new(user_accounts.user_quote_token_account, false),
+            AccountMeta::new(*self.base_vault.key, false),
+            AccountMeta::new(*self.quote_vault.key, false),
+            AccountMeta::new_readonly(*protocol_fee_recipient.key, false),
+            AccountMeta::new(*protocol_fee_token_account.key, false),
+            AccountMeta::new_readonly(user_accounts.base_token_program, false),
+            AccountMeta::new_readonly(user_accounts.quote_token_program, false),
+            AccountMeta::new_readonly(*system_program.key, false),
+            AccountMeta::new_readonly(*associated_token_program.key, false),
+            AccountMeta::new_readonly(*event_authority.key, false),
+            AccountMeta::new_readonly(Self::PROGRAM_ID, false),
+        ];
+        if let Some((vault_ata, vault_authority)) = self.coin_creator_vault()? {
+            metas.push(AccountMeta::new(*vault_ata.key, false));
+            metas.push(AccountMeta::new_readonly(*vault_authority.key, false));
+        }
+        metas.push(AccountMeta::new_readonly(
+            *global_vol_accumulator.key,
+            false,
+        ));
+        metas.push(AccountMeta::new(*user_volume_accumulator.key, false));
+        metas.push(AccountMeta::new_readonly(*fee_config.key, false));
+        metas.push(AccountMeta::new_readonly(*fee_program.key, false));
+
+        // sell(base_amount_in, min_quote_amount_out), buy(base_amount_out, max_quote_amount_in)
+        let (discriminator, first, second) = match direction {
+            EdgeSide::LeftToRight => (Self::SELL_DISCRIMINATOR, amount_in, min_out),
+            EdgeSide::RightToLeft => {
+                let base_out = self.parse_fees()?.quote_exact_input(
+                    self.vault_reserves()?,
+                    false,
+                    amount_in,
+                )?;
+                (Self::BUY_DISCRIMINATOR, base_out.max(min_out), amount_in)
+            }
+        };
+        let mut data = discriminator.to_vec();
+        data.extend_from_slice(&first.to_le_bytes());
+        data.extend_from_slice(&second.to_le_bytes());
+
+        Ok(Instruction {
+            program_id: Self::PROGRAM_ID,
+            accounts: metas,
+            data,
+        })
+    }
+
+    fn estimate_compute_units(&self) -> u32 {
+        Self::SWAP_COMPUTE_UNITS
+    }
+
+    /// Each fee rounds up to a whole atom of the quote it comes out of, so a sale has to
+    /// clear several atoms of quote before any is left over: the exact-out quote for a
+    /// single atom accounts for that where the marginal price doesn't
+    fn min_swap_amount(&self, input_mint: &Pubkey, _clock: &Clock) -> Result<u64> {
+        self.parse_fees()?.quote_exact_output(
+            self.vault_reserves()?,
+            *input_mint == self.base_token.key(),
+            1,
+        )
+    }
+
+    fn validate_accounts(&self) -> Result<()> {
+        check_vault_mints(self)?;
+        self.check_pool_state()?;
+        self.coin_creator_vault()?;
+        Ok(())
+    }
+
+    /// The associated token program at index 14
+    fn associated_token_program(&self) -> Option<&Pubkey> {
+        Some(self.accounts[14].key)
+    }
+
+    fn invoke_create_token_account<'a>(
+        &self,
+        fee_payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        token_account: AccountInfo<'a>,
+        mint: AccountInfo<'a>,
+        token_program: AccountInfo<'a>,
+    ) -> Result<()> {
+        let create_ix = create_associated_token_account_idempotent(
+            fee_payer.key,
+            authority.key,
+            mint.key,
+            token_program.key,
+        );
+
+        // The system and associated token programs from the span, then the user's
+        // accounts. Same lifetime cast as `invoke_swap`.
+        let mut accounts_vec: Vec<AccountInfo<'info>> =
+            vec![self.accounts[13].clone(), self.accounts[14].clone()];
+        for account in [fee_payer, authority, token_account, mint, token_program] {
+            accounts_vec.push(unsafe {
+                std::mem::transmute::<AccountInfo<'a>, AccountInfo<'info>>(account)
+            });
+        }
+
+        unsafe {
+            let accounts: &[AccountInfo<'a>] = std::mem::transmute(accounts_vec.as_slice());
+            invoke(&create_ix, accounts)?;
+        }
+        Ok(())
+    }
+
     fn log_accounts(&self) -> Result<()> {
         msg!(
             "Pump AMM accounts: program_id={}, pool_id={}, base_vault={}, quote_vault={}, base_token={}, quote_token={}",
@@ -117,7 +546,31 @@ impl<'info> ProgramMeta for PumpAmm<'info> {
 impl<'info> PumpAmm<'info> {
     pub const PROGRAM_ID: Pubkey =
         Pubkey::from_str_const("pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA");
+    /// Shortest span `find_program_instance` accepts, program id included; the coin
+    /// creator vault ATA and authority may follow
+    pub const EXPECTED_ACCOUNTS: usize = 16;
+    /// Typical compute units of one swap CPI
+    pub const SWAP_COMPUTE_UNITS: u32 = 80_000;
+    /// Anchor discriminators of `buy` and `sell`
+    pub const BUY_DISCRIMINATOR: [u8; 8] = [0x66, 0x06, 0x3d, 0x12, 0x01, 0xda, 0xeb, 0xea];
+    pub const SELL_DISCRIMINATOR: [u8; 8] = [0x33, 0xe6, 0x85, 0xa4, 0x01, 0x7f, 0x83, 0xad];
+    /// Adapter over a swap span of the program id, pool, base and quote vaults and base
+    /// and quote mints (0-5), protocol fee recipient and its token account (6, 7), event
+    /// authority (8), `fee_config` and the fee program (9, 10), user volume accumulator
+    /// (11), global config (12), system and associated token programs (13, 14) and global
+    /// volume accumulator (15). Pools with a coin creator also need its vault ATA and
+    /// authority (16, 17).
+    ///
+    /// Shorter spans fail with `InsufficientAccounts`, as swaps read up to index 15.
     pub fn new(accounts: &[AccountInfo<'info>]) -> Result<Self> {
+        if accounts.len() < Self::EXPECTED_ACCOUNTS {
+            msg!(
+                "Pump AMM span holds {} accounts, swaps need at least {}",
+                accounts.len(),
+                Self::EXPECTED_ACCOUNTS
+            );
+            return Err(error!(SolarBError::InsufficientAccounts));
+        }
         let mut iter = accounts.iter();
         let program_id = next_account_info(&mut iter)?; // 0
         let pool_id = next_account_info(&mut iter)?; // 1
@@ -137,113 +590,121 @@ impl<'info> PumpAmm<'info> {
         })
     }
 
-    pub fn parse_vaults(&self) -> Result<(u128, u128)> {
-        let base_vault = parse_token_account(&self.base_vault)?;
-        let quote_vault = parse_token_account(&self.quote_vault)?;
-        Ok((base_vault.amount as u128, quote_vault.amount as u128))
+    /// Quote-only view of a pool whose fees and reserves were fetched already, so off-chain
+    /// callers don't have to build its accounts
+    #[cfg(feature = "client")]
+    pub fn from_state(
+        fees: PumpFees,
+        vaults: (u128, u128),
+        mints: (Pubkey, Pubkey),
+    ) -> PumpAmmState {
+        PumpAmmState {
+            fees,
+            vaults,
+            mints,
+        }
+    }
+
+    /// Current fee rates from the stored `fee_config` account (index 9), less the coin
+    /// creator fee when the pool has no coin creator
+    pub fn parse_fees(&self) -> Result<PumpFees> {
+        let fees = match self.accounts.get(9) {
+            Some(fee_config) => PumpFees::parse(&fee_config.try_borrow_data()?)?,
+            None => PumpFees::default(),
+        };
+        Ok(fees.for_coin_creator(&self.coin_creator()?))
+    }
+
+    /// Coin creator recorded in the pool account, the default key when it has none. Pools
+    /// created before the field was added are too short to hold one.
+    pub fn coin_creator(&self) -> Result<Pubkey> {
+        let data = self.pool_id.try_borrow_data()?;
+        if data.len() < POOL_COIN_CREATOR_OFFSET + 32 {
+            return Ok(Pubkey::default());
+        }
+        read_pubkey(&data, POOL_COIN_CREATOR_OFFSET)
+    }
+
+    /// The coin creator vault ATA and authority (indices 16 and 17) when supplied. A pool
+    /// with a coin creator pays its fee into them, so it fails without them.
+    fn coin_creator_vault(&self) -> Result<Option<(&AccountInfo<'info>, &AccountInfo<'info>)>> {
+        match (self.accounts.get(16), self.accounts.get(17)) {
+            (Some(vault_ata), Some(vault_authority)) => Ok(Some((vault_ata, vault_authority))),
+            _ if self.coin_creator()? != Pubkey::default() => {
+                msg!(
+                    "Pump AMM pool {} has a coin creator but no creator vault accounts",
+                    self.pool_id.key
+                );
+                Err(error!(SolarBError::MissingCoinCreatorVault))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Checks that the pool account records the supplied vaults and mints.
+    ///
+    /// The pool state does not track reserves of its own: the program prices swaps off the
+    /// balances of the vault token accounts it records, so quotes only agree with the
+    /// program's view when those are the vaults we read.
+    pub fn check_pool_state(&self) -> Result<()> {
+        let data = self.pool_id.try_borrow_data()?;
+        let expected = [
+            ("base_mint", POOL_BASE_MINT_OFFSET, self.base_token.key),
+            ("quote_mint", POOL_QUOTE_MINT_OFFSET, self.quote_token.key),
+            (
+                "base_vault",
+                POOL_BASE_TOKEN_ACCOUNT_OFFSET,
+                self.base_vault.key,
+            ),
+            (
+                "quote_vault",
+                POOL_QUOTE_TOKEN_ACCOUNT_OFFSET,
+                self.quote_vault.key,
+            ),
+        ];
+        for (name, offset, supplied) in expected {
+            let recorded = read_pubkey(&data, offset)?;
+            if recorded != *supplied {
+                msg!(
+                    "Pump AMM pool {} {} mismatch: pool state has {}, supplied {}",
+                    self.pool_id.key,
+                    name,
+                    recorded,
+                    supplied
+                );
+                return Err(error!(SolarBError::PoolStateMismatch));
+            }
+        }
+        Ok(())
     }
 
-    /// Calculate base output amount for a given quote input amount
-    /// Formula: base_amount_out = base_reserve - (base_reserve * quote_reserve) / (quote_reserve + quote_amount_in)
-    /// Then applies 0.02% fee (multiply by 0.9998)
+    /// Exact-in quote: output amount for `amount_in` of `input_mint`, net of fees
     pub fn swap_base_in_impl(
         &self,
         input_mint: Pubkey,
         amount_in: u64,
         _clock: Clock,
     ) -> Result<u64> {
-        // Get reserves from vaults
-        let base_vault_account = parse_token_account(&self.base_vault)?;
-        let quote_vault_account = parse_token_account(&self.quote_vault)?;
-        let base_reserve = base_vault_account.amount as u128;
-        let quote_reserve = quote_vault_account.amount as u128;
-
-        // quote_amount_in is the input parameter (amount_in)
-        // base_amount_out = base_reserve - (base_reserve * quote_reserve) / (quote_reserve + quote_amount_in)
-        let numerator = base_reserve
-            .checked_mul(quote_reserve)
-            .ok_or(ProgramError::InvalidArgument)?;
-        let denominator = quote_reserve
-            .checked_add(amount_in as u128)
-            .ok_or(ProgramError::InvalidArgument)?;
-        let quotient = numerator
-            .checked_div(denominator)
-            .ok_or(ProgramError::InvalidArgument)?;
-        let base_amount_out = base_reserve
-            .checked_sub(quotient)
-            .ok_or(ProgramError::InvalidArgument)?;
-
-        // Apply 0.02% fee → multiply by 0.9998 (use integer arithmetic: * 9998 / 10000)
-        let base_amount_out_after_fee = base_amount_out
-            .checked_mul(9_998)
-            .and_then(|x| x.checked_div(10_000))
-            .ok_or(ProgramError::InvalidArgument)?;
-        
-        let amount_out  = amount_with_slippage(base_amount_out_after_fee as u64, 0.02, false);
-        Ok(amount_out as u64)
-    }
-
-    /// Calculate base output amount for a given quote input amount
-    /// Formula: base_amount_out = base_reserve - (base_reserve * quote_reserve) / (quote_reserve + quote_amount_in)
-    /// Then applies lp_fee (0.2%), protocol_fee (0.05%), and multiplies by 1.0023
+        self.parse_fees()?.quote_exact_input(
+            self.vault_reserves()?,
+            input_mint == self.base_token.key(),
+            amount_in,
+        )
+    }
+
+    /// Exact-out quote: amount of `input_mint` needed to receive `amount_out` of the other
+    /// token, fees included
     pub fn swap_base_out_impl(
         &self,
         input_mint: Pubkey,
-        amount_in: u64,
+        amount_out: u64,
         _clock: Clock,
     ) -> Result<u64> {
-        // Get reserves from vaults
-        let base_vault_account = parse_token_account(&self.base_vault)?;
-        let quote_vault_account = parse_token_account(&self.quote_vault)?;
-        let base_reserve = base_vault_account.amount as u128;
-        let quote_reserve = quote_vault_account.amount as u128;
-        // quote_amount_out = quote_reserve - (base_reserve * quote_reserve) / (base_reserve + base_amount_in)
-
-        // let base_reserve = 114912171739565u128;
-        // let quote_reserve = 12070053361u128;
-
-        let numerator = base_reserve
-            .checked_mul(quote_reserve)
-            .ok_or(ProgramError::InvalidArgument)?;
-        let denominator = base_reserve
-            .checked_add(amount_in as u128)
-            .ok_or(ProgramError::InvalidArgument)?;
-        let quotient = numerator
-            .checked_div(denominator)
-            .ok_or(ProgramError::InvalidArgument)?;
-        let quote_amount_out = quote_reserve
-            .checked_sub(quotient)
-            .ok_or(ProgramError::InvalidArgument)?;
-
-        // lp_fee = int(quote_amount_out * 0.002) (0.2%)
-        let lp_fee = quote_amount_out
-            .checked_mul(2)
-            .and_then(|x| x.checked_div(1_000))
-            .ok_or(ProgramError::InvalidArgument)?;
-
-        // protocol_fee = int(quote_amount_out * 0.0005) (0.05%)
-        let protocol_fee = quote_amount_out
-            .checked_mul(5)
-            .and_then(|x| x.checked_div(10_000))
-            .ok_or(ProgramError::InvalidArgument)?;
-
-        // fees = lp_fee + protocol_fee
-        let fees = lp_fee
-            .checked_add(protocol_fee)
-            .ok_or(ProgramError::InvalidArgument)?;
-
-        // quote_amount_out - fees
-        let quote_after_fees = quote_amount_out
-            .checked_sub(fees)
-            .ok_or(ProgramError::InvalidArgument)?;
-
-        // Multiply by 1.0023 (use integer arithmetic: * 10023 / 10000)
-        let final_amount = quote_after_fees
-            .checked_mul(10_023)
-            .and_then(|x| x.checked_div(10_000))
-            .ok_or(ProgramError::InvalidArgument)?;
-
-        Ok(final_amount as u64)
+        self.parse_fees()?.quote_exact_output(
+            self.vault_reserves()?,
+            input_mint == self.base_token.key(),
+            amount_out,
+        )
     }
 
     pub fn invoke_swap_base_in_impl<'a>(
@@ -251,7 +712,7 @@ impl<'info> PumpAmm<'info> {
         _input_mint: Pubkey,
         max_amount_in: u64,
         amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -259,133 +720,22 @@ impl<'info> PumpAmm<'info> {
         mint_1_token_program: AccountInfo<'a>,
         mint_2_token_program: AccountInfo<'a>,
     ) -> Result<()> {
-        let (
-            base_token_program,
-            quote_token_program,
-            user_base_token_account,
-            user_quote_token_account,
-        ) = if mint_1_account.key == self.base_token.key {
-            (
-                mint_1_token_program,
-                mint_2_token_program,
-                user_mint_1_token_account,
-                user_mint_2_token_account,
-            )
-        } else if mint_2_account.key == self.base_token.key {
-            (
-                mint_2_token_program,
-                mint_1_token_program,
-                user_mint_2_token_account,
-                user_mint_1_token_account,
-            )
-        } else {
-            return Err(ProgramError::InvalidAccountData.into());
-        };
-
-        // Get stored accounts from self.get_accounts() - these are the accounts stored in the struct
-        let stored_accounts = self.accounts.clone();
-        let program_id_stored = &stored_accounts[0];
-        let pool_id = &stored_accounts[1];
-        let base_vault = &stored_accounts[2];
-        let quote_vault = &stored_accounts[3];
-        let base_token = &stored_accounts[4];
-        let quote_token = &stored_accounts[5];
-        let protocol_fee_recipient = &stored_accounts[6];
-        let protocol_fee_token_account = &stored_accounts[7];
-        let event_authority = &stored_accounts[8];
-        let fee_config = &stored_accounts[9];
-        let fee_program = &stored_accounts[10];
-        let user_volume_accumulator = &stored_accounts[11];
-        let pump_amm_global = &stored_accounts[12];
-        let system_program = &stored_accounts[13];
-        let associated_token_instruction_program = &stored_accounts[14];
-        let global_vol_accumulator = &stored_accounts[15];
-
-        // Extract optional vault_ata and vault_authority if present
-        let (vault_ata, vault_authority) = if stored_accounts.len() >= 18 {
-            (Some(&stored_accounts[16]), Some(&stored_accounts[17]))
-        } else {
-            (None, None)
-        };
-
-        let amount_out_value = amount_out.unwrap_or(0);
-        let mut metas = vec![
-            AccountMeta::new(*pool_id.key, false),
-            AccountMeta::new(*payer.key, true),
-            AccountMeta::new_readonly(*pump_amm_global.key, false),
-            AccountMeta::new_readonly(*base_token.key, false),
-            AccountMeta::new_readonly(*quote_token.key, false),
-            AccountMeta::new(*user_base_token_account.key, false),
-            AccountMeta::new(*user_quote_token_account.key, false),
-            AccountMeta::new(*base_vault.key, false),
-            AccountMeta::new(*quote_vault.key, false),
-            AccountMeta::new_readonly(*protocol_fee_recipient.key, false),
-            AccountMeta::new(*protocol_fee_token_account.key, false),
-            AccountMeta::new_readonly(*base_token_program.key, false),
-            AccountMeta::new_readonly(*quote_token_program.key, false),
-            AccountMeta::new_readonly(*system_program.key, false),
-            AccountMeta::new_readonly(*associated_token_instruction_program.key, false),
-            AccountMeta::new_readonly(*event_authority.key, false),
-            AccountMeta::new_readonly(Self::PROGRAM_ID, false),
-        ];
-        if let (Some(vault_ata_acc), Some(vault_authority_acc)) = (vault_ata, vault_authority) {
-            metas.push(AccountMeta::new(*vault_ata_acc.key, false));
-            metas.push(AccountMeta::new_readonly(*vault_authority_acc.key, false));
-        }
-        metas.push(AccountMeta::new_readonly(
-            *global_vol_accumulator.key,
-            false,
-        ));
-        metas.push(AccountMeta::new(*user_volume_accumulator.key, false));
-        metas.push(AccountMeta::new_readonly(*fee_config.key, false));
-        metas.push(AccountMeta::new_readonly(*fee_program.key, false));
-
-        let mut data = vec![0x66, 0x06, 0x3d, 0x12, 0x01, 0xda, 0xeb, 0xea];
-        data.extend_from_slice(&amount_out_value.to_le_bytes());
-        data.extend_from_slice(&max_amount_in.to_le_bytes());
-
-        let swap_ix = Instruction {
-            program_id: Self::PROGRAM_ID,
-            accounts: metas,
-            data,
-        };
-        // Order must match metas exactly!
-        let mut accounts: Vec<AccountInfo<'info>> = vec![
-            pool_id.clone(),                                         // 0: writable
-            unsafe { std::mem::transmute(payer.to_account_info()) }, // 1: writable, signer
-            pump_amm_global.clone(),                                 // 2: readonly
-            base_token.clone(),                                      // 3: readonly
-            quote_token.clone(),                                     // 4: readonly
-            unsafe { std::mem::transmute(user_base_token_account.to_account_info()) }, // 5: writable
-            unsafe { std::mem::transmute(user_quote_token_account.to_account_info()) }, // 6: writable
-            base_vault.clone(),                 // 7: writable
-            quote_vault.clone(),                // 8: writable
-            protocol_fee_recipient.clone(),     // 9: readonly
-            protocol_fee_token_account.clone(), // 10: writable
-            unsafe { std::mem::transmute(base_token_program.to_account_info()) }, // 11: readonly
-            unsafe { std::mem::transmute(quote_token_program.to_account_info()) }, // 12: readonly
-            system_program.clone(),             // 13: readonly
-            associated_token_instruction_program.clone(), // 14: readonly
-            event_authority.clone(),            // 15: readonly
-            program_id_stored.clone(),          // 16: readonly (PROGRAM_ID)
-        ];
-
-        if let (Some(vault_ata_acc), Some(vault_authority_acc)) = (vault_ata, vault_authority) {
-            accounts.push(vault_ata_acc.clone());
-            accounts.push(vault_authority_acc.clone());
-        }
-
-        accounts.push(global_vol_accumulator.clone());
-        accounts.push(user_volume_accumulator.clone());
-        accounts.push(fee_config.clone());
-        accounts.push(fee_program.clone());
-
-        // Cast entire vector to AccountInfo<'a> for invoke
-        unsafe {
-            let accounts_slice: &[AccountInfo<'a>] = std::mem::transmute(accounts.as_slice());
-            invoke(&swap_ix, accounts_slice)?;
-        }
-        Ok(())
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            EdgeSide::RightToLeft,
+            max_amount_in,
+            amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
     }
 
     pub fn invoke_swap_base_out_impl<'a>(
@@ -393,7 +743,7 @@ impl<'info> PumpAmm<'info> {
         _input_mint: Pubkey,
         amount_in: u64,
         min_amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -401,141 +751,60 @@ impl<'info> PumpAmm<'info> {
         mint_1_token_program: AccountInfo<'a>,
         mint_2_token_program: AccountInfo<'a>,
     ) -> Result<()> {
-        let (
-            base_token_program,
-            quote_token_program,
-            user_base_token_account,
-            user_quote_token_account,
-        ) = if mint_1_account.key == self.base_token.key {
-            (
-                mint_1_token_program,
-                mint_2_token_program,
-                user_mint_1_token_account,
-                user_mint_2_token_account,
-            )
-        } else if mint_2_account.key == self.base_token.key {
-            (
-                mint_2_token_program,
-                mint_1_token_program,
-                user_mint_2_token_account,
-                user_mint_1_token_account,
-            )
-        } else {
-            return Err(ProgramError::InvalidAccountData.into());
-        };
-
-        // Get stored accounts from self.get_accounts() - these are the accounts stored in the struct
-        let stored_accounts = self.accounts.clone();
-        let program_id_stored = &stored_accounts[0];
-        let pool_id = &stored_accounts[1];
-        let base_vault = &stored_accounts[2];
-        let quote_vault = &stored_accounts[3];
-        let base_token = &stored_accounts[4];
-        let quote_token = &stored_accounts[5];
-        let protocol_fee_recipient = &stored_accounts[6];
-        let protocol_fee_token_account = &stored_accounts[7];
-        let event_authority = &stored_accounts[8];
-        let fee_config = &stored_accounts[9];
-        let fee_program = &stored_accounts[10];
-        let user_volume_accumulator = &stored_accounts[11];
-        let pump_amm_global = &stored_accounts[12];
-        let system_program = &stored_accounts[13];
-        let associated_token_instruction_program = &stored_accounts[14];
-        let global_vol_accumulator = &stored_accounts[15];
-
-        // Extract optional vault_ata and vault_authority if present
-        let (vault_ata, vault_authority) = if stored_accounts.len() >= 18 {
-            (Some(&stored_accounts[16]), Some(&stored_accounts[17]))
-        } else {
-            (None, None)
-        };
-
-        // Note: payer, user_base_token_account, user_quote_token_account, base_token_program, quote_token_program
-        // are function parameters (already available from lines 442-463)
-
-        let min_amount_out_value = min_amount_out.unwrap_or(0);
-        let mut metas = vec![
-            AccountMeta::new(*pool_id.key, false),
-            AccountMeta::new(*payer.key, true),
-            AccountMeta::new_readonly(*pump_amm_global.key, false),
-            AccountMeta::new_readonly(*base_token.key, false),
-            AccountMeta::new_readonly(*quote_token.key, false),
-            AccountMeta::new(*user_base_token_account.key, false),
-            AccountMeta::new(*user_quote_token_account.key, false),
-            AccountMeta::new(*base_vault.key, false),
-            AccountMeta::new(*quote_vault.key, false),
-            AccountMeta::new_readonly(*protocol_fee_recipient.key, false),
-            AccountMeta::new(*protocol_fee_token_account.key, false),
-            AccountMeta::new_readonly(*base_token_program.key, false),
-            AccountMeta::new_readonly(*quote_token_program.key, false),
-            AccountMeta::new_readonly(*system_program.key, false),
-            AccountMeta::new_readonly(*associated_token_instruction_program.key, false),
-            AccountMeta::new_readonly(*event_authority.key, false),
-            AccountMeta::new_readonly(*self.program_id.key, false),
-        ];
-        if let (Some(vault_ata_acc), Some(vault_authority_acc)) = (vault_ata, vault_authority) {
-            metas.push(AccountMeta::new(*vault_ata_acc.key, false));
-            metas.push(AccountMeta::new_readonly(*vault_authority_acc.key, false));
-        }
-        metas.push(AccountMeta::new_readonly(
-            *global_vol_accumulator.key,
-            false,
-        ));
-        metas.push(AccountMeta::new(*user_volume_accumulator.key, false));
-        metas.push(AccountMeta::new_readonly(*fee_config.key, false));
-        metas.push(AccountMeta::new_readonly(*fee_program.key, false));
-
-        let mut data = vec![0x33, 0xe6, 0x85, 0xa4, 0x01, 0x7f, 0x83, 0xad];
-        data.extend_from_slice(&amount_in.to_le_bytes());
-        data.extend_from_slice(&min_amount_out_value.to_le_bytes());
-
-        let swap_ix = Instruction {
-            program_id: *self.program_id.key,
-            accounts: metas,
-            data,
-        };
-
-        // Order must match metas exactly!
-        let mut accounts: Vec<AccountInfo<'info>> = vec![
-            pool_id.clone(),                                         // 0: writable
-            unsafe { std::mem::transmute(payer.to_account_info()) }, // 1: writable, signer
-            pump_amm_global.clone(),                                 // 2: readonly
-            base_token.clone(),                                      // 3: readonly
-            quote_token.clone(),                                     // 4: readonly
-            unsafe { std::mem::transmute(user_base_token_account.to_account_info()) }, // 5: writable
-            unsafe { std::mem::transmute(user_quote_token_account.to_account_info()) }, // 6: writable
-            base_vault.clone(),                 // 7: writable
-            quote_vault.clone(),                // 8: writable
-            protocol_fee_recipient.clone(),     // 9: readonly
-            protocol_fee_token_account.clone(), // 10: writable
-            unsafe { std::mem::transmute(base_token_program.to_account_info()) }, // 11: readonly
-            unsafe { std::mem::transmute(quote_token_program.to_account_info()) }, // 12: readonly
-            system_program.clone(),             // 13: readonly
-            associated_token_instruction_program.clone(), // 14: readonly
-            event_authority.clone(),            // 15: readonly
-            program_id_stored.clone(),          // 16: readonly (PROGRAM_ID)
-        ];
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            EdgeSide::LeftToRight,
+            amount_in,
+            min_amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
+    }
 
-        if let (Some(vault_ata_acc), Some(vault_authority_acc)) = (vault_ata, vault_authority) {
-            accounts.push(vault_ata_acc.clone()); // 17: writable
-            accounts.push(vault_authority_acc.clone()); // 18: readonly
+    fn invoke_swap<'a>(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        authority: AccountInfo<'a>,
+        accounts: ResolvedAccounts<'a>,
+    ) -> Result<()> {
+        let swap_ix =
+            self.build_swap_ix(direction, amount_in, min_out, &accounts.keys(authority.key))?;
+
+        // The span's own accounts, creator vault included, then the user's; invoke matches
+        // them to the metas by key. Same lifetime cast as the other adapters.
+        let mut accounts_vec: Vec<AccountInfo<'info>> = self.accounts.clone();
+        for account in [
+            authority,
+            accounts.user_base_token_account,
+            accounts.user_quote_token_account,
+            accounts.base_token_program,
+            accounts.quote_token_program,
+        ] {
+            accounts_vec.push(unsafe {
+                std::mem::transmute::<AccountInfo<'a>, AccountInfo<'info>>(account)
+            });
         }
-        accounts.push(global_vol_accumulator.clone());
-        accounts.push(user_volume_accumulator.clone());
-        accounts.push(fee_config.clone()); // 21 or 19: readonly
-        accounts.push(fee_program.clone()); // 22 or 20: readonly
 
-        // Cast entire vector to AccountInfo<'a> for invoke
         unsafe {
-            let accounts_slice: &[AccountInfo<'a>] = std::mem::transmute(accounts.as_slice());
-            invoke(&swap_ix, accounts_slice)?;
+            let accounts: &[AccountInfo<'a>] = std::mem::transmute(accounts_vec.as_slice());
+            invoke(&swap_ix, accounts)?;
         }
         Ok(())
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use anchor_lang::solana_program::program_pack::Pack;
     use anchor_lang::solana_program::{account_info::AccountInfo, pubkey::Pubkey, system_program};
@@ -670,39 +939,28 @@ mod tests {
         );
 
         // Create pool_id account (no pool data needed since it's applied to vault accounts)
+        let program_id = create_mock_account_info(PumpAmm::PROGRAM_ID, system_program::id(), None);
         let pool_id = create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
         let base_token = create_mock_account_info(base_mint, system_program::id(), None);
         let quote_token = create_mock_account_info(quote_mint, system_program::id(), None);
-        let protocol_fee_recipient =
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
-        let protocol_fee_token_account =
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
-        let event_authority =
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
-        let coin_creator =
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
-        let vault_ata = create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
-        let vault_authority =
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
-        let fee_config = create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
-        let fee_program =
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None);
-
-        let accounts = vec![
+        let mut accounts = vec![
+            program_id,
             pool_id,
             base_vault_info,
             quote_vault_info,
             base_token,
             quote_token,
-            protocol_fee_recipient,
-            protocol_fee_token_account,
-            event_authority,
-            coin_creator,
-            vault_ata,
-            vault_authority,
-            fee_config,
-            fee_program,
         ];
+        // Fee recipient and its token account, event authority, fee config and program,
+        // volume accumulators, global config, system and associated token programs, and
+        // the coin creator vault ATA and authority
+        for _ in 6..18 {
+            accounts.push(create_mock_account_info(
+                Pubkey::new_unique(),
+                system_program::id(),
+                None,
+            ));
+        }
 
         let pump_amm = PumpAmm::new(&accounts).unwrap();
 
@@ -733,7 +991,7 @@ mod tests {
             eprintln!("Quote vault amount parsed as u64: {}", quote_amount_parsed);
         }
 
-        let (base_amount, quote_amount) = pump_amm.parse_vaults().unwrap();
+        let (base_amount, quote_amount) = pump_amm.vault_reserves().unwrap();
         eprintln!(
             "Parsed base_amount: {}, quote_amount: {}",
             base_amount, quote_amount
@@ -745,14 +1003,8 @@ mod tests {
 
     #[test]
     fn test_pump_amm_get_swap_base_in_amount() {
-        // Setup: base_reserve = 1_000_000_000, quote_reserve = 100_000_000
-        // Input: quote_amount_in = 10_000_000 (10 tokens)
-        // Expected: base_amount_out = base_reserve - (base_reserve * quote_reserve) / (quote_reserve + quote_amount_in)
-        //          = 1_000_000_000 - (1_000_000_000 * 100_000_000) / (100_000_000 + 10_000_000)
-        //          = 1_000_000_000 - 100_000_000_000_000_000 / 110_000_000
-        //          = 1_000_000_000 - 909_090_909
-        //          = 90_909_091
-        // After 0.02% fee: 90_909_091 * 9998 / 10000 = 90_727_272
+        // Buy with quote: 0.25% of the quote input goes to fees, the rest is swapped
+        // base_amount_out = base_reserve * effective_quote / (quote_reserve + effective_quote)
 
         let base_mint = Pubkey::new_unique();
         let quote_mint = Pubkey::new_unique();
@@ -845,11 +1097,8 @@ mod tests {
         // base_reserve = 936605012306479, quote_reserve = 18905080188 (from pool_data)
         let base_reserve = 936_605_012_306_479u128;
         let quote_reserve = 18_905_080_188u128;
-        let numerator = base_reserve * quote_reserve;
-        let denominator = quote_reserve + quote_amount_in as u128;
-        let quotient = numerator / denominator;
-        let base_amount_out = base_reserve - quotient;
-        let expected = (base_amount_out * 9_998 / 10_000) as u64;
+        let effective_quote = quote_amount_in as u128 * 10_000 / 10_025;
+        let expected = (base_reserve * effective_quote / (quote_reserve + effective_quote)) as u64;
 
         assert_eq!(result, expected);
         assert!(result > 0);
@@ -857,18 +1106,9 @@ mod tests {
 
     #[test]
     fn test_pump_amm_swap_base_sol_base() {
-        // Setup: base_reserve = 1_000_000_000, quote_reserve = 100_000_000
-        // Input: base_amount_in = 10_000_000
-        // Expected: quote_amount_out = quote_reserve - (base_reserve * quote_reserve) / (base_reserve + base_amount_in)
-        //          = 100_000_000 - (1_000_000_000 * 100_000_000) / (1_000_000_000 + 10_000_000)
-        //          = 100_000_000 - 100_000_000_000_000_000 / 1_010_000_000
-        //          = 100_000_000 - 99_009_900
-        //          = 990_100
-        // lp_fee = 990_100 * 0.002 = 1_980
-        // protocol_fee = 990_100 * 0.0005 = 495
-        // fees = 1_980 + 495 = 2_475
-        // quote_after_fees = 990_100 - 2_475 = 987_625
-        // final = 987_625 * 1.0023 = 989_896
+        // SOL is the base token: base_reserve = 18_905_080_188, quote_reserve = 936_605_012_306_479
+        // Sell 1 SOL: quote_amount_out = quote_reserve * base_amount_in / (base_reserve + base_amount_in)
+        // lp_fee = ceil(quote_amount_out * 0.002), protocol_fee = ceil(quote_amount_out * 0.0005)
 
         let base_mint = Pubkey::new_unique();
         let quote_mint = Pubkey::new_unique();
@@ -948,13 +1188,9 @@ mod tests {
 
         let pump_amm = PumpAmm::new(&accounts).unwrap();
 
-        // Test with base_amount_in = 10_000_000
         let base_amount_in = 1_000_000_000u64;
-        msg!("base_amount_in: {:?}", base_amount_in / 1_000_000_000);
-        let clock = Clock::default();
-        let input_mint = base_mint; // Use base_mint directly since base_token was moved into accounts
         let result = pump_amm
-            .swap_base_out(input_mint, base_amount_in, clock)
+            .swap_base_in(base_mint, base_amount_in, Clock::default())
             .unwrap();
         eprintln!(
             "{:?} SOL -> {:?} TOKEN",
@@ -962,50 +1198,30 @@ mod tests {
             result as f64 / 1_000_000_000.0,
         );
 
-        // Test with base_amount_in = 10_000_000
-        let base_amount_in = result;
-        let clock = Clock::default();
-        let input_mint = quote_mint; // Use quote_mint directly since quote_token was moved into accounts
-        let result = pump_amm
-            .swap_base_in(input_mint, base_amount_in, clock)
-            .unwrap();
-        eprintln!(
-            "{:?} TOKEN -> {:?} SOL",
-            base_amount_in as f64 / 1_000_000_000.0,
-            result as f64 / 1_000_000_000.0,
-        );
         // Manual calculation for verification using actual reserves from pool_data
-        let base_reserve = 936_605_012_306_479u128;
-        let quote_reserve = 18_905_080_188u128;
-        let numerator = base_reserve * quote_reserve;
-        let denominator = base_reserve + base_amount_in as u128;
-        let quotient = numerator / denominator;
-        let quote_amount_out = quote_reserve - quotient;
-
-        let lp_fee = quote_amount_out * 2 / 1_000;
-        let protocol_fee = quote_amount_out * 5 / 10_000;
-        let fees = lp_fee + protocol_fee;
-        let quote_after_fees = quote_amount_out - fees;
-        let expected = (quote_after_fees * 10_023 / 10_000) as u64;
+        let base_reserve = 18_905_080_188u128;
+        let quote_reserve = 936_605_012_306_479u128;
+        let quote_amount_out =
+            quote_reserve * base_amount_in as u128 / (base_reserve + base_amount_in as u128);
+        let lp_fee = (quote_amount_out * 20).div_ceil(10_000);
+        let protocol_fee = (quote_amount_out * 5).div_ceil(10_000);
+        let expected = (quote_amount_out - lp_fee - protocol_fee) as u64;
 
         assert_eq!(result, expected);
         assert!(result > 0);
+
+        // Selling back for exactly that quote needs the same SOL, up to rounding
+        let base_needed = pump_amm
+            .swap_base_out(base_mint, result, Clock::default())
+            .unwrap();
+        assert!(base_needed.abs_diff(base_amount_in) <= 1);
     }
 
     #[test]
     fn test_pump_amm_swap_base_sol_quote() {
-        // Setup: base_reserve = 1_000_000_000, quote_reserve = 100_000_000
-        // Input: base_amount_in = 10_000_000
-        // Expected: quote_amount_out = quote_reserve - (base_reserve * quote_reserve) / (base_reserve + base_amount_in)
-        //          = 100_000_000 - (1_000_000_000 * 100_000_000) / (1_000_000_000 + 10_000_000)
-        //          = 100_000_000 - 100_000_000_000_000_000 / 1_010_000_000
-        //          = 100_000_000 - 99_009_900
-        //          = 990_100
-        // lp_fee = 990_100 * 0.002 = 1_980
-        // protocol_fee = 990_100 * 0.0005 = 495
-        // fees = 1_980 + 495 = 2_475
-        // quote_after_fees = 990_100 - 2_475 = 987_625
-        // final = 987_625 * 1.0023 = 989_896
+        // SOL is the quote token: base_reserve = 936_605_012_306_479, quote_reserve = 18_905_080_188
+        // Buy with 1 SOL: 0.25% of the input goes to fees, the rest is swapped
+        // base_amount_out = base_reserve * effective_quote / (quote_reserve + effective_quote)
 
         let base_mint = Pubkey::new_unique();
         let quote_mint = Pubkey::new_unique();
@@ -1085,48 +1301,30 @@ mod tests {
 
         let pump_amm = PumpAmm::new(&accounts).unwrap();
 
-        // Test with base_amount_in = 10_000_000
-        let base_amount_in = 1_000_000_000u64;
-        msg!("base_amount_in: {:?}", base_amount_in / 1_000_000_000);
-        let clock = Clock::default();
-        let input_mint = base_mint; // Use base_mint directly since base_token was moved into accounts
+        let quote_amount_in = 1_000_000_000u64;
         let result = pump_amm
-            .swap_base_in(input_mint, base_amount_in, clock)
+            .swap_base_in(quote_mint, quote_amount_in, Clock::default())
             .unwrap();
         eprintln!(
             "{:?} SOL -> {:?} TOKEN",
-            base_amount_in as f64 / 1_000_000_000.0,
+            quote_amount_in as f64 / 1_000_000_000.0,
             result as f64 / 1_000_000_000.0,
         );
 
-        // Test with base_amount_in = 10_000_000
-        let base_amount_in = result;
-        let clock = Clock::default();
-        let input_mint = quote_mint; // Use quote_mint directly since quote_token was moved into accounts
-        let result = pump_amm
-            .swap_base_out(input_mint, base_amount_in, clock)
-            .unwrap();
-        eprintln!(
-            "{:?} TOKEN -> {:?} SOL",
-            base_amount_in as f64 / 1_000_000_000.0,
-            result as f64 / 1_000_000_000.0,
-        );
         // Manual calculation for verification using actual reserves from pool_data
         let base_reserve = 936_605_012_306_479u128;
         let quote_reserve = 18_905_080_188u128;
-        let numerator = base_reserve * quote_reserve;
-        let denominator = base_reserve + base_amount_in as u128;
-        let quotient = numerator / denominator;
-        let quote_amount_out = quote_reserve - quotient;
-
-        let lp_fee = quote_amount_out * 2 / 1_000;
-        let protocol_fee = quote_amount_out * 5 / 10_000;
-        let fees = lp_fee + protocol_fee;
-        let quote_after_fees = quote_amount_out - fees;
-        let expected = (quote_after_fees * 10_023 / 10_000) as u64;
+        let effective_quote = quote_amount_in as u128 * 10_000 / 10_025;
+        let expected = (base_reserve * effective_quote / (quote_reserve + effective_quote)) as u64;
 
         assert_eq!(result, expected);
         assert!(result > 0);
+
+        // Buying exactly that many tokens costs the same SOL, up to rounding
+        let quote_needed = pump_amm
+            .swap_base_out(quote_mint, result, Clock::default())
+            .unwrap();
+        assert!(quote_needed.abs_diff(quote_amount_in) <= 2);
     }
 
     #[test]
@@ -1156,21 +1354,22 @@ mod tests {
             quote_pool_data, // Use pool_data
         );
 
-        let accounts = vec![
+        let mut accounts = vec![
+            create_mock_account_info(PumpAmm::PROGRAM_ID, system_program::id(), None),
             create_mock_account_info(Pubkey::new_unique(), system_program::id(), None),
             base_vault_info,
             quote_vault_info,
             create_mock_account_info(base_mint, system_program::id(), None),
             create_mock_account_info(quote_mint, system_program::id(), None),
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None),
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None),
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None),
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None),
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None),
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None),
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None),
-            create_mock_account_info(Pubkey::new_unique(), system_program::id(), None),
         ];
+        // The pool has no coin creator, so the span can stop before its vault accounts
+        for _ in 6..PumpAmm::EXPECTED_ACCOUNTS {
+            accounts.push(create_mock_account_info(
+                Pubkey::new_unique(),
+                system_program::id(),
+                None,
+            ));
+        }
 
         let pump_amm = PumpAmm::new(&accounts).unwrap();
 
@@ -1180,4 +1379,572 @@ mod tests {
         let result = pump_amm.swap_base_in(input_mint, 0, clock).unwrap();
         assert_eq!(result, 0);
     }
+
+    // Accounts for a pool whose vaults hold the given reserves, with base/quote mints at 4 and 5
+    pub(crate) fn create_pool_accounts(
+        base_reserve: u64,
+        quote_reserve: u64,
+    ) -> Vec<AccountInfo<'static>> {
+        create_pool_accounts_for_mints(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            base_reserve,
+            quote_reserve,
+        )
+    }
+
+    // Same pool between the given mints, so several pools can trade the same pair
+    pub(crate) fn create_pool_accounts_for_mints(
+        base_mint: Pubkey,
+        quote_mint: Pubkey,
+        base_reserve: u64,
+        quote_reserve: u64,
+    ) -> Vec<AccountInfo<'static>> {
+        let base_vault = Pubkey::new_unique();
+        let quote_vault = Pubkey::new_unique();
+        let token_program = anchor_spl::token::ID;
+
+        let token_account_data = |mint: Pubkey, amount: u64| {
+            let mut data = vec![0u8; Account::LEN];
+            Account {
+                mint,
+                owner: Pubkey::new_unique(),
+                amount,
+                state: anchor_spl::token::spl_token::state::AccountState::Initialized,
+                ..Default::default()
+            }
+            .pack_into_slice(&mut data);
+            data
+        };
+
+        let mut accounts = vec![
+            create_mock_account_info(PumpAmm::PROGRAM_ID, system_program::id(), None),
+            create_mock_account_info(
+                Pubkey::new_unique(),
+                PumpAmm::PROGRAM_ID,
+                Some(pool_state_data(
+                    &base_mint,
+                    &quote_mint,
+                    &base_vault,
+                    &quote_vault,
+                )),
+            ),
+            create_mock_token_account_info(
+                base_vault,
+                base_mint,
+                base_reserve,
+                token_program,
+                Some(token_account_data(base_mint, base_reserve)),
+            ),
+            create_mock_token_account_info(
+                quote_vault,
+                quote_mint,
+                quote_reserve,
+                token_program,
+                Some(token_account_data(quote_mint, quote_reserve)),
+            ),
+            create_mock_account_info(base_mint, system_program::id(), None),
+            create_mock_account_info(quote_mint, system_program::id(), None),
+        ];
+        for _ in 6..18 {
+            accounts.push(create_mock_account_info(
+                Pubkey::new_unique(),
+                system_program::id(),
+                None,
+            ));
+        }
+        accounts
+    }
+
+    #[test]
+    fn test_swap_base_in_deterministic() {
+        let accounts = create_pool_accounts(936_605_012_306_479, 18_905_080_188);
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+        let quote_mint = *accounts[5].key;
+
+        let clock = Clock {
+            slot: 300_000_000,
+            epoch_start_timestamp: 0,
+            epoch: 700,
+            leader_schedule_epoch: 0,
+            unix_timestamp: 1_700_000_000,
+        };
+        let first = pump_amm
+            .swap_base_in(quote_mint, 10_000_000, clock.clone())
+            .unwrap();
+        let second = pump_amm
+            .swap_base_in(quote_mint, 10_000_000, clock)
+            .unwrap();
+        assert!(first > 0);
+        assert_eq!(first.to_le_bytes(), second.to_le_bytes());
+    }
+
+    #[test]
+    fn test_swap_base_out_inverts_swap_base_in() {
+        let accounts = create_pool_accounts(1_000_000_000_000, 1_000_000_000_000);
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+        let base_mint = *accounts[4].key;
+        let quote_mint = *accounts[5].key;
+
+        for amount_in in [1_000u64, 1_000_000, 1_000_000_000, 100_000_000_000] {
+            for input_mint in [base_mint, quote_mint] {
+                let amount_out = pump_amm
+                    .swap_base_in(input_mint, amount_in, Clock::default())
+                    .unwrap();
+                assert!(amount_out > 0);
+                // Fees make the output strictly smaller than the input on a balanced pool
+                assert!(amount_out < amount_in);
+
+                let required_in = pump_amm
+                    .swap_base_out(input_mint, amount_out, Clock::default())
+                    .unwrap();
+                assert!(
+                    required_in.abs_diff(amount_in) <= 2,
+                    "amount_in={} required_in={}",
+                    amount_in,
+                    required_in
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_swap_base_out_rejects_output_beyond_reserves() {
+        let accounts = create_pool_accounts(1_000_000_000, 1_000_000_000);
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+        let base_mint = *accounts[4].key;
+        let quote_mint = *accounts[5].key;
+
+        assert!(pump_amm
+            .swap_base_out(quote_mint, 1_000_000_000, Clock::default())
+            .is_err());
+        assert!(pump_amm
+            .swap_base_out(base_mint, 1_000_000_000, Clock::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_swap_base_out_is_never_one_unit_short() {
+        // Reserves that leave a remainder on nearly every division
+        let accounts = create_pool_accounts(1_000_000_007, 999_999_937);
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+        let base_mint = *accounts[4].key;
+        let quote_mint = *accounts[5].key;
+
+        for input_mint in [base_mint, quote_mint] {
+            for amount_out in (1..2_000_000).step_by(9_973) {
+                let required_in = pump_amm
+                    .swap_base_out(input_mint, amount_out, Clock::default())
+                    .unwrap();
+                let received = pump_amm
+                    .swap_base_in(input_mint, required_in, Clock::default())
+                    .unwrap();
+                assert!(
+                    received >= amount_out,
+                    "{} in for {} out only returns {}",
+                    required_in,
+                    amount_out,
+                    received
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_buy_base_output_inverts_buy_quote_input() {
+        let (base_reserve, quote_reserve) = (1_000_000_007, 999_999_937);
+        // With and without the coin creator fee
+        for fees in [
+            PumpFees::default(),
+            PumpFees::default().for_coin_creator(&Pubkey::default()),
+        ] {
+            let charged_fees = fees.fees_bps().iter().filter(|fee| **fee > 0).count() as u128;
+            for base_out in (1..2_000_000).step_by(9_973) {
+                let quote_in = fees
+                    .buy_base_output(base_reserve, quote_reserve, base_out)
+                    .unwrap();
+                let bought = |quote_in| {
+                    fees.buy_quote_input(base_reserve, quote_reserve, quote_in)
+                        .unwrap()
+                };
+                // Enough, and at these near-even reserves over by no more than the extra
+                // fee roundings: one unit without the coin creator fee
+                assert!(
+                    (base_out..=base_out + charged_fees - 1).contains(&bought(quote_in)),
+                    "{} quote for {} base buys {}",
+                    quote_in,
+                    base_out,
+                    bought(quote_in)
+                );
+                // Over the least input by at most one unit per fee rounding on its own
+                assert!(bought(quote_in - charged_fees) < base_out);
+            }
+        }
+    }
+
+    // Pool account data recording the given mints and vaults at their borsh offsets
+    fn pool_state_data(
+        base_mint: &Pubkey,
+        quote_mint: &Pubkey,
+        base_vault: &Pubkey,
+        quote_vault: &Pubkey,
+    ) -> Vec<u8> {
+        let mut data = vec![0u8; 243];
+        data[POOL_BASE_MINT_OFFSET..POOL_BASE_MINT_OFFSET + 32].copy_from_slice(base_mint.as_ref());
+        data[POOL_QUOTE_MINT_OFFSET..POOL_QUOTE_MINT_OFFSET + 32]
+            .copy_from_slice(quote_mint.as_ref());
+        data[POOL_BASE_TOKEN_ACCOUNT_OFFSET..POOL_BASE_TOKEN_ACCOUNT_OFFSET + 32]
+            .copy_from_slice(base_vault.as_ref());
+        data[POOL_QUOTE_TOKEN_ACCOUNT_OFFSET..POOL_QUOTE_TOKEN_ACCOUNT_OFFSET + 32]
+            .copy_from_slice(quote_vault.as_ref());
+        data
+    }
+
+    #[test]
+    fn test_check_pool_state_accepts_matching_vaults() {
+        let mut accounts = create_pool_accounts(1_000_000_000, 1_000_000_000);
+        let data = pool_state_data(
+            accounts[4].key,
+            accounts[5].key,
+            accounts[2].key,
+            accounts[3].key,
+        );
+        accounts[1] = create_mock_account_info(*accounts[1].key, PumpAmm::PROGRAM_ID, Some(data));
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+
+        assert!(pump_amm.check_pool_state().is_ok());
+    }
+
+    #[test]
+    fn test_check_pool_state_detects_mismatched_vault() {
+        let mut accounts = create_pool_accounts(1_000_000_000, 1_000_000_000);
+        // The pool reads its quote reserve from a different vault than the one supplied
+        let data = pool_state_data(
+            accounts[4].key,
+            accounts[5].key,
+            accounts[2].key,
+            &Pubkey::new_unique(),
+        );
+        accounts[1] = create_mock_account_info(*accounts[1].key, PumpAmm::PROGRAM_ID, Some(data));
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+
+        let err = pump_amm.check_pool_state().unwrap_err();
+        assert_eq!(err, error!(SolarBError::PoolStateMismatch));
+    }
+
+    #[test]
+    fn test_check_pool_state_rejects_truncated_pool_data() {
+        let mut accounts = create_pool_accounts(1_000_000_000, 1_000_000_000);
+        accounts[1] = create_mock_account_info(
+            *accounts[1].key,
+            PumpAmm::PROGRAM_ID,
+            Some(vec![0u8; POOL_BASE_TOKEN_ACCOUNT_OFFSET]),
+        );
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+
+        assert!(pump_amm.check_pool_state().is_err());
+    }
+
+    // fee_config account data carrying the given flat lp, protocol and coin creator fees
+    fn fee_config_data(
+        lp_fee_bps: u64,
+        protocol_fee_bps: u64,
+        coin_creator_fee_bps: u64,
+    ) -> Vec<u8> {
+        let mut data = vec![0u8; 65];
+        data[FEE_CONFIG_LP_FEE_BPS_OFFSET..FEE_CONFIG_LP_FEE_BPS_OFFSET + 8]
+            .copy_from_slice(&lp_fee_bps.to_le_bytes());
+        data[FEE_CONFIG_PROTOCOL_FEE_BPS_OFFSET..FEE_CONFIG_PROTOCOL_FEE_BPS_OFFSET + 8]
+            .copy_from_slice(&protocol_fee_bps.to_le_bytes());
+        data[FEE_CONFIG_COIN_CREATOR_FEE_BPS_OFFSET..FEE_CONFIG_COIN_CREATOR_FEE_BPS_OFFSET + 8]
+            .copy_from_slice(&coin_creator_fee_bps.to_le_bytes());
+        data
+    }
+
+    // Record `coin_creator` in the pool account of `create_pool_accounts`
+    fn set_coin_creator(accounts: &[AccountInfo<'static>], coin_creator: &Pubkey) {
+        accounts[1].try_borrow_mut_data().unwrap()
+            [POOL_COIN_CREATOR_OFFSET..POOL_COIN_CREATOR_OFFSET + 32]
+            .copy_from_slice(coin_creator.as_ref());
+    }
+
+    #[test]
+    fn test_parse_fees_defaults_when_fee_config_empty() {
+        let accounts = create_pool_accounts(1_000_000_000, 1_000_000_000);
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+
+        // The fixture pool has no coin creator, so only the default lp and protocol fees apply
+        assert_eq!(
+            pump_amm.parse_fees().unwrap(),
+            PumpFees {
+                coin_creator_fee_bps: 0,
+                ..PumpFees::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_swap_uses_fee_config_rates() {
+        let default_accounts = create_pool_accounts(1_000_000_000_000, 1_000_000_000_000);
+        let mut accounts = default_accounts.clone();
+        accounts[9] = create_mock_account_info(
+            *accounts[9].key,
+            system_program::id(),
+            Some(fee_config_data(100, 50, 0)),
+        );
+        let default_amm = PumpAmm::new(&default_accounts).unwrap();
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+        let base_mint = *accounts[4].key;
+        let quote_mint = *accounts[5].key;
+
+        let fees = pump_amm.parse_fees().unwrap();
+        assert_eq!(
+            fees,
+            PumpFees {
+                lp_fee_bps: 100,
+                protocol_fee_bps: 50,
+                coin_creator_fee_bps: 0,
+            }
+        );
+
+        for input_mint in [base_mint, quote_mint] {
+            let default_out = default_amm
+                .swap_base_in(input_mint, 1_000_000_000, Clock::default())
+                .unwrap();
+            let out = pump_amm
+                .swap_base_in(input_mint, 1_000_000_000, Clock::default())
+                .unwrap();
+            // 1.5% in fees instead of 0.25%
+            assert!(out < default_out);
+
+            let default_in = default_amm
+                .swap_base_out(input_mint, 1_000_000_000, Clock::default())
+                .unwrap();
+            let required_in = pump_amm
+                .swap_base_out(input_mint, 1_000_000_000, Clock::default())
+                .unwrap();
+            assert!(required_in > default_in);
+        }
+
+        let out = pump_amm
+            .swap_base_in(base_mint, 1_000_000_000, Clock::default())
+            .unwrap();
+        assert_eq!(
+            out as u128,
+            fees.sell_base_input(1_000_000_000_000, 1_000_000_000_000, 1_000_000_000)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_fees_rejects_fees_above_denominator() {
+        assert!(PumpFees::parse(&fee_config_data(9_000, 1_000, 0)).is_err());
+        assert!(PumpFees::parse(&[0u8; 48]).is_err());
+    }
+
+    #[test]
+    fn test_coin_creator_fee_only_charged_with_coin_creator() {
+        let mut accounts = create_pool_accounts(1_000_000_000_000, 1_000_000_000_000);
+        accounts[9] = create_mock_account_info(
+            *accounts[9].key,
+            system_program::id(),
+            Some(fee_config_data(20, 5, 30)),
+        );
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+        let mints = [*accounts[4].key, *accounts[5].key];
+        let quote = |input_mint: Pubkey| {
+            (
+                pump_amm
+                    .swap_base_in(input_mint, 1_000_000_000, Clock::default())
+                    .unwrap(),
+                pump_amm
+                    .swap_base_out(input_mint, 1_000_000_000, Clock::default())
+                    .unwrap(),
+            )
+        };
+
+        assert_eq!(pump_amm.parse_fees().unwrap().coin_creator_fee_bps, 0);
+        let plain = mints.map(quote);
+
+        set_coin_creator(&accounts, &Pubkey::new_unique());
+        let fees = pump_amm.parse_fees().unwrap();
+        assert_eq!(fees.coin_creator_fee_bps, 30);
+        assert_eq!(fees.total_fee_bps(), 55);
+        let with_creator = mints.map(quote);
+
+        // 0.55% in fees instead of 0.25%, whichever way the swap goes
+        for ((plain_out, plain_in), (creator_out, creator_in)) in
+            plain.into_iter().zip(with_creator)
+        {
+            assert!(creator_out < plain_out);
+            assert!(creator_in > plain_in);
+        }
+        assert_eq!(
+            with_creator[0].0 as u128,
+            fees.sell_base_input(1_000_000_000_000, 1_000_000_000_000, 1_000_000_000)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_coin_creator_vault_required_only_with_coin_creator() {
+        let accounts = create_pool_accounts(1_000_000_000, 1_000_000_000);
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+        let (vault_ata, vault_authority) = pump_amm.coin_creator_vault().unwrap().unwrap();
+        assert_eq!(
+            (vault_ata.key, vault_authority.key),
+            (accounts[16].key, accounts[17].key)
+        );
+
+        // Without a coin creator the swap doesn't take the vault accounts
+        let pump_amm = PumpAmm::new(&accounts[..16]).unwrap();
+        assert!(pump_amm.coin_creator_vault().unwrap().is_none());
+        pump_amm.validate_accounts().unwrap();
+
+        set_coin_creator(&accounts, &Pubkey::new_unique());
+        let pump_amm = PumpAmm::new(&accounts[..16]).unwrap();
+        assert_eq!(
+            pump_amm.validate_accounts().unwrap_err(),
+            error!(SolarBError::MissingCoinCreatorVault)
+        );
+        assert!(PumpAmm::new(&accounts)
+            .unwrap()
+            .coin_creator_vault()
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_new_requires_swap_accounts() {
+        let accounts = create_pool_accounts(1_000_000_000, 1_000_000_000);
+        assert_eq!(accounts.len(), 18);
+
+        // The 13-account layout ends before the system and associated token programs
+        for len in [13, PumpAmm::EXPECTED_ACCOUNTS - 1] {
+            assert_eq!(
+                PumpAmm::new(&accounts[..len]).err(),
+                Some(error!(SolarBError::InsufficientAccounts))
+            );
+        }
+        for span in [&accounts[..PumpAmm::EXPECTED_ACCOUNTS], &accounts[..]] {
+            let pump_amm = PumpAmm::new(span).unwrap();
+            assert_eq!(pump_amm.associated_token_program(), Some(accounts[14].key));
+            pump_amm.validate_accounts().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_build_swap_ix_follows_direction() {
+        let accounts = create_pool_accounts(1_000_000_000, 1_000_000_000);
+        let user = SwapUserAccounts {
+            authority: Pubkey::new_unique(),
+            base_token_program: anchor_spl::token::ID,
+            quote_token_program: anchor_spl::token::ID,
+            user_base_token_account: Pubkey::new_unique(),
+            user_quote_token_account: Pubkey::new_unique(),
+        };
+
+        // The coin creator vault accounts only go in when the span carries them
+        for (span, account_count) in [(&accounts[..], 23), (&accounts[..16], 21)] {
+            let pump_amm = PumpAmm::new(span).unwrap();
+            // Buys take the whole quote for the quote going in, not just the minimum
+            let base_out = pump_amm
+                .parse_fees()
+                .unwrap()
+                .quote_exact_input(pump_amm.vault_reserves().unwrap(), false, 1_000)
+                .unwrap();
+            assert!(base_out > 990);
+            for (direction, discriminator, base_amount, quote_amount) in [
+                (
+                    EdgeSide::LeftToRight,
+                    PumpAmm::SELL_DISCRIMINATOR,
+                    1_000u64,
+                    990u64,
+                ),
+                (
+                    EdgeSide::RightToLeft,
+                    PumpAmm::BUY_DISCRIMINATOR,
+                    base_out,
+                    1_000,
+                ),
+            ] {
+                let ix = pump_amm
+                    .build_swap_ix(direction, 1_000, 990, &user)
+                    .unwrap();
+                assert_eq!(ix.program_id, PumpAmm::PROGRAM_ID);
+                assert_eq!(ix.accounts.len(), account_count);
+                assert_eq!(ix.data[..8], discriminator);
+                assert_eq!(ix.data[8..16], base_amount.to_le_bytes());
+                assert_eq!(ix.data[16..24], quote_amount.to_le_bytes());
+                assert_eq!(ix.accounts[1], AccountMeta::new(user.authority, true));
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_prices_reflect_fees_on_balanced_pool() {
+        let accounts = create_pool_accounts(1_000_000_000_000, 1_000_000_000_000);
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+        let reserve = 1_000_000_000_000u128;
+
+        let price_base_in = pump_amm
+            .compute_price_swap_base_in(reserve, reserve)
+            .unwrap();
+        let price_base_out = pump_amm
+            .compute_price_swap_base_out(reserve, reserve)
+            .unwrap();
+
+        // Selling loses the 0.25% fee off the output, buying pays it on top of the input
+        assert!((price_base_in - 0.9975).abs() < 1e-12);
+        assert!((price_base_out - 1.0 / 1.0025).abs() < 1e-12);
+        assert!(price_base_out > price_base_in);
+        // A round trip at the margin loses about the fee factor twice
+        assert!((price_base_in * price_base_out - 0.9975 / 1.0025).abs() < 1e-12);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_from_state_quotes_like_the_accounts() {
+        let mut accounts = create_pool_accounts(1_000_000_000_000, 2_000_000_000_000);
+        accounts[9] = create_mock_account_info(
+            *accounts[9].key,
+            system_program::id(),
+            Some(fee_config_data(100, 50, 0)),
+        );
+        let pump_amm = PumpAmm::new(&accounts).unwrap();
+        let (base_mint, quote_mint) = (*accounts[4].key, *accounts[5].key);
+        let state = PumpAmm::from_state(
+            pump_amm.parse_fees().unwrap(),
+            (1_000_000_000_000, 2_000_000_000_000),
+            (base_mint, quote_mint),
+        );
+
+        assert_eq!(state.get_mints(), pump_amm.get_mints());
+        assert_eq!(
+            state.compute_price_swap_base_in(),
+            pump_amm
+                .compute_price_swap_base_in(1_000_000_000_000, 2_000_000_000_000)
+                .unwrap()
+        );
+        assert_eq!(
+            state.compute_price_swap_base_out(),
+            pump_amm
+                .compute_price_swap_base_out(1_000_000_000_000, 2_000_000_000_000)
+                .unwrap()
+        );
+        for input_mint in [base_mint, quote_mint] {
+            assert_eq!(
+                state.swap_base_in(input_mint, 1_000_000_000).unwrap(),
+                pump_amm
+                    .swap_base_in(input_mint, 1_000_000_000, Clock::default())
+                    .unwrap()
+            );
+            assert_eq!(
+                state.swap_base_out(input_mint, 1_000_000_000).unwrap(),
+                pump_amm
+                    .swap_base_out(input_mint, 1_000_000_000, Clock::default())
+                    .unwrap()
+            );
+        }
+    }
 }
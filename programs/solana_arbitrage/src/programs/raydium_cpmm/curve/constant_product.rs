@@ -1,7 +1,7 @@
 //! The Uniswap invariantConstantProductCurve::
 
 use super::calculator::{RoundDirection, TradingTokenResult};
-use crate::programs::raydium_cpmm::utils::CheckedCeilDiv;
+use crate::math::{mul_div, Rounding};
 
 /// ConstantProductCurve struct implementing CurveCalculator
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -20,11 +20,15 @@ impl ConstantProductCurve {
         output_vault_amount: u128,
     ) -> u128 {
         // (x + delta_x) * (y - delta_y) = x * y
-        // delta_y = (delta_x * y) / (x + delta_x)
-        let numerator = input_amount.checked_mul(output_vault_amount).unwrap();
+        // delta_y = (delta_x * y) / (x + delta_x), rounded down
         let denominator = input_vault_amount.checked_add(input_amount).unwrap();
-        let output_amount = numerator.checked_div(denominator).unwrap();
-        output_amount
+        mul_div(
+            input_amount,
+            output_vault_amount,
+            denominator,
+            Rounding::Down,
+        )
+        .unwrap()
     }
 
     pub fn swap_base_output_without_fees(
@@ -33,11 +37,9 @@ impl ConstantProductCurve {
         output_vault_amount: u128,
     ) -> u128 {
         // (x + delta_x) * (y - delta_y) = x * y
-        // delta_x = (x * delta_y) / (y - delta_y)
-        let numerator = input_vault_amount.checked_mul(output_amount).unwrap();
+        // delta_x = (x * delta_y) / (y - delta_y), rounded up
         let denominator = output_vault_amount.checked_sub(output_amount).unwrap();
-        let input_amount = numerator.checked_ceil_div(denominator).unwrap();
-        input_amount
+        mul_div(input_vault_amount, output_amount, denominator, Rounding::Up).unwrap()
     }
 
     /// Get the amount of trading tokens for the given amount of pool tokens,
@@ -7,12 +7,19 @@ pub mod utils;
 // Now import using relative paths from declared modules
 use self::curve::calculator::CurveCalculator;
 use self::curve::calculator::TradeDirection;
+use self::curve::fees::FEE_RATE_DENOMINATOR_VALUE;
 use self::error::ErrorCode;
-use self::states::{AmmConfig, PoolState, SwapParams};
-use self::utils::token::{amount_with_slippage, get_transfer_fee, get_transfer_inverse_fee};
-use crate::utils::utils::parse_token_account;
+use self::states::{AmmConfig, PoolState, PoolStatusBitIndex, SwapParams};
+use self::utils::token::{get_transfer_fee, get_transfer_inverse_fee};
+use crate::arbitrage::base::EdgeSide;
+use crate::programs::programs::{
+    check_recorded_keys, check_vault_mints, marginal_price, read_mint_decimals,
+};
+use crate::utils::utils::{
+    parse_token_account, resolve_swap_accounts, ResolvedAccounts, SwapUserAccounts,
+};
 use crate::{
-    programs::ProgramMeta,
+    programs::{ProgramMeta, SolarBError},
     // Market,
 };
 use anchor_lang::prelude::*;
@@ -24,9 +31,19 @@ use anchor_lang::solana_program::{
     pubkey::Pubkey,
 };
 use bytemuck;
+#[cfg(test)]
+use std::cell::Cell;
+use std::cell::RefCell;
 
 pub struct RaydiumCpSwapProgram {}
 
+/// Seed of the CP-Swap authority PDA that owns the pool vaults and LP mint
+pub const AUTH_SEED: &str = "vault_and_lp_mint_auth_seed";
+
+/// Anchor discriminators of the CP-Swap `swap_base_input` and `swap_base_output` instructions
+const SWAP_BASE_INPUT_DISCRIMINATOR: [u8; 8] = [143, 190, 90, 218, 196, 30, 51, 222];
+const SWAP_BASE_OUTPUT_DISCRIMINATOR: [u8; 8] = [55, 217, 98, 86, 163, 74, 180, 173];
+
 // =====================
 // RaydiumCPMM meta parser
 // =====================
@@ -43,6 +60,10 @@ pub struct RaydiumCPMM<'info> {
     // pub amm_config: AccountInfo<'info>,
     // pub observation_key: AccountInfo<'info>,
     // pub authority: AccountInfo<'info>,
+    /// Pool state deserialized on first use, shared by the quote and the swap instruction
+    pool_state: RefCell<Option<PoolState>>,
+    #[cfg(test)]
+    pool_state_loads: Cell<u32>,
 }
 
 impl<'info> ProgramMeta for RaydiumCPMM<'info> {
@@ -50,6 +71,14 @@ impl<'info> ProgramMeta for RaydiumCPMM<'info> {
         &Self::PROGRAM_ID
     }
 
+    fn program_label(&self) -> &'static str {
+        "RaydiumCPMM"
+    }
+
+    fn pool_key(&self) -> &Pubkey {
+        self.pool_id.key
+    }
+
     fn get_vaults(&self) -> (&AccountInfo<'_>, &AccountInfo<'_>) {
         unsafe {
             (
@@ -59,6 +88,30 @@ impl<'info> ProgramMeta for RaydiumCPMM<'info> {
         }
     }
 
+    fn get_mints(&self) -> (&Pubkey, &Pubkey) {
+        (self.base_token.key, self.quote_token.key)
+    }
+
+    fn mint_decimals(&self) -> (u8, u8) {
+        read_mint_decimals(&self.base_token, &self.quote_token)
+    }
+
+    fn compute_price_swap_base_in(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        Ok(marginal_price(
+            base_amount,
+            quote_amount,
+            self.trade_fee_rate()?,
+        ))
+    }
+
+    fn compute_price_swap_base_out(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        Ok(marginal_price(
+            quote_amount,
+            base_amount,
+            self.trade_fee_rate()?,
+        ))
+    }
+
     fn swap_base_in(&self, input_mint: Pubkey, amount_in: u64, clock: Clock) -> Result<u64> {
         self.swap_base_in_impl(input_mint, amount_in, clock)
     }
@@ -73,7 +126,7 @@ impl<'info> ProgramMeta for RaydiumCPMM<'info> {
         input_mint: Pubkey,
         max_amount_in: u64,
         amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -85,7 +138,7 @@ impl<'info> ProgramMeta for RaydiumCPMM<'info> {
             input_mint,
             max_amount_in,
             amount_out,
-            payer,
+            authority,
             user_mint_1_token_account,
             user_mint_2_token_account,
             mint_1_account,
@@ -100,7 +153,7 @@ impl<'info> ProgramMeta for RaydiumCPMM<'info> {
         input_mint: Pubkey,
         amount_in: u64,
         min_amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -112,7 +165,7 @@ impl<'info> ProgramMeta for RaydiumCPMM<'info> {
             input_mint,
             min_amount_out.unwrap_or(0), // RaydiumCPSwap has reversed params
             amount_in,
-            payer,
+            authority,
             user_mint_1_token_account,
             user_mint_2_token_account,
             mint_1_account,
@@ -122,6 +175,91 @@ impl<'info> ProgramMeta for RaydiumCPMM<'info> {
         )
     }
 
+    /// Selling base is `swap_base_input`; buying it is `swap_base_output`, with `min_out` the
+    /// exact base amount and `amount_in` the most quote to spend. Both share one account list.
+    fn build_swap_ix(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        user_accounts: &SwapUserAccounts,
+    ) -> Result<Instruction> {
+        // Load pool state to get amm_config, authority bump and observation key
+        let pool = self.pool_state()?;
+        let authority_key = Self::authority(pool.auth_bump)?;
+
+        let (discriminator, first_arg, second_arg) = match direction {
+            EdgeSide::LeftToRight => (SWAP_BASE_INPUT_DISCRIMINATOR, amount_in, min_out),
+            EdgeSide::RightToLeft => (SWAP_BASE_OUTPUT_DISCRIMINATOR, min_out, amount_in),
+        };
+        let mut data = discriminator.to_vec();
+        data.extend_from_slice(&first_arg.to_le_bytes());
+        data.extend_from_slice(&second_arg.to_le_bytes());
+
+        let base_side = (
+            user_accounts.user_base_token_account,
+            *self.base_vault.key,
+            user_accounts.base_token_program,
+            *self.base_token.key,
+        );
+        let quote_side = (
+            user_accounts.user_quote_token_account,
+            *self.quote_vault.key,
+            user_accounts.quote_token_program,
+            *self.quote_token.key,
+        );
+        let (input, output) = match direction {
+            EdgeSide::LeftToRight => (base_side, quote_side),
+            EdgeSide::RightToLeft => (quote_side, base_side),
+        };
+
+        let metas = vec![
+            AccountMeta::new(user_accounts.authority, true),
+            AccountMeta::new_readonly(authority_key, false),
+            AccountMeta::new(pool.amm_config, false),
+            AccountMeta::new(*self.pool_id.key, false),
+            AccountMeta::new(input.0, false),
+            AccountMeta::new(output.0, false),
+            AccountMeta::new(input.1, false),
+            AccountMeta::new(output.1, false),
+            AccountMeta::new_readonly(input.2, false),
+            AccountMeta::new_readonly(output.2, false),
+            AccountMeta::new_readonly(input.3, false),
+            AccountMeta::new_readonly(output.3, false),
+            AccountMeta::new(pool.observation_key, false),
+        ];
+
+        Ok(Instruction {
+            program_id: Self::PROGRAM_ID,
+            accounts: metas,
+            data,
+        })
+    }
+
+    fn estimate_compute_units(&self) -> u32 {
+        Self::SWAP_COMPUTE_UNITS
+    }
+
+    fn is_tradable(&self, _clock: &Clock) -> Result<bool> {
+        Ok(self
+            .pool_state()?
+            .get_status_by_bit(PoolStatusBitIndex::Swap))
+    }
+
+    fn validate_accounts(&self) -> Result<()> {
+        check_vault_mints(self)?;
+        let pool = self.pool_state()?;
+        check_recorded_keys(
+            self.pool_id.key,
+            &[
+                ("token_0_mint", pool.token_0_mint, self.base_token.key),
+                ("token_1_mint", pool.token_1_mint, self.quote_token.key),
+                ("token_0_vault", pool.token_0_vault, self.base_vault.key),
+                ("token_1_vault", pool.token_1_vault, self.quote_vault.key),
+            ],
+        )
+    }
+
     fn log_accounts(&self) -> Result<()> {
         msg!(
             "Raydium CPMM accounts: pool={}, base_vault={}, quote_vault={}, base_token={}, quote_token={}",
@@ -137,7 +275,18 @@ impl<'info> ProgramMeta for RaydiumCPMM<'info> {
 
 impl<'info> RaydiumCPMM<'info> {
     pub const PROGRAM_ID: Pubkey =
-        Pubkey::from_str_const("CPMDWBwJDtYax9qW7AyRuVC19Cc4L4Vcy4n2BHAbHkCW");
+        Pubkey::from_str_const("CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C");
+    /// Span length `new` needs, program id included, the AMM config last
+    pub const EXPECTED_ACCOUNTS: usize = 7;
+    /// Typical compute units of one swap CPI
+    pub const SWAP_COMPUTE_UNITS: u32 = 60_000;
+
+    /// CP-Swap authority PDA, derived from `AUTH_SEED` and the bump stored in the pool state
+    pub fn authority(auth_bump: u8) -> Result<Pubkey> {
+        Pubkey::create_program_address(&[AUTH_SEED.as_bytes(), &[auth_bump]], &Self::PROGRAM_ID)
+            .map_err(|_| ProgramError::InvalidSeeds.into())
+    }
+
     pub fn new(accounts: &[AccountInfo<'info>]) -> Result<Self> {
         let mut iter = accounts.iter();
         let program_id = next_account_info(&mut iter)?;
@@ -146,10 +295,9 @@ impl<'info> RaydiumCPMM<'info> {
         let quote_vault = next_account_info(&mut iter)?;
         let base_token = next_account_info(&mut iter)?;
         let quote_token = next_account_info(&mut iter)?;
-        // let amm_config = next_account_info(&mut iter)?;
         // let observation_key = next_account_info(&mut iter)?;
 
-        Ok(RaydiumCPMM {
+        let raydium = RaydiumCPMM {
             accounts: accounts.to_vec(),
             pool_id: pool_id.clone(),
             program_id: program_id.clone(),
@@ -157,17 +305,57 @@ impl<'info> RaydiumCPMM<'info> {
             quote_vault: quote_vault.clone(),
             base_token: base_token.clone(),
             quote_token: quote_token.clone(),
-        })
+            pool_state: RefCell::new(None),
+            #[cfg(test)]
+            pool_state_loads: Cell::new(0),
+        };
+        // The fee quotes read the AMM config from the account after the mints
+        let amm_config = iter.next().ok_or(error!(SolarBError::MissingAmmConfig))?;
+        let pool = raydium.pool_state()?;
+        if *amm_config.key != pool.amm_config {
+            msg!(
+                "Raydium CPMM pool {} uses AMM config {}, got {}",
+                pool_id.key,
+                pool.amm_config,
+                amm_config.key
+            );
+            return Err(error!(SolarBError::MissingAmmConfig));
+        }
+        Ok(raydium)
+    }
+
+    /// Pool state, read from `pool_id` once and cached until the pool is swapped against
+    pub fn pool_state(&self) -> Result<PoolState> {
+        if let Some(pool) = *self.pool_state.borrow() {
+            return Ok(pool);
+        }
+
+        let data = self.pool_id.try_borrow_data()?;
+        let pool_data = data
+            .get(8..8 + std::mem::size_of::<PoolState>())
+            .ok_or(error!(SolarBError::PoolDeserializeFailed))?;
+        let pool = bytemuck::pod_read_unaligned::<PoolState>(pool_data);
+        #[cfg(test)]
+        self.pool_state_loads.set(self.pool_state_loads.get() + 1);
+
+        *self.pool_state.borrow_mut() = Some(pool);
+        Ok(pool)
+    }
+
+    /// Share of the input taken as the trade fee, from the AMM config account
+    pub fn trade_fee_rate(&self) -> Result<f64> {
+        let amm_data = self.accounts[6].try_borrow_data()?;
+        let amm_config: AmmConfig = AmmConfig::try_from_bytes(&amm_data)?;
+        Ok(amm_config.trade_fee_rate as f64 / FEE_RATE_DENOMINATOR_VALUE as f64)
     }
 
     pub fn swap_base_in_impl(
         &self,
         input_mint: Pubkey,
         amount_in: u64,
-        _clock: Clock,
+        clock: Clock,
     ) -> Result<u64> {
-        let pool_data = self.pool_id.try_borrow_data()?;
-        let pool = bytemuck::pod_read_unaligned::<PoolState>(&pool_data[8..]);
+        let pool = self.pool_state()?;
 
         let amm_data = self.accounts[6].try_borrow_data()?;
         let amm_config: AmmConfig = AmmConfig::try_from_bytes(&amm_data)?;
@@ -190,12 +378,14 @@ impl<'info> RaydiumCPMM<'info> {
                 )
             };
 
-        let transfer_fee = get_transfer_fee(input_token_account, amount_in)?;
+        let transfer_fee = get_transfer_fee(input_token_account, amount_in, clock.epoch)?;
         let actual_amount_in = amount_in.saturating_sub(transfer_fee);
 
         // Parse vault amounts
-        let input_vault_account = parse_token_account(input_vault)?;
-        let output_vault_account = parse_token_account(output_vault)?;
+        let input_vault_account =
+            parse_token_account(input_vault).map_err(|_| error!(SolarBError::VaultParseFailed))?;
+        let output_vault_account =
+            parse_token_account(output_vault).map_err(|_| error!(SolarBError::VaultParseFailed))?;
 
         let SwapParams {
             trade_direction,
@@ -237,24 +427,23 @@ impl<'info> RaydiumCPMM<'info> {
                 &self.base_token
             }
         };
-        let transfer_fee = get_transfer_fee(output_token_account, amount_out)?;
+        let transfer_fee = get_transfer_fee(output_token_account, amount_out, clock.epoch)?;
+        // No `amount_with_slippage` at 0% as in Raydium's client: its f64 round trip moves
+        // amounts past 2^53
         let amount_received = amount_out
             .checked_sub(transfer_fee)
             .ok_or(ErrorCode::MathOverflow)?;
-        // calc mint out amount with slippage (0% slippage)
-        let minimum_amount_out = amount_with_slippage(amount_received, 0.0, false);
 
-        Ok(minimum_amount_out)
+        Ok(amount_received)
     }
 
     pub fn swap_base_out_impl(
         &self,
         input_mint: Pubkey,
         amount_out: u64,
-        _clock: Clock,
+        clock: Clock,
     ) -> Result<u64> {
-        let pool_data = self.pool_id.try_borrow_data()?;
-        let pool = bytemuck::pod_read_unaligned::<PoolState>(&pool_data[8..]);
+        let pool = self.pool_state()?;
 
         let amm_data = self.accounts[6].try_borrow_data()?;
         let amm_config: AmmConfig = AmmConfig::try_from_bytes(&amm_data)?;
@@ -272,7 +461,8 @@ impl<'info> RaydiumCPMM<'info> {
         } else {
             &self.quote_token
         };
-        let out_transfer_fee = get_transfer_inverse_fee(output_token_account, amount_out)?;
+        let out_transfer_fee =
+            get_transfer_inverse_fee(output_token_account, amount_out, clock.epoch)?;
         let amount_out_with_transfer_fee = amount_out
             .checked_add(out_transfer_fee)
             .ok_or(ErrorCode::MathOverflow)?;
@@ -286,8 +476,10 @@ impl<'info> RaydiumCPMM<'info> {
         };
 
         // Parse vault amounts
-        let input_vault_account = parse_token_account(input_vault)?;
-        let output_vault_account = parse_token_account(output_vault)?;
+        let input_vault_account =
+            parse_token_account(input_vault).map_err(|_| error!(SolarBError::VaultParseFailed))?;
+        let output_vault_account =
+            parse_token_account(output_vault).map_err(|_| error!(SolarBError::VaultParseFailed))?;
 
         let SwapParams {
             trade_direction: _,
@@ -325,13 +517,13 @@ impl<'info> RaydiumCPMM<'info> {
             &self.quote_token
         };
         let amount_in_transfer_fee =
-            get_transfer_inverse_fee(input_token_account, source_amount_swapped)?;
+            get_transfer_inverse_fee(input_token_account, source_amount_swapped, clock.epoch)?;
 
-        let input_transfer_amount = source_amount_swapped
+        // Taken as is rather than through `amount_with_slippage` at 0%, whose f64 round
+        // trip can land below it past 2^53 and leave the swap short
+        let max_amount_in = source_amount_swapped
             .checked_add(amount_in_transfer_fee)
             .ok_or(ErrorCode::MathOverflow)?;
-        // calc max in with slippage (0% slippage)
-        let max_amount_in = amount_with_slippage(input_transfer_amount, 0.0, true);
 
         Ok(max_amount_in)
     }
@@ -341,7 +533,7 @@ impl<'info> RaydiumCPMM<'info> {
         _input_mint: Pubkey,
         max_amount_in: u64,
         amount_out: Option<u64>,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -349,99 +541,23 @@ impl<'info> RaydiumCPMM<'info> {
         mint_1_token_program: AccountInfo<'a>,
         mint_2_token_program: AccountInfo<'a>,
     ) -> Result<()> {
-        let (
-            input_token_program,
-            output_token_program,
-            user_input_token_account,
-            user_output_token_account,
-            input_vault,
-            output_vault,
-            input_mint,
-            output_mint,
-        ) = if mint_1_account.key() == self.base_token.key() {
-            (
-                mint_1_token_program,
-                mint_2_token_program,
-                user_mint_1_token_account,
-                user_mint_2_token_account,
-                &self.base_vault,
-                &self.quote_vault,
-                mint_1_account,
-                mint_2_account,
-            )
-        } else if mint_2_account.key() == self.base_token.key() {
-            (
-                mint_2_token_program,
-                mint_1_token_program,
-                user_mint_2_token_account,
-                user_mint_1_token_account,
-                &self.base_vault,
-                &self.quote_vault,
-                mint_2_account,
-                mint_1_account,
-            )
-        } else {
-            return Err(ProgramError::InvalidAccountData.into());
-        };
-
-        // Load pool state to get amm_config and authority
-        let pool_data = self.pool_id.try_borrow_data()?;
-        let pool = bytemuck::pod_read_unaligned::<PoolState>(&pool_data[8..]);
-        let amm_config_key = pool.amm_config;
-        let authority_key = pool.pool_creator; // Or derive from pool_id if needed
-
-        // Get observation_key from pool state
-        let observation_key_key = pool.observation_key;
-
-        let amount_out_value = amount_out.unwrap_or(0);
-        let metas = vec![
-            AccountMeta::new(*payer.key, true),
-            AccountMeta::new(authority_key, false),
-            AccountMeta::new(amm_config_key, false),
-            AccountMeta::new(*self.pool_id.key, false),
-            AccountMeta::new(*user_input_token_account.key, false),
-            AccountMeta::new(*user_output_token_account.key, false),
-            AccountMeta::new(*input_vault.key, false),
-            AccountMeta::new(*output_vault.key, false),
-            AccountMeta::new_readonly(*input_token_program.key, false),
-            AccountMeta::new_readonly(*output_token_program.key, false),
-            AccountMeta::new_readonly(*input_mint.key, false),
-            AccountMeta::new_readonly(*output_mint.key, false),
-            AccountMeta::new(observation_key_key, false),
-        ];
-        let mut data = vec![143, 190, 90, 218, 196, 30, 51, 222];
-        data.extend_from_slice(&max_amount_in.to_le_bytes());
-        data.extend_from_slice(&amount_out_value.to_le_bytes());
-
-        let swap_ix = Instruction {
-            program_id: Self::PROGRAM_ID,
-            accounts: metas,
-            data,
-        };
-
-        // Collect all required accounts for invoke
-        // Order must match metas exactly!
-        let mut accounts_vec: Vec<AccountInfo<'info>> = vec![self.pool_id.clone()];
-
-        // Add accounts from function parameters (cast from 'a to 'info)
-        accounts_vec.push(unsafe { std::mem::transmute(payer.to_account_info()) });
-        accounts_vec
-            .push(unsafe { std::mem::transmute(user_input_token_account.to_account_info()) });
-        accounts_vec
-            .push(unsafe { std::mem::transmute(user_output_token_account.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(input_vault.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(output_vault.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(input_token_program.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(output_token_program.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(input_mint.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(output_mint.to_account_info()) });
-
-        // Cast entire vector to AccountInfo<'a> for invoke
-        unsafe {
-            let accounts_slice: &[AccountInfo<'a>] = std::mem::transmute(accounts_vec.as_slice());
-            invoke(&swap_ix, accounts_slice)?;
-        }
-        Ok(())
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        // Swapping base in: base goes in, quote comes out
+        self.invoke_swap(
+            EdgeSide::LeftToRight,
+            max_amount_in,
+            amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
     }
 
     pub fn invoke_swap_base_out_impl<'a>(
@@ -449,7 +565,7 @@ impl<'info> RaydiumCPMM<'info> {
         _input_mint: Pubkey,
         amount_out: u64,
         max_amount_in: u64,
-        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
         user_mint_1_token_account: AccountInfo<'a>,
         user_mint_2_token_account: AccountInfo<'a>,
         mint_1_account: AccountInfo<'a>,
@@ -457,101 +573,94 @@ impl<'info> RaydiumCPMM<'info> {
         mint_1_token_program: AccountInfo<'a>,
         mint_2_token_program: AccountInfo<'a>,
     ) -> Result<()> {
-        let (
-            input_token_program,
-            output_token_program,
-            user_input_token_account,
-            user_output_token_account,
-            input_vault,
-            output_vault,
-            input_mint,
-            output_mint,
-        ) = if mint_1_account.key() == self.base_token.key() {
-            (
-                mint_2_token_program,
-                mint_1_token_program,
-                user_mint_2_token_account,
-                user_mint_1_token_account,
-                &self.quote_vault,
-                &self.base_vault,
-                mint_2_account,
-                mint_1_account,
-            )
-        } else if mint_2_account.key() == self.base_token.key() {
-            (
-                mint_1_token_program,
-                mint_2_token_program,
-                user_mint_1_token_account,
-                user_mint_2_token_account,
-                &self.quote_vault,
-                &self.base_vault,
-                mint_1_account,
-                mint_2_account,
-            )
-        } else {
-            return Err(ProgramError::InvalidAccountData.into());
-        };
-
-        // Load pool state to get amm_config and authority
-        let pool_data = self.pool_id.try_borrow_data()?;
-        let pool = bytemuck::pod_read_unaligned::<PoolState>(&pool_data[8..]);
-        let amm_config_key = pool.amm_config;
-        let authority_key = pool.pool_creator;
-        let observation_key_key = pool.observation_key;
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        // Swapping base out: quote goes in, base comes out
+        self.invoke_swap(
+            EdgeSide::RightToLeft,
+            max_amount_in,
+            amount_out,
+            authority,
+            accounts,
+        )
+    }
 
-        let metas = vec![
-            AccountMeta::new(*payer.key, true),
-            AccountMeta::new(authority_key, false),
-            AccountMeta::new(amm_config_key, false),
-            AccountMeta::new(*self.pool_id.key, false),
-            AccountMeta::new(*user_input_token_account.key, false),
-            AccountMeta::new(*user_output_token_account.key, false),
-            AccountMeta::new(*input_vault.key, false),
-            AccountMeta::new(*output_vault.key, false),
-            AccountMeta::new_readonly(*input_token_program.key, false),
-            AccountMeta::new_readonly(*output_token_program.key, false),
-            AccountMeta::new_readonly(*input_mint.key, false),
-            AccountMeta::new_readonly(*output_mint.key, false),
-            AccountMeta::new(observation_key_key, false),
-        ];
-        let mut data = vec![55, 217, 98, 86, 163, 74, 180, 173];
-        data.extend_from_slice(&amount_out.to_le_bytes());
-        data.extend_from_slice(&max_amount_in.to_le_bytes());
+    fn invoke_swap<'a>(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        authority: AccountInfo<'a>,
+        accounts: ResolvedAccounts<'a>,
+    ) -> Result<()> {
+        let swap_ix = self.build_swap_ix(
+            direction.clone(),
+            amount_in,
+            min_out,
+            &accounts.keys(authority.key),
+        )?;
 
-        let swap_ix = Instruction {
-            program_id: Self::PROGRAM_ID,
-            accounts: metas,
-            data,
+        let ResolvedAccounts {
+            base_mint,
+            quote_mint,
+            base_token_program,
+            quote_token_program,
+            user_base_token_account,
+            user_quote_token_account,
+        } = accounts;
+        let user_side = match direction {
+            EdgeSide::LeftToRight => [
+                user_base_token_account,
+                user_quote_token_account,
+                base_token_program,
+                quote_token_program,
+                base_mint,
+                quote_mint,
+            ],
+            EdgeSide::RightToLeft => [
+                user_quote_token_account,
+                user_base_token_account,
+                quote_token_program,
+                base_token_program,
+                quote_mint,
+                base_mint,
+            ],
         };
 
         // Collect all required accounts for invoke
-        // Order must match metas exactly!
-        let mut accounts_vec: Vec<AccountInfo<'info>> = vec![self.pool_id.clone()];
+        let mut accounts_vec: Vec<AccountInfo<'info>> = vec![
+            self.pool_id.clone(),
+            self.base_vault.clone(),
+            self.quote_vault.clone(),
+        ];
 
         // Add accounts from function parameters (cast from 'a to 'info)
-        accounts_vec.push(unsafe { std::mem::transmute(payer.to_account_info()) });
-        accounts_vec
-            .push(unsafe { std::mem::transmute(user_input_token_account.to_account_info()) });
-        accounts_vec
-            .push(unsafe { std::mem::transmute(user_output_token_account.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(input_vault.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(output_vault.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(input_token_program.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(output_token_program.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(input_mint.to_account_info()) });
-        accounts_vec.push(unsafe { std::mem::transmute(output_mint.to_account_info()) });
+        for account in std::iter::once(authority).chain(user_side) {
+            accounts_vec.push(unsafe {
+                std::mem::transmute::<AccountInfo<'a>, AccountInfo<'info>>(account)
+            });
+        }
 
         // Cast entire vector to AccountInfo<'a> for invoke
         unsafe {
             let accounts_slice: &[AccountInfo<'a>] = std::mem::transmute(accounts_vec.as_slice());
             invoke(&swap_ix, accounts_slice)?;
         }
+        // The swap moved the pool's reserves, so later quotes must re-read it
+        self.pool_state.take();
         Ok(())
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use anchor_lang::prelude::Clock;
     use anchor_lang::solana_program::{account_info::AccountInfo, pubkey::Pubkey, system_program};
@@ -1232,4 +1341,358 @@ mod tests {
             base_received as f64 / 1_000_000_000.0
         );
     }
+
+    fn user_keys() -> SwapUserAccounts {
+        SwapUserAccounts {
+            authority: Pubkey::new_unique(),
+            base_token_program: anchor_spl::token::ID,
+            quote_token_program: anchor_spl::token::ID,
+            user_base_token_account: Pubkey::new_unique(),
+            user_quote_token_account: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn test_swap_instruction_uses_authority_pda() {
+        let (expected_authority, auth_bump) =
+            Pubkey::find_program_address(&[AUTH_SEED.as_bytes()], &RaydiumCPMM::PROGRAM_ID);
+        let pool = PoolState {
+            amm_config: Pubkey::new_unique(),
+            pool_creator: Pubkey::new_unique(),
+            observation_key: Pubkey::new_unique(),
+            auth_bump,
+            ..Default::default()
+        };
+        let mut pool_data = vec![0u8; 8];
+        pool_data.extend_from_slice(bytemuck::bytes_of(&pool));
+
+        let owner = system_program::id();
+        let accounts: Vec<AccountInfo<'static>> = vec![
+            create_mock_account_info_with_data(RaydiumCPMM::PROGRAM_ID, owner, None),
+            create_mock_account_info_with_data(Pubkey::new_unique(), owner, Some(pool_data)),
+            create_mock_account_info_with_data(Pubkey::new_unique(), owner, None),
+            create_mock_account_info_with_data(Pubkey::new_unique(), owner, None),
+            create_mock_account_info_with_data(Pubkey::new_unique(), owner, None),
+            create_mock_account_info_with_data(Pubkey::new_unique(), owner, None),
+            create_mock_account_info_with_data(pool.amm_config, owner, None),
+        ];
+        let raydium = RaydiumCPMM::new(&accounts).unwrap();
+
+        let swap_ix = raydium
+            .build_swap_ix(EdgeSide::LeftToRight, 1_000, 990, &user_keys())
+            .unwrap();
+
+        assert_eq!(swap_ix.program_id, RaydiumCPMM::PROGRAM_ID);
+        assert_eq!(swap_ix.accounts[1].pubkey, expected_authority);
+        assert_ne!(swap_ix.accounts[1].pubkey, pool.pool_creator);
+        assert!(!swap_ix.accounts[1].is_writable);
+        assert_eq!(swap_ix.accounts[2].pubkey, pool.amm_config);
+        assert_eq!(swap_ix.accounts[12].pubkey, pool.observation_key);
+    }
+
+    #[test]
+    fn test_authority_matches_known_deployments() {
+        // (CP-Swap program, authority its pools' vaults and LP mints belong to) on mainnet,
+        // which `PROGRAM_ID` targets, and on devnet
+        let deployments = [
+            (
+                "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C",
+                "GpMZbSM2GgvTKHJirzeGfMFoaZ8UR2X7F4v8vHTvxFbL",
+            ),
+            (
+                "CPMDWBwJDtYax9qW7AyRuVC19Cc4L4Vcy4n2BHAbHkCW",
+                "7rQ1QFNosMkUCuh7Z7fPbTHvh73b68sQYdirycEzJVuw",
+            ),
+        ];
+        for (program_id, authority) in deployments {
+            let program_id = Pubkey::from_str_const(program_id);
+            let (derived, auth_bump) =
+                Pubkey::find_program_address(&[AUTH_SEED.as_bytes()], &program_id);
+            assert_eq!(derived, Pubkey::from_str_const(authority));
+            if program_id == RaydiumCPMM::PROGRAM_ID {
+                assert_eq!(RaydiumCPMM::authority(auth_bump).unwrap(), derived);
+            }
+        }
+    }
+
+    // Accounts for a RaydiumCPMM instance over a 5_000_000 / 250_000 token pool
+    pub(crate) fn create_swap_test_accounts() -> Vec<AccountInfo<'static>> {
+        use anchor_lang::solana_program::program_pack::Pack;
+        use anchor_spl::token::spl_token::state::{Account, AccountState};
+        use anchor_spl::token::Token;
+
+        let token_0_mint = Pubkey::new_unique();
+        let token_1_mint = Pubkey::new_unique();
+        let token_0_vault = Pubkey::new_unique();
+        let token_1_vault = Pubkey::new_unique();
+        let amm_config_key = Pubkey::new_unique();
+
+        let token_account_data = |mint: Pubkey, amount: u64| {
+            let mut data = vec![0u8; Account::LEN];
+            Account {
+                mint,
+                owner: Pubkey::new_unique(),
+                amount,
+                state: AccountState::Initialized,
+                ..Default::default()
+            }
+            .pack_into_slice(&mut data);
+            data
+        };
+
+        let pool = PoolState {
+            token_0_vault,
+            token_1_vault,
+            token_0_mint,
+            token_1_mint,
+            amm_config: amm_config_key,
+            ..Default::default()
+        };
+        let mut pool_data = vec![0u8; 8];
+        pool_data.extend_from_slice(bytemuck::bytes_of(&pool));
+
+        let amm_config = AmmConfig {
+            trade_fee_rate: 2_500,
+            protocol_fee_rate: 120_000,
+            fund_fee_rate: 40_000,
+            ..Default::default()
+        };
+        let mut amm_config_data = Vec::new();
+        amm_config.try_serialize(&mut amm_config_data).unwrap();
+
+        let owner = system_program::id();
+        vec![
+            create_mock_account_info_with_data(RaydiumCPMM::PROGRAM_ID, owner, None),
+            create_mock_account_info_with_data(Pubkey::new_unique(), owner, Some(pool_data)),
+            create_mock_account_info_with_data(
+                token_0_vault,
+                Token::id(),
+                Some(token_account_data(token_0_mint, 5_000_000_000_000)),
+            ),
+            create_mock_account_info_with_data(
+                token_1_vault,
+                Token::id(),
+                Some(token_account_data(token_1_mint, 250_000_000_000)),
+            ),
+            create_mock_account_info_with_data(token_0_mint, Token::id(), None),
+            create_mock_account_info_with_data(token_1_mint, Token::id(), None),
+            create_mock_account_info_with_data(amm_config_key, owner, Some(amm_config_data)),
+        ]
+    }
+
+    pub(crate) fn swap_test_clock() -> Clock {
+        Clock {
+            slot: 300_000_000,
+            epoch_start_timestamp: 0,
+            epoch: 700,
+            leader_schedule_epoch: 0,
+            unix_timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_swap_base_in_deterministic() {
+        let accounts = create_swap_test_accounts();
+        let raydium = RaydiumCPMM::new(&accounts).unwrap();
+        let token_0_mint = *accounts[4].key;
+
+        let first = raydium
+            .swap_base_in(token_0_mint, 1_000_000_000, swap_test_clock())
+            .unwrap();
+        let second = raydium
+            .swap_base_in(token_0_mint, 1_000_000_000, swap_test_clock())
+            .unwrap();
+        assert!(first > 0);
+        assert_eq!(first.to_le_bytes(), second.to_le_bytes());
+    }
+
+    #[test]
+    fn test_pool_state_deserialized_once_per_edge() {
+        let accounts = create_swap_test_accounts();
+        // `new` loads it to check the AMM config
+        let raydium = RaydiumCPMM::new(&accounts).unwrap();
+        assert_eq!(raydium.pool_state_loads.get(), 1);
+
+        // Quote, then build the swap for the same edge as invoke_swap_base_in does
+        raydium
+            .swap_base_in(*accounts[4].key, 1_000_000_000, swap_test_clock())
+            .unwrap();
+        raydium
+            .build_swap_ix(EdgeSide::LeftToRight, 1_000_000_000, 0, &user_keys())
+            .unwrap();
+        assert_eq!(raydium.pool_state_loads.get(), 1);
+    }
+
+    #[test]
+    fn test_truncated_pool_fails_to_deserialize() {
+        let mut accounts = create_swap_test_accounts();
+        accounts[1] = create_mock_account_info_with_data(
+            *accounts[1].key,
+            system_program::id(),
+            Some(vec![0u8; 64]),
+        );
+        assert_eq!(
+            RaydiumCPMM::new(&accounts).err(),
+            Some(error!(SolarBError::PoolDeserializeFailed))
+        );
+    }
+
+    #[test]
+    fn test_missing_amm_config_is_rejected() {
+        let mut accounts = create_swap_test_accounts();
+        accounts.truncate(6);
+        assert_eq!(
+            RaydiumCPMM::new(&accounts).err(),
+            Some(error!(SolarBError::MissingAmmConfig))
+        );
+    }
+
+    #[test]
+    fn test_wrong_amm_config_is_rejected() {
+        let mut accounts = create_swap_test_accounts();
+        let amm_config_data = accounts[6].try_borrow_data().unwrap().to_vec();
+        accounts[6] = create_mock_account_info_with_data(
+            Pubkey::new_unique(),
+            system_program::id(),
+            Some(amm_config_data),
+        );
+        assert_eq!(
+            RaydiumCPMM::new(&accounts).err(),
+            Some(error!(SolarBError::MissingAmmConfig))
+        );
+    }
+
+    #[test]
+    fn test_build_swap_ix_follows_direction() {
+        let accounts = create_swap_test_accounts();
+        let raydium = RaydiumCPMM::new(&accounts).unwrap();
+        let user = user_keys();
+
+        for (direction, discriminator, input, input_vault) in [
+            (
+                EdgeSide::LeftToRight,
+                SWAP_BASE_INPUT_DISCRIMINATOR,
+                user.user_base_token_account,
+                *accounts[2].key,
+            ),
+            (
+                EdgeSide::RightToLeft,
+                SWAP_BASE_OUTPUT_DISCRIMINATOR,
+                user.user_quote_token_account,
+                *accounts[3].key,
+            ),
+        ] {
+            let swap_ix = raydium.build_swap_ix(direction, 1_000, 990, &user).unwrap();
+            assert_eq!(swap_ix.program_id, RaydiumCPMM::PROGRAM_ID);
+            assert_eq!(swap_ix.accounts.len(), 13);
+            assert_eq!(swap_ix.data[..8], discriminator);
+            assert_eq!(swap_ix.accounts[0], AccountMeta::new(user.authority, true));
+            assert_eq!(swap_ix.accounts[4].pubkey, input);
+            assert_eq!(swap_ix.accounts[6].pubkey, input_vault);
+        }
+    }
+
+    #[test]
+    fn test_compute_prices_reflect_trade_fee_on_balanced_pool() {
+        let accounts = create_swap_test_accounts();
+        let raydium = RaydiumCPMM::new(&accounts).unwrap();
+        let reserve = 1_000_000_000_000u128;
+
+        let price_base_in = raydium
+            .compute_price_swap_base_in(reserve, reserve)
+            .unwrap();
+        let price_base_out = raydium
+            .compute_price_swap_base_out(reserve, reserve)
+            .unwrap();
+
+        // 0.25% trade fee on the input in either direction
+        assert!((price_base_in - 0.9975).abs() < 1e-12);
+        assert!((price_base_out - 0.9975).abs() < 1e-12);
+        assert!(price_base_in * price_base_out < 1.0);
+    }
+
+    // `accounts` with both mints swapped for Token-2022 mints under the same keys, charging
+    // 1% on transfers before `FEE_TRANSITION_EPOCH` and 5% from it on
+    fn with_transfer_fee_mints(
+        mut accounts: Vec<AccountInfo<'static>>,
+    ) -> Vec<AccountInfo<'static>> {
+        use crate::utils::token::tests::create_fee_schedule_mint;
+
+        for index in [4, 5] {
+            let fee_mint = create_fee_schedule_mint();
+            let data = fee_mint.try_borrow_data().unwrap().to_vec();
+            accounts[index] = create_mock_account_info_with_data(
+                *accounts[index].key,
+                *fee_mint.owner,
+                Some(data),
+            );
+        }
+        accounts
+    }
+
+    #[test]
+    fn test_swap_base_out_covers_transfer_fees() {
+        use crate::utils::token::tests::FEE_TRANSITION_EPOCH;
+
+        let accounts = with_transfer_fee_mints(create_swap_test_accounts());
+        let raydium = RaydiumCPMM::new(&accounts).unwrap();
+        let (token_0_mint, token_1_mint) = (*accounts[4].key, *accounts[5].key);
+
+        for epoch in [FEE_TRANSITION_EPOCH - 1, FEE_TRANSITION_EPOCH] {
+            let clock = Clock {
+                epoch,
+                ..swap_test_clock()
+            };
+            for input_mint in [token_0_mint, token_1_mint] {
+                for amount_out in [1, 7, 101, 12_345, 987_654_321, 10_000_000_000] {
+                    assert_max_amount_in_suffices(&raydium, input_mint, amount_out, &clock);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_swap_base_out_covers_transfer_fees_past_f64_precision() {
+        let accounts = with_transfer_fee_mints(create_swap_test_accounts());
+        for (vault, reserve) in [
+            (2, 4_000_000_000_000_000_000u64),
+            (3, 200_000_000_000_000_000),
+        ] {
+            accounts[vault].try_borrow_mut_data().unwrap()[64..72]
+                .copy_from_slice(&reserve.to_le_bytes());
+        }
+        let raydium = RaydiumCPMM::new(&accounts).unwrap();
+
+        // Inputs here run past 2^53, where an f64 round trip drops the low bits
+        for input_mint in [*accounts[4].key, *accounts[5].key] {
+            for step in 0..200 {
+                let amount_out = 3_000_000_000_000_000 + step * 7_777_777;
+                assert_max_amount_in_suffices(&raydium, input_mint, amount_out, &swap_test_clock());
+            }
+        }
+    }
+
+    // Sending the exact-out quote for `amount_out` pays the input transfer fee, the pool fee
+    // and the output transfer fee and still delivers at least `amount_out`
+    fn assert_max_amount_in_suffices(
+        raydium: &RaydiumCPMM,
+        input_mint: Pubkey,
+        amount_out: u64,
+        clock: &Clock,
+    ) {
+        let max_amount_in = raydium
+            .swap_base_out(input_mint, amount_out, clock.clone())
+            .unwrap();
+        let received = raydium
+            .swap_base_in(input_mint, max_amount_in, clock.clone())
+            .unwrap();
+        assert!(
+            received >= amount_out,
+            "epoch {}: {} in for {} out yields {}",
+            clock.epoch,
+            max_amount_in,
+            amount_out,
+            received
+        );
+    }
 }
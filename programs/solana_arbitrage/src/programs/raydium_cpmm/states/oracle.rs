@@ -141,10 +141,10 @@ impl ObservationState {
     }
 }
 
-/// Returns the block timestamp truncated to 32 bits, i.e. mod 2**32
+/// Returns the timestamp of `clock` truncated to 32 bits, i.e. mod 2**32
 ///
-pub fn block_timestamp() -> u64 {
-    Clock::get().unwrap().unix_timestamp as u64 // truncation is desired
+pub fn block_timestamp(clock: &Clock) -> u64 {
+    clock.unix_timestamp as u64 // truncation is desired
 }
 
 #[cfg(test)]
@@ -148,6 +148,7 @@ impl PoolState {
         observation_key: Pubkey,
         creator_fee_on: CreatorFeeOn,
         enable_creator_fee: bool,
+        recent_epoch: u64,
     ) {
         self.amm_config = amm_config.key();
         self.pool_creator = pool_creator.key();
@@ -169,7 +170,7 @@ impl PoolState {
         self.fund_fees_token_0 = 0;
         self.fund_fees_token_1 = 0;
         self.open_time = open_time;
-        self.recent_epoch = Clock::get().unwrap().epoch;
+        self.recent_epoch = recent_epoch;
         self.creator_fee_on = creator_fee_on.to_u8();
         self.enable_creator_fee = enable_creator_fee;
         self.padding1 = [0u8; 6];
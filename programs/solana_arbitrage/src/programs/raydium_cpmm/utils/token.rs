@@ -122,8 +122,12 @@ pub fn token_burn<'a>(
     )
 }
 
-/// Calculate the fee for output amount
-pub fn get_transfer_inverse_fee(mint_info: &AccountInfo, post_fee_amount: u64) -> Result<u64> {
+/// Calculate the fee for output amount at `epoch`
+pub fn get_transfer_inverse_fee(
+    mint_info: &AccountInfo,
+    post_fee_amount: u64,
+    epoch: u64,
+) -> Result<u64> {
     if *mint_info.owner == Token::id() {
         return Ok(0);
     }
@@ -136,8 +140,6 @@ pub fn get_transfer_inverse_fee(mint_info: &AccountInfo, post_fee_amount: u64) -
     )?;
 
     let fee = if let Ok(transfer_fee_config) = mint.get_extension::<TransferFeeConfig>() {
-        let epoch = Clock::get()?.epoch;
-
         let transfer_fee = transfer_fee_config.get_epoch_fee(epoch);
         if u16::from(transfer_fee.transfer_fee_basis_points) == MAX_FEE_BASIS_POINTS {
             u64::from(transfer_fee.maximum_fee)
@@ -159,8 +161,8 @@ pub fn get_transfer_inverse_fee(mint_info: &AccountInfo, post_fee_amount: u64) -
     Ok(fee)
 }
 
-/// Calculate the fee for input amount
-pub fn get_transfer_fee(mint_info: &AccountInfo, pre_fee_amount: u64) -> Result<u64> {
+/// Calculate the fee for input amount at `epoch`
+pub fn get_transfer_fee(mint_info: &AccountInfo, pre_fee_amount: u64, epoch: u64) -> Result<u64> {
     if *mint_info.owner == Token::id() {
         return Ok(0);
     }
@@ -171,7 +173,7 @@ pub fn get_transfer_fee(mint_info: &AccountInfo, pre_fee_amount: u64) -> Result<
 
     let fee = if let Ok(transfer_fee_config) = mint.get_extension::<TransferFeeConfig>() {
         transfer_fee_config
-            .calculate_epoch_fee(Clock::get()?.epoch, pre_fee_amount)
+            .calculate_epoch_fee(epoch, pre_fee_amount)
             .unwrap()
     } else {
         0
@@ -312,3 +314,32 @@ pub fn amount_with_slippage(amount: u64, slippage: f64, round_up: bool) -> u64 {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::token::tests::{create_fee_schedule_mint, FEE_TRANSITION_EPOCH};
+
+    #[test]
+    fn test_transfer_fees_use_the_given_epoch() {
+        let mint_info = create_fee_schedule_mint();
+
+        // 1% before the transition, 5% from it on
+        assert_eq!(
+            get_transfer_fee(&mint_info, 1_000_000, FEE_TRANSITION_EPOCH - 1).unwrap(),
+            10_000
+        );
+        assert_eq!(
+            get_transfer_fee(&mint_info, 1_000_000, FEE_TRANSITION_EPOCH).unwrap(),
+            50_000
+        );
+        assert_eq!(
+            get_transfer_inverse_fee(&mint_info, 990_000, FEE_TRANSITION_EPOCH - 1).unwrap(),
+            10_000
+        );
+        assert_eq!(
+            get_transfer_inverse_fee(&mint_info, 950_000, FEE_TRANSITION_EPOCH).unwrap(),
+            50_000
+        );
+    }
+}
@@ -0,0 +1,1024 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    account_info::next_account_info,
+    instruction::{AccountMeta, Instruction},
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use ruint::aliases::U256;
+
+use crate::arbitrage::base::EdgeSide;
+use crate::programs::programs::{
+    check_recorded_keys, check_vault_mints, quote_exact_out_by_search, read_mint_decimals,
+};
+use crate::programs::{ProgramMeta, SolarBError};
+use crate::utils::utils::{resolve_swap_accounts, ResolvedAccounts, SwapUserAccounts};
+
+// Packed offsets into the stable-swap `SwapInfo` account, 395 bytes, no discriminator
+const SWAP_IS_INITIALIZED_OFFSET: usize = 0;
+const SWAP_IS_PAUSED_OFFSET: usize = 1;
+const SWAP_NONCE_OFFSET: usize = 2;
+const SWAP_INITIAL_AMP_FACTOR_OFFSET: usize = 3;
+const SWAP_TARGET_AMP_FACTOR_OFFSET: usize = 11;
+const SWAP_START_RAMP_TS_OFFSET: usize = 19;
+const SWAP_STOP_RAMP_TS_OFFSET: usize = 27;
+const SWAP_TOKEN_A_OFFSET: usize = 107;
+const SWAP_TOKEN_B_OFFSET: usize = 139;
+const SWAP_TOKEN_A_MINT_OFFSET: usize = 203;
+const SWAP_TOKEN_B_MINT_OFFSET: usize = 235;
+const SWAP_ADMIN_FEE_A_OFFSET: usize = 267;
+const SWAP_ADMIN_FEE_B_OFFSET: usize = 299;
+const SWAP_TRADE_FEE_NUMERATOR_OFFSET: usize = 363;
+const SWAP_TRADE_FEE_DENOMINATOR_OFFSET: usize = 371;
+
+/// Two-coin pools only
+const N_COINS: u64 = 2;
+/// Newton iterations the program allows for `D` and `y` before giving up on convergence
+const MAX_ITERATIONS: usize = 256;
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    let bytes = data
+        .get(offset..offset + N)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    Ok(bytes.try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(data, offset)?))
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    Ok(Pubkey::new_from_array(read_bytes(data, offset)?))
+}
+
+// Newton's method stops once successive values are within one atom of each other
+fn converged(value: U256, previous: U256) -> bool {
+    value.max(previous) - value.min(previous) <= U256::from(1)
+}
+
+/// StableSwap invariant `D` of reserves `amount_a` and `amount_b` at `amp_factor`, by
+/// Newton's method as the program computes it. `D` is the total both reserves would hold
+/// at a 1:1 price, so a balanced pool's `D` is the sum of its reserves.
+pub fn compute_d(amp_factor: u64, amount_a: u64, amount_b: u64) -> Option<U256> {
+    let sum_x = U256::from(amount_a) + U256::from(amount_b);
+    if sum_x.is_zero() {
+        return Some(U256::ZERO);
+    }
+    let n = U256::from(N_COINS);
+    let amount_a_times_coins = U256::from(amount_a) * n;
+    let amount_b_times_coins = U256::from(amount_b) * n;
+    let leverage = U256::from(amp_factor) * n;
+
+    let mut d = sum_x;
+    for _ in 0..MAX_ITERATIONS {
+        let d_product = d
+            .checked_mul(d)?
+            .checked_div(amount_a_times_coins)?
+            .checked_mul(d)?
+            .checked_div(amount_b_times_coins)?;
+        let numerator = d.checked_mul(
+            d_product
+                .checked_mul(n)?
+                .checked_add(leverage.checked_mul(sum_x)?)?,
+        )?;
+        let denominator = d
+            .checked_mul(leverage.checked_sub(U256::from(1))?)?
+            .checked_add(d_product.checked_mul(n + U256::from(1))?)?;
+        let d_prev = d;
+        d = numerator.checked_div(denominator)?;
+        if converged(d, d_prev) {
+            break;
+        }
+    }
+    Some(d)
+}
+
+/// Reserve `y` the other side holds when one side holds `x` and the invariant stays `d`
+pub fn compute_y(amp_factor: u64, x: U256, d: U256) -> Option<U256> {
+    let n = U256::from(N_COINS);
+    let leverage = U256::from(amp_factor).checked_mul(n)?;
+
+    // c = D^3 / (n^2 * x * A * n), b = x + D / (A * n); solve y^2 + (b - D) * y = c
+    let c = d
+        .checked_mul(d)?
+        .checked_div(x.checked_mul(n)?)?
+        .checked_mul(d)?
+        .checked_div(leverage.checked_mul(n)?)?;
+    let b = d.checked_div(leverage)?.checked_add(x)?;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let numerator = y.checked_mul(y)?.checked_add(c)?;
+        let denominator = y.checked_mul(n)?.checked_add(b)?.checked_sub(d)?;
+        let y_prev = y;
+        y = numerator.checked_div(denominator)?;
+        if converged(y, y_prev) {
+            break;
+        }
+    }
+    Some(y)
+}
+
+/// The part of the `SwapInfo` account needed to quote a swap
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SwapState {
+    pub is_initialized: bool,
+    pub is_paused: bool,
+    pub nonce: u8,
+    pub initial_amp_factor: u64,
+    pub target_amp_factor: u64,
+    pub start_ramp_ts: i64,
+    pub stop_ramp_ts: i64,
+    pub trade_fee_numerator: u64,
+    pub trade_fee_denominator: u64,
+}
+
+impl SwapState {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Ok(SwapState {
+            is_initialized: read_bytes::<1>(data, SWAP_IS_INITIALIZED_OFFSET)?[0] != 0,
+            is_paused: read_bytes::<1>(data, SWAP_IS_PAUSED_OFFSET)?[0] != 0,
+            nonce: read_bytes::<1>(data, SWAP_NONCE_OFFSET)?[0],
+            initial_amp_factor: read_u64(data, SWAP_INITIAL_AMP_FACTOR_OFFSET)?,
+            target_amp_factor: read_u64(data, SWAP_TARGET_AMP_FACTOR_OFFSET)?,
+            start_ramp_ts: i64::from_le_bytes(read_bytes(data, SWAP_START_RAMP_TS_OFFSET)?),
+            stop_ramp_ts: i64::from_le_bytes(read_bytes(data, SWAP_STOP_RAMP_TS_OFFSET)?),
+            trade_fee_numerator: read_u64(data, SWAP_TRADE_FEE_NUMERATOR_OFFSET)?,
+            trade_fee_denominator: read_u64(data, SWAP_TRADE_FEE_DENOMINATOR_OFFSET)?,
+        })
+    }
+
+    /// Amplification at `now`: the admin ramps it linearly from `initial_amp_factor` to
+    /// `target_amp_factor` between the two ramp timestamps, and it holds the target after
+    pub fn amp_factor(&self, now: i64) -> Option<u64> {
+        if now >= self.stop_ramp_ts {
+            return Some(self.target_amp_factor);
+        }
+        let time_range = self.stop_ramp_ts.checked_sub(self.start_ramp_ts)? as u128;
+        let time_delta = now
+            .max(self.start_ramp_ts)
+            .checked_sub(self.start_ramp_ts)? as u128;
+        let ramped = |amp_range: u64| -> Option<u64> {
+            u64::try_from((amp_range as u128).checked_mul(time_delta)? / time_range).ok()
+        };
+        if self.target_amp_factor >= self.initial_amp_factor {
+            self.initial_amp_factor
+                .checked_add(ramped(self.target_amp_factor - self.initial_amp_factor)?)
+        } else {
+            self.initial_amp_factor
+                .checked_sub(ramped(self.initial_amp_factor - self.target_amp_factor)?)
+        }
+    }
+
+    /// Share of the output kept as the trade fee
+    pub fn fee_rate(&self) -> f64 {
+        if self.trade_fee_denominator == 0 {
+            0.0
+        } else {
+            self.trade_fee_numerator as f64 / self.trade_fee_denominator as f64
+        }
+    }
+
+    /// Trade fee on `amount` of output, rounded down
+    pub fn trade_fee(&self, amount: u64) -> Option<u64> {
+        if self.trade_fee_denominator == 0 {
+            return Some(0);
+        }
+        let fee = (amount as u128).checked_mul(self.trade_fee_numerator as u128)?
+            / self.trade_fee_denominator as u128;
+        u64::try_from(fee).ok()
+    }
+
+    /// Exact-in quote: the output side drops to the reserve that keeps `D` once `amount_in`
+    /// is added to the input side, and the trade fee comes off what it gave up
+    pub fn swap_to(
+        &self,
+        amount_in: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        now: i64,
+    ) -> Option<u64> {
+        if amount_in == 0 {
+            return Some(0);
+        }
+        let amp_factor = self.amp_factor(now)?;
+        let d = compute_d(amp_factor, reserve_in, reserve_out)?;
+        let new_reserve_in = U256::from(reserve_in).checked_add(U256::from(amount_in))?;
+        let y = compute_y(amp_factor, new_reserve_in, d)?;
+        let dy = u64::try_from(U256::from(reserve_out).checked_sub(y)?).ok()?;
+        dy.checked_sub(self.trade_fee(dy)?)
+    }
+
+    /// Output per unit of input at the margin, net of the trade fee: the slope of the
+    /// invariant at the reserves, which is 1 at balance and tends to the constant product's
+    /// `reserve_out / reserve_in` as the amplification drops
+    pub fn marginal_price(&self, reserve_in: u128, reserve_out: u128, amp_factor: u64) -> f64 {
+        let (Ok(x), Ok(y)) = (u64::try_from(reserve_in), u64::try_from(reserve_out)) else {
+            return 0.0;
+        };
+        if x == 0 || y == 0 {
+            return 0.0;
+        }
+        let Some(d) = compute_d(amp_factor, x, y).and_then(|d| u128::try_from(d).ok()) else {
+            return 0.0;
+        };
+        let (x, y, d) = (x as f64, y as f64, d as f64);
+        let leverage = (amp_factor * N_COINS) as f64;
+        let d_cubed = d * d * d / 4.0;
+        let slope = (leverage + d_cubed / (x * x * y)) / (leverage + d_cubed / (x * y * y));
+        slope * (1.0 - self.fee_rate())
+    }
+}
+
+/// Saber stable-swap pool.
+///
+/// Quotes follow the StableSwap invariant rather than a constant product: the
+/// amplification coefficient flattens the curve around the 1:1 price, so pools of pegged
+/// assets trade close to par until one side runs low. The trade fee is taken from the
+/// output.
+pub struct Saber<'info> {
+    pub program_id: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+    pub swap_info: AccountInfo<'info>,
+    pub base_vault: AccountInfo<'info>,
+    pub quote_vault: AccountInfo<'info>,
+    pub base_token: AccountInfo<'info>,
+    pub quote_token: AccountInfo<'info>,
+    pub base_admin_fee: AccountInfo<'info>,
+    pub quote_admin_fee: AccountInfo<'info>,
+}
+
+impl<'info> ProgramMeta for Saber<'info> {
+    fn get_id(&self) -> &Pubkey {
+        &Self::PROGRAM_ID
+    }
+
+    fn program_label(&self) -> &'static str {
+        "Saber"
+    }
+
+    fn pool_key(&self) -> &Pubkey {
+        self.swap_info.key
+    }
+
+    fn get_vaults(&self) -> (&AccountInfo<'_>, &AccountInfo<'_>) {
+        unsafe {
+            (
+                &*(&self.base_vault as *const AccountInfo<'info> as *const AccountInfo<'_>),
+                &*(&self.quote_vault as *const AccountInfo<'info> as *const AccountInfo<'_>),
+            )
+        }
+    }
+
+    fn get_mints(&self) -> (&Pubkey, &Pubkey) {
+        (self.base_token.key, self.quote_token.key)
+    }
+
+    fn mint_decimals(&self) -> (u8, u8) {
+        read_mint_decimals(&self.base_token, &self.quote_token)
+    }
+
+    /// Priced at the amplification the pool ramps towards, as no clock is passed in
+    fn compute_price_swap_base_in(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        let state = self.swap_state()?;
+        Ok(state.marginal_price(base_amount, quote_amount, state.target_amp_factor))
+    }
+
+    fn compute_price_swap_base_out(&self, base_amount: u128, quote_amount: u128) -> Result<f64> {
+        let state = self.swap_state()?;
+        Ok(state.marginal_price(quote_amount, base_amount, state.target_amp_factor))
+    }
+
+    fn swap_base_in(&self, input_mint: Pubkey, amount_in: u64, clock: Clock) -> Result<u64> {
+        self.quote_exact_in(input_mint, amount_in, &clock)
+    }
+
+    /// Searched through the exact-in quote rather than solving the invariant backwards
+    fn swap_base_out(&self, input_mint: Pubkey, amount_out: u64, clock: Clock) -> Result<u64> {
+        quote_exact_out_by_search(amount_out, |amount_in| {
+            self.quote_exact_in(input_mint, amount_in, &clock)
+        })
+    }
+
+    fn invoke_swap_base_in<'a>(
+        &self,
+        input_mint: Pubkey,
+        max_amount_in: u64,
+        amount_out: Option<u64>,
+        authority: AccountInfo<'a>,
+        user_mint_1_token_account: AccountInfo<'a>,
+        user_mint_2_token_account: AccountInfo<'a>,
+        mint_1_account: AccountInfo<'a>,
+        mint_2_account: AccountInfo<'a>,
+        mint_1_token_program: AccountInfo<'a>,
+        mint_2_token_program: AccountInfo<'a>,
+    ) -> Result<()> {
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            input_mint,
+            max_amount_in,
+            amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
+    }
+
+    fn invoke_swap_base_out<'a>(
+        &self,
+        input_mint: Pubkey,
+        amount_in: u64,
+        min_amount_out: Option<u64>,
+        authority: AccountInfo<'a>,
+        user_mint_1_token_account: AccountInfo<'a>,
+        user_mint_2_token_account: AccountInfo<'a>,
+        mint_1_account: AccountInfo<'a>,
+        mint_2_account: AccountInfo<'a>,
+        mint_1_token_program: AccountInfo<'a>,
+        mint_2_token_program: AccountInfo<'a>,
+    ) -> Result<()> {
+        let accounts = resolve_swap_accounts(
+            self.base_token.key,
+            mint_1_account,
+            mint_2_account,
+            mint_1_token_program,
+            mint_2_token_program,
+            user_mint_1_token_account,
+            user_mint_2_token_account,
+        )?;
+        self.invoke_swap(
+            input_mint,
+            amount_in,
+            min_amount_out.unwrap_or(0),
+            authority,
+            accounts,
+        )
+    }
+
+    /// `swap` of `amount_in` from the user's input token account to their output one, with
+    /// the admin's share of the fee paid into the output side's admin fee account
+    fn build_swap_ix(
+        &self,
+        direction: EdgeSide,
+        amount_in: u64,
+        min_out: u64,
+        user_accounts: &SwapUserAccounts,
+    ) -> Result<Instruction> {
+        let (source, destination, swap_source, swap_destination, admin_fee, token_program) =
+            match direction {
+                EdgeSide::LeftToRight => (
+                    user_accounts.user_base_token_account,
+                    user_accounts.user_quote_token_account,
+                    &self.base_vault,
+                    &self.quote_vault,
+                    &self.quote_admin_fee,
+                    user_accounts.base_token_program,
+                ),
+                EdgeSide::RightToLeft => (
+                    user_accounts.user_quote_token_account,
+                    user_accounts.user_base_token_account,
+                    &self.quote_vault,
+                    &self.base_vault,
+                    &self.base_admin_fee,
+                    user_accounts.quote_token_program,
+                ),
+            };
+
+        let mut data = vec![Self::SWAP_TAG];
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&min_out.to_le_bytes());
+        Ok(Instruction {
+            program_id: *self.program_id.key,
+            accounts: vec![
+                AccountMeta::new_readonly(*self.swap_info.key, false),
+                AccountMeta::new_readonly(*self.authority.key, false),
+                AccountMeta::new_readonly(user_accounts.authority, true),
+                AccountMeta::new(source, false),
+                AccountMeta::new(*swap_source.key, false),
+                AccountMeta::new(*swap_destination.key, false),
+                AccountMeta::new(destination, false),
+                AccountMeta::new(*admin_fee.key, false),
+                AccountMeta::new_readonly(token_program, false),
+            ],
+            data,
+        })
+    }
+
+    fn estimate_compute_units(&self) -> u32 {
+        Self::SWAP_COMPUTE_UNITS
+    }
+
+    /// Paused and uninitialized pools reject swaps
+    fn is_tradable(&self, _clock: &Clock) -> Result<bool> {
+        let state = self.swap_state()?;
+        Ok(state.is_initialized && !state.is_paused)
+    }
+
+    fn validate_accounts(&self) -> Result<()> {
+        check_vault_mints(self)?;
+        let swap_data = self.swap_info.try_borrow_data()?;
+        check_recorded_keys(
+            self.swap_info.key,
+            &[
+                (
+                    "token_a_mint",
+                    read_pubkey(&swap_data, SWAP_TOKEN_A_MINT_OFFSET)?,
+                    self.base_token.key,
+                ),
+                (
+                    "token_b_mint",
+                    read_pubkey(&swap_data, SWAP_TOKEN_B_MINT_OFFSET)?,
+                    self.quote_token.key,
+                ),
+                (
+                    "token_a",
+                    read_pubkey(&swap_data, SWAP_TOKEN_A_OFFSET)?,
+                    self.base_vault.key,
+                ),
+                (
+                    "token_b",
+                    read_pubkey(&swap_data, SWAP_TOKEN_B_OFFSET)?,
+                    self.quote_vault.key,
+                ),
+                (
+                    "admin_fee_key_a",
+                    read_pubkey(&swap_data, SWAP_ADMIN_FEE_A_OFFSET)?,
+                    self.base_admin_fee.key,
+                ),
+                (
+                    "admin_fee_key_b",
+                    read_pubkey(&swap_data, SWAP_ADMIN_FEE_B_OFFSET)?,
+                    self.quote_admin_fee.key,
+                ),
+            ],
+        )
+    }
+
+    fn log_accounts(&self) -> Result<()> {
+        msg!(
+            "Saber accounts: swap={}, authority={}, base_vault={}, quote_vault={}, base_token={}, quote_token={}, base_admin_fee={}, quote_admin_fee={}",
+            self.swap_info.key,
+            self.authority.key,
+            self.base_vault.key,
+            self.quote_vault.key,
+            self.base_token.key,
+            self.quote_token.key,
+            self.base_admin_fee.key,
+            self.quote_admin_fee.key,
+        );
+        Ok(())
+    }
+}
+
+impl<'info> Saber<'info> {
+    pub const PROGRAM_ID: Pubkey =
+        Pubkey::from_str_const("SSwpkEEcbUqx4vtoEByFjSkhKdCT862DNVb52nZg1UZ");
+    /// Shortest span `find_program_instance` accepts, program id included
+    pub const EXPECTED_ACCOUNTS: usize = 9;
+    /// Typical compute units of one swap CPI, Newton iterations included
+    pub const SWAP_COMPUTE_UNITS: u32 = 40_000;
+    /// Instruction tag of `swap`; the program isn't an Anchor program
+    const SWAP_TAG: u8 = 1;
+
+    pub fn new(accounts: &[AccountInfo<'info>]) -> Result<Self> {
+        let mut iter = accounts.iter();
+        let program_id = next_account_info(&mut iter)?; // 0
+        let authority = next_account_info(&mut iter)?; // 1
+        let swap_info = next_account_info(&mut iter)?; // 2
+        let base_vault = next_account_info(&mut iter)?; // 3
+        let quote_vault = next_account_info(&mut iter)?; // 4
+        let base_token = next_account_info(&mut iter)?; // 5
+        let quote_token = next_account_info(&mut iter)?; // 6
+        let base_admin_fee = next_account_info(&mut iter)?; // 7
+        let quote_admin_fee = next_account_info(&mut iter)?; // 8
+
+        Ok(Saber {
+            program_id: program_id.clone(),
+            authority: authority.clone(),
+            swap_info: swap_info.clone(),
+            base_vault: base_vault.clone(),
+            quote_vault: quote_vault.clone(),
+            base_token: base_token.clone(),
+            quote_token: quote_token.clone(),
+            base_admin_fee: base_admin_fee.clone(),
+            quote_admin_fee: quote_admin_fee.clone(),
+        })
+    }
+
+    pub fn swap_state(&self) -> Result<SwapState> {
+        SwapState::parse(&self.swap_info.try_borrow_data()?)
+    }
+
+    /// Exact-in quote at the amplification `clock` falls in the ramp. Fails with
+    /// `MathOverflow` when the invariant doesn't converge to an output the pool can pay.
+    pub fn quote_exact_in(&self, input_mint: Pubkey, amount_in: u64, clock: &Clock) -> Result<u64> {
+        let state = self.swap_state()?;
+        let (base_reserve, quote_reserve) = self.vault_reserves()?;
+        let (reserve_in, reserve_out) = if input_mint == *self.base_token.key {
+            (base_reserve, quote_reserve)
+        } else {
+            (quote_reserve, base_reserve)
+        };
+        state
+            .swap_to(
+                amount_in,
+                reserve_in as u64,
+                reserve_out as u64,
+                clock.unix_timestamp,
+            )
+            .ok_or(error!(SolarBError::MathOverflow))
+    }
+
+    fn invoke_swap<'a>(
+        &self,
+        input_mint: Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        authority: AccountInfo<'a>,
+        accounts: ResolvedAccounts<'a>,
+    ) -> Result<()> {
+        let direction = if input_mint == *self.base_token.key {
+            EdgeSide::LeftToRight
+        } else {
+            EdgeSide::RightToLeft
+        };
+        let swap_ix = self.build_swap_ix(
+            direction.clone(),
+            amount_in,
+            minimum_amount_out,
+            &accounts.keys(authority.key),
+        )?;
+        let token_program = match direction {
+            EdgeSide::LeftToRight => accounts.base_token_program,
+            EdgeSide::RightToLeft => accounts.quote_token_program,
+        };
+
+        // Same lifetime cast as the other adapters: the pool's accounts outlive the swap
+        let mut accounts_vec: Vec<AccountInfo<'info>> = vec![
+            self.swap_info.to_account_info(),
+            self.authority.to_account_info(),
+            self.base_vault.to_account_info(),
+            self.quote_vault.to_account_info(),
+            self.base_admin_fee.to_account_info(),
+            self.quote_admin_fee.to_account_info(),
+            self.program_id.to_account_info(),
+        ];
+        for account in [
+            authority,
+            accounts.user_base_token_account,
+            accounts.user_quote_token_account,
+            token_program,
+        ] {
+            accounts_vec.push(unsafe {
+                std::mem::transmute::<AccountInfo<'a>, AccountInfo<'info>>(account)
+            });
+        }
+
+        unsafe {
+            let accounts =
+                std::mem::transmute::<&[AccountInfo<'info>], &[AccountInfo<'a>]>(&accounts_vec);
+            invoke(&swap_ix, accounts)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::math::constant_product_out;
+    use crate::programs::test_utils::{mint_data, token_account_data};
+    use anchor_lang::solana_program::system_program;
+
+    const TOKEN_PROGRAM: Pubkey =
+        Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+    // Helper function to create a mock AccountInfo
+    fn create_mock_account_info(
+        key: Pubkey,
+        owner: Pubkey,
+        account_data: Option<Vec<u8>>,
+    ) -> AccountInfo<'static> {
+        let data = if let Some(provided_data) = account_data {
+            Box::leak(Box::new(provided_data))
+        } else {
+            Box::leak(Box::new(Vec::new()))
+        };
+        let lamports = Box::leak(Box::new(0u64));
+        let owner_static = Box::leak(Box::new(owner));
+        let key_static = Box::leak(Box::new(key));
+
+        AccountInfo::new(
+            key_static,
+            false,
+            false,
+            lamports,
+            data,
+            owner_static,
+            false,
+            0,
+        )
+    }
+
+    // `SwapInfo` packed field by field in upstream order, rather than through the offsets
+    // the parser reads. `keys` are the two vaults, the two mints and the two admin fee
+    // accounts.
+    fn swap_info_data(state: SwapState, keys: [Pubkey; 6]) -> Vec<u8> {
+        let [vault_a, vault_b, mint_a, mint_b, admin_fee_a, admin_fee_b] = keys;
+        let mut data = vec![
+            state.is_initialized as u8,
+            state.is_paused as u8,
+            state.nonce,
+        ];
+        // Amplification ramp, then the future admin deadline
+        for value in [
+            state.initial_amp_factor,
+            state.target_amp_factor,
+            state.start_ramp_ts as u64,
+            state.stop_ramp_ts as u64,
+            0,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        // Future admin, admin, the token infos' vaults, the pool mint, the token infos' mints
+        // and admin fee accounts
+        for key in [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            vault_a,
+            vault_b,
+            Pubkey::new_unique(),
+            mint_a,
+            mint_b,
+            admin_fee_a,
+            admin_fee_b,
+        ] {
+            data.extend_from_slice(key.as_ref());
+        }
+        // Admin trade, admin withdraw, trade and withdraw fees, each a numerator and a
+        // denominator
+        for value in [
+            0,
+            1,
+            0,
+            1,
+            state.trade_fee_numerator,
+            state.trade_fee_denominator,
+            0,
+            1,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    // The USDC/USDT pool's parameters: amplification 100 and a 4 bps trade fee
+    fn swap_state(amp_factor: u64) -> SwapState {
+        SwapState {
+            is_initialized: true,
+            is_paused: false,
+            nonce: 255,
+            initial_amp_factor: amp_factor,
+            target_amp_factor: amp_factor,
+            start_ramp_ts: 0,
+            stop_ramp_ts: 0,
+            trade_fee_numerator: 4,
+            trade_fee_denominator: 10_000,
+        }
+    }
+
+    /// Pool over two fresh 6-decimal mints holding `reserves`
+    pub(crate) fn create_pool_accounts(
+        reserves: (u64, u64),
+        state: SwapState,
+    ) -> Vec<AccountInfo<'static>> {
+        create_pool_accounts_for_mints(Pubkey::new_unique(), Pubkey::new_unique(), reserves, state)
+    }
+
+    fn create_pool_accounts_for_mints(
+        base_mint: Pubkey,
+        quote_mint: Pubkey,
+        (base_reserve, quote_reserve): (u64, u64),
+        state: SwapState,
+    ) -> Vec<AccountInfo<'static>> {
+        let owner = system_program::id();
+        let [base_vault, quote_vault, base_admin_fee, quote_admin_fee] =
+            std::array::from_fn(|_| Pubkey::new_unique());
+        let keys = [
+            base_vault,
+            quote_vault,
+            base_mint,
+            quote_mint,
+            base_admin_fee,
+            quote_admin_fee,
+        ];
+        vec![
+            create_mock_account_info(Saber::PROGRAM_ID, owner, None),
+            create_mock_account_info(Pubkey::new_unique(), owner, None),
+            create_mock_account_info(
+                Pubkey::new_unique(),
+                Saber::PROGRAM_ID,
+                Some(swap_info_data(state, keys)),
+            ),
+            create_mock_account_info(
+                base_vault,
+                TOKEN_PROGRAM,
+                Some(token_account_data(base_mint, base_reserve)),
+            ),
+            create_mock_account_info(
+                quote_vault,
+                TOKEN_PROGRAM,
+                Some(token_account_data(quote_mint, quote_reserve)),
+            ),
+            create_mock_account_info(base_mint, TOKEN_PROGRAM, Some(mint_data(0, 6))),
+            create_mock_account_info(quote_mint, TOKEN_PROGRAM, Some(mint_data(0, 6))),
+            create_mock_account_info(
+                base_admin_fee,
+                TOKEN_PROGRAM,
+                Some(token_account_data(base_mint, 0)),
+            ),
+            create_mock_account_info(
+                quote_admin_fee,
+                TOKEN_PROGRAM,
+                Some(token_account_data(quote_mint, 0)),
+            ),
+        ]
+    }
+
+    /// USDC/USDT pool, 32M USDC against 28M USDT, at the mainnet pool's amplification
+    /// and fee
+    pub(crate) fn create_test_accounts() -> Vec<AccountInfo<'static>> {
+        create_pool_accounts((32_000_000_000_000, 28_000_000_000_000), swap_state(100))
+    }
+
+    #[test]
+    fn test_saber_new_insufficient_accounts() {
+        let accounts = create_test_accounts();
+        assert!(Saber::new(&accounts[..8]).is_err());
+    }
+
+    #[test]
+    fn test_saber_new_sufficient_accounts() {
+        let accounts = create_test_accounts();
+        let saber = Saber::new(&accounts).unwrap();
+
+        assert_eq!(*saber.program_id.key, Saber::PROGRAM_ID);
+        assert_eq!(saber.pool_key(), accounts[2].key);
+        assert_eq!(saber.get_mints(), (accounts[5].key, accounts[6].key));
+        assert_eq!(saber.mint_decimals(), (6, 6));
+        assert_eq!(saber.swap_state().unwrap(), swap_state(100));
+    }
+
+    #[test]
+    fn test_swap_info_reads_upstream_layout() {
+        let usdc = Pubkey::from_str_const("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+        let usdt = Pubkey::from_str_const("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB");
+        let state = SwapState {
+            initial_amp_factor: 50,
+            start_ramp_ts: 1_000,
+            stop_ramp_ts: 2_000,
+            ..swap_state(100)
+        };
+        let accounts = create_pool_accounts_for_mints(usdc, usdt, (1, 1), state);
+        let saber = Saber::new(&accounts).unwrap();
+
+        assert_eq!(saber.swap_state().unwrap(), state);
+        assert_eq!(saber.get_mints(), (&usdc, &usdt));
+        let state = saber.swap_state().unwrap();
+        assert_eq!(state.amp_factor(1_500), Some(75));
+        assert_eq!(state.amp_factor(2_000), Some(100));
+        assert!((state.fee_rate() - 0.0004).abs() < 1e-12);
+        // Vaults, mints and admin fee accounts are where the swap info records them
+        assert!(saber.validate_accounts().is_ok());
+    }
+
+    #[test]
+    fn test_compute_d_of_balanced_reserves_is_their_sum() {
+        for amp_factor in [1, 100, 10_000] {
+            let d = compute_d(amp_factor, 1_000_000_000, 1_000_000_000).unwrap();
+            assert!(converged(d, U256::from(2_000_000_000u64)), "{}", d);
+        }
+        assert_eq!(compute_d(100, 0, 0), Some(U256::ZERO));
+        // The invariant sits between the constant product's 2 * sqrt(xy) and the sum
+        let d = u128::try_from(compute_d(100, 32_000_000, 28_000_000).unwrap()).unwrap();
+        assert!(d > 2 * 29_933_259 && d < 60_000_000, "{}", d);
+    }
+
+    #[test]
+    fn test_amp_factor_ramps_linearly() {
+        let state = SwapState {
+            initial_amp_factor: 100,
+            target_amp_factor: 200,
+            start_ramp_ts: 1_000,
+            stop_ramp_ts: 2_000,
+            ..swap_state(100)
+        };
+        assert_eq!(state.amp_factor(0), Some(100));
+        assert_eq!(state.amp_factor(1_000), Some(100));
+        assert_eq!(state.amp_factor(1_500), Some(150));
+        assert_eq!(state.amp_factor(2_000), Some(200));
+        assert_eq!(state.amp_factor(i64::MAX), Some(200));
+
+        let ramp_down = SwapState {
+            initial_amp_factor: 200,
+            target_amp_factor: 100,
+            ..state
+        };
+        assert_eq!(ramp_down.amp_factor(1_250), Some(175));
+    }
+
+    #[test]
+    fn test_stable_swap_trades_closer_to_par_than_constant_product() {
+        let accounts = create_test_accounts();
+        let saber = Saber::new(&accounts).unwrap();
+        let (usdc, usdt) = (*accounts[5].key, *accounts[6].key);
+        let (usdc_reserve, usdt_reserve) = (32_000_000_000_000u128, 28_000_000_000_000u128);
+        let state = swap_state(100);
+
+        // 1M USDC in, against the same reserves and fee
+        let amount_in = 1_000_000_000_000u64;
+        let stable_out = saber
+            .swap_base_in(usdc, amount_in, Clock::default())
+            .unwrap();
+        let product_out =
+            constant_product_out(amount_in as u128, usdc_reserve, usdt_reserve, 0).unwrap() as u64;
+        let product_out = product_out - state.trade_fee(product_out).unwrap();
+
+        // The stable pool pays within half a percent of par where the constant product
+        // gives up over 13%
+        assert!(stable_out < amount_in);
+        assert!(amount_in - stable_out < amount_in / 200, "{}", stable_out);
+        assert!(
+            amount_in - product_out > amount_in * 13 / 100,
+            "{}",
+            product_out
+        );
+        assert!((amount_in - stable_out) * 20 < amount_in - product_out);
+
+        // USDT is the scarce side, so selling it in earns a little over par, less the fee
+        let usdc_out = saber
+            .swap_base_in(usdt, amount_in, Clock::default())
+            .unwrap();
+        assert!(usdc_out > stable_out && usdc_out.abs_diff(amount_in) < amount_in / 200);
+
+        // Without amplification the curve is a constant product again
+        let flat = Saber::new(&create_pool_accounts(
+            (usdc_reserve as u64, usdt_reserve as u64),
+            swap_state(1),
+        ))
+        .unwrap();
+        let flat_out = flat
+            .swap_base_in(*flat.base_token.key, amount_in, Clock::default())
+            .unwrap();
+        assert!(flat_out < stable_out && flat_out < amount_in * 95 / 100);
+    }
+
+    #[test]
+    fn test_marginal_price_matches_small_quote() {
+        let accounts = create_test_accounts();
+        let saber = Saber::new(&accounts).unwrap();
+        let (base_reserve, quote_reserve) = saber.vault_reserves().unwrap();
+
+        let amount = 1_000_000u64;
+        let price_base_in = saber
+            .compute_price_swap_base_in(base_reserve, quote_reserve)
+            .unwrap();
+        let out = saber
+            .swap_base_in(*accounts[5].key, amount, Clock::default())
+            .unwrap();
+        assert!(
+            (price_base_in - out as f64 / amount as f64).abs() < 1e-5,
+            "{} vs {}",
+            price_base_in,
+            out
+        );
+        let price_base_out = saber
+            .compute_price_swap_base_out(base_reserve, quote_reserve)
+            .unwrap();
+        assert!(price_base_in < 1.0 && price_base_out > 1.0);
+
+        // Balanced reserves trade at par less the fee
+        let balanced = saber
+            .compute_price_swap_base_in(1_000_000_000, 1_000_000_000)
+            .unwrap();
+        assert!((balanced - (1.0 - 0.0004)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_paused_pool_is_not_tradable() {
+        let accounts = create_test_accounts();
+        assert!(Saber::new(&accounts)
+            .unwrap()
+            .is_tradable(&Clock::default())
+            .unwrap());
+
+        let paused = SwapState {
+            is_paused: true,
+            ..swap_state(100)
+        };
+        let accounts = create_pool_accounts((1_000_000, 1_000_000), paused);
+        assert!(!Saber::new(&accounts)
+            .unwrap()
+            .is_tradable(&Clock::default())
+            .unwrap());
+    }
+
+    #[test]
+    fn test_validate_accounts() {
+        let accounts = create_test_accounts();
+        assert!(Saber::new(&accounts).unwrap().validate_accounts().is_ok());
+
+        // Some other token account in the base admin fee slot
+        let mut foreign = accounts.clone();
+        foreign[7] = create_mock_account_info(
+            Pubkey::new_unique(),
+            TOKEN_PROGRAM,
+            Some(token_account_data(*accounts[5].key, 0)),
+        );
+        let err = Saber::new(&foreign)
+            .unwrap()
+            .validate_accounts()
+            .unwrap_err();
+        assert_eq!(err, error!(SolarBError::PoolStateMismatch));
+    }
+
+    #[test]
+    fn test_build_swap_ix_follows_direction() {
+        let accounts = create_test_accounts();
+        let saber = Saber::new(&accounts).unwrap();
+        let user = create_mock_accounts_for_swap(&accounts);
+        let authority = Pubkey::new_unique();
+
+        for direction in [EdgeSide::LeftToRight, EdgeSide::RightToLeft] {
+            let resolved = resolve_swap_accounts(
+                accounts[5].key,
+                user[0].clone(),
+                user[1].clone(),
+                user[2].clone(),
+                user[2].clone(),
+                user[3].clone(),
+                user[4].clone(),
+            )
+            .unwrap();
+            let ix = saber
+                .build_swap_ix(direction.clone(), 1_000, 990, &resolved.keys(&authority))
+                .unwrap();
+
+            assert_eq!(ix.program_id, Saber::PROGRAM_ID);
+            assert_eq!(ix.accounts.len(), 9);
+            assert_eq!(ix.accounts[0].pubkey, *accounts[2].key);
+            assert_eq!(ix.accounts[2], AccountMeta::new_readonly(authority, true));
+            let (source, swap_source, swap_destination, destination, admin_fee) = match direction {
+                EdgeSide::LeftToRight => (
+                    user[3].key,
+                    accounts[3].key,
+                    accounts[4].key,
+                    user[4].key,
+                    accounts[8].key,
+                ),
+                EdgeSide::RightToLeft => (
+                    user[4].key,
+                    accounts[4].key,
+                    accounts[3].key,
+                    user[3].key,
+                    accounts[7].key,
+                ),
+            };
+            let keys: Vec<&Pubkey> = ix.accounts[3..8].iter().map(|meta| &meta.pubkey).collect();
+            assert_eq!(
+                keys,
+                [
+                    source,
+                    swap_source,
+                    swap_destination,
+                    destination,
+                    admin_fee
+                ]
+            );
+
+            assert_eq!(ix.data[0], Saber::SWAP_TAG);
+            assert_eq!(ix.data[1..9], 1_000u64.to_le_bytes());
+            assert_eq!(ix.data[9..], 990u64.to_le_bytes());
+        }
+    }
+
+    // User-side mints, token program and token accounts for the pool's two mints
+    fn create_mock_accounts_for_swap(pool: &[AccountInfo<'static>]) -> Vec<AccountInfo<'static>> {
+        let owner = system_program::id();
+        vec![
+            pool[5].clone(),
+            pool[6].clone(),
+            create_mock_account_info(TOKEN_PROGRAM, owner, None),
+            create_mock_account_info(Pubkey::new_unique(), TOKEN_PROGRAM, None),
+            create_mock_account_info(Pubkey::new_unique(), TOKEN_PROGRAM, None),
+        ]
+    }
+}
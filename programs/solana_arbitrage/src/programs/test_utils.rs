@@ -0,0 +1,21 @@
+use anchor_lang::solana_program::pubkey::Pubkey;
+
+// SPL token account: mint (32) + owner (32) + amount (8) + delegate (36) + state (1) + ...
+pub(crate) fn token_account_data(mint: Pubkey, amount: u64) -> Vec<u8> {
+    let mut data = vec![0u8; 165];
+    data[0..32].copy_from_slice(&mint.to_bytes());
+    data[32..64].copy_from_slice(&Pubkey::new_unique().to_bytes());
+    data[64..72].copy_from_slice(&amount.to_le_bytes());
+    data[108] = 1; // Initialized
+    data
+}
+
+// SPL mint: mint_authority (36) + supply (8) + decimals (1) + is_initialized (1) + ...
+#[cfg(any(feature = "damm-v1", feature = "lifinity", feature = "saber"))]
+pub(crate) fn mint_data(supply: u64, decimals: u8) -> Vec<u8> {
+    let mut data = vec![0u8; 82];
+    data[36..44].copy_from_slice(&supply.to_le_bytes());
+    data[44] = decimals;
+    data[45] = 1;
+    data
+}
@@ -27,8 +27,9 @@ pub struct TransferFeeExcludedAmount {
 pub fn calculate_transfer_fee_excluded_amount(
     token_mint: &InterfaceAccount<'_, Mint>,
     transfer_fee_included_amount: u64,
+    epoch: u64,
 ) -> Result<TransferFeeExcludedAmount> {
-    if let Some(epoch_transfer_fee) = get_epoch_transfer_fee(token_mint)? {
+    if let Some(epoch_transfer_fee) = get_epoch_transfer_fee(token_mint, epoch)? {
         let transfer_fee = epoch_transfer_fee
             .calculate_fee(transfer_fee_included_amount)
             .unwrap();
@@ -50,6 +51,7 @@ pub fn calculate_transfer_fee_excluded_amount(
 pub fn calculate_transfer_fee_included_amount(
     token_mint: &InterfaceAccount<'_, Mint>,
     transfer_fee_excluded_amount: u64,
+    epoch: u64,
 ) -> Result<TransferFeeIncludedAmount> {
     if transfer_fee_excluded_amount == 0 {
         return Ok(TransferFeeIncludedAmount {
@@ -60,7 +62,7 @@ pub fn calculate_transfer_fee_included_amount(
 
     // now transfer_fee_excluded_amount > 0
 
-    if let Some(epoch_transfer_fee) = get_epoch_transfer_fee(token_mint)? {
+    if let Some(epoch_transfer_fee) = get_epoch_transfer_fee(token_mint, epoch)? {
         let transfer_fee: u64 =
             if u16::from(epoch_transfer_fee.transfer_fee_basis_points) == MAX_FEE_BASIS_POINTS {
                 // edge-case: if transfer fee rate is 100%, current SPL implementation returns 0 as inverse fee.
@@ -100,8 +102,11 @@ pub fn calculate_transfer_fee_included_amount(
     })
 }
 
+/// Transfer fee of a Token-2022 mint in force at `epoch`: the newer rate from its scheduled
+/// epoch on, the older one before
 pub fn get_epoch_transfer_fee(
     token_mint: &InterfaceAccount<'_, Mint>,
+    epoch: u64,
 ) -> Result<Option<TransferFee>> {
     let token_mint_info = token_mint.to_account_info();
     if *token_mint_info.owner == Token::id() {
@@ -114,9 +119,84 @@ pub fn get_epoch_transfer_fee(
     if let Ok(transfer_fee_config) =
         token_mint_unpacked.get_extension::<extension::transfer_fee::TransferFeeConfig>()
     {
-        let epoch = Clock::get()?.epoch;
         return Ok(Some(*transfer_fee_config.get_epoch_fee(epoch)));
     }
 
     Ok(None)
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use anchor_spl::token_2022::spl_token_2022::extension::{
+        transfer_fee::TransferFeeConfig, BaseStateWithExtensionsMut, ExtensionType,
+        StateWithExtensionsMut,
+    };
+
+    /// Epoch from which `create_fee_schedule_mint` charges its newer rate
+    pub(crate) const FEE_TRANSITION_EPOCH: u64 = 10;
+
+    // Token-2022 mint charging 1% before `FEE_TRANSITION_EPOCH` and 5% from it on
+    pub(crate) fn create_fee_schedule_mint() -> AccountInfo<'static> {
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+            ExtensionType::TransferFeeConfig,
+        ])
+        .unwrap();
+        let mut data = vec![0u8; space];
+        let mut state =
+            StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data)
+                .unwrap();
+        let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        config.older_transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+        config.newer_transfer_fee = TransferFee {
+            epoch: FEE_TRANSITION_EPOCH.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: 500.into(),
+        };
+        state.base.decimals = 6;
+        state.base.is_initialized = true;
+        state.pack_base();
+        state.init_account_type().unwrap();
+
+        let data = Box::leak(Box::new(data));
+        AccountInfo::new(
+            Box::leak(Box::new(Pubkey::new_unique())),
+            false,
+            false,
+            Box::leak(Box::new(0u64)),
+            data,
+            &spl_token_2022::ID,
+            false,
+            0,
+        )
+    }
+
+    #[test]
+    fn test_transfer_fee_follows_epoch_schedule() {
+        let mint_info = Box::leak(Box::new(create_fee_schedule_mint()));
+        let mint = InterfaceAccount::<Mint>::try_from(&*mint_info).unwrap();
+
+        for (epoch, basis_points) in [
+            (0, 100),
+            (FEE_TRANSITION_EPOCH - 1, 100),
+            (FEE_TRANSITION_EPOCH, 500),
+            (FEE_TRANSITION_EPOCH + 100, 500),
+        ] {
+            let fee = get_epoch_transfer_fee(&mint, epoch).unwrap().unwrap();
+            assert_eq!(u16::from(fee.transfer_fee_basis_points), basis_points);
+
+            let excluded = calculate_transfer_fee_excluded_amount(&mint, 1_000_000, epoch).unwrap();
+            assert_eq!(excluded.transfer_fee, 100 * basis_points as u64);
+            assert_eq!(excluded.amount, 1_000_000 - excluded.transfer_fee);
+
+            // Grossing the net amount up again gives back the original transfer
+            let included =
+                calculate_transfer_fee_included_amount(&mint, excluded.amount, epoch).unwrap();
+            assert_eq!(included.amount, 1_000_000);
+        }
+    }
+}
@@ -1,13 +1,122 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::TokenAccount;
+use anchor_spl::token_interface::{Mint, TokenAccount};
 
+use crate::programs::SolarBError;
+
+/// Parses an SPL Token or Token-2022 account. The extension-aware unpack reads the base
+/// layout and ignores any extension TLV data after it, so Token-2022 vaults parse too.
 pub fn parse_token_account<'info>(account: &AccountInfo<'info>) -> Result<TokenAccount> {
     let mut data = &account.try_borrow_data()?[..];
     let token_account = TokenAccount::try_deserialize(&mut data)?;
     Ok(token_account)
 }
 
+pub fn parse_mint<'info>(account: &AccountInfo<'info>) -> Result<Mint> {
+    let mut data = &account.try_borrow_data()?[..];
+    let mint = Mint::try_deserialize(&mut data)?;
+    Ok(mint)
+}
+
+/// Event authority PDA an Anchor program signs its `emit_cpi!` events with
+pub fn derive_event_authority(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], program_id).0
+}
+
+/// Fails with `InvalidEventAuthority` unless `event_authority` is the event authority PDA
+/// of `program_id`, so a swap CPI is never handed a caller's account in its place
+pub fn check_event_authority(program_id: &Pubkey, event_authority: &AccountInfo) -> Result<()> {
+    require!(
+        *event_authority.key == derive_event_authority(program_id),
+        SolarBError::InvalidEventAuthority
+    );
+    Ok(())
+}
 
+/// A swap's user-side accounts, ordered by the pool's base and quote mints
+pub struct ResolvedAccounts<'a> {
+    pub base_mint: AccountInfo<'a>,
+    pub quote_mint: AccountInfo<'a>,
+    pub base_token_program: AccountInfo<'a>,
+    pub quote_token_program: AccountInfo<'a>,
+    pub user_base_token_account: AccountInfo<'a>,
+    pub user_quote_token_account: AccountInfo<'a>,
+}
+
+impl ResolvedAccounts<'_> {
+    /// Keys of the resolved accounts, with `authority` signing the swap
+    pub fn keys(&self, authority: &Pubkey) -> SwapUserAccounts {
+        SwapUserAccounts {
+            authority: *authority,
+            base_token_program: *self.base_token_program.key,
+            quote_token_program: *self.quote_token_program.key,
+            user_base_token_account: *self.user_base_token_account.key,
+            user_quote_token_account: *self.user_quote_token_account.key,
+        }
+    }
+}
+
+/// Keys of a swap's user-side accounts, ordered by the pool's base and quote mints; all a
+/// swap instruction needs from the user, so it can be laid out without their accounts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SwapUserAccounts {
+    /// Owner of the user token accounts, signing the swap
+    pub authority: Pubkey,
+    pub base_token_program: Pubkey,
+    pub quote_token_program: Pubkey,
+    pub user_base_token_account: Pubkey,
+    pub user_quote_token_account: Pubkey,
+}
+
+/// Sorts the accounts of two mints, passed in either order, into base and quote by which
+/// mint is `base_token`. Fails with `InvalidAccountData` when neither is, and with
+/// `TokenProgramMismatch` when a mint isn't owned by the token program supplied with it
+/// (say a Token-2022 mint passed with the classic program), which the swap CPI would
+/// otherwise reject without saying why.
+pub fn resolve_swap_accounts<'a>(
+    base_token: &Pubkey,
+    mint_1: AccountInfo<'a>,
+    mint_2: AccountInfo<'a>,
+    mint_1_token_program: AccountInfo<'a>,
+    mint_2_token_program: AccountInfo<'a>,
+    user_mint_1_token_account: AccountInfo<'a>,
+    user_mint_2_token_account: AccountInfo<'a>,
+) -> Result<ResolvedAccounts<'a>> {
+    for (mint, token_program) in [
+        (&mint_1, &mint_1_token_program),
+        (&mint_2, &mint_2_token_program),
+    ] {
+        if mint.owner != token_program.key {
+            msg!(
+                "Mint {} is owned by {}, not by token program {}",
+                mint.key,
+                mint.owner,
+                token_program.key
+            );
+            return Err(error!(SolarBError::TokenProgramMismatch));
+        }
+    }
+    if mint_1.key == base_token {
+        Ok(ResolvedAccounts {
+            base_mint: mint_1,
+            quote_mint: mint_2,
+            base_token_program: mint_1_token_program,
+            quote_token_program: mint_2_token_program,
+            user_base_token_account: user_mint_1_token_account,
+            user_quote_token_account: user_mint_2_token_account,
+        })
+    } else if mint_2.key == base_token {
+        Ok(ResolvedAccounts {
+            base_mint: mint_2,
+            quote_mint: mint_1,
+            base_token_program: mint_2_token_program,
+            quote_token_program: mint_1_token_program,
+            user_base_token_account: user_mint_2_token_account,
+            user_quote_token_account: user_mint_1_token_account,
+        })
+    } else {
+        Err(ProgramError::InvalidAccountData.into())
+    }
+}
 
 pub fn amount_with_slippage(amount: u64, slippage: f64, round_up: bool) -> u64 {
     if round_up {
@@ -15,4 +124,177 @@ pub fn amount_with_slippage(amount: u64, slippage: f64, round_up: bool) -> u64 {
     } else {
         ((amount as f64) * (1_f64 - slippage)).floor() as u64
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program_pack::Pack;
+    use anchor_spl::token_2022::spl_token_2022::{
+        extension::{
+            memo_transfer::MemoTransfer, BaseStateWithExtensionsMut, ExtensionType,
+            StateWithExtensionsMut,
+        },
+        state::{Account, AccountState},
+    };
+
+    fn token_account(mint: Pubkey, amount: u64) -> Account {
+        Account {
+            mint,
+            owner: Pubkey::new_unique(),
+            amount,
+            state: AccountState::Initialized,
+            ..Account::default()
+        }
+    }
+
+    fn account_info(owner: Pubkey, data: Vec<u8>) -> AccountInfo<'static> {
+        keyed_account_info(Pubkey::new_unique(), owner, data)
+    }
+
+    fn keyed_account_info(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> AccountInfo<'static> {
+        AccountInfo::new(
+            Box::leak(Box::new(key)),
+            false,
+            false,
+            Box::leak(Box::new(0)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(owner)),
+            false,
+            0,
+        )
+    }
+
+    #[test]
+    fn test_parse_token_account_classic() {
+        let mint = Pubkey::new_unique();
+        let mut data = vec![0u8; Account::LEN];
+        token_account(mint, 42).pack_into_slice(&mut data);
+
+        let account = account_info(anchor_spl::token::ID, data);
+        let parsed = parse_token_account(&account).unwrap();
+        assert_eq!(parsed.mint, mint);
+        assert_eq!(parsed.amount, 42);
+    }
+
+    #[test]
+    fn test_parse_token_account_with_token_2022_extension() {
+        let mint = Pubkey::new_unique();
+        let len =
+            ExtensionType::try_calculate_account_len::<Account>(&[ExtensionType::MemoTransfer])
+                .unwrap();
+        let mut data = vec![0u8; len];
+        {
+            let mut state =
+                StateWithExtensionsMut::<Account>::unpack_uninitialized(&mut data).unwrap();
+            state.base = token_account(mint, 1_234_567);
+            state.pack_base();
+            state.init_account_type().unwrap();
+            state
+                .init_extension::<MemoTransfer>(true)
+                .unwrap()
+                .require_incoming_transfer_memos = true.into();
+        }
+        assert!(data.len() > Account::LEN);
+
+        let account = account_info(anchor_spl::token_2022::ID, data);
+        let parsed = parse_token_account(&account).unwrap();
+        assert_eq!(parsed.mint, mint);
+        assert_eq!(parsed.amount, 1_234_567);
+    }
+
+    // Mint, token program and user token account of each of two classic token mints
+    fn swap_accounts() -> [[AccountInfo<'static>; 3]; 2] {
+        let token_program = anchor_spl::token::ID;
+        [0, 1].map(|_| {
+            [
+                account_info(token_program, Vec::new()),
+                keyed_account_info(token_program, Pubkey::default(), Vec::new()),
+                account_info(token_program, Vec::new()),
+            ]
+        })
+    }
+
+    fn resolve(
+        base_token: &Pubkey,
+        [[mint_1, program_1, user_1], [mint_2, program_2, user_2]]: [[AccountInfo<'static>; 3]; 2],
+    ) -> Result<ResolvedAccounts<'static>> {
+        resolve_swap_accounts(
+            base_token, mint_1, mint_2, program_1, program_2, user_1, user_2,
+        )
+    }
+
+    // Keys of the resolved accounts, base side first
+    fn resolved_keys(resolved: &ResolvedAccounts) -> [Pubkey; 6] {
+        [
+            *resolved.base_mint.key,
+            *resolved.base_token_program.key,
+            *resolved.user_base_token_account.key,
+            *resolved.quote_mint.key,
+            *resolved.quote_token_program.key,
+            *resolved.user_quote_token_account.key,
+        ]
+    }
+
+    #[test]
+    fn test_resolve_swap_accounts_mint_1_is_base() {
+        let accounts = swap_accounts();
+        let keys: Vec<Pubkey> = accounts
+            .iter()
+            .flatten()
+            .map(|account| *account.key)
+            .collect();
+        let base_token = keys[0];
+
+        let resolved = resolve(&base_token, accounts).unwrap();
+        assert_eq!(resolved_keys(&resolved).to_vec(), keys);
+    }
+
+    #[test]
+    fn test_resolve_swap_accounts_mint_2_is_base() {
+        let accounts = swap_accounts();
+        let keys: Vec<Pubkey> = accounts
+            .iter()
+            .flatten()
+            .map(|account| *account.key)
+            .collect();
+        let base_token = keys[3];
+
+        let resolved = resolve(&base_token, accounts).unwrap();
+        assert_eq!(resolved_keys(&resolved)[..3], keys[3..]);
+        assert_eq!(resolved_keys(&resolved)[3..], keys[..3]);
+    }
+
+    #[test]
+    fn test_resolve_swap_accounts_neither_is_base() {
+        let result = resolve(&Pubkey::new_unique(), swap_accounts());
+        assert_eq!(
+            result.err().unwrap(),
+            ProgramError::InvalidAccountData.into()
+        );
+    }
+
+    #[test]
+    fn test_resolve_swap_accounts_token_program_mismatch() {
+        // A Token-2022 mint supplied with the classic token program, on either side
+        for side in [0, 1] {
+            let mut accounts = swap_accounts();
+            let base_token = *accounts[1 - side][0].key;
+            accounts[side][0] = account_info(anchor_spl::token_2022::ID, Vec::new());
+            assert_eq!(
+                resolve(&base_token, accounts).err().unwrap(),
+                error!(SolarBError::TokenProgramMismatch)
+            );
+        }
+
+        // Supplied with its own program it resolves
+        let mut accounts = swap_accounts();
+        let mint = account_info(anchor_spl::token_2022::ID, Vec::new());
+        let base_token = *mint.key;
+        accounts[1][0] = mint;
+        accounts[1][1] =
+            keyed_account_info(anchor_spl::token_2022::ID, Pubkey::default(), Vec::new());
+        let resolved = resolve(&base_token, accounts).unwrap();
+        assert_eq!(*resolved.base_token_program.key, anchor_spl::token_2022::ID);
+    }
+}